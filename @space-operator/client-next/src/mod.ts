@@ -60,6 +60,7 @@ export type {
   JsonValue,
   LogLevel,
   NodeError,
+  NodeErrorKind,
   NodeFinish,
   NodeId,
   NodeLog,
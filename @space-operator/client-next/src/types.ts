@@ -388,8 +388,14 @@ export interface NodeError {
   node_id: NodeId;
   times: number;
   error: string;
+  kind?: NodeErrorKind;
 }
 
+export type NodeErrorKind = {
+  type: "resource_limit";
+  limit: "heap" | "memory" | "cpu" | "wall_clock" | "log" | "output_size";
+};
+
 export interface NodeLog {
   flow_run_id: FlowRunId;
   time: string;
@@ -405,6 +411,14 @@ export interface NodeFinish {
   time: string;
   node_id: NodeId;
   times: number;
+  transactions?: TxOutcome[];
+}
+
+export interface TxOutcome {
+  attempts: number;
+  rebuilds: number;
+  slot: number;
+  priority_fee: number | null;
 }
 
 export interface ApiInputEvent {
@@ -46,6 +46,7 @@ export {
   type ISignatureRequest,
   type LogLevel,
   type NodeError,
+  type NodeErrorKind,
   type NodeFinish,
   type NodeLog,
   type NodeOutput,
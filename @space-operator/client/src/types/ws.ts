@@ -252,8 +252,14 @@ export interface NodeError {
   node_id: NodeId;
   times: number;
   error: string;
+  kind?: NodeErrorKind;
 }
 
+export type NodeErrorKind = {
+  type: "resource_limit";
+  limit: "heap" | "memory" | "cpu" | "wall_clock" | "log" | "output_size";
+};
+
 export interface NodeLog {
   flow_run_id: FlowRunId;
   time: string;
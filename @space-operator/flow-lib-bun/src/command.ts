@@ -44,7 +44,7 @@ export interface CommandTrait {
   run(ctx: Context, params: Record<string, any>): Promise<Record<string, any>>;
 }
 
-export type CommandType = "native" | "deno" | "bun" | "WASM" | "mock";
+export type CommandType = "native" | "deno" | "bun" | "python" | "WASM" | "mock";
 
 export type ValueTypeBound = string;
 
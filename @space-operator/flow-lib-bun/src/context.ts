@@ -346,4 +346,9 @@ export interface SolanaClientConfig {
   cluster: SolanaNet;
 }
 
-export type SolanaNet = "devnet" | "testnet" | "mainnet-beta";
+export type SolanaNet =
+  | "devnet"
+  | "testnet"
+  | "mainnet-beta"
+  | "localnet"
+  | "custom";
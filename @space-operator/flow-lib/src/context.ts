@@ -325,4 +325,9 @@ export interface SolanaClientConfig {
   cluster: SolanaNet;
 }
 
-export type SolanaNet = "devnet" | "testnet" | "mainnet-beta";
+export type SolanaNet =
+  | "devnet"
+  | "testnet"
+  | "mainnet-beta"
+  | "localnet"
+  | "custom";
@@ -52,6 +52,7 @@ fn command_type_expr(ty: &CommandType) -> &'static str {
         CommandType::Wasm => "flow_lib::CommandType::Wasm",
         CommandType::Deno => "flow_lib::CommandType::Deno",
         CommandType::Bun => "flow_lib::CommandType::Bun",
+        CommandType::Python => "flow_lib::CommandType::Python",
     }
 }
 
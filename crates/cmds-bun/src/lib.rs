@@ -6,7 +6,11 @@
 
 use anyhow::Context as _;
 use flow_lib::{
-    command::{CommandError, CommandTrait, default_node_data, prelude::async_trait},
+    command::{
+        CommandError, CommandTrait, default_node_data,
+        limits::{HostCalls, Limit, LimitExceeded, ScriptLimits},
+        prelude::async_trait,
+    },
     config::client::{self, NodeData},
     utils::LocalBoxFuture,
 };
@@ -15,7 +19,10 @@ use std::{
     collections::VecDeque,
     path::{Path, PathBuf},
     process::Stdio,
-    sync::{Arc, Mutex},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
     time::{Duration, Instant},
 };
 use tempfile::{TempDir, tempdir};
@@ -298,10 +305,16 @@ struct RunningBun {
     child: Child,
     started_at: Instant,
     stderr_tail: Arc<Mutex<VecDeque<String>>>,
+    /// Bytes written to stdout and stderr.
+    log_bytes: Arc<AtomicU64>,
     _dir: TempDir,
 }
 
-async fn spawn_running_bun(source: &str, node_data: &NodeData) -> Result<RunningBun, CommandError> {
+async fn spawn_running_bun(
+    source: &str,
+    node_data: &NodeData,
+    limits: &ScriptLimits,
+) -> Result<RunningBun, CommandError> {
     let dir = tempdir()?;
 
     tokio::fs::write(dir.path().join("cmd.ts"), source)
@@ -340,6 +353,12 @@ async fn spawn_running_bun(source: &str, node_data: &NodeData) -> Result<Running
         tracing::info!("starting bun subprocess with --smol");
         bun.arg("--smol");
     }
+    if let Some(heap_mb) = limits.heap_mb {
+        // JavaScriptCore sizes its heap from the RAM size it detects
+        bun.env("BUN_JSC_forceRAMSize", (heap_mb * 1024 * 1024).to_string());
+    }
+    #[cfg(unix)]
+    limits.apply_rlimits(&mut bun);
 
     let mut spawned = bun
         .current_dir(dir.path())
@@ -379,17 +398,22 @@ async fn spawn_running_bun(source: &str, node_data: &NodeData) -> Result<Running
 
     let stderr_tail = Arc::new(Mutex::new(VecDeque::new()));
     let stderr_tail_reader = Arc::clone(&stderr_tail);
+    let log_bytes = Arc::new(AtomicU64::new(0));
+    let stderr_log_bytes = Arc::clone(&log_bytes);
     let stderr = spawned.stderr.take().unwrap();
     tokio::spawn(async move {
         let mut stderr = BufReader::new(stderr).lines();
         while let Ok(Some(line)) = stderr.next_line().await {
+            stderr_log_bytes.fetch_add(line.len() as u64 + 1, Ordering::Relaxed);
             tracing::warn!("{}", line);
             push_stderr_line(&stderr_tail_reader, line);
         }
     });
 
+    let stdout_log_bytes = Arc::clone(&log_bytes);
     tokio::spawn(async move {
         while let Ok(Some(line)) = stdout.next_line().await {
+            stdout_log_bytes.fetch_add(line.len() as u64 + 1, Ordering::Relaxed);
             tracing::debug!("{}", line);
         }
     });
@@ -399,6 +423,7 @@ async fn spawn_running_bun(source: &str, node_data: &NodeData) -> Result<Running
         child: spawned,
         started_at,
         stderr_tail,
+        log_bytes,
         _dir: dir,
     })
 }
@@ -424,6 +449,7 @@ pub(crate) async fn new_owned(nd: NodeData) -> Result<Box<dyn CommandTrait>, Com
     let source = source_from_config(&nd)
         .ok_or_else(|| CommandError::msg("bun command source/code not found"))?;
     Ok(Box::new(BunCommand {
+        limits: ScriptLimits::deployment().for_node(&nd.config),
         node_data: sanitized_node_data(nd),
         source,
         running: AsyncMutex::new(None),
@@ -503,6 +529,7 @@ include!(concat!(env!("OUT_DIR"), "/bun_nodes_generated.rs"));
 pub struct BunCommand {
     node_data: NodeData,
     source: String,
+    limits: ScriptLimits,
     running: AsyncMutex<Option<RunningBun>>,
 }
 
@@ -513,7 +540,8 @@ enum BunRunOutcome {
         diagnostics: Option<String>,
         reset_process: bool,
     },
-    Timeout {
+    LimitExceeded {
+        limit: Limit,
         details: String,
     },
     Canceled {
@@ -545,7 +573,7 @@ impl BunCommand {
         };
 
         if needs_spawn {
-            *running = Some(spawn_running_bun(&self.source, &self.node_data).await?);
+            *running = Some(spawn_running_bun(&self.source, &self.node_data, &self.limits).await?);
         }
 
         Ok(())
@@ -568,11 +596,13 @@ impl CommandTrait for BunCommand {
     }
     async fn run(
         &self,
-        ctx: flow_lib::context::CommandContext,
+        mut ctx: flow_lib::context::CommandContext,
         params: flow_lib::ValueSet,
     ) -> Result<flow_lib::value::Map, CommandError> {
         let cancel_token = ctx.get::<CancellationToken>().cloned();
         let timeout = bun_run_timeout();
+        let host_calls = HostCalls::default();
+        host_calls.track(&mut ctx);
         let mut running = self.running.lock().await;
         self.ensure_running(&mut running).await?;
 
@@ -587,79 +617,60 @@ impl CommandTrait for BunCommand {
             );
             let run = client.run(ctx, params);
             tokio::pin!(run);
-
-            if let Some(cancel_token) = cancel_token {
-                tokio::select! {
-                    _ = cancel_token.cancelled() => BunRunOutcome::Canceled {
-                        details: terminate_running_bun(state, "bun command canceled").await,
-                    },
-                    result = tokio::time::timeout(timeout, &mut run) => match result {
-                        Ok(Ok(output)) => BunRunOutcome::Success(output),
-                        Ok(Err(error)) => {
-                            let (diagnostics, reset_process) = match state.child.try_wait() {
-                                Ok(Some(status)) => (
-                                    Some(runtime_failure_details(
-                                        state.started_at,
-                                        status,
-                                        &state.stderr_tail,
-                                    )),
-                                    true,
-                                ),
-                                Ok(None) => (None, false),
-                                Err(wait_error) => (
-                                    Some(format!(
-                                        "bun subprocess status check failed after {}ms: {}",
-                                        state.started_at.elapsed().as_millis(),
-                                        wait_error
-                                    )),
-                                    true,
-                                ),
-                            };
-                            BunRunOutcome::Failure {
-                                error,
-                                diagnostics,
-                                reset_process,
+            let pid = state.child.id();
+            let log_bytes = Arc::clone(&state.log_bytes);
+
+            tokio::select! {
+                _ = async {
+                    match &cancel_token {
+                        Some(cancel_token) => cancel_token.cancelled().await,
+                        None => std::future::pending().await,
+                    }
+                } => BunRunOutcome::Canceled {
+                    details: terminate_running_bun(state, "bun command canceled").await,
+                },
+                exceeded = self.limits.watch(pid, &log_bytes, &host_calls) => BunRunOutcome::LimitExceeded {
+                    limit: exceeded.limit,
+                    details: terminate_running_bun(state, &exceeded.detail).await,
+                },
+                result = tokio::time::timeout(timeout, &mut run) => match result {
+                    Ok(Ok(output)) => BunRunOutcome::Success(output),
+                    Ok(Err(error)) => match state.child.try_wait() {
+                        Ok(Some(status)) => {
+                            let details = runtime_failure_details(
+                                state.started_at,
+                                status,
+                                &state.stderr_tail,
+                            );
+                            match self.limits.exceeded_by_exit(status) {
+                                Some(exceeded) => BunRunOutcome::LimitExceeded {
+                                    limit: exceeded.limit,
+                                    details: format!("{}\n{details}", exceeded.detail),
+                                },
+                                None => BunRunOutcome::Failure {
+                                    error,
+                                    diagnostics: Some(details),
+                                    reset_process: true,
+                                },
                             }
                         }
-                        Err(_) => BunRunOutcome::Timeout {
-                            details: terminate_running_bun(
-                                state,
-                                &format!("bun command timed out after {}s", timeout.as_secs()),
-                            )
-                            .await,
+                        Ok(None) => BunRunOutcome::Failure {
+                            error,
+                            diagnostics: None,
+                            reset_process: false,
                         },
-                    }
-                }
-            } else {
-                match tokio::time::timeout(timeout, &mut run).await {
-                    Ok(Ok(output)) => BunRunOutcome::Success(output),
-                    Ok(Err(error)) => {
-                        let (diagnostics, reset_process) = match state.child.try_wait() {
-                            Ok(Some(status)) => (
-                                Some(runtime_failure_details(
-                                    state.started_at,
-                                    status,
-                                    &state.stderr_tail,
-                                )),
-                                true,
-                            ),
-                            Ok(None) => (None, false),
-                            Err(wait_error) => (
-                                Some(format!(
-                                    "bun subprocess status check failed after {}ms: {}",
-                                    state.started_at.elapsed().as_millis(),
-                                    wait_error
-                                )),
-                                true,
-                            ),
-                        };
-                        BunRunOutcome::Failure {
+                        Err(wait_error) => BunRunOutcome::Failure {
                             error,
-                            diagnostics,
-                            reset_process,
-                        }
-                    }
-                    Err(_) => BunRunOutcome::Timeout {
+                            diagnostics: Some(format!(
+                                "bun subprocess status check failed after {}ms: {}",
+                                state.started_at.elapsed().as_millis(),
+                                wait_error
+                            )),
+                            reset_process: true,
+                        },
+                    },
+                    Err(_) => BunRunOutcome::LimitExceeded {
+                        limit: Limit::WallClock,
                         details: terminate_running_bun(
                             state,
                             &format!("bun command timed out after {}s", timeout.as_secs()),
@@ -671,7 +682,10 @@ impl CommandTrait for BunCommand {
         };
 
         match outcome {
-            BunRunOutcome::Success(output) => Ok(output),
+            BunRunOutcome::Success(output) => {
+                self.limits.check_output(&output)?;
+                Ok(output)
+            }
             BunRunOutcome::Failure {
                 error,
                 diagnostics,
@@ -686,7 +700,11 @@ impl CommandTrait for BunCommand {
                 );
                 Err(CommandError::msg(message))
             }
-            BunRunOutcome::Timeout { details } | BunRunOutcome::Canceled { details } => {
+            BunRunOutcome::LimitExceeded { limit, details } => {
+                running.take();
+                Err(LimitExceeded::new(limit, details).into())
+            }
+            BunRunOutcome::Canceled { details } => {
                 running.take();
                 Err(CommandError::msg(details))
             }
@@ -902,9 +920,7 @@ export default class AdapterExecute extends BaseCommand {
                 let observed = observed.clone();
                 async move {
                     *observed.lock().unwrap() = Some(req.instructions.clone());
-                    Ok(execute::Response {
-                        signature: Some(Signature::default()),
-                    })
+                    Ok(execute::Response::signature(Signature::default()))
                 }
             }
         }));
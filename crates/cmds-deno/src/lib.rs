@@ -1,22 +1,36 @@
 use anyhow::Context as _;
 use flow_lib::{
+    UserId,
     command::{
         CommandDescription, CommandError, CommandTrait, MatchCommand, default_node_data,
+        limits::{HostCalls, Limit, LimitExceeded, ScriptLimits},
         prelude::{Either, async_trait},
     },
     config::client::{self, NodeData},
     utils::LocalBoxFuture,
 };
 use flow_rpc::client::RpcCommandClient;
-use std::path::Path;
-use std::process::Stdio;
-use tempfile::tempdir;
+use pool::{Lease, Pool, PoolConfig, PoolKey, PoolWorker, pool_key};
+use std::{
+    cell::RefCell,
+    path::Path,
+    process::Stdio,
+    sync::{
+        Arc, LazyLock, Mutex, Once,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+use tempfile::{TempDir, tempdir};
 use tokio::{
     io::{AsyncBufReadExt, AsyncReadExt, BufReader},
+    net::TcpStream,
     process::{Child, Command},
 };
 use url::Url;
 
+pub mod pool;
+
 fn source_from_config(nd: &NodeData) -> Option<String> {
     ["source", "code"].into_iter().find_map(|key| {
         nd.config.get(key).and_then(|json| {
@@ -53,23 +67,91 @@ macro_rules! include {
     };
 }
 
-pub(crate) async fn new_owned(nd: NodeData) -> Result<Box<dyn CommandTrait>, CommandError> {
-    let source = source_from_config(&nd)
-        .ok_or_else(|| CommandError::msg("deno_script source/code not found"))?;
-    let use_local_deps = cfg!(feature = "local-deps") || cfg!(test);
+struct DenoWorker {
+    base_url: Url,
+    port: u16,
+    child: Mutex<Child>,
+    /// Bytes written to stdout and stderr.
+    log_bytes: Arc<AtomicU64>,
+    /// V8 reported that the heap limit was reached.
+    heap_oom: Arc<AtomicBool>,
+    stderr_closed: Arc<AtomicBool>,
+    _dir: TempDir,
+}
+
+impl PoolWorker for DenoWorker {
+    fn is_alive(&mut self) -> bool {
+        matches!(self.child.get_mut().unwrap().try_wait(), Ok(None))
+    }
+}
 
+fn is_heap_oom(line: &str) -> bool {
+    line.contains("Reached heap limit") || line.contains("heap out of memory")
+}
+
+impl DenoWorker {
+    fn kill(&self) {
+        self.child.lock().unwrap().start_kill().ok();
+    }
+
+    /// The kernel limit that ended the worker, if it exited.
+    fn exceeded_rlimit(&self, limits: &ScriptLimits) -> Option<LimitExceeded> {
+        let status = self.child.lock().unwrap().try_wait().ok()??;
+        limits.exceeded_by_exit(status)
+    }
+
+    /// Whether the worker died because of the V8 heap limit, waiting a bit
+    /// for its last stderr lines.
+    async fn died_of_heap_oom(&self) -> bool {
+        for _ in 0..20 {
+            if self.heap_oom.load(Ordering::Relaxed) || self.stderr_closed.load(Ordering::Relaxed) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        self.heap_oom.load(Ordering::Relaxed)
+    }
+
+    /// Check that the worker still accepts connections.
+    async fn check_health(&mut self) -> bool {
+        self.is_alive()
+            && matches!(
+                tokio::time::timeout(
+                    Duration::from_secs(1),
+                    TcpStream::connect(("127.0.0.1", self.port)),
+                )
+                .await,
+                Ok(Ok(_))
+            )
+    }
+}
+
+static POOL: LazyLock<Pool<DenoWorker>> = LazyLock::new(|| Pool::new(PoolConfig::from_env()));
+
+/// The worker pool, its sweeper is started on first use.
+fn pool() -> &'static Pool<DenoWorker> {
+    static SWEEPER: Once = Once::new();
+    SWEEPER.call_once(|| {
+        if POOL.config().max_size > 0 {
+            POOL.spawn_sweeper(POOL.config().sweep_interval());
+        }
+    });
+    &POOL
+}
+
+async fn spawn_worker(
+    source: &str,
+    node_data_json: &str,
+    deps: &str,
+    use_local_deps: bool,
+    limits: &ScriptLimits,
+) -> Result<DenoWorker, CommandError> {
     let dir = tempdir()?;
 
-    tokio::fs::write(dir.path().join("cmd.ts"), &source)
+    tokio::fs::write(dir.path().join("cmd.ts"), source)
         .await
         .context("write cmd.ts")?;
 
-    let mut node_data = nd.clone();
-    if let Some(obj) = node_data.config.as_object_mut() {
-        obj.remove("code");
-        obj.remove("source");
-    }
-    let node_data_json = serde_json::to_string(&node_data).context("serialize NodeData")?;
     tokio::fs::write(dir.path().join("node-data.json"), node_data_json)
         .await
         .context("write node-data.json")?;
@@ -78,16 +160,9 @@ pub(crate) async fn new_owned(nd: NodeData) -> Result<Box<dyn CommandTrait>, Com
         .await
         .context("write run.ts")?;
 
-    tokio::fs::write(
-        dir.path().join("deps.ts"),
-        if use_local_deps {
-            include!("/deps_local.ts")
-        } else {
-            include!("/deps_jsr.ts")
-        },
-    )
-    .await
-    .context("write deps.ts")?;
+    tokio::fs::write(dir.path().join("deps.ts"), deps)
+        .await
+        .context("write deps.ts")?;
 
     // Deno 2.x requires a deno.json to resolve npm: specifiers.
     // Use "none" so Deno resolves from its global cache instead of
@@ -140,14 +215,20 @@ pub(crate) async fn new_owned(nd: NodeData) -> Result<Box<dyn CommandTrait>, Com
     ]
     .join(",");
 
-    let mut spawned = Command::new("deno")
-        .current_dir(dir.path())
+    let mut deno = Command::new("deno");
+    deno.current_dir(dir.path())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .env("DENO_DIR", &deno_dir)
         .env("NO_COLOR", "1")
         .kill_on_drop(true)
-        .arg("run")
+        .arg("run");
+    if let Some(heap_mb) = limits.heap_mb {
+        deno.arg(format!("--v8-flags=--max-old-space-size={heap_mb}"));
+    }
+    #[cfg(unix)]
+    limits.apply_rlimits(&mut deno);
+    let mut spawned = deno
         .arg("--allow-net")
         .arg(format!("--deny-net={}", local_networks))
         .arg("--allow-env=WS_NO_BUFFER_UTIL")
@@ -169,20 +250,118 @@ pub(crate) async fn new_owned(nd: NodeData) -> Result<Box<dyn CommandTrait>, Com
             return Err(CommandError::msg(error));
         }
     };
-    let base_url = Url::parse(&format!("http://127.0.0.1:{port}")).unwrap();
-    let cmd = RpcCommandClient::new(base_url, String::new(), node_data.clone());
-    let cmd = DenoCommand {
-        inner: cmd,
-        spawned,
-        source,
-    };
-    tokio::spawn(async move {
-        while let Ok(Some(line)) = stdout.next_line().await {
-            tracing::debug!("{}", line);
+    let log_bytes = Arc::new(AtomicU64::new(0));
+    let heap_oom = Arc::new(AtomicBool::new(false));
+    let stderr_closed = Arc::new(AtomicBool::new(false));
+    tokio::spawn({
+        let log_bytes = log_bytes.clone();
+        async move {
+            while let Ok(Some(line)) = stdout.next_line().await {
+                log_bytes.fetch_add(line.len() as u64 + 1, Ordering::Relaxed);
+                tracing::debug!("{}", line);
+            }
         }
     });
+    // pooled workers live long enough to fill the stderr pipe
+    let mut stderr = BufReader::new(spawned.stderr.take().unwrap()).lines();
+    tokio::spawn({
+        let log_bytes = log_bytes.clone();
+        let heap_oom = heap_oom.clone();
+        let stderr_closed = stderr_closed.clone();
+        async move {
+            while let Ok(Some(line)) = stderr.next_line().await {
+                log_bytes.fetch_add(line.len() as u64 + 1, Ordering::Relaxed);
+                if is_heap_oom(&line) {
+                    heap_oom.store(true, Ordering::Relaxed);
+                }
+                tracing::warn!("{}", line);
+            }
+            stderr_closed.store(true, Ordering::Relaxed);
+        }
+    });
+
+    Ok(DenoWorker {
+        base_url: Url::parse(&format!("http://127.0.0.1:{port}")).unwrap(),
+        port,
+        child: Mutex::new(spawned),
+        log_bytes,
+        heap_oom,
+        stderr_closed,
+        _dir: dir,
+    })
+}
+
+/// Take a healthy worker from the pool or start a new one.
+async fn acquire_worker(
+    key: PoolKey,
+    source: &str,
+    node_data_json: &str,
+    deps: &str,
+    use_local_deps: bool,
+    limits: &ScriptLimits,
+) -> Result<Lease<DenoWorker>, CommandError> {
+    while let Some(mut lease) = pool().take(&key) {
+        if lease.worker.check_health().await {
+            tracing::debug!("reusing deno worker on port {}", lease.worker.port);
+            return Ok(lease);
+        }
+    }
+    let worker = spawn_worker(source, node_data_json, deps, use_local_deps, limits).await?;
+    Ok(Lease {
+        key,
+        worker,
+        invocations: 0,
+    })
+}
 
-    Ok(Box::new(cmd))
+/// Key of the workers that can run a script.
+///
+/// Workers are not shared between flow owners, so that module-level state of
+/// a script does not leak to other users.
+fn worker_key(
+    owner: &UserId,
+    source: &str,
+    node_data_json: &str,
+    deps: &str,
+    limits: &ScriptLimits,
+) -> PoolKey {
+    pool_key([
+        owner.as_bytes().as_slice(),
+        source.as_bytes(),
+        node_data_json.as_bytes(),
+        include!("/run.ts").as_bytes(),
+        deps.as_bytes(),
+        &limits.heap_mb.unwrap_or(0).to_le_bytes(),
+    ])
+}
+
+pub(crate) async fn new_owned(nd: NodeData) -> Result<Box<dyn CommandTrait>, CommandError> {
+    let source = source_from_config(&nd)
+        .ok_or_else(|| CommandError::msg("deno_script source/code not found"))?;
+    let use_local_deps = cfg!(feature = "local-deps") || cfg!(test);
+    let limits = ScriptLimits::deployment().for_node(&nd.config);
+
+    let mut node_data = nd.clone();
+    if let Some(obj) = node_data.config.as_object_mut() {
+        obj.remove("code");
+        obj.remove("source");
+    }
+    let node_data_json = serde_json::to_string(&node_data).context("serialize NodeData")?;
+    let deps = if use_local_deps {
+        include!("/deps_local.ts")
+    } else {
+        include!("/deps_jsr.ts")
+    };
+
+    Ok(Box::new(DenoCommand {
+        node_data,
+        node_data_json,
+        deps,
+        use_local_deps,
+        worker: RefCell::new(None),
+        limits,
+        source,
+    }))
 }
 
 pub fn new(nd: &NodeData) -> LocalBoxFuture<'static, Result<Box<dyn CommandTrait>, CommandError>> {
@@ -199,37 +378,119 @@ flow_lib::submit!(CommandDescription {
 });
 
 pub struct DenoCommand {
-    inner: RpcCommandClient,
-    spawned: Child,
+    node_data: NodeData,
+    node_data_json: String,
+    deps: &'static str,
+    use_local_deps: bool,
+    /// Worker of the last run, the flow owner is only known when running.
+    worker: RefCell<Option<Lease<DenoWorker>>>,
+    limits: ScriptLimits,
     source: String,
 }
 
+impl DenoCommand {
+    async fn lease(&self, owner: &UserId) -> Result<Lease<DenoWorker>, CommandError> {
+        let key = worker_key(
+            owner,
+            &self.source,
+            &self.node_data_json,
+            self.deps,
+            &self.limits,
+        );
+        let kept = self.worker.borrow_mut().take();
+        if let Some(mut lease) = kept {
+            if lease.key == key && lease.worker.check_health().await {
+                return Ok(lease);
+            }
+            pool().release(lease);
+        }
+        acquire_worker(
+            key,
+            &self.source,
+            &self.node_data_json,
+            self.deps,
+            self.use_local_deps,
+            &self.limits,
+        )
+        .await
+    }
+
+    fn keep(&self, lease: Lease<DenoWorker>) {
+        // another run of this node could have started a worker meanwhile
+        if let Some(previous) = self.worker.borrow_mut().replace(lease) {
+            pool().release(previous);
+        }
+    }
+
+    fn release_worker(&mut self) {
+        if let Some(lease) = self.worker.get_mut().take() {
+            pool().release(lease);
+        }
+    }
+}
+
+impl Drop for DenoCommand {
+    fn drop(&mut self) {
+        self.release_worker();
+    }
+}
+
 #[async_trait(?Send)]
 impl CommandTrait for DenoCommand {
     fn r#type(&self) -> flow_lib::CommandType {
         flow_lib::CommandType::Deno
     }
     fn name(&self) -> flow_lib::Name {
-        self.inner.name()
+        self.node_data.node_id.clone()
     }
     fn inputs(&self) -> Vec<flow_lib::CmdInputDescription> {
-        self.inner.inputs()
+        self.node_data.cmd_inputs()
     }
     fn outputs(&self) -> Vec<flow_lib::CmdOutputDescription> {
-        self.inner.outputs()
-    }
-    fn permissions(&self) -> flow_lib::command::prelude::Permissions {
-        self.inner.permissions()
+        self.node_data.cmd_outputs()
     }
     async fn run(
         &self,
-        ctx: flow_lib::context::CommandContext,
+        mut ctx: flow_lib::context::CommandContext,
         params: flow_lib::ValueSet,
     ) -> Result<flow_lib::value::Map, CommandError> {
-        self.inner.run(ctx, params).await
+        let host_calls = HostCalls::default();
+        host_calls.track(&mut ctx);
+        let mut lease = self.lease(&ctx.flow_owner().id).await?;
+        lease.invocations += 1;
+        let worker = &lease.worker;
+        let inner = RpcCommandClient::new(
+            worker.base_url.clone(),
+            String::new(),
+            self.node_data.clone(),
+        );
+
+        let pid = worker.child.lock().unwrap().id();
+        let result = tokio::select! {
+            result = inner.run(ctx, params) => match result {
+                Ok(output) => Ok(output),
+                Err(error) => Err(match self.limits.heap_mb {
+                    Some(heap_mb) if worker.died_of_heap_oom().await => {
+                        LimitExceeded::new(Limit::Heap, format!("max {heap_mb} MB")).into()
+                    }
+                    _ => match worker.exceeded_rlimit(&self.limits) {
+                        Some(exceeded) => exceeded.into(),
+                        None => error,
+                    },
+                }),
+            },
+            exceeded = self.limits.watch(pid, &worker.log_bytes, &host_calls) => {
+                worker.kill();
+                Err(exceeded.into())
+            }
+        };
+        self.keep(lease);
+        let output = result?;
+        self.limits.check_output(&output)?;
+        Ok(output)
     }
     async fn destroy(&mut self) {
-        self.spawned.kill().await.ok();
+        self.release_worker();
     }
     fn node_data(&self) -> client::NodeData {
         let mut data = default_node_data(self);
@@ -342,6 +603,39 @@ mod tests {
         assert_eq!(c, 25.0);
     }
 
+    #[actix_web::test]
+    async fn test_worker_reused() {
+        tracing_subscriber::fmt::try_init().ok();
+        const SOURCE: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/add.ts"));
+        const JSON: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/add.jsonc"));
+        let node_data_json = serde_json::to_string(&node_data(JSON, SOURCE)).unwrap();
+        let deps = include!("/deps_local.ts");
+        let limits = ScriptLimits::default();
+        let owner = UserId::new_v4();
+        let key = worker_key(&owner, SOURCE, &node_data_json, deps, &limits);
+        assert_ne!(
+            key,
+            worker_key(&UserId::new_v4(), SOURCE, &node_data_json, deps, &limits)
+        );
+
+        let lease = acquire_worker(key, SOURCE, &node_data_json, deps, true, &limits)
+            .await
+            .unwrap();
+        let port = lease.worker.port;
+        pool().release(lease);
+
+        let mut lease = acquire_worker(key, SOURCE, &node_data_json, deps, true, &limits)
+            .await
+            .unwrap();
+        assert_eq!(lease.worker.port, port);
+        assert!(lease.worker.check_health().await);
+
+        // recycled after too many runs
+        lease.invocations = pool().config().max_invocations;
+        pool().release(lease);
+        assert!(pool().take(&key).is_none());
+    }
+
     #[actix_web::test]
     async fn test_ctx_kv_reports_migration_guidance() {
         tracing_subscriber::fmt::try_init().ok();
@@ -426,9 +720,7 @@ export default class AdapterExecute implements lib.CommandTrait {
                 let observed = observed.clone();
                 async move {
                     *observed.lock().unwrap() = Some(req.instructions.clone());
-                    Ok(execute::Response {
-                        signature: Some(Signature::default()),
-                    })
+                    Ok(execute::Response::signature(Signature::default()))
                 }
             }
         }));
@@ -0,0 +1,330 @@
+//! Warm pool of Deno workers shared across flow runs.
+//!
+//! Workers are keyed by a hash of the flow owner and everything that is
+//! written into their working directory, so a worker is only reused for the
+//! exact same script, node data and dependency set of the same user.
+//! Module-level state in a script survives between runs of a pooled worker.
+
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+const DEFAULT_MAX_SIZE: usize = 16;
+const DEFAULT_IDLE_TIMEOUT_SECS: u64 = 300;
+const DEFAULT_MAX_INVOCATIONS: u32 = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolConfig {
+    /// Maximum number of idle workers, `0` disables pooling.
+    pub max_size: usize,
+    /// Idle workers older than this are killed, also when the pool is not used.
+    pub idle_timeout: Duration,
+    /// Workers are recycled after serving this many runs.
+    pub max_invocations: u32,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            max_size: DEFAULT_MAX_SIZE,
+            idle_timeout: Duration::from_secs(DEFAULT_IDLE_TIMEOUT_SECS),
+            max_invocations: DEFAULT_MAX_INVOCATIONS,
+        }
+    }
+}
+
+fn env_parse<T: std::str::FromStr>(name: &str) -> Option<T> {
+    std::env::var(name).ok().and_then(|s| s.trim().parse().ok())
+}
+
+impl PoolConfig {
+    /// How often idle workers are checked for expiry.
+    pub fn sweep_interval(&self) -> Duration {
+        (self.idle_timeout / 2).clamp(Duration::from_secs(1), Duration::from_secs(60))
+    }
+
+    /// Read `DENO_POOL_MAX_SIZE`, `DENO_POOL_IDLE_TIMEOUT_SECS` and
+    /// `DENO_POOL_MAX_INVOCATIONS`, falling back to the defaults.
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        Self {
+            max_size: env_parse("DENO_POOL_MAX_SIZE").unwrap_or(default.max_size),
+            idle_timeout: env_parse("DENO_POOL_IDLE_TIMEOUT_SECS")
+                .map(Duration::from_secs)
+                .unwrap_or(default.idle_timeout),
+            max_invocations: env_parse::<u32>("DENO_POOL_MAX_INVOCATIONS")
+                .unwrap_or(default.max_invocations)
+                .max(1),
+        }
+    }
+}
+
+pub type PoolKey = [u8; 32];
+
+/// Hash the parts that make up a worker, each one length-prefixed.
+pub fn pool_key<'a>(parts: impl IntoIterator<Item = &'a [u8]>) -> PoolKey {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update((part.len() as u64).to_le_bytes());
+        hasher.update(part);
+    }
+    hasher.finalize().into()
+}
+
+pub trait PoolWorker {
+    /// Cheap liveness check, called with the pool locked.
+    fn is_alive(&mut self) -> bool;
+}
+
+/// A worker checked out of the pool.
+pub struct Lease<W> {
+    pub key: PoolKey,
+    pub worker: W,
+    pub invocations: u32,
+}
+
+struct Idle<W> {
+    lease: Lease<W>,
+    since: Instant,
+}
+
+pub struct Pool<W> {
+    config: PoolConfig,
+    idle: Mutex<HashMap<PoolKey, Vec<Idle<W>>>>,
+}
+
+impl<W: PoolWorker> Pool<W> {
+    pub fn new(config: PoolConfig) -> Self {
+        Self {
+            config,
+            idle: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn config(&self) -> &PoolConfig {
+        &self.config
+    }
+
+    pub fn idle_count(&self) -> usize {
+        self.idle.lock().unwrap().values().map(Vec::len).sum()
+    }
+
+    /// Remove expired and dead workers, returning them.
+    fn sweep(&self, idle: &mut HashMap<PoolKey, Vec<Idle<W>>>) -> Vec<Idle<W>> {
+        let now = Instant::now();
+        let mut expired = Vec::new();
+        idle.retain(|_, workers| {
+            for mut w in std::mem::take(workers) {
+                if now.duration_since(w.since) < self.config.idle_timeout
+                    && w.lease.worker.is_alive()
+                {
+                    workers.push(w);
+                } else {
+                    expired.push(w);
+                }
+            }
+            !workers.is_empty()
+        });
+        expired
+    }
+
+    /// Drop idle workers that are past `idle_timeout` or dead, workers are
+    /// killed when dropped.
+    pub fn evict_expired(&self) {
+        let expired = self.sweep(&mut self.idle.lock().unwrap());
+        if !expired.is_empty() {
+            tracing::debug!("evicted {} idle worker(s)", expired.len());
+        }
+    }
+
+    /// Take the most recently used idle worker for `key`.
+    pub fn take(&self, key: &PoolKey) -> Option<Lease<W>> {
+        let mut idle = self.idle.lock().unwrap();
+        let _expired = self.sweep(&mut idle);
+        let workers = idle.get_mut(key)?;
+        let lease = workers.pop().map(|w| w.lease);
+        if workers.is_empty() {
+            idle.remove(key);
+        }
+        lease
+    }
+
+    /// Return a worker to the pool. Dead workers and workers that reached
+    /// `max_invocations` are dropped, and the oldest idle worker is evicted
+    /// when the pool is full.
+    pub fn release(&self, mut lease: Lease<W>) {
+        if self.config.max_size == 0
+            || lease.invocations >= self.config.max_invocations
+            || !lease.worker.is_alive()
+        {
+            return;
+        }
+
+        let mut idle = self.idle.lock().unwrap();
+        let _expired = self.sweep(&mut idle);
+        let count = idle.values().map(Vec::len).sum::<usize>();
+        if count >= self.config.max_size {
+            let oldest = idle
+                .iter()
+                .filter_map(|(key, workers)| workers.first().map(|w| (*key, w.since)))
+                .min_by_key(|(_, since)| *since)
+                .map(|(key, _)| key);
+            if let Some(key) = oldest {
+                let workers = idle.get_mut(&key).expect("key exists");
+                workers.remove(0);
+                if workers.is_empty() {
+                    idle.remove(&key);
+                }
+            }
+        }
+        idle.entry(lease.key).or_default().push(Idle {
+            lease,
+            since: Instant::now(),
+        });
+    }
+}
+
+impl<W: PoolWorker + Send + 'static> Pool<W> {
+    /// Call [`Pool::evict_expired`] every `interval` on a background thread,
+    /// so that idle workers expire without pool traffic.
+    pub fn spawn_sweeper(&'static self, interval: Duration) {
+        std::thread::Builder::new()
+            .name("deno-pool-sweeper".to_owned())
+            .spawn(move || {
+                loop {
+                    std::thread::sleep(interval);
+                    self.evict_expired();
+                }
+            })
+            .expect("spawn deno pool sweeper");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        sync::{
+            Arc,
+            atomic::{AtomicBool, Ordering},
+        },
+        thread::sleep,
+    };
+
+    struct FakeWorker {
+        id: u32,
+        alive: Arc<AtomicBool>,
+    }
+
+    impl PoolWorker for FakeWorker {
+        fn is_alive(&mut self) -> bool {
+            self.alive.load(Ordering::SeqCst)
+        }
+    }
+
+    fn lease(key: PoolKey, id: u32) -> Lease<FakeWorker> {
+        Lease {
+            key,
+            worker: FakeWorker {
+                id,
+                alive: Arc::new(AtomicBool::new(true)),
+            },
+            invocations: 0,
+        }
+    }
+
+    fn config(max_size: usize) -> PoolConfig {
+        PoolConfig {
+            max_size,
+            idle_timeout: Duration::from_secs(60),
+            max_invocations: 3,
+        }
+    }
+
+    #[test]
+    fn test_pool_key() {
+        assert_ne!(
+            pool_key([b"ab".as_slice(), b"c"]),
+            pool_key([b"a".as_slice(), b"bc"])
+        );
+        assert_eq!(pool_key([b"a".as_slice()]), pool_key([b"a".as_slice()]));
+    }
+
+    #[test]
+    fn test_reuse() {
+        let pool = Pool::new(config(4));
+        let (a, b) = (pool_key([b"a".as_slice()]), pool_key([b"b".as_slice()]));
+        pool.release(lease(a, 1));
+        assert!(pool.take(&b).is_none());
+        let taken = pool.take(&a).unwrap();
+        assert_eq!(taken.worker.id, 1);
+        assert_eq!(pool.idle_count(), 0);
+    }
+
+    #[test]
+    fn test_recycle_and_health() {
+        let pool = Pool::new(config(4));
+        let key = pool_key([b"a".as_slice()]);
+
+        let mut used = lease(key, 1);
+        used.invocations = 3;
+        pool.release(used);
+        assert_eq!(pool.idle_count(), 0);
+
+        let dead = lease(key, 2);
+        let alive = dead.worker.alive.clone();
+        pool.release(dead);
+        assert_eq!(pool.idle_count(), 1);
+        alive.store(false, Ordering::SeqCst);
+        assert!(pool.take(&key).is_none());
+    }
+
+    #[test]
+    fn test_max_size_and_idle_timeout() {
+        let pool = Pool::new(config(2));
+        let keys = [b"a", b"b", b"c"].map(|k| pool_key([k.as_slice()]));
+        for (id, key) in keys.iter().enumerate() {
+            pool.release(lease(*key, id as u32));
+            sleep(Duration::from_millis(5));
+        }
+        assert_eq!(pool.idle_count(), 2);
+        assert!(pool.take(&keys[0]).is_none());
+        assert!(pool.take(&keys[2]).is_some());
+
+        let pool = Pool::new(PoolConfig {
+            idle_timeout: Duration::from_millis(10),
+            ..config(2)
+        });
+        pool.release(lease(keys[0], 0));
+        sleep(Duration::from_millis(20));
+        assert!(pool.take(&keys[0]).is_none());
+    }
+
+    #[test]
+    fn test_sweeper() {
+        let pool = Box::leak(Box::new(Pool::new(PoolConfig {
+            idle_timeout: Duration::from_millis(10),
+            ..config(2)
+        })));
+        pool.spawn_sweeper(Duration::from_millis(5));
+        let idle = lease(pool_key([b"a".as_slice()]), 0);
+        let alive = idle.worker.alive.clone();
+        pool.release(idle);
+        assert_eq!(pool.idle_count(), 1);
+
+        // no take or release after this
+        sleep(Duration::from_millis(100));
+        assert_eq!(pool.idle_count(), 0);
+        assert_eq!(Arc::strong_count(&alive), 1, "worker is dropped");
+    }
+
+    #[test]
+    fn test_disabled() {
+        let pool = Pool::new(config(0));
+        pool.release(lease(pool_key([b"a".as_slice()]), 0));
+        assert_eq!(pool.idle_count(), 0);
+    }
+}
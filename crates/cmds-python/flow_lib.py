@@ -0,0 +1,339 @@
+"""
+Python equivalent of @space-operator/flow-lib-bun.
+
+Converts flow values to and from Python objects, and exposes the command
+context (logs, signature requests and instruction execution) to scripts.
+Only the standard library is used so that it works in any interpreter.
+"""
+
+import base64
+import json
+import time
+import urllib.request
+from decimal import Decimal
+
+_B58_ALPHABET = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz"
+_B58_INDEX = {c: i for i, c in enumerate(_B58_ALPHABET)}
+
+LOG_TIMEOUT_SECS = 2
+
+
+def b58encode(data: bytes) -> str:
+    n = int.from_bytes(data, "big")
+    out = ""
+    while n > 0:
+        n, r = divmod(n, 58)
+        out = _B58_ALPHABET[r] + out
+    pad = len(data) - len(data.lstrip(b"\0"))
+    return "1" * pad + out
+
+
+def b58decode(s: str) -> bytes:
+    n = 0
+    for c in s:
+        if c not in _B58_INDEX:
+            raise ValueError(f"invalid base58 character: {c!r}")
+        n = n * 58 + _B58_INDEX[c]
+    body = n.to_bytes((n.bit_length() + 7) // 8, "big")
+    pad = len(s) - len(s.lstrip("1"))
+    return b"\0" * pad + body
+
+
+def _pubkey_bytes(pubkey) -> bytes:
+    if isinstance(pubkey, str):
+        pubkey = b58decode(pubkey)
+    pubkey = bytes(pubkey)
+    if len(pubkey) != 32:
+        raise ValueError(f"expected a 32-byte public key, got {len(pubkey)} bytes")
+    return pubkey
+
+
+def from_value(value: dict):
+    """Convert a JSON-encoded flow value to a Python object."""
+    ((tag, v),) = value.items()
+    if tag == "N":
+        return None
+    if tag == "S" or tag == "B":
+        return v
+    if tag in ("U", "I", "U1", "I1"):
+        return int(v)
+    if tag == "F":
+        return float(v)
+    if tag == "D":
+        return Decimal(v)
+    if tag in ("B3", "B6"):
+        return b58decode(v)
+    if tag == "BY":
+        return base64.b64decode(v)
+    if tag == "A":
+        return [from_value(x) for x in v]
+    if tag == "M":
+        return {k: from_value(x) for k, x in v.items()}
+    raise ValueError(f"unknown value type: {tag}")
+
+
+def to_value(obj) -> dict:
+    """Convert a Python object to a JSON-encoded flow value."""
+    if obj is None:
+        return {"N": 0}
+    if isinstance(obj, bool):
+        return {"B": obj}
+    if isinstance(obj, int):
+        if 0 <= obj < 2**64:
+            return {"U": str(obj)}
+        if -(2**63) <= obj < 0:
+            return {"I": str(obj)}
+        if 0 <= obj < 2**128:
+            return {"U1": str(obj)}
+        if -(2**127) <= obj < 0:
+            return {"I1": str(obj)}
+        raise ValueError(f"integer out of range: {obj}")
+    if isinstance(obj, float):
+        return {"F": repr(obj)}
+    if isinstance(obj, Decimal):
+        return {"D": str(obj)}
+    if isinstance(obj, str):
+        return {"S": obj}
+    if isinstance(obj, (bytes, bytearray, memoryview)):
+        obj = bytes(obj)
+        if len(obj) == 32:
+            return {"B3": b58encode(obj)}
+        if len(obj) == 64:
+            return {"B6": b58encode(obj)}
+        return {"BY": base64.b64encode(obj).decode()}
+    if isinstance(obj, (list, tuple)):
+        return {"A": [to_value(x) for x in obj]}
+    if isinstance(obj, dict):
+        return {"M": to_map(obj)}
+    raise TypeError(f"cannot convert {type(obj).__name__} to a flow value")
+
+
+def to_map(obj: dict) -> dict:
+    return {str(k): to_value(v) for k, v in obj.items()}
+
+
+def _msgpack(obj) -> bytes:
+    """Minimal MessagePack encoder, enough for `Instructions`."""
+    if obj is None:
+        return b"\xc0"
+    if isinstance(obj, bool):
+        return b"\xc3" if obj else b"\xc2"
+    if isinstance(obj, int):
+        if 0 <= obj < 0x80:
+            return bytes([obj])
+        if -32 <= obj < 0:
+            return (obj & 0xFF).to_bytes(1, "big")
+        if 0 <= obj < 2**64:
+            return b"\xcf" + obj.to_bytes(8, "big")
+        return b"\xd3" + obj.to_bytes(8, "big", signed=True)
+    if isinstance(obj, (bytes, bytearray)):
+        n = len(obj)
+        head = b"\xc4" + n.to_bytes(1, "big") if n < 2**8 else (
+            b"\xc5" + n.to_bytes(2, "big") if n < 2**16 else b"\xc6" + n.to_bytes(4, "big")
+        )
+        return head + bytes(obj)
+    if isinstance(obj, str):
+        data = obj.encode()
+        n = len(data)
+        if n < 32:
+            head = bytes([0xA0 | n])
+        elif n < 2**8:
+            head = b"\xd9" + n.to_bytes(1, "big")
+        elif n < 2**16:
+            head = b"\xda" + n.to_bytes(2, "big")
+        else:
+            head = b"\xdb" + n.to_bytes(4, "big")
+        return head + data
+    if isinstance(obj, (list, tuple)):
+        n = len(obj)
+        head = bytes([0x90 | n]) if n < 16 else (
+            b"\xdc" + n.to_bytes(2, "big") if n < 2**16 else b"\xdd" + n.to_bytes(4, "big")
+        )
+        return head + b"".join(_msgpack(x) for x in obj)
+    if isinstance(obj, dict):
+        n = len(obj)
+        head = bytes([0x80 | n]) if n < 16 else (
+            b"\xde" + n.to_bytes(2, "big") if n < 2**16 else b"\xdf" + n.to_bytes(4, "big")
+        )
+        return head + b"".join(_msgpack(k) + _msgpack(v) for k, v in obj.items())
+    raise TypeError(f"cannot encode {type(obj).__name__}")
+
+
+class Instructions:
+    """
+    Instructions to execute with `Context.execute`.
+
+    `signers` are 64-byte keypairs, or public keys (base58 strings or
+    32 bytes) of wallets that will be asked for a signature.
+    `instructions` are dicts with `program_id`, `accounts` (dicts with
+    `pubkey`, `is_signer` and `is_writable`) and `data` bytes.
+    """
+
+    def __init__(self, fee_payer, signers, instructions):
+        self.fee_payer = _pubkey_bytes(fee_payer)
+        self.signers = signers
+        self.instructions = instructions
+
+    def _signer(self, signer):
+        if isinstance(signer, (bytes, bytearray)) and len(signer) == 64:
+            return bytes(signer)
+        return {"public_key": _pubkey_bytes(signer), "token": None}
+
+    def encode(self) -> str:
+        data = {
+            "fee_payer": self.fee_payer,
+            "signers": [self._signer(s) for s in self.signers],
+            "instructions": [
+                {
+                    "program_id": _pubkey_bytes(i["program_id"]),
+                    "accounts": [
+                        {
+                            "pubkey": _pubkey_bytes(a["pubkey"]),
+                            "is_signer": bool(a["is_signer"]),
+                            "is_writable": bool(a["is_writable"]),
+                        }
+                        for a in i["accounts"]
+                    ],
+                    "data": bytes(i["data"]),
+                }
+                for i in self.instructions
+            ],
+            "lookup_tables": None,
+        }
+        return base64.b64encode(_msgpack(data)).decode()
+
+
+def _call(service: dict, input, timeout: float):
+    body = json.dumps(
+        {
+            "envelope": "",
+            "svc_name": service["name"],
+            "svc_id": service["id"],
+            "input": input,
+        }
+    ).encode()
+    url = service["base_url"].rstrip("/") + "/call"
+    req = urllib.request.Request(
+        url, data=body, headers={"content-type": "application/json"}, method="POST"
+    )
+    with urllib.request.urlopen(req, timeout=timeout) as resp:
+        result = json.load(resp)
+    if result.get("success") is False:
+        raise RuntimeError(str(result.get("data")))
+    return result.get("data")
+
+
+class LogWriter:
+    """File-like object sending each line to the node logs."""
+
+    def __init__(self, ctx: "Context", level: str):
+        self._ctx = ctx
+        self._level = level
+        self._buffer = ""
+
+    def write(self, s: str) -> int:
+        self._buffer += s
+        *lines, self._buffer = self._buffer.split("\n")
+        for line in lines:
+            self._ctx.log(self._level, line)
+        return len(s)
+
+    def flush(self):
+        if self._buffer:
+            self._ctx.log(self._level, self._buffer)
+            self._buffer = ""
+
+
+class Context:
+    """Services and information about the current invocation."""
+
+    def __init__(self, proxy: dict):
+        self._proxy = proxy
+        self._data = proxy["data"]
+
+    @property
+    def flow_owner(self) -> dict:
+        return self._data["flow"]["set"]["flow_owner"]
+
+    @property
+    def started_by(self) -> dict:
+        return self._data["flow"]["set"]["started_by"]
+
+    @property
+    def environment(self) -> dict:
+        return self._data["flow"]["environment"]
+
+    @property
+    def endpoints(self) -> dict:
+        return self._data["flow"]["set"]["endpoints"]
+
+    @property
+    def solana_url(self) -> str:
+        return self._data["flow"]["set"]["solana"]["url"]
+
+    @property
+    def flow_run_id(self) -> str:
+        return self._data["flow"]["flow_run_id"]
+
+    @property
+    def node_id(self) -> str:
+        return self._data["node_id"]
+
+    @property
+    def times(self) -> int:
+        return self._data["times"]
+
+    def _http_timeout(self, extra_secs: float = 0) -> float:
+        return max(1, self._data["flow"]["set"]["http"]["timeout_in_secs"] + extra_secs)
+
+    def log(self, level: str, *args):
+        """Send a log line with `level` (INFO, WARN, ERROR, DEBUG or TRACE)."""
+        content = " ".join(a if isinstance(a, str) else repr(a) for a in args)
+        try:
+            _call(self._proxy["log"], {"level": level, "content": content}, LOG_TIMEOUT_SECS)
+        except Exception:
+            pass
+
+    def _request_signature(self, pubkey, data: bytes, kind: str):
+        output = _call(
+            self._proxy["signer"],
+            {
+                "id": None,
+                "time": int(time.time() * 1000),
+                "pubkey": b58encode(_pubkey_bytes(pubkey)),
+                "message": base64.b64encode(bytes(data)).decode(),
+                "timeout": 60 * 2,
+                "kind": kind,
+                "flow_run_id": self.flow_run_id,
+                "signatures": None,
+            },
+            self._http_timeout(20),
+        )
+        new_message = output.get("new_message")
+        return (
+            b58decode(output["signature"]),
+            base64.b64decode(new_message) if new_message else None,
+        )
+
+    def request_signature(self, pubkey, message: bytes):
+        """
+        Request a signature of a serialized Solana transaction message from
+        the owner of `pubkey`. Returns `(signature, new_message)`, where
+        `new_message` is the message updated by the wallet, or `None`.
+        """
+        return self._request_signature(pubkey, message, "transaction_message")
+
+    def request_message_signature(self, pubkey, message: bytes) -> bytes:
+        """Request a signature of an arbitrary message from the owner of `pubkey`."""
+        signature, _ = self._request_signature(pubkey, message, "message")
+        return signature
+
+    def execute(self, instructions: Instructions, output: dict):
+        """Execute `instructions`, returning the transaction signature if any."""
+        data = _call(
+            self._proxy["execute"],
+            {"instructions": instructions.encode(), "output": to_map(output)},
+            self._http_timeout(),
+        )
+        signature = data.get("signature") if data else None
+        return b58decode(signature) if signature else None
@@ -0,0 +1,119 @@
+"""
+Python run harness, equivalent to cmds-bun/run.ts.
+
+Reads node-data.json and cmd.py from the same directory, starts the RPC
+server on a random port, and prints the port to stdout.
+
+cmd.py defines either `main(ctx, inputs)` or a `Command` class whose
+instances have `run(ctx, inputs)`. Both can be `async`.
+"""
+
+import asyncio
+import contextlib
+import importlib.util
+import inspect
+import json
+import os
+import sys
+import traceback
+from http.server import BaseHTTPRequestHandler, HTTPServer
+
+import flow_lib
+
+RUN_SVC = "run"
+HERE = os.path.dirname(os.path.abspath(__file__))
+
+
+def load_command():
+    with open(os.path.join(HERE, "node-data.json")) as f:
+        node_data = json.load(f)
+
+    spec = importlib.util.spec_from_file_location("user_command", os.path.join(HERE, "cmd.py"))
+    module = importlib.util.module_from_spec(spec)
+    sys.modules["user_command"] = module
+    spec.loader.exec_module(module)
+
+    if hasattr(module, "Command"):
+        cls = module.Command
+        try:
+            instance = cls(node_data)
+        except TypeError:
+            instance = cls()
+        return instance.run
+    if hasattr(module, "main"):
+        return module.main
+    raise RuntimeError("cmd.py must define `main(ctx, inputs)` or a `Command` class")
+
+
+def call(run, ctx, inputs):
+    result = run(ctx, inputs)
+    if inspect.isawaitable(result):
+        result = asyncio.run(_await(result))
+    return result
+
+
+async def _await(awaitable):
+    return await awaitable
+
+
+class Handler(BaseHTTPRequestHandler):
+    run = None
+
+    def log_message(self, format, *args):
+        pass
+
+    def _reply(self, body: dict):
+        data = json.dumps(body).encode()
+        self.send_response(200)
+        self.send_header("content-type", "application/json")
+        self.send_header("content-length", str(len(data)))
+        self.end_headers()
+        self.wfile.write(data)
+
+    def do_POST(self):
+        if self.path != "/call":
+            self.send_error(404)
+            return
+        length = int(self.headers.get("content-length", 0))
+        req = json.loads(self.rfile.read(length))
+        if req.get("svc_name") != RUN_SVC:
+            self._reply({"envelope": req.get("envelope", ""), "success": False, "data": "not found"})
+            return
+
+        ctx = flow_lib.Context(req["input"]["ctx"])
+        log = flow_lib.LogWriter(ctx, "INFO")
+        try:
+            inputs = {k: flow_lib.from_value(v) for k, v in req["input"]["params"].items()}
+            with contextlib.redirect_stdout(log):
+                outputs = call(Handler.run, ctx, inputs)
+            if outputs is None:
+                outputs = {}
+            if not isinstance(outputs, dict):
+                raise TypeError(f"command must return a dict, got {type(outputs).__name__}")
+            data, success = {"Ok": flow_lib.to_map(outputs)}, True
+        except Exception as error:
+            traceback.print_exc(file=sys.stderr)
+            data, success = {"Err": f"{type(error).__name__}: {error}"}, False
+        finally:
+            log.flush()
+
+        self._reply({"envelope": req.get("envelope", ""), "success": success, "data": data})
+
+
+def main():
+    try:
+        Handler.run = load_command()
+        server = HTTPServer(("127.0.0.1", 0), Handler)
+    except Exception:
+        sys.stderr.write("[python-rpc] startup:\n")
+        traceback.print_exc(file=sys.stderr)
+        sys.exit(1)
+
+    # The Rust backend reads the first line to get the port
+    sys.stdout.write(f"{server.server_address[1]}\n")
+    sys.stdout.flush()
+    server.serve_forever()
+
+
+if __name__ == "__main__":
+    main()
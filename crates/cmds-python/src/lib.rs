@@ -0,0 +1,942 @@
+//! Python script command runner.
+//!
+//! Parallel to `cmds-bun`: spawns a Python subprocess running `run.py`, which
+//! speaks the same HTTP RPC protocol (POST /call), so the Rust side is the
+//! same. Scripts use the embedded `flow_lib.py` for values and context calls.
+
+use anyhow::Context as _;
+use flow_lib::{
+    CommandType, UserId,
+    command::{
+        CommandDescription, CommandError, CommandTrait, MatchCommand, MatchName, default_node_data,
+        limits::{HostCalls, Limit, LimitExceeded, ScriptLimits},
+        prelude::{Either, async_trait},
+    },
+    config::client::{self, NodeData},
+    utils::LocalBoxFuture,
+};
+use flow_rpc::client::RpcCommandClient;
+use std::{
+    collections::VecDeque,
+    path::Path,
+    process::Stdio,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+use tempfile::{TempDir, tempdir};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, BufReader},
+    process::{Child, Command},
+    sync::Mutex as AsyncMutex,
+};
+use tokio_util::sync::CancellationToken;
+use url::Url;
+use venv::Venv;
+
+#[cfg(unix)]
+use std::os::unix::process::ExitStatusExt;
+
+pub mod venv;
+
+const STDERR_TAIL_LIMIT: usize = 64;
+const DEFAULT_PYTHON_STARTUP_TIMEOUT_SECS: u64 = 30;
+const DEFAULT_PYTHON_RUN_TIMEOUT_SECS: u64 = 180;
+const PYTHON_ENV_ALLOWLIST: &[&str] = &[
+    "ALL_PROXY",
+    "HOME",
+    "HTTP_PROXY",
+    "HTTPS_PROXY",
+    "LANG",
+    "LC_ALL",
+    "LOGNAME",
+    "NO_PROXY",
+    "PATH",
+    "PIP_CERT",
+    "PIP_INDEX_URL",
+    "REQUESTS_CA_BUNDLE",
+    "SSL_CERT_DIR",
+    "SSL_CERT_FILE",
+    "TEMP",
+    "TMP",
+    "TMPDIR",
+    "TZ",
+    "USER",
+];
+
+const RUN_PY: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/run.py"));
+const FLOW_LIB_PY: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/flow_lib.py"));
+
+fn source_from_config(nd: &NodeData) -> Option<String> {
+    ["source", "code"].into_iter().find_map(|key| {
+        nd.config.get(key).and_then(|json| {
+            match flow_lib::command::parse_value_tagged_or_json(json.clone()) {
+                flow_lib::value::Value::String(s) => Some(s),
+                _ => None,
+            }
+        })
+    })
+}
+
+pub(crate) fn duration_from_env(name: &str, default_secs: u64) -> Duration {
+    std::env::var(name)
+        .ok()
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .filter(|secs| *secs > 0)
+        .map(Duration::from_secs)
+        .unwrap_or_else(|| Duration::from_secs(default_secs))
+}
+
+fn python_startup_timeout() -> Duration {
+    duration_from_env(
+        "SPACE_OPERATOR_PYTHON_STARTUP_TIMEOUT_SECS",
+        DEFAULT_PYTHON_STARTUP_TIMEOUT_SECS,
+    )
+}
+
+fn python_run_timeout() -> Duration {
+    duration_from_env(
+        "SPACE_OPERATOR_PYTHON_TIMEOUT_SECS",
+        DEFAULT_PYTHON_RUN_TIMEOUT_SECS,
+    )
+}
+
+pub(crate) fn copy_allowed_environment(command: &mut Command) {
+    command.env_clear();
+    for key in PYTHON_ENV_ALLOWLIST {
+        if let Some(value) = std::env::var_os(key) {
+            command.env(key, value);
+        }
+    }
+    command.env("PYTHONIOENCODING", "utf-8");
+    command.env("PYTHONDONTWRITEBYTECODE", "1");
+}
+
+fn configure_python_process(command: &mut Command) {
+    #[cfg(unix)]
+    command.process_group(0);
+}
+
+fn push_stderr_line(stderr_tail: &Arc<Mutex<VecDeque<String>>>, line: String) {
+    let mut stderr_tail = stderr_tail.lock().unwrap();
+    if stderr_tail.len() >= STDERR_TAIL_LIMIT {
+        stderr_tail.pop_front();
+    }
+    stderr_tail.push_back(line);
+}
+
+fn stderr_tail_text(stderr_tail: &Arc<Mutex<VecDeque<String>>>) -> Option<String> {
+    let stderr_tail = stderr_tail.lock().unwrap();
+    (!stderr_tail.is_empty()).then(|| stderr_tail.iter().cloned().collect::<Vec<_>>().join("\n"))
+}
+
+fn exit_status_summary(status: std::process::ExitStatus) -> String {
+    if let Some(code) = status.code() {
+        return format!("exit code {code}");
+    }
+
+    #[cfg(unix)]
+    if let Some(signal) = status.signal() {
+        return format!("signal {signal}");
+    }
+
+    "unknown exit status".to_owned()
+}
+
+fn runtime_failure_details(
+    started_at: Instant,
+    status: std::process::ExitStatus,
+    stderr_tail: &Arc<Mutex<VecDeque<String>>>,
+) -> String {
+    let elapsed_ms = started_at.elapsed().as_millis();
+    let mut details = format!(
+        "python subprocess exited after {elapsed_ms}ms ({})",
+        exit_status_summary(status)
+    );
+    if let Some(stderr) = stderr_tail_text(stderr_tail) {
+        details.push_str("\nlast python stderr:\n");
+        details.push_str(&stderr);
+    }
+    details
+}
+
+async fn terminate_child_process(child: &mut Child) -> std::io::Result<std::process::ExitStatus> {
+    #[cfg(unix)]
+    {
+        if let Some(pid) = child.id() {
+            let result = unsafe { libc::kill(-(pid as i32), libc::SIGKILL) };
+            if result == -1 {
+                let error = std::io::Error::last_os_error();
+                if error.raw_os_error() != Some(libc::ESRCH) {
+                    return Err(error);
+                }
+            }
+        } else {
+            child.start_kill()?;
+        }
+        child.wait().await
+    }
+
+    #[cfg(not(unix))]
+    {
+        child.start_kill()?;
+        child.wait().await
+    }
+}
+
+fn sanitized_node_data(mut nd: NodeData) -> NodeData {
+    if let Some(obj) = nd.config.as_object_mut() {
+        obj.remove("code");
+        obj.remove("source");
+    }
+    nd
+}
+
+async fn startup_failure_details(mut child: Child, summary: String) -> CommandError {
+    let status = terminate_child_process(&mut child).await.ok();
+
+    let mut stderr = String::new();
+    if let Some(pipe) = child.stderr.take() {
+        let mut reader = BufReader::new(pipe);
+        if let Err(error) = reader.read_to_string(&mut stderr).await {
+            tracing::warn!("read error: {}", error);
+        }
+    }
+
+    let mut message = summary;
+    if let Some(status) = status {
+        message.push_str(&format!(" ({})", exit_status_summary(status)));
+    }
+    let stderr = stderr.trim();
+    if !stderr.is_empty() {
+        message.push('\n');
+        message.push_str(stderr);
+    }
+    CommandError::msg(message)
+}
+
+struct RunningPython {
+    base_url: Url,
+    child: Child,
+    started_at: Instant,
+    stderr_tail: Arc<Mutex<VecDeque<String>>>,
+    /// Bytes written to stdout and stderr.
+    log_bytes: Arc<AtomicU64>,
+    _dir: TempDir,
+}
+
+async fn write_script_files(
+    dir: &Path,
+    source: &str,
+    node_data: &NodeData,
+) -> Result<(), CommandError> {
+    tokio::fs::write(dir.join("cmd.py"), source)
+        .await
+        .context("write cmd.py")?;
+
+    let node_data_json = serde_json::to_string(node_data).context("serialize NodeData")?;
+    tokio::fs::write(dir.join("node-data.json"), node_data_json)
+        .await
+        .context("write node-data.json")?;
+
+    tokio::fs::write(dir.join("run.py"), RUN_PY)
+        .await
+        .context("write run.py")?;
+
+    tokio::fs::write(dir.join("flow_lib.py"), FLOW_LIB_PY)
+        .await
+        .context("write flow_lib.py")?;
+
+    Ok(())
+}
+
+async fn spawn_running_python(
+    source: &str,
+    node_data: &NodeData,
+    venv: &Venv,
+    owner: &UserId,
+    limits: &ScriptLimits,
+) -> Result<RunningPython, CommandError> {
+    let python = venv.python(owner, limits).await?;
+
+    let dir = tempdir()?;
+    write_script_files(dir.path(), source, node_data).await?;
+
+    let mut command = Command::new(&python);
+    configure_python_process(&mut command);
+    copy_allowed_environment(&mut command);
+    #[cfg(unix)]
+    limits.apply_rlimits(&mut command);
+
+    let mut spawned = command
+        .current_dir(dir.path())
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        // unbuffered, and ignore PYTHON* variables and the user site directory
+        .args(["-u", "-E", "-s", "run.py"])
+        .spawn()
+        .with_context(|| format!("spawn {}", python.display()))?;
+
+    let startup_timeout = python_startup_timeout();
+    let mut stdout = BufReader::new(spawned.stdout.take().unwrap()).lines();
+    let port = match tokio::time::timeout(startup_timeout, stdout.next_line()).await {
+        Ok(line) => match line? {
+            Some(line) => line.parse::<u16>().context("parse port")?,
+            None => {
+                return Err(startup_failure_details(
+                    spawned,
+                    "could not start python command".to_owned(),
+                )
+                .await);
+            }
+        },
+        Err(_) => {
+            return Err(startup_failure_details(
+                spawned,
+                format!(
+                    "timed out waiting {}s for python command startup",
+                    startup_timeout.as_secs()
+                ),
+            )
+            .await);
+        }
+    };
+    let started_at = Instant::now();
+
+    let stderr_tail = Arc::new(Mutex::new(VecDeque::new()));
+    let stderr_tail_reader = Arc::clone(&stderr_tail);
+    let log_bytes = Arc::new(AtomicU64::new(0));
+    let stderr_log_bytes = Arc::clone(&log_bytes);
+    let stderr = spawned.stderr.take().unwrap();
+    tokio::spawn(async move {
+        let mut stderr = BufReader::new(stderr).lines();
+        while let Ok(Some(line)) = stderr.next_line().await {
+            stderr_log_bytes.fetch_add(line.len() as u64 + 1, Ordering::Relaxed);
+            tracing::warn!("{}", line);
+            push_stderr_line(&stderr_tail_reader, line);
+        }
+    });
+
+    let stdout_log_bytes = Arc::clone(&log_bytes);
+    tokio::spawn(async move {
+        while let Ok(Some(line)) = stdout.next_line().await {
+            stdout_log_bytes.fetch_add(line.len() as u64 + 1, Ordering::Relaxed);
+            tracing::debug!("{}", line);
+        }
+    });
+
+    Ok(RunningPython {
+        base_url: Url::parse(&format!("http://127.0.0.1:{port}")).unwrap(),
+        child: spawned,
+        started_at,
+        stderr_tail,
+        log_bytes,
+        _dir: dir,
+    })
+}
+
+async fn terminate_running_python(state: &mut RunningPython, reason: &str) -> String {
+    match terminate_child_process(&mut state.child).await {
+        Ok(status) => format!(
+            "{reason}\n{}",
+            runtime_failure_details(state.started_at, status, &state.stderr_tail)
+        ),
+        Err(error) => {
+            let mut details = format!("{reason}\nfailed to terminate python subprocess: {error}");
+            if let Some(stderr) = stderr_tail_text(&state.stderr_tail) {
+                details.push_str("\nlast python stderr:\n");
+                details.push_str(&stderr);
+            }
+            details
+        }
+    }
+}
+
+pub(crate) async fn new_owned(nd: NodeData) -> Result<Box<dyn CommandTrait>, CommandError> {
+    let source = source_from_config(&nd)
+        .ok_or_else(|| CommandError::msg("python command source/code not found"))?;
+    Ok(Box::new(PythonCommand {
+        limits: ScriptLimits::deployment().for_node(&nd.config),
+        venv: Venv::from_config(&nd.config)?,
+        node_data: sanitized_node_data(nd),
+        source,
+        running: AsyncMutex::new(None),
+    }))
+}
+
+pub fn new(nd: &NodeData) -> LocalBoxFuture<'static, Result<Box<dyn CommandTrait>, CommandError>> {
+    let nd = nd.clone();
+    Box::pin(new_owned(nd))
+}
+
+flow_lib::submit!(CommandDescription {
+    matcher: MatchCommand {
+        r#type: CommandType::Python,
+        name: MatchName::Regex(std::borrow::Cow::Borrowed("")),
+    },
+    fn_new: Either::Right(new),
+});
+
+pub struct PythonCommand {
+    node_data: NodeData,
+    source: String,
+    venv: Venv,
+    limits: ScriptLimits,
+    running: AsyncMutex<Option<RunningPython>>,
+}
+
+enum PythonRunOutcome {
+    Success(flow_lib::value::Map),
+    Failure {
+        error: CommandError,
+        diagnostics: Option<String>,
+        reset_process: bool,
+    },
+    LimitExceeded {
+        limit: Limit,
+        details: String,
+    },
+    Canceled {
+        details: String,
+    },
+}
+
+impl PythonCommand {
+    async fn ensure_running(
+        &self,
+        running: &mut Option<RunningPython>,
+        owner: &UserId,
+    ) -> Result<(), CommandError> {
+        let needs_spawn = match running.as_mut() {
+            Some(state) => match state.child.try_wait() {
+                Ok(Some(status)) => {
+                    tracing::warn!(
+                        "{}",
+                        runtime_failure_details(state.started_at, status, &state.stderr_tail)
+                    );
+                    true
+                }
+                Ok(None) => false,
+                Err(error) => {
+                    tracing::warn!("python subprocess status check failed: {}", error);
+                    true
+                }
+            },
+            None => true,
+        };
+
+        if needs_spawn {
+            *running = Some(
+                spawn_running_python(
+                    &self.source,
+                    &self.node_data,
+                    &self.venv,
+                    owner,
+                    &self.limits,
+                )
+                .await?,
+            );
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait(?Send)]
+impl CommandTrait for PythonCommand {
+    fn r#type(&self) -> flow_lib::CommandType {
+        flow_lib::CommandType::Python
+    }
+    fn name(&self) -> flow_lib::Name {
+        self.node_data.node_id.clone()
+    }
+    fn inputs(&self) -> Vec<flow_lib::CmdInputDescription> {
+        self.node_data.cmd_inputs()
+    }
+    fn outputs(&self) -> Vec<flow_lib::CmdOutputDescription> {
+        self.node_data.cmd_outputs()
+    }
+    async fn run(
+        &self,
+        mut ctx: flow_lib::context::CommandContext,
+        params: flow_lib::ValueSet,
+    ) -> Result<flow_lib::value::Map, CommandError> {
+        let cancel_token = ctx.get::<CancellationToken>().cloned();
+        // the run timeout caps the wall clock, so that host calls are not counted
+        let timeout = python_run_timeout().as_secs();
+        let limits = ScriptLimits {
+            wall_clock_secs: Some(
+                self.limits
+                    .wall_clock_secs
+                    .map_or(timeout, |secs| secs.min(timeout)),
+            ),
+            ..self.limits
+        };
+        let host_calls = HostCalls::default();
+        host_calls.track(&mut ctx);
+        let owner = ctx.flow_owner().id;
+        let mut running = self.running.lock().await;
+        self.ensure_running(&mut running, &owner).await?;
+
+        let outcome = {
+            let state = running
+                .as_mut()
+                .expect("ensure_running always populates the Python runtime");
+            let client = RpcCommandClient::new(
+                state.base_url.clone(),
+                String::new(),
+                self.node_data.clone(),
+            );
+            let run = client.run(ctx, params);
+            tokio::pin!(run);
+            let pid = state.child.id();
+            let log_bytes = Arc::clone(&state.log_bytes);
+
+            tokio::select! {
+                _ = async {
+                    match &cancel_token {
+                        Some(cancel_token) => cancel_token.cancelled().await,
+                        None => std::future::pending().await,
+                    }
+                } => PythonRunOutcome::Canceled {
+                    details: terminate_running_python(state, "python command canceled").await,
+                },
+                exceeded = limits.watch(pid, &log_bytes, &host_calls) => PythonRunOutcome::LimitExceeded {
+                    limit: exceeded.limit,
+                    details: terminate_running_python(state, &exceeded.detail).await,
+                },
+                result = &mut run => match result {
+                    Ok(output) => PythonRunOutcome::Success(output),
+                    Err(error) => match state.child.try_wait() {
+                        Ok(Some(status)) => {
+                            let details = runtime_failure_details(
+                                state.started_at,
+                                status,
+                                &state.stderr_tail,
+                            );
+                            match self.limits.exceeded_by_exit(status) {
+                                Some(exceeded) => PythonRunOutcome::LimitExceeded {
+                                    limit: exceeded.limit,
+                                    details: format!("{}\n{details}", exceeded.detail),
+                                },
+                                None => PythonRunOutcome::Failure {
+                                    error,
+                                    diagnostics: Some(details),
+                                    reset_process: true,
+                                },
+                            }
+                        }
+                        Ok(None) => PythonRunOutcome::Failure {
+                            error,
+                            diagnostics: None,
+                            reset_process: false,
+                        },
+                        Err(wait_error) => PythonRunOutcome::Failure {
+                            error,
+                            diagnostics: Some(format!(
+                                "python subprocess status check failed after {}ms: {}",
+                                state.started_at.elapsed().as_millis(),
+                                wait_error
+                            )),
+                            reset_process: true,
+                        },
+                    },
+                }
+            }
+        };
+
+        match outcome {
+            PythonRunOutcome::Success(output) => {
+                self.limits.check_output(&output)?;
+                Ok(output)
+            }
+            PythonRunOutcome::Failure {
+                error,
+                diagnostics,
+                reset_process,
+            } => {
+                if reset_process {
+                    running.take();
+                }
+                let message = diagnostics.map_or_else(
+                    || error.to_string(),
+                    |details| format!("{error}\n{details}"),
+                );
+                Err(CommandError::msg(message))
+            }
+            PythonRunOutcome::LimitExceeded { limit, details } => {
+                running.take();
+                Err(LimitExceeded::new(limit, details).into())
+            }
+            PythonRunOutcome::Canceled { details } => {
+                running.take();
+                Err(CommandError::msg(details))
+            }
+        }
+    }
+    async fn destroy(&mut self) {
+        if let Some(mut state) = self.running.get_mut().take() {
+            terminate_child_process(&mut state.child).await.ok();
+        }
+    }
+    fn node_data(&self) -> client::NodeData {
+        let mut data = default_node_data(self);
+        if let Some(obj) = data.config.as_object_mut() {
+            obj.insert("source".to_owned(), self.source.clone().into());
+        }
+        data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flow_lib::{
+        config::{
+            client::{InputPort, OutputPort},
+            node::parse_definition,
+        },
+        context::{CommandContext, FlowServices, FlowSetServices, execute, get_jwt, signer},
+        flow_run_events,
+        solana::{Instructions, Pubkey, Signature, Wallet},
+        utils::tower_client::unimplemented_svc,
+    };
+    use uuid::Uuid;
+
+    const ADD_SOURCE: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/add.py"));
+    const ADD_JSON: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/add.jsonc"));
+
+    fn node_data(def: &str, source: &str, config: serde_json::Value) -> NodeData {
+        let def = parse_definition(def).unwrap();
+        let mut config = config;
+        config["source"] = source.into();
+        NodeData {
+            r#type: def.r#type,
+            node_id: def.data.node_id,
+            outputs: def
+                .outputs
+                .into_iter()
+                .map(|x| OutputPort {
+                    id: Uuid::new_v4(),
+                    name: x.name,
+                    optional: x.optional,
+                    r#type: x.r#type,
+                    tooltip: None,
+                })
+                .collect(),
+            inputs: def
+                .inputs
+                .into_iter()
+                .map(|x| InputPort {
+                    id: Uuid::new_v4(),
+                    name: x.name,
+                    required: x.required,
+                    passthrough: x.passthrough,
+                    type_bounds: x.type_bounds,
+                    tooltip: None,
+                })
+                .collect(),
+            config,
+            wasm: None,
+            instruction_info: None,
+        }
+    }
+
+    fn test_context(execute_svc: execute::Svc, signer_svc: signer::Svc) -> CommandContext {
+        let base = CommandContext::test_context();
+        let data = base.raw().data.clone();
+        let node_id = data.node_id;
+        let times = data.times;
+        let (tx, _) = flow_run_events::channel();
+
+        let mut ctx = CommandContext::builder()
+            .execute(execute_svc)
+            .get_jwt(unimplemented_svc::<
+                get_jwt::Request,
+                get_jwt::Response,
+                get_jwt::Error,
+            >())
+            .flow(FlowServices {
+                signer: signer_svc,
+                set: FlowSetServices {
+                    http: base.http().clone(),
+                    solana_client: base.solana_client().clone(),
+                    helius: None,
+                    extensions: Default::default(),
+                    api_input: unimplemented_svc(),
+                },
+            })
+            .data(data)
+            .node_log(flow_run_events::NodeLogSender::new(tx, node_id, times))
+            .build();
+        ctx.extensions_mut()
+            .unwrap()
+            .insert(tower_rpc::Server::start_http_server().unwrap());
+        ctx
+    }
+
+    fn unimplemented_context() -> CommandContext {
+        test_context(
+            unimplemented_svc::<execute::Request, execute::Response, execute::Error>(),
+            unimplemented_svc::<signer::SignatureRequest, signer::SignatureResponse, signer::Error>(
+            ),
+        )
+    }
+
+    #[test]
+    fn test_venv_config() {
+        assert_eq!(
+            Venv::from_config(&serde_json::json!({})).unwrap(),
+            Venv::None
+        );
+        assert_eq!(
+            Venv::from_config(&serde_json::json!({ "venv": "ml" })).unwrap(),
+            Venv::Named("ml".to_owned())
+        );
+        assert_eq!(
+            Venv::from_config(&serde_json::json!({ "requirements": ["base58==2.1.1"] })).unwrap(),
+            Venv::Requirements(vec!["base58==2.1.1".to_owned()])
+        );
+        for config in [
+            serde_json::json!({ "venv": "../ml" }),
+            serde_json::json!({ "venv": "a/b" }),
+            serde_json::json!({ "venv": ".." }),
+            serde_json::json!({ "requirements": ["--index-url=http://evil"] }),
+            serde_json::json!({ "requirements": ["a\n-e ."] }),
+            serde_json::json!({ "requirements": ["base58 @ https://evil/base58.whl"] }),
+            serde_json::json!({ "requirements": ["git+https://github.com/evil/base58"] }),
+            serde_json::json!({ "requirements": ["./base58"] }),
+            serde_json::json!({ "venv": "ml", "requirements": ["base58"] }),
+        ] {
+            assert!(Venv::from_config(&config).is_err(), "{config}");
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_run() {
+        tracing_subscriber::fmt::try_init().ok();
+
+        let cmd = new(&node_data(ADD_JSON, ADD_SOURCE, serde_json::json!({})))
+            .await
+            .unwrap();
+        let output = cmd
+            .run(
+                unimplemented_context(),
+                value::map! { "a" => 12, "b" => 13 },
+            )
+            .await
+            .unwrap();
+        let c = value::from_value::<f64>(output["c"].clone()).unwrap();
+        assert_eq!(c, 25.0);
+    }
+
+    #[actix_web::test]
+    async fn test_missing_venv() {
+        let cmd = new(&node_data(
+            ADD_JSON,
+            ADD_SOURCE,
+            serde_json::json!({ "venv": "space-operator-missing-venv" }),
+        ))
+        .await
+        .unwrap();
+        let err = cmd
+            .run(unimplemented_context(), value::map! { "a" => 1, "b" => 2 })
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("not found"), "{err:?}");
+    }
+
+    #[actix_web::test]
+    async fn test_script_error() {
+        const SOURCE: &str = r#"
+def main(ctx, inputs):
+    raise ValueError("bad input")
+"#;
+        let cmd = new(&node_data(ADD_JSON, SOURCE, serde_json::json!({})))
+            .await
+            .unwrap();
+        let err = cmd
+            .run(unimplemented_context(), value::map! { "a" => 1, "b" => 2 })
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("ValueError: bad input"), "{err:?}");
+    }
+
+    #[actix_web::test]
+    async fn test_wall_clock_limit() {
+        const SOURCE: &str = r#"
+import time
+
+def main(ctx, inputs):
+    time.sleep(30)
+    return {}
+"#;
+        let cmd = new(&node_data(
+            ADD_JSON,
+            SOURCE,
+            serde_json::json!({ "limits": { "wall_clock_secs": 1 } }),
+        ))
+        .await
+        .unwrap();
+        let err = cmd
+            .run(unimplemented_context(), value::map! { "a" => 1, "b" => 2 })
+            .await
+            .unwrap_err();
+        let exceeded = err.downcast_ref::<LimitExceeded>().unwrap();
+        assert_eq!(exceeded.limit, Limit::WallClock);
+    }
+
+    #[actix_web::test]
+    async fn test_execute_with_public_key_signer() {
+        tracing_subscriber::fmt::try_init().ok();
+
+        const JSON: &str = r#"{
+          "version": "0.1",
+          "name": "python_execute",
+          "prefix": "python",
+          "type": "python",
+          "author_handle": "spo",
+          "ports": {
+            "inputs": [
+              { "name": "signer", "type_bounds": ["string"], "required": true, "passthrough": false }
+            ],
+            "outputs": [
+              { "name": "signature", "type": "signature", "optional": false }
+            ]
+          },
+          "config_schema": {},
+          "config": {}
+        }"#;
+        const SOURCE: &str = r#"
+from flow_lib import Instructions
+
+async def main(ctx, inputs):
+    signer = inputs["signer"]
+    signature = ctx.execute(Instructions(signer, [signer], []), {})
+    return {"signature": signature}
+"#;
+
+        let observed = Arc::new(Mutex::new(None::<Instructions>));
+        let execute_svc = execute::Svc::new(tower::service_fn({
+            let observed = observed.clone();
+            move |req: execute::Request| {
+                let observed = observed.clone();
+                async move {
+                    *observed.lock().unwrap() = Some(req.instructions.clone());
+                    Ok(execute::Response::signature(Signature::from([3u8; 64])))
+                }
+            }
+        }));
+        let ctx = test_context(
+            execute_svc,
+            unimplemented_svc::<signer::SignatureRequest, signer::SignatureResponse, signer::Error>(
+            ),
+        );
+
+        let signer = Pubkey::new_unique();
+        let cmd = new(&node_data(JSON, SOURCE, serde_json::json!({})))
+            .await
+            .unwrap();
+        let output = cmd
+            .run(ctx, value::map! { "signer" => signer.to_string() })
+            .await
+            .unwrap();
+
+        assert_eq!(
+            value::from_value::<Signature>(output["signature"].clone()).unwrap(),
+            Signature::from([3u8; 64])
+        );
+        let instructions = observed.lock().unwrap().clone().unwrap();
+        assert_eq!(instructions.fee_payer, signer);
+        assert_eq!(
+            instructions.signers,
+            vec![Wallet::Adapter {
+                public_key: signer,
+                token: None,
+            }]
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_request_signature_uses_signer_service() {
+        tracing_subscriber::fmt::try_init().ok();
+
+        const JSON: &str = r#"{
+          "version": "0.1",
+          "name": "python_signature",
+          "prefix": "python",
+          "type": "python",
+          "author_handle": "spo",
+          "ports": {
+            "inputs": [
+              { "name": "signer", "type_bounds": ["string"], "required": true, "passthrough": false }
+            ],
+            "outputs": [
+              { "name": "signature_length", "type": "u64", "optional": false },
+              { "name": "new_message_length", "type": "u64", "optional": false }
+            ]
+          },
+          "config_schema": {},
+          "config": {}
+        }"#;
+        const SOURCE: &str = r#"
+def main(ctx, inputs):
+    print("requesting signature")
+    signature, new_message = ctx.request_signature(inputs["signer"], b"\x01\x02\x03\x04")
+    return {
+        "signature_length": len(signature),
+        "new_message_length": len(new_message or b""),
+    }
+"#;
+
+        let observed = Arc::new(Mutex::new(None::<signer::SignatureRequest>));
+        let signer_svc = signer::Svc::new(tower::service_fn({
+            let observed = observed.clone();
+            move |req: signer::SignatureRequest| {
+                let observed = observed.clone();
+                async move {
+                    *observed.lock().unwrap() = Some(req);
+                    Ok(signer::SignatureResponse {
+                        signature: Signature::from([7u8; 64]),
+                        new_message: Some(b"updated".to_vec().into()),
+                    })
+                }
+            }
+        }));
+        let ctx = test_context(
+            unimplemented_svc::<execute::Request, execute::Response, execute::Error>(),
+            signer_svc,
+        );
+
+        let signer = Pubkey::new_unique();
+        let cmd = new(&node_data(JSON, SOURCE, serde_json::json!({})))
+            .await
+            .unwrap();
+        let output = cmd
+            .run(ctx, value::map! { "signer" => signer.to_string() })
+            .await
+            .unwrap();
+
+        assert_eq!(
+            value::from_value::<u64>(output["signature_length"].clone()).unwrap(),
+            64
+        );
+        assert_eq!(
+            value::from_value::<u64>(output["new_message_length"].clone()).unwrap(),
+            7
+        );
+
+        let request = observed.lock().unwrap().clone().unwrap();
+        assert_eq!(request.pubkey, signer);
+        assert_eq!(request.message.as_ref(), b"\x01\x02\x03\x04");
+        assert_eq!(request.timeout, Duration::from_secs(120));
+        assert_eq!(
+            request.kind,
+            signer::SignatureRequestKind::TransactionMessage
+        );
+    }
+}
@@ -0,0 +1,298 @@
+//! Interpreter selection and per-node virtualenvs.
+//!
+//! The interpreter is pinned by the deployment with `SPACE_OPERATOR_PYTHON`.
+//! A node can name a pre-provisioned virtualenv under
+//! `SPACE_OPERATOR_PYTHON_VENVS` with `venv`, or list `requirements` to get a
+//! virtualenv created on first use and shared by the nodes of an owner with
+//! the same list. Only plain index requirements are accepted, and pip runs
+//! under the node's [`ScriptLimits`].
+
+use anyhow::{Context as _, anyhow, bail};
+use flow_lib::{
+    UserId,
+    command::{CommandError, limits::ScriptLimits},
+    value::Value,
+};
+use serde_json::Value as JsonValue;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    fmt::Write as _,
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::{Arc, LazyLock, Mutex},
+    time::Duration,
+};
+use tokio::{process::Command, sync::Mutex as AsyncMutex};
+
+const DEFAULT_INTERPRETER: &str = "python3";
+const DEFAULT_VENV_TIMEOUT_SECS: u64 = 600;
+const READY_MARKER: &str = ".ready";
+
+/// Serializes the creation of each virtualenv, so that nodes sharing
+/// requirements do not install into the same directory concurrently.
+/// Entries are evicted once no creation holds them.
+static CREATE_LOCKS: LazyLock<Mutex<HashMap<PathBuf, Arc<AsyncMutex<()>>>>> =
+    LazyLock::new(Default::default);
+
+fn create_lock(dir: &Path) -> Arc<AsyncMutex<()>> {
+    let mut locks = CREATE_LOCKS.lock().unwrap();
+    locks.retain(|_, lock| Arc::strong_count(lock) > 1);
+    locks.entry(dir.to_owned()).or_default().clone()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Venv {
+    /// Use the pinned interpreter directly.
+    None,
+    /// A virtualenv provisioned by the deployment.
+    Named(String),
+    /// A virtualenv created from a requirements list.
+    Requirements(Vec<String>),
+}
+
+pub fn interpreter() -> PathBuf {
+    std::env::var_os("SPACE_OPERATOR_PYTHON")
+        .filter(|path| !path.is_empty())
+        .map_or_else(|| PathBuf::from(DEFAULT_INTERPRETER), PathBuf::from)
+}
+
+pub fn venv_root() -> PathBuf {
+    std::env::var_os("SPACE_OPERATOR_PYTHON_VENVS")
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| home::home_dir().map(|home| home.join(".cache/space-operator/python-venvs")))
+        .unwrap_or_else(|| std::env::temp_dir().join("space-operator-python-venvs"))
+}
+
+fn venv_timeout() -> Duration {
+    crate::duration_from_env(
+        "SPACE_OPERATOR_PYTHON_VENV_TIMEOUT_SECS",
+        DEFAULT_VENV_TIMEOUT_SECS,
+    )
+}
+
+fn venv_python(dir: &Path) -> PathBuf {
+    if cfg!(windows) {
+        dir.join("Scripts").join("python.exe")
+    } else {
+        dir.join("bin").join("python")
+    }
+}
+
+fn config_value(config: &JsonValue, key: &str) -> Option<Value> {
+    config
+        .get(key)
+        .map(|json| flow_lib::command::parse_value_tagged_or_json(json.clone()))
+        .filter(|value| !matches!(value, Value::Null))
+}
+
+pub fn validate_venv_name(name: &str) -> Result<(), CommandError> {
+    let valid = !name.is_empty()
+        && name != "."
+        && name != ".."
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if !valid {
+        bail!("invalid python venv name: {name:?}");
+    }
+    Ok(())
+}
+
+/// Only requirements resolved from the package index are allowed.
+pub fn validate_requirement(requirement: &str) -> Result<(), CommandError> {
+    let requirement = requirement.trim();
+    // options such as `--index-url` or `-e` are not allowed
+    if requirement.is_empty() || requirement.starts_with('-') || requirement.contains(['\n', '\r'])
+    {
+        bail!("invalid python requirement: {requirement:?}");
+    }
+    // direct references (`pkg @ https://...`), VCS URLs (`git+https://...`)
+    // and local paths
+    if !requirement.starts_with(|c: char| c.is_ascii_alphanumeric())
+        || requirement.contains(['@', '/', '\\'])
+        || requirement.contains("://")
+    {
+        bail!("python requirement must be a package from the index: {requirement:?}");
+    }
+    Ok(())
+}
+
+impl Venv {
+    /// Read `venv` and `requirements` from a node config.
+    pub fn from_config(config: &JsonValue) -> Result<Self, CommandError> {
+        let name = config_value(config, "venv");
+        let requirements = config_value(config, "requirements");
+        match (name, requirements) {
+            (Some(_), Some(_)) => bail!("python node cannot set both `venv` and `requirements`"),
+            (Some(Value::String(name)), None) => {
+                validate_venv_name(&name)?;
+                Ok(Self::Named(name))
+            }
+            (Some(_), None) => bail!("python `venv` must be a string"),
+            (None, Some(Value::Array(list))) => {
+                let requirements = list
+                    .into_iter()
+                    .map(|value| match value {
+                        Value::String(s) => {
+                            validate_requirement(&s)?;
+                            Ok(s.trim().to_owned())
+                        }
+                        _ => Err(anyhow!("python `requirements` must be strings")),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(if requirements.is_empty() {
+                    Self::None
+                } else {
+                    Self::Requirements(requirements)
+                })
+            }
+            (None, Some(_)) => bail!("python `requirements` must be an array of strings"),
+            (None, None) => Ok(Self::None),
+        }
+    }
+
+    /// Directory name of a requirements virtualenv, derived from the
+    /// interpreter, the owner and the requirements.
+    fn requirements_dir_name(
+        interpreter: &Path,
+        owner: &UserId,
+        requirements: &[String],
+    ) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(interpreter.as_os_str().as_encoded_bytes());
+        hasher.update(b"\n");
+        hasher.update(owner.as_bytes());
+        for requirement in requirements {
+            hasher.update(b"\n");
+            hasher.update(requirement.as_bytes());
+        }
+        let hash = hasher.finalize();
+        hash[..8].iter().fold(String::from("req-"), |mut s, b| {
+            write!(s, "{b:02x}").unwrap();
+            s
+        })
+    }
+
+    /// Return the interpreter to run the node with, creating its virtualenv
+    /// for `owner` if needed.
+    pub async fn python(
+        &self,
+        owner: &UserId,
+        limits: &ScriptLimits,
+    ) -> Result<PathBuf, CommandError> {
+        match self {
+            Self::None => Ok(interpreter()),
+            Self::Named(name) => {
+                let python = venv_python(&venv_root().join(name));
+                if !python.exists() {
+                    bail!("python venv {name:?} not found at {}", python.display());
+                }
+                Ok(python)
+            }
+            Self::Requirements(requirements) => {
+                let interpreter = interpreter();
+                let dir = venv_root().join(Self::requirements_dir_name(
+                    &interpreter,
+                    owner,
+                    requirements,
+                ));
+                let timeout = limits
+                    .wall_clock()
+                    .map_or(venv_timeout(), |limit| limit.min(venv_timeout()));
+                tokio::time::timeout(
+                    timeout,
+                    create_venv(&interpreter, &dir, requirements, limits),
+                )
+                .await
+                .map_err(|_| {
+                    anyhow!(
+                        "timed out after {}s creating python venv",
+                        timeout.as_secs()
+                    )
+                })??;
+                Ok(venv_python(&dir))
+            }
+        }
+    }
+}
+
+async fn run_setup(
+    command: &mut Command,
+    what: &str,
+    limits: &ScriptLimits,
+) -> Result<(), CommandError> {
+    crate::copy_allowed_environment(command);
+    #[cfg(unix)]
+    limits.apply_rlimits(command);
+    let output = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .output()
+        .await
+        .with_context(|| format!("spawn {what}"))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let tail = stderr.lines().rev().take(20).collect::<Vec<_>>();
+        let tail = tail.into_iter().rev().collect::<Vec<_>>().join("\n");
+        if let Some(exceeded) = limits.exceeded_by_exit(output.status) {
+            return Err(exceeded.into());
+        }
+        bail!("{what} failed ({}):\n{tail}", output.status);
+    }
+    Ok(())
+}
+
+async fn create_venv(
+    interpreter: &Path,
+    dir: &Path,
+    requirements: &[String],
+    limits: &ScriptLimits,
+) -> Result<(), CommandError> {
+    let lock = create_lock(dir);
+    let _guard = lock.lock().await;
+    if dir.join(READY_MARKER).exists() {
+        return Ok(());
+    }
+    if dir.exists() {
+        // left over by an interrupted install
+        tokio::fs::remove_dir_all(dir)
+            .await
+            .context("remove incomplete python venv")?;
+    }
+    if let Some(parent) = dir.parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .context("create python venv root")?;
+    }
+
+    tracing::info!("creating python venv {}", dir.display());
+    run_setup(
+        Command::new(interpreter).arg("-m").arg("venv").arg(dir),
+        "python -m venv",
+        limits,
+    )
+    .await?;
+
+    let requirements_file = dir.join("requirements.txt");
+    tokio::fs::write(&requirements_file, requirements.join("\n"))
+        .await
+        .context("write requirements.txt")?;
+    run_setup(
+        Command::new(venv_python(dir))
+            .args(["-m", "pip", "install", "--disable-pip-version-check"])
+            .args(["--no-input", "-r"])
+            .arg(&requirements_file),
+        "pip install",
+        limits,
+    )
+    .await?;
+
+    tokio::fs::write(dir.join(READY_MARKER), "")
+        .await
+        .context("write python venv marker")?;
+    Ok(())
+}
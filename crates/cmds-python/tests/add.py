@@ -0,0 +1,2 @@
+def main(ctx, inputs):
+    return {"c": inputs["a"] + inputs["b"]}
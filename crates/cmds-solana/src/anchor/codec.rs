@@ -0,0 +1,514 @@
+//! Borsh encoding and decoding of [`Value`] according to IDL types.
+
+use super::idl::{Idl, IdlFields, IdlType, IdlTypeDefTy};
+use anyhow::{anyhow, bail};
+use flow_lib::command::prelude::*;
+use std::str::FromStr;
+
+/// Guards against infinitely recursive type definitions.
+const MAX_DEPTH: usize = 64;
+
+fn int_of(value: &Value) -> Option<i128> {
+    match value {
+        Value::I64(n) => Some(*n as i128),
+        Value::U64(n) => Some(*n as i128),
+        Value::I128(n) => Some(*n),
+        Value::U128(n) => i128::try_from(*n).ok(),
+        Value::Decimal(d) if d.fract().is_zero() => i128::try_from(*d).ok(),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+fn u128_of(value: &Value) -> Option<u128> {
+    match value {
+        Value::U128(n) => Some(*n),
+        Value::String(s) => s.parse().ok(),
+        value => int_of(value).and_then(|n| u128::try_from(n).ok()),
+    }
+}
+
+fn f64_of(value: &Value) -> Option<f64> {
+    match value {
+        Value::F64(f) => Some(*f),
+        Value::Decimal(d) => f64::try_from(*d).ok(),
+        Value::String(s) => s.parse().ok(),
+        value => int_of(value).map(|n| n as f64),
+    }
+}
+
+pub fn pubkey_of(value: &Value) -> Option<Pubkey> {
+    match value {
+        Value::B32(b) => Some(Pubkey::new_from_array(*b)),
+        Value::String(s) => Pubkey::from_str(s).ok(),
+        _ => None,
+    }
+}
+
+fn bytes_of(value: &Value) -> Option<Vec<u8>> {
+    match value {
+        Value::Bytes(b) => Some(b.to_vec()),
+        Value::B32(b) => Some(b.to_vec()),
+        Value::B64(b) => Some(b.to_vec()),
+        Value::Array(items) => items
+            .iter()
+            .map(|v| int_of(v).and_then(|n| u8::try_from(n).ok()))
+            .collect(),
+        _ => None,
+    }
+}
+
+fn is_u8(ty: &IdlType) -> bool {
+    matches!(ty, IdlType::Primitive(name) if name == "u8")
+}
+
+pub struct Encoder<'a> {
+    idl: &'a Idl,
+}
+
+impl<'a> Encoder<'a> {
+    pub fn new(idl: &'a Idl) -> Self {
+        Self { idl }
+    }
+
+    pub fn encode(
+        &self,
+        ty: &IdlType,
+        value: &Value,
+        out: &mut Vec<u8>,
+    ) -> Result<(), CommandError> {
+        self.encode_depth(ty, value, out, 0)
+    }
+
+    fn encode_depth(
+        &self,
+        ty: &IdlType,
+        value: &Value,
+        out: &mut Vec<u8>,
+        depth: usize,
+    ) -> Result<(), CommandError> {
+        if depth > MAX_DEPTH {
+            bail!("type nesting is too deep");
+        }
+        let mismatch = || anyhow!("can not encode {:?} as {:?}", value, ty);
+        match ty {
+            IdlType::Primitive(name) => {
+                macro_rules! int {
+                    ($t:ty) => {{
+                        let n = int_of(value).ok_or_else(mismatch)?;
+                        out.extend(<$t>::try_from(n).map_err(|_| mismatch())?.to_le_bytes());
+                    }};
+                }
+                match name.as_str() {
+                    "bool" => match value {
+                        Value::Bool(b) => out.push(*b as u8),
+                        _ => return Err(mismatch()),
+                    },
+                    "u8" => int!(u8),
+                    "i8" => int!(i8),
+                    "u16" => int!(u16),
+                    "i16" => int!(i16),
+                    "u32" => int!(u32),
+                    "i32" => int!(i32),
+                    "u64" => int!(u64),
+                    "i64" => int!(i64),
+                    "i128" => int!(i128),
+                    "u128" => out.extend(u128_of(value).ok_or_else(mismatch)?.to_le_bytes()),
+                    "f32" => out.extend((f64_of(value).ok_or_else(mismatch)? as f32).to_le_bytes()),
+                    "f64" => out.extend(f64_of(value).ok_or_else(mismatch)?.to_le_bytes()),
+                    "string" => match value {
+                        Value::String(s) => {
+                            out.extend((s.len() as u32).to_le_bytes());
+                            out.extend(s.as_bytes());
+                        }
+                        _ => return Err(mismatch()),
+                    },
+                    "bytes" => {
+                        let bytes = bytes_of(value).ok_or_else(mismatch)?;
+                        out.extend((bytes.len() as u32).to_le_bytes());
+                        out.extend(bytes);
+                    }
+                    "pubkey" | "publicKey" => {
+                        out.extend(pubkey_of(value).ok_or_else(mismatch)?.to_bytes())
+                    }
+                    other => bail!("unsupported IDL type: {}", other),
+                }
+            }
+            IdlType::Vec { vec } => {
+                if is_u8(vec) {
+                    let bytes = bytes_of(value).ok_or_else(mismatch)?;
+                    out.extend((bytes.len() as u32).to_le_bytes());
+                    out.extend(bytes);
+                    return Ok(());
+                }
+                let Value::Array(items) = value else {
+                    return Err(mismatch());
+                };
+                out.extend((items.len() as u32).to_le_bytes());
+                for item in items {
+                    self.encode_depth(vec, item, out, depth + 1)?;
+                }
+            }
+            IdlType::Option { option } => match value {
+                Value::Null => out.push(0),
+                value => {
+                    out.push(1);
+                    self.encode_depth(option, value, out, depth + 1)?;
+                }
+            },
+            IdlType::COption { coption } => match value {
+                Value::Null => out.extend(0u32.to_le_bytes()),
+                value => {
+                    out.extend(1u32.to_le_bytes());
+                    self.encode_depth(coption, value, out, depth + 1)?;
+                }
+            },
+            IdlType::Array {
+                array: (item_ty, len),
+            } => {
+                if is_u8(item_ty) {
+                    let bytes = bytes_of(value).ok_or_else(mismatch)?;
+                    if bytes.len() != *len {
+                        bail!("expected {} bytes, got {}", len, bytes.len());
+                    }
+                    out.extend(bytes);
+                    return Ok(());
+                }
+                let Value::Array(items) = value else {
+                    return Err(mismatch());
+                };
+                if items.len() != *len {
+                    bail!("expected {} items, got {}", len, items.len());
+                }
+                for item in items {
+                    self.encode_depth(item_ty, item, out, depth + 1)?;
+                }
+            }
+            IdlType::Defined { defined } => {
+                let def = self.idl.type_def(defined.name())?;
+                self.encode_def(def, value, out, depth + 1)
+                    .map_err(|e| anyhow!("{}: {}", defined.name(), e))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn encode_fields(
+        &self,
+        fields: Option<&IdlFields>,
+        value: &Value,
+        out: &mut Vec<u8>,
+        depth: usize,
+    ) -> Result<(), CommandError> {
+        match fields {
+            None => Ok(()),
+            Some(IdlFields::Named(fields)) => {
+                let Value::Map(map) = value else {
+                    bail!("expected a map, got {:?}", value);
+                };
+                for field in fields {
+                    let value = map.get(&field.name).unwrap_or(&Value::Null);
+                    self.encode_depth(&field.ty, value, out, depth)
+                        .map_err(|e| anyhow!("field {}: {}", field.name, e))?;
+                }
+                Ok(())
+            }
+            Some(IdlFields::Tuple(types)) => {
+                let Value::Array(items) = value else {
+                    bail!("expected an array, got {:?}", value);
+                };
+                if items.len() != types.len() {
+                    bail!("expected {} items, got {}", types.len(), items.len());
+                }
+                for (ty, item) in types.iter().zip(items) {
+                    self.encode_depth(ty, item, out, depth)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn encode_def(
+        &self,
+        def: &IdlTypeDefTy,
+        value: &Value,
+        out: &mut Vec<u8>,
+        depth: usize,
+    ) -> Result<(), CommandError> {
+        match def {
+            IdlTypeDefTy::Struct { fields } => {
+                self.encode_fields(fields.as_ref(), value, out, depth)
+            }
+            IdlTypeDefTy::Type { alias } => self.encode_depth(alias, value, out, depth),
+            IdlTypeDefTy::Enum { variants } => {
+                // unit variants can be given by name, others as `{ "Variant": fields }`
+                let (name, fields) = match value {
+                    Value::String(name) => (name.as_str(), &Value::Null),
+                    Value::Map(map) if map.len() == 1 => {
+                        let (name, fields) = map.iter().next().unwrap();
+                        (name.as_str(), fields)
+                    }
+                    _ => bail!("expected an enum variant, got {:?}", value),
+                };
+                let (index, variant) = variants
+                    .iter()
+                    .enumerate()
+                    .find(|(_, v)| v.name == name)
+                    .ok_or_else(|| anyhow!("unknown enum variant: {}", name))?;
+                out.push(u8::try_from(index)?);
+                self.encode_fields(variant.fields.as_ref(), fields, out, depth)
+            }
+        }
+    }
+}
+
+pub struct Decoder<'a> {
+    idl: &'a Idl,
+}
+
+fn take<'d>(data: &mut &'d [u8], len: usize) -> Result<&'d [u8], CommandError> {
+    if data.len() < len {
+        bail!("unexpected end of data");
+    }
+    let (head, tail) = data.split_at(len);
+    *data = tail;
+    Ok(head)
+}
+
+fn take_array<const N: usize>(data: &mut &[u8]) -> Result<[u8; N], CommandError> {
+    Ok(take(data, N)?.try_into()?)
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(idl: &'a Idl) -> Self {
+        Self { idl }
+    }
+
+    pub fn decode(&self, ty: &IdlType, data: &mut &[u8]) -> Result<Value, CommandError> {
+        self.decode_depth(ty, data, 0)
+    }
+
+    /// Decode the fields of a struct or enum definition.
+    pub fn decode_def(&self, def: &IdlTypeDefTy, data: &mut &[u8]) -> Result<Value, CommandError> {
+        self.decode_def_depth(def, data, 0)
+    }
+
+    fn decode_depth(
+        &self,
+        ty: &IdlType,
+        data: &mut &[u8],
+        depth: usize,
+    ) -> Result<Value, CommandError> {
+        if depth > MAX_DEPTH {
+            bail!("type nesting is too deep");
+        }
+        Ok(match ty {
+            IdlType::Primitive(name) => match name.as_str() {
+                "bool" => Value::Bool(take(data, 1)?[0] != 0),
+                "u8" => take_array::<1>(data)?[0].into(),
+                "i8" => i8::from_le_bytes(take_array(data)?).into(),
+                "u16" => u16::from_le_bytes(take_array(data)?).into(),
+                "i16" => i16::from_le_bytes(take_array(data)?).into(),
+                "u32" => u32::from_le_bytes(take_array(data)?).into(),
+                "i32" => i32::from_le_bytes(take_array(data)?).into(),
+                "u64" => u64::from_le_bytes(take_array(data)?).into(),
+                "i64" => i64::from_le_bytes(take_array(data)?).into(),
+                "u128" => u128::from_le_bytes(take_array(data)?).into(),
+                "i128" => i128::from_le_bytes(take_array(data)?).into(),
+                "f32" => f32::from_le_bytes(take_array(data)?).into(),
+                "f64" => f64::from_le_bytes(take_array(data)?).into(),
+                "string" => {
+                    let len = u32::from_le_bytes(take_array(data)?) as usize;
+                    String::from_utf8(take(data, len)?.to_vec())?.into()
+                }
+                "bytes" => {
+                    let len = u32::from_le_bytes(take_array(data)?) as usize;
+                    Value::Bytes(take(data, len)?.to_vec().into())
+                }
+                "pubkey" | "publicKey" => Value::B32(take_array(data)?),
+                other => bail!("unsupported IDL type: {}", other),
+            },
+            IdlType::Vec { vec } => {
+                let len = u32::from_le_bytes(take_array(data)?) as usize;
+                if is_u8(vec) {
+                    return Ok(Value::Bytes(take(data, len)?.to_vec().into()));
+                }
+                // every item takes at least one byte, except for empty structs
+                let mut items = Vec::with_capacity(len.min(data.len()));
+                for _ in 0..len {
+                    items.push(self.decode_depth(vec, data, depth + 1)?);
+                }
+                Value::Array(items)
+            }
+            IdlType::Option { option } => match take(data, 1)?[0] {
+                0 => Value::Null,
+                _ => self.decode_depth(option, data, depth + 1)?,
+            },
+            IdlType::COption { coption } => match u32::from_le_bytes(take_array(data)?) {
+                0 => Value::Null,
+                _ => self.decode_depth(coption, data, depth + 1)?,
+            },
+            IdlType::Array {
+                array: (item_ty, len),
+            } => {
+                if is_u8(item_ty) {
+                    let bytes = take(data, *len)?;
+                    return Ok(match *len {
+                        32 => Value::B32(bytes.try_into()?),
+                        64 => Value::B64(bytes.try_into()?),
+                        _ => Value::Bytes(bytes.to_vec().into()),
+                    });
+                }
+                let mut items = Vec::with_capacity((*len).min(data.len()));
+                for _ in 0..*len {
+                    items.push(self.decode_depth(item_ty, data, depth + 1)?);
+                }
+                Value::Array(items)
+            }
+            IdlType::Defined { defined } => {
+                let def = self.idl.type_def(defined.name())?;
+                self.decode_def_depth(def, data, depth + 1)?
+            }
+        })
+    }
+
+    fn decode_fields(
+        &self,
+        fields: Option<&IdlFields>,
+        data: &mut &[u8],
+        depth: usize,
+    ) -> Result<Value, CommandError> {
+        Ok(match fields {
+            None => Value::Null,
+            Some(IdlFields::Named(fields)) => Value::Map(
+                fields
+                    .iter()
+                    .map(|field| {
+                        let value = self
+                            .decode_depth(&field.ty, data, depth)
+                            .map_err(|e| anyhow!("field {}: {}", field.name, e))?;
+                        Ok((field.name.clone(), value))
+                    })
+                    .collect::<Result<_, CommandError>>()?,
+            ),
+            Some(IdlFields::Tuple(types)) => Value::Array(
+                types
+                    .iter()
+                    .map(|ty| self.decode_depth(ty, data, depth))
+                    .collect::<Result<_, _>>()?,
+            ),
+        })
+    }
+
+    fn decode_def_depth(
+        &self,
+        def: &IdlTypeDefTy,
+        data: &mut &[u8],
+        depth: usize,
+    ) -> Result<Value, CommandError> {
+        match def {
+            IdlTypeDefTy::Struct { fields } => self.decode_fields(fields.as_ref(), data, depth),
+            IdlTypeDefTy::Type { alias } => self.decode_depth(alias, data, depth),
+            IdlTypeDefTy::Enum { variants } => {
+                let index = take(data, 1)?[0] as usize;
+                let variant = variants
+                    .get(index)
+                    .ok_or_else(|| anyhow!("invalid enum variant index: {}", index))?;
+                Ok(match variant.fields {
+                    None => Value::String(variant.name.clone()),
+                    Some(ref fields) => Value::Map(value::map! {
+                        variant.name.clone() => self.decode_fields(Some(fields), data, depth)?,
+                    }),
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anchor::idl::IdlDefined;
+
+    fn idl() -> Idl {
+        Idl::parse(serde_json::json!({
+            "address": "11111111111111111111111111111111",
+            "instructions": [],
+            "types": [
+                {
+                    "name": "Params",
+                    "type": {
+                        "kind": "struct",
+                        "fields": [
+                            { "name": "amount", "type": "u64" },
+                            { "name": "owner", "type": "pubkey" },
+                            { "name": "memo", "type": { "option": "string" } },
+                            { "name": "side", "type": { "defined": { "name": "Side" } } },
+                            { "name": "data", "type": { "vec": "u8" } },
+                        ],
+                    },
+                },
+                {
+                    "name": "Side",
+                    "type": {
+                        "kind": "enum",
+                        "variants": [
+                            { "name": "Bid" },
+                            { "name": "Ask", "fields": [{ "name": "price", "type": "i32" }] },
+                        ],
+                    },
+                },
+            ],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let idl = idl();
+        let ty = IdlType::Defined {
+            defined: IdlDefined::Legacy("Params".to_owned()),
+        };
+        let value = Value::Map(value::map! {
+            "amount" => 5u64,
+            "owner" => Value::B32([1; 32]),
+            "memo" => "hi",
+            "side" => Value::Map(value::map! { "Ask" => Value::Map(value::map! { "price" => -3i32 }) }),
+            "data" => Value::Bytes(vec![1u8, 2].into()),
+        });
+
+        let mut out = Vec::new();
+        Encoder::new(&idl).encode(&ty, &value, &mut out).unwrap();
+        assert_eq!(out.len(), 8 + 32 + 1 + 4 + 2 + 1 + 4 + 4 + 2);
+        assert_eq!(&out[..8], &5u64.to_le_bytes());
+
+        let decoded = Decoder::new(&idl).decode(&ty, &mut &out[..]).unwrap();
+        assert_eq!(decoded, value);
+    }
+
+    #[test]
+    fn test_encode_errors() {
+        let idl = idl();
+        let mut out = Vec::new();
+        let encoder = Encoder::new(&idl);
+        let u8_ty = IdlType::Primitive("u8".to_owned());
+        assert!(encoder.encode(&u8_ty, &Value::U64(256), &mut out).is_err());
+        assert!(
+            encoder
+                .encode(&u8_ty, &Value::String("a".to_owned()), &mut out)
+                .is_err()
+        );
+        let side = IdlType::Defined {
+            defined: IdlDefined::Legacy("Side".to_owned()),
+        };
+        assert!(
+            encoder
+                .encode(&side, &Value::String("Hold".to_owned()), &mut out)
+                .is_err()
+        );
+        encoder
+            .encode(&side, &Value::String("Bid".to_owned()), &mut out)
+            .unwrap();
+        assert_eq!(out, [0]);
+    }
+}
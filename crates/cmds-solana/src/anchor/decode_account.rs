@@ -0,0 +1,142 @@
+use super::{
+    codec::Decoder,
+    idl::{Idl, IdlAccountDef},
+    load_idl,
+};
+use crate::prelude::*;
+use anyhow::anyhow;
+
+const NAME: &str = "anchor_decode_account";
+
+const DEFINITION: &str = flow_lib::node_definition!("anchor/anchor_decode_account.jsonc");
+
+flow_lib::submit!(CommandDescription::new(NAME, |_| build()));
+
+fn build() -> BuildResult {
+    static CACHE: BuilderCache =
+        BuilderCache::new(|| CmdBuilder::new(DEFINITION)?.check_name(NAME));
+    Ok(CACHE.clone()?.build(run))
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Input {
+    #[serde(default, with = "value::pubkey::opt")]
+    account: Option<Pubkey>,
+    #[serde(default)]
+    data: Option<Bytes>,
+    #[serde(default, with = "value::pubkey::opt")]
+    program_id: Option<Pubkey>,
+    #[serde(default)]
+    idl: Option<JsonValue>,
+    #[serde(default)]
+    account_type: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Output {
+    account_type: String,
+    decoded: Value,
+}
+
+/// Find the account type by name, or by the discriminator at the start of `data`.
+fn find_account_def<'a>(
+    idl: &'a Idl,
+    name: Option<&str>,
+    data: &[u8],
+) -> Result<&'a IdlAccountDef, CommandError> {
+    let def = match name {
+        Some(name) => idl.account(name)?,
+        None => idl
+            .accounts
+            .iter()
+            .find(|def| data.starts_with(&def.discriminator()))
+            .ok_or_else(|| anyhow!("account data does not match any account type in the IDL"))?,
+    };
+    if !data.starts_with(&def.discriminator()) {
+        return Err(anyhow!("account is not of type {}", def.name));
+    }
+    Ok(def)
+}
+
+pub(crate) fn decode(idl: &Idl, name: Option<&str>, data: &[u8]) -> Result<Output, CommandError> {
+    let def = find_account_def(idl, name, data)?;
+    let ty = idl.type_def(&def.name)?;
+    let mut rest = &data[def.discriminator().len()..];
+    let decoded = Decoder::new(idl).decode_def(ty, &mut rest)?;
+    Ok(Output {
+        account_type: def.name.clone(),
+        decoded,
+    })
+}
+
+async fn run(ctx: CommandContext, input: Input) -> Result<Output, CommandError> {
+    let client = ctx.solana_client();
+    let (data, owner) = match (input.data, input.account) {
+        (Some(data), _) if input.idl.is_some() || input.program_id.is_some() => {
+            (data.to_vec(), None)
+        }
+        (data, Some(account)) => {
+            let fetched = client
+                .get_account(&account)
+                .await
+                .map_err(|e| anyhow!("failed to fetch account {}: {}", account, e))?;
+            let data = data.map(|data| data.to_vec()).unwrap_or(fetched.data);
+            (data, Some(fetched.owner))
+        }
+        (Some(_), None) => {
+            return Err(CommandError::msg("either idl or program_id is required"));
+        }
+        (None, None) => return Err(CommandError::msg("either account or data is required")),
+    };
+
+    // the IDL is fetched from the account's owner, unless given otherwise
+    let (idl, _) = load_idl(client, input.idl, input.program_id.or(owner)).await?;
+
+    decode(&idl, input.account_type.as_deref(), &data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build() {
+        build().unwrap();
+    }
+
+    #[test]
+    fn test_decode() {
+        let idl = Idl::parse(serde_json::json!({
+            "address": "11111111111111111111111111111111",
+            "instructions": [],
+            "accounts": [{ "name": "Counter", "discriminator": [1, 2, 3, 4, 5, 6, 7, 8] }],
+            "types": [{
+                "name": "Counter",
+                "type": {
+                    "kind": "struct",
+                    "fields": [
+                        { "name": "authority", "type": "pubkey" },
+                        { "name": "count", "type": "u64" },
+                    ],
+                },
+            }],
+        }))
+        .unwrap();
+
+        let mut data = vec![1, 2, 3, 4, 5, 6, 7, 8];
+        data.extend([9; 32]);
+        data.extend(42u64.to_le_bytes());
+
+        let output = decode(&idl, None, &data).unwrap();
+        assert_eq!(output.account_type, "Counter");
+        assert_eq!(
+            output.decoded,
+            Value::Map(value::map! {
+                "authority" => Value::B32([9; 32]),
+                "count" => 42u64,
+            })
+        );
+
+        assert!(decode(&idl, Some("Counter"), &data[1..]).is_err());
+    }
+}
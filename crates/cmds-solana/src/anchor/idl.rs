@@ -0,0 +1,347 @@
+//! Anchor IDL types.
+//!
+//! Both the current IDL spec (Anchor 0.30+) and the legacy format are accepted:
+//! legacy `isMut`/`isSigner` flags, `publicKey` and string `defined` types are
+//! normalized, and missing discriminators are computed from names.
+
+use anyhow::anyhow;
+use flate2::read::ZlibDecoder;
+use flow_lib::command::prelude::*;
+use sha2::{Digest, Sha256};
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use std::io::Read;
+
+/// Seed of the IDL account, created with `anchor idl init`.
+const IDL_SEED: &str = "anchor:idl";
+
+/// discriminator (8) + authority (32) + data length (4)
+const IDL_ACCOUNT_HEADER: usize = 8 + 32 + 4;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Idl {
+    #[serde(default)]
+    pub address: Option<String>,
+    #[serde(default)]
+    pub metadata: Option<IdlMetadata>,
+    pub instructions: Vec<IdlInstruction>,
+    #[serde(default)]
+    pub accounts: Vec<IdlAccountDef>,
+    #[serde(default)]
+    pub types: Vec<IdlTypeDef>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IdlMetadata {
+    #[serde(default)]
+    pub address: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IdlInstruction {
+    pub name: String,
+    #[serde(default)]
+    pub discriminator: Option<Vec<u8>>,
+    pub accounts: Vec<IdlAccountItem>,
+    pub args: Vec<IdlField>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum IdlAccountItem {
+    Composite(IdlAccounts),
+    Single(IdlAccount),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IdlAccounts {
+    pub name: String,
+    pub accounts: Vec<IdlAccountItem>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IdlAccount {
+    pub name: String,
+    #[serde(default, alias = "isMut")]
+    pub writable: bool,
+    #[serde(default, alias = "isSigner")]
+    pub signer: bool,
+    #[serde(default, alias = "isOptional")]
+    pub optional: bool,
+    #[serde(default)]
+    pub address: Option<String>,
+    #[serde(default)]
+    pub pda: Option<IdlPda>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IdlPda {
+    pub seeds: Vec<IdlSeed>,
+    #[serde(default)]
+    pub program: Option<IdlSeed>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum IdlSeed {
+    Const { value: Vec<u8> },
+    Arg { path: String },
+    Account { path: String },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IdlAccountDef {
+    pub name: String,
+    #[serde(default)]
+    pub discriminator: Option<Vec<u8>>,
+    /// Only in legacy IDLs, newer ones put the type in `types`.
+    #[serde(default, rename = "type")]
+    pub ty: Option<IdlTypeDefTy>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IdlTypeDef {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: IdlTypeDefTy,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum IdlTypeDefTy {
+    Struct {
+        #[serde(default)]
+        fields: Option<IdlFields>,
+    },
+    Enum {
+        variants: Vec<IdlVariant>,
+    },
+    Type {
+        alias: IdlType,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum IdlFields {
+    Named(Vec<IdlField>),
+    Tuple(Vec<IdlType>),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IdlVariant {
+    pub name: String,
+    #[serde(default)]
+    pub fields: Option<IdlFields>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IdlField {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: IdlType,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum IdlType {
+    Primitive(String),
+    Vec { vec: Box<IdlType> },
+    Option { option: Box<IdlType> },
+    COption { coption: Box<IdlType> },
+    Array { array: (Box<IdlType>, usize) },
+    Defined { defined: IdlDefined },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum IdlDefined {
+    Legacy(String),
+    Named { name: String },
+}
+
+impl IdlDefined {
+    pub fn name(&self) -> &str {
+        match self {
+            IdlDefined::Legacy(name) | IdlDefined::Named { name } => name,
+        }
+    }
+}
+
+pub fn to_snake_case(name: &str) -> String {
+    let mut result = String::with_capacity(name.len() + 4);
+    for (i, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() {
+            if i > 0 && !result.ends_with('_') {
+                result.push('_');
+            }
+            result.push(c.to_ascii_lowercase());
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+fn sighash(namespace: &str, name: &str) -> Vec<u8> {
+    let hash = Sha256::digest(format!("{namespace}:{name}").as_bytes());
+    hash[..8].to_vec()
+}
+
+impl Idl {
+    pub fn parse(value: JsonValue) -> Result<Self, CommandError> {
+        let value = match value {
+            JsonValue::String(s) => serde_json::from_str(&s)?,
+            value => value,
+        };
+        serde_json::from_value(value).map_err(|e| anyhow!("invalid IDL: {}", e))
+    }
+
+    /// Program address declared in the IDL.
+    pub fn program_id(&self) -> Option<Pubkey> {
+        self.address
+            .as_ref()
+            .or_else(|| self.metadata.as_ref()?.address.as_ref())?
+            .parse()
+            .ok()
+    }
+
+    pub fn instruction(&self, name: &str) -> Result<&IdlInstruction, CommandError> {
+        let snake = to_snake_case(name);
+        self.instructions
+            .iter()
+            .find(|ix| ix.name == name || to_snake_case(&ix.name) == snake)
+            .ok_or_else(|| anyhow!("instruction not found in IDL: {}", name))
+    }
+
+    pub fn account(&self, name: &str) -> Result<&IdlAccountDef, CommandError> {
+        self.accounts
+            .iter()
+            .find(|a| a.name == name)
+            .ok_or_else(|| anyhow!("account type not found in IDL: {}", name))
+    }
+
+    pub fn type_def(&self, name: &str) -> Result<&IdlTypeDefTy, CommandError> {
+        if let Some(def) = self.types.iter().find(|t| t.name == name) {
+            return Ok(&def.ty);
+        }
+        self.accounts
+            .iter()
+            .find(|a| a.name == name)
+            .and_then(|a| a.ty.as_ref())
+            .ok_or_else(|| anyhow!("type not found in IDL: {}", name))
+    }
+
+    /// Read the IDL stored on-chain by `anchor idl init`.
+    pub async fn fetch(client: &RpcClient, program_id: &Pubkey) -> Result<Self, CommandError> {
+        let address = idl_address(program_id)?;
+        let data = client
+            .get_account_data(&address)
+            .await
+            .map_err(|e| anyhow!("failed to fetch IDL account {}: {}", address, e))?;
+        Self::from_account_data(&data)
+    }
+
+    pub fn from_account_data(data: &[u8]) -> Result<Self, CommandError> {
+        if data.len() < IDL_ACCOUNT_HEADER {
+            return Err(anyhow!("IDL account is too small"));
+        }
+        let len = u32::from_le_bytes(data[40..44].try_into()?) as usize;
+        let compressed = data
+            .get(IDL_ACCOUNT_HEADER..IDL_ACCOUNT_HEADER + len)
+            .ok_or_else(|| anyhow!("IDL account data is truncated"))?;
+        let mut json = Vec::new();
+        ZlibDecoder::new(compressed).read_to_end(&mut json)?;
+        Self::parse(serde_json::from_slice(&json)?)
+    }
+}
+
+impl IdlInstruction {
+    pub fn discriminator(&self) -> Vec<u8> {
+        self.discriminator
+            .clone()
+            .unwrap_or_else(|| sighash("global", &to_snake_case(&self.name)))
+    }
+
+    /// Accounts in instruction order, with composite accounts flattened.
+    pub fn flat_accounts(&self) -> Vec<&IdlAccount> {
+        fn flatten<'a>(items: &'a [IdlAccountItem], result: &mut Vec<&'a IdlAccount>) {
+            for item in items {
+                match item {
+                    IdlAccountItem::Single(account) => result.push(account),
+                    IdlAccountItem::Composite(accounts) => flatten(&accounts.accounts, result),
+                }
+            }
+        }
+        let mut result = Vec::new();
+        flatten(&self.accounts, &mut result);
+        result
+    }
+}
+
+impl IdlAccountDef {
+    pub fn discriminator(&self) -> Vec<u8> {
+        self.discriminator
+            .clone()
+            .unwrap_or_else(|| sighash("account", &self.name))
+    }
+}
+
+pub fn idl_address(program_id: &Pubkey) -> Result<Pubkey, CommandError> {
+    let (base, _) = Pubkey::find_program_address(&[], program_id);
+    Ok(Pubkey::create_with_seed(&base, IDL_SEED, program_id)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{Compression, write::ZlibEncoder};
+    use std::io::Write;
+
+    #[test]
+    fn test_parse_legacy() {
+        let idl = Idl::parse(serde_json::json!({
+            "version": "0.1.0",
+            "name": "counter",
+            "instructions": [{
+                "name": "incrementBy",
+                "accounts": [
+                    { "name": "counter", "isMut": true, "isSigner": false },
+                    { "name": "authority", "isMut": false, "isSigner": true },
+                ],
+                "args": [{ "name": "amount", "type": "u64" }],
+            }],
+            "accounts": [{
+                "name": "Counter",
+                "type": { "kind": "struct", "fields": [{ "name": "authority", "type": "publicKey" }] },
+            }],
+            "metadata": { "address": "11111111111111111111111111111111" },
+        }))
+        .unwrap();
+
+        let ix = idl.instruction("increment_by").unwrap();
+        assert_eq!(ix.discriminator(), sighash("global", "increment_by"));
+        let accounts = ix.flat_accounts();
+        assert!(accounts[0].writable);
+        assert!(accounts[1].signer);
+        assert!(idl.type_def("Counter").is_ok());
+        assert_eq!(idl.program_id(), Some(Pubkey::default()));
+    }
+
+    #[test]
+    fn test_from_account_data() {
+        let json = serde_json::to_vec(&serde_json::json!({
+            "address": "11111111111111111111111111111111",
+            "instructions": [],
+        }))
+        .unwrap();
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&json).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut data = vec![0u8; 40];
+        data.extend((compressed.len() as u32).to_le_bytes());
+        data.extend(compressed);
+        let idl = Idl::from_account_data(&data).unwrap();
+        assert!(idl.instructions.is_empty());
+    }
+}
@@ -0,0 +1,435 @@
+use super::{
+    codec::Encoder,
+    idl::{
+        Idl, IdlAccount, IdlFields, IdlInstruction, IdlSeed, IdlType, IdlTypeDefTy, to_snake_case,
+    },
+    load_idl,
+};
+use crate::prelude::*;
+use anyhow::anyhow;
+use solana_program::instruction::AccountMeta;
+
+const NAME: &str = "anchor_instruction";
+
+const DEFINITION: &str = flow_lib::node_definition!("anchor/anchor_instruction.jsonc");
+
+flow_lib::submit!(CommandDescription::new(NAME, |_| build()));
+
+fn build() -> BuildResult {
+    static CACHE: BuilderCache = BuilderCache::new(|| {
+        CmdBuilder::new(DEFINITION)?
+            .check_name(NAME)?
+            .simple_instruction_info("signature")
+    });
+    Ok(CACHE.clone()?.build(run))
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Input {
+    fee_payer: Wallet,
+    #[serde(default, with = "value::pubkey::opt")]
+    program_id: Option<Pubkey>,
+    #[serde(default)]
+    idl: Option<JsonValue>,
+    instruction: String,
+    #[serde(default)]
+    accounts: HashMap<String, WalletOrPubkey>,
+    #[serde(default)]
+    args: value::Map,
+    #[serde(default = "value::default::bool_true")]
+    submit: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Output {
+    /// Resolved address of every account of the instruction, including PDAs.
+    accounts: value::Map,
+    #[serde(default, with = "value::signature::opt")]
+    signature: Option<Signature>,
+}
+
+/// Find an entry by its IDL name, accepting both camelCase and snake_case keys.
+fn lookup<'a, T>(map: &'a HashMap<String, T>, name: &str) -> Option<&'a T> {
+    map.get(name).or_else(|| {
+        let snake = to_snake_case(name);
+        map.iter()
+            .find(|(key, _)| to_snake_case(key) == snake)
+            .map(|(_, value)| value)
+    })
+}
+
+fn same_name(a: &str, b: &str) -> bool {
+    a == b || to_snake_case(a) == to_snake_case(b)
+}
+
+/// Encode a value the way Anchor uses it as a PDA seed: strings and byte
+/// vectors are used without their length prefix.
+fn seed_bytes(idl: &Idl, ty: &IdlType, value: &Value) -> Result<Vec<u8>, CommandError> {
+    let mut out = Vec::new();
+    Encoder::new(idl).encode(ty, value, &mut out)?;
+    let prefixed = match ty {
+        IdlType::Primitive(name) => name == "string" || name == "bytes",
+        IdlType::Vec { vec } => matches!(&**vec, IdlType::Primitive(name) if name == "u8"),
+        _ => false,
+    };
+    if prefixed {
+        out.drain(..4);
+    }
+    Ok(out)
+}
+
+/// Resolve an `arg` seed, `path` may point into a struct argument (`params.owner`).
+fn arg_seed(
+    idl: &Idl,
+    ix: &IdlInstruction,
+    args: &value::Map,
+    path: &str,
+) -> Result<Vec<u8>, CommandError> {
+    let mut segments = path.split('.');
+    let first = segments.next().unwrap_or_default();
+    let arg = ix
+        .args
+        .iter()
+        .find(|arg| same_name(&arg.name, first))
+        .ok_or_else(|| anyhow!("seed refers to unknown argument: {}", path))?;
+    let mut ty = &arg.ty;
+    let mut value = lookup(args, &arg.name).unwrap_or(&Value::Null);
+    for segment in segments {
+        let field = match ty {
+            IdlType::Defined { defined } => match idl.type_def(defined.name())? {
+                IdlTypeDefTy::Struct {
+                    fields: Some(IdlFields::Named(fields)),
+                } => fields.iter().find(|f| same_name(&f.name, segment)),
+                _ => None,
+            },
+            _ => None,
+        }
+        .ok_or_else(|| anyhow!("invalid seed path: {}", path))?;
+        ty = &field.ty;
+        value = match value {
+            Value::Map(map) => lookup(map, &field.name).unwrap_or(&Value::Null),
+            _ => &Value::Null,
+        };
+    }
+    seed_bytes(idl, ty, value).map_err(|e| anyhow!("seed {}: {}", path, e))
+}
+
+/// Try to derive a PDA, returns `None` if a seed refers to an account that is not resolved yet.
+fn resolve_pda(
+    idl: &Idl,
+    ix: &IdlInstruction,
+    args: &value::Map,
+    program_id: &Pubkey,
+    resolved: &HashMap<String, Pubkey>,
+    account: &IdlAccount,
+) -> Result<Option<Pubkey>, CommandError> {
+    let Some(pda) = &account.pda else {
+        return Ok(None);
+    };
+    let seed = |seed: &IdlSeed| -> Result<Option<Vec<u8>>, CommandError> {
+        Ok(match seed {
+            IdlSeed::Const { value } => Some(value.clone()),
+            IdlSeed::Arg { path } => Some(arg_seed(idl, ix, args, path)?),
+            // seeds from account data (`account.field`) are not supported,
+            // the account must then be provided in the input
+            IdlSeed::Account { path } if path.contains('.') => None,
+            IdlSeed::Account { path } => {
+                lookup(resolved, path).map(|pubkey| pubkey.to_bytes().to_vec())
+            }
+        })
+    };
+    let mut seeds = Vec::with_capacity(pda.seeds.len());
+    for s in &pda.seeds {
+        match seed(s)? {
+            Some(bytes) => seeds.push(bytes),
+            None => return Ok(None),
+        }
+    }
+    let program = match &pda.program {
+        None => *program_id,
+        Some(program) => match seed(program)? {
+            Some(bytes) => Pubkey::try_from(bytes.as_slice())
+                .map_err(|_| anyhow!("invalid PDA program for account {}", account.name))?,
+            None => return Ok(None),
+        },
+    };
+    let seeds = seeds.iter().map(Vec::as_slice).collect::<Vec<_>>();
+    Ok(Some(Pubkey::find_program_address(&seeds, &program).0))
+}
+
+fn wallet_pubkey(account: &WalletOrPubkey) -> Pubkey {
+    match account {
+        WalletOrPubkey::Wallet(wallet) => wallet.pubkey(),
+        WalletOrPubkey::Pubkey(pubkey) => *pubkey,
+    }
+}
+
+/// Resolve the accounts of an instruction, in IDL order.
+///
+/// Accounts are taken from the input, from the fixed address in the IDL, or
+/// derived from PDA seeds. Missing optional accounts are replaced with the
+/// program ID, as Anchor expects.
+fn resolve_accounts(
+    idl: &Idl,
+    ix: &IdlInstruction,
+    input: &Input,
+    program_id: &Pubkey,
+) -> Result<Vec<(String, Pubkey)>, CommandError> {
+    let idl_accounts = ix.flat_accounts();
+    let mut resolved = HashMap::<String, Pubkey>::new();
+    for account in &idl_accounts {
+        let pubkey = match lookup(&input.accounts, &account.name) {
+            Some(provided) => Some(wallet_pubkey(provided)),
+            None => match &account.address {
+                Some(address) => Some(
+                    address
+                        .parse()
+                        .map_err(|_| anyhow!("invalid address for account {}", account.name))?,
+                ),
+                None => None,
+            },
+        };
+        if let Some(pubkey) = pubkey {
+            resolved.insert(account.name.clone(), pubkey);
+        }
+    }
+
+    // PDA seeds can refer to other PDAs, resolve until nothing changes
+    loop {
+        let mut progress = false;
+        for account in &idl_accounts {
+            if resolved.contains_key(&account.name) {
+                continue;
+            }
+            if let Some(pubkey) = resolve_pda(idl, ix, &input.args, program_id, &resolved, account)?
+            {
+                resolved.insert(account.name.clone(), pubkey);
+                progress = true;
+            }
+        }
+        if !progress {
+            break;
+        }
+    }
+
+    idl_accounts
+        .iter()
+        .map(|account| match resolved.get(&account.name) {
+            Some(pubkey) => Ok((account.name.clone(), *pubkey)),
+            None if account.optional => Ok((account.name.clone(), *program_id)),
+            None => Err(anyhow!("missing account: {}", account.name)),
+        })
+        .collect()
+}
+
+fn instruction_data(
+    idl: &Idl,
+    ix: &IdlInstruction,
+    args: &value::Map,
+) -> Result<Vec<u8>, CommandError> {
+    let mut data = ix.discriminator();
+    let encoder = Encoder::new(idl);
+    for arg in &ix.args {
+        let value = lookup(args, &arg.name).unwrap_or(&Value::Null);
+        encoder
+            .encode(&arg.ty, value, &mut data)
+            .map_err(|e| anyhow!("argument {}: {}", arg.name, e))?;
+    }
+    Ok(data)
+}
+
+async fn run(mut ctx: CommandContext, input: Input) -> Result<Output, CommandError> {
+    let (idl, program_id) =
+        load_idl(ctx.solana_client(), input.idl.clone(), input.program_id).await?;
+    let ix = idl.instruction(&input.instruction)?;
+
+    let accounts = resolve_accounts(&idl, ix, &input, &program_id)?;
+    let data = instruction_data(&idl, ix, &input.args)?;
+
+    let fee_payer = input.fee_payer.pubkey();
+    let mut signers = vec![input.fee_payer.clone()];
+    let mut metas = Vec::with_capacity(accounts.len());
+    for (account, (name, pubkey)) in ix.flat_accounts().into_iter().zip(&accounts) {
+        let is_placeholder = account.optional && *pubkey == program_id;
+        let is_signer = account.signer && !is_placeholder;
+        if is_signer && !signers.iter().any(|s| s.pubkey() == *pubkey) {
+            let wallet = lookup(&input.accounts, name)
+                .ok_or_else(|| anyhow!("signer account {} must be provided", name))?;
+            signers.push(wallet.clone().to_keypair());
+        }
+        metas.push(if account.writable && !is_placeholder {
+            AccountMeta::new(*pubkey, is_signer)
+        } else {
+            AccountMeta::new_readonly(*pubkey, is_signer)
+        });
+    }
+
+    let instruction = Instruction {
+        program_id,
+        accounts: metas,
+        data,
+    };
+
+    let ins = Instructions {
+        lookup_tables: None,
+        fee_payer,
+        signers,
+        instructions: [instruction].into(),
+    };
+
+    let ins = if input.submit {
+        ins
+    } else {
+        Default::default()
+    };
+
+    let signature = ctx.execute(ins, <_>::default()).await?.signature;
+
+    let accounts = accounts
+        .into_iter()
+        .map(|(name, pubkey)| (name, Value::from(pubkey)))
+        .collect();
+
+    Ok(Output {
+        accounts,
+        signature,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn idl() -> Idl {
+        Idl::parse(serde_json::json!({
+            "address": "11111111111111111111111111111112",
+            "instructions": [{
+                "name": "initialize",
+                "accounts": [
+                    { "name": "payer", "writable": true, "signer": true },
+                    {
+                        "name": "counter",
+                        "writable": true,
+                        "pda": { "seeds": [
+                            { "kind": "const", "value": [99, 111, 117, 110, 116, 101, 114] },
+                            { "kind": "account", "path": "payer" },
+                            { "kind": "arg", "path": "name" },
+                        ] },
+                    },
+                    { "name": "rent", "optional": true },
+                    { "name": "system_program", "address": "11111111111111111111111111111111" },
+                ],
+                "args": [
+                    { "name": "name", "type": "string" },
+                    { "name": "start", "type": "u64" },
+                ],
+            }],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_build() {
+        build().unwrap();
+    }
+
+    #[test]
+    fn test_resolve() {
+        let idl = idl();
+        let program_id = idl.program_id().unwrap();
+        let payer = Pubkey::new_unique();
+        let input = Input {
+            fee_payer: WalletOrPubkey::Pubkey(payer).to_keypair(),
+            program_id: None,
+            idl: None,
+            instruction: "initialize".to_owned(),
+            accounts: [("payer".to_owned(), WalletOrPubkey::Pubkey(payer))]
+                .into_iter()
+                .collect(),
+            args: value::map! { "name" => "abc", "start" => 7u64 },
+            submit: false,
+        };
+        let ix = idl.instruction("initialize").unwrap();
+
+        let accounts = resolve_accounts(&idl, ix, &input, &program_id).unwrap();
+        let counter =
+            Pubkey::find_program_address(&[b"counter", payer.as_ref(), b"abc"], &program_id).0;
+        assert_eq!(accounts[0].1, payer);
+        assert_eq!(accounts[1].1, counter);
+        assert_eq!(accounts[2].1, program_id);
+        assert_eq!(accounts[3].1, Pubkey::default());
+
+        let data = instruction_data(&idl, ix, &input.args).unwrap();
+        assert_eq!(data.len(), 8 + 4 + 3 + 8);
+        assert_eq!(&data[..8], &ix.discriminator()[..]);
+    }
+
+    #[test]
+    fn test_account_data_seed() {
+        let idl = Idl::parse(serde_json::json!({
+            "address": "11111111111111111111111111111112",
+            "instructions": [{
+                "name": "deposit",
+                "accounts": [
+                    { "name": "mint" },
+                    { "name": "pool" },
+                    {
+                        "name": "vault",
+                        "writable": true,
+                        "pda": { "seeds": [
+                            { "kind": "const", "value": [118, 97, 117, 108, 116] },
+                            { "kind": "account", "path": "pool.mint" },
+                        ] },
+                    },
+                ],
+                "args": [],
+            }],
+        }))
+        .unwrap();
+        let program_id = idl.program_id().unwrap();
+        let ix = idl.instruction("deposit").unwrap();
+        let mut input = Input {
+            fee_payer: WalletOrPubkey::Pubkey(Pubkey::new_unique()).to_keypair(),
+            program_id: None,
+            idl: None,
+            instruction: "deposit".to_owned(),
+            accounts: [
+                ("mint".to_owned(), WalletOrPubkey::Pubkey(Pubkey::new_unique())),
+                ("pool".to_owned(), WalletOrPubkey::Pubkey(Pubkey::new_unique())),
+            ]
+            .into_iter()
+            .collect(),
+            args: value::Map::new(),
+            submit: false,
+        };
+
+        // `pool.mint` is read from the pool's data, not the `mint` account
+        let err = resolve_accounts(&idl, ix, &input, &program_id).unwrap_err();
+        assert!(err.to_string().contains("vault"), "{err}");
+
+        let vault = Pubkey::new_unique();
+        input
+            .accounts
+            .insert("vault".to_owned(), WalletOrPubkey::Pubkey(vault));
+        let accounts = resolve_accounts(&idl, ix, &input, &program_id).unwrap();
+        assert_eq!(accounts[2].1, vault);
+    }
+
+    #[test]
+    fn test_missing_account() {
+        let idl = idl();
+        let program_id = idl.program_id().unwrap();
+        let input = Input {
+            fee_payer: WalletOrPubkey::Pubkey(Pubkey::new_unique()).to_keypair(),
+            program_id: None,
+            idl: None,
+            instruction: "initialize".to_owned(),
+            accounts: HashMap::new(),
+            args: value::Map::new(),
+            submit: false,
+        };
+        let ix = idl.instruction("initialize").unwrap();
+        let err = resolve_accounts(&idl, ix, &input, &program_id).unwrap_err();
+        assert!(err.to_string().contains("payer"));
+    }
+}
@@ -0,0 +1,33 @@
+//! Generic commands for Anchor programs, driven by the program's IDL.
+
+use crate::prelude::*;
+use idl::Idl;
+
+pub mod codec;
+pub mod decode_account;
+pub mod idl;
+pub mod instruction;
+
+/// Use the IDL given as input, or fetch it from the program's IDL account.
+///
+/// Returns the IDL and the program ID, which defaults to the address declared in the IDL.
+pub async fn load_idl(
+    client: &RpcClient,
+    idl: Option<JsonValue>,
+    program_id: Option<Pubkey>,
+) -> Result<(Idl, Pubkey), CommandError> {
+    match idl {
+        Some(idl) => {
+            let idl = Idl::parse(idl)?;
+            let program_id = program_id.or_else(|| idl.program_id()).ok_or_else(|| {
+                CommandError::msg("program_id is required, IDL does not declare an address")
+            })?;
+            Ok((idl, program_id))
+        }
+        None => {
+            let program_id = program_id
+                .ok_or_else(|| CommandError::msg("either idl or program_id is required"))?;
+            Ok((Idl::fetch(client, &program_id).await?, program_id))
+        }
+    }
+}
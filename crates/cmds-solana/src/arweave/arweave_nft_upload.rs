@@ -160,7 +160,9 @@ impl Uploader {
                 "https://devnet.bundlr.network".to_owned(),
                 "https://devnet.irys.xyz".to_owned(),
             ),
-            SolanaNet::Testnet => return Err(crate::Error::BundlrNotAvailableOnTestnet),
+            SolanaNet::Testnet | SolanaNet::Localnet | SolanaNet::Custom => {
+                return Err(crate::Error::BundlrNotAvailable(cluster));
+            }
         };
 
         Ok(Uploader {
@@ -0,0 +1,625 @@
+use crate::{
+    anchor::{
+        codec::Decoder,
+        idl::{Idl, to_snake_case},
+    },
+    prelude::*,
+};
+use anyhow::{anyhow, bail};
+use borsh::BorshDeserialize;
+use solana_account_decoder::UiAccountEncoding;
+use solana_address_lookup_table_interface::state::AddressLookupTable;
+use solana_compute_budget_interface::ComputeBudgetInstruction;
+use solana_message::VersionedMessage;
+use solana_rpc_client_api::config::{
+    RpcSimulateTransactionAccountsConfig, RpcSimulateTransactionConfig,
+};
+use solana_system_interface::instruction::SystemInstruction;
+use solana_transaction::versioned::VersionedTransaction;
+
+const NAME: &str = "decode_transaction";
+
+/// Keys accepted by one `getMultipleAccounts` call.
+const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+const DEFINITION: &str = flow_lib::node_definition!("decode_transaction.jsonc");
+
+flow_lib::submit!(CommandDescription::new(NAME, |_| build()));
+
+fn build() -> BuildResult {
+    static CACHE: BuilderCache =
+        BuilderCache::new(|| CmdBuilder::new(DEFINITION)?.check_name(NAME));
+    Ok(CACHE.clone()?.build(run))
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Input {
+    /// Serialized transaction, base64 or base58.
+    transaction: String,
+    #[serde(default)]
+    idls: Vec<JsonValue>,
+    #[serde(default = "value::default::bool_true")]
+    simulate: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Output {
+    version: String,
+    #[serde(with = "value::pubkey")]
+    fee_payer: Pubkey,
+    recent_blockhash: String,
+    signatures: Vec<String>,
+    accounts: Vec<AccountRole>,
+    instructions: Vec<DecodedInstruction>,
+    simulation: Option<Simulation>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AccountRole {
+    #[serde(with = "value::pubkey")]
+    pubkey: Pubkey,
+    signer: bool,
+    writable: bool,
+    /// Loaded from an address lookup table.
+    lookup: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct DecodedInstruction {
+    #[serde(with = "value::pubkey")]
+    program_id: Pubkey,
+    program: Option<String>,
+    name: Option<String>,
+    args: Option<Value>,
+    accounts: Vec<InstructionAccount>,
+    data: Bytes,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct InstructionAccount {
+    #[serde(with = "value::pubkey")]
+    pubkey: Pubkey,
+    signer: bool,
+    writable: bool,
+    /// Account name from the IDL.
+    name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Simulation {
+    err: Option<String>,
+    logs: Vec<String>,
+    units_consumed: Option<u64>,
+    balance_changes: Vec<BalanceChange>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct BalanceChange {
+    #[serde(with = "value::pubkey")]
+    pubkey: Pubkey,
+    pre: u64,
+    post: u64,
+    change: i64,
+}
+
+/// The base58 alphabet is a subset of base64's, so a base58 string often
+/// decodes as base64 too: base58 is tried when the base64 bytes are not a
+/// valid transaction.
+fn parse_transaction(s: &str) -> Result<VersionedTransaction, CommandError> {
+    let s = s.trim();
+    let mut error = None;
+    for bytes in [base64::decode(s).ok(), bs58::decode(s).into_vec().ok()]
+        .into_iter()
+        .flatten()
+    {
+        match deserialize_transaction(&bytes) {
+            Ok(tx) => return Ok(tx),
+            Err(e) => error = Some(e),
+        }
+    }
+    Err(error.unwrap_or_else(|| anyhow!("transaction is neither base64 nor base58")))
+}
+
+fn deserialize_transaction(bytes: &[u8]) -> Result<VersionedTransaction, CommandError> {
+    let tx: VersionedTransaction =
+        bincode::deserialize(bytes).map_err(|e| anyhow!("invalid transaction: {}", e))?;
+    // account roles and instruction decoding index by the header
+    tx.message
+        .sanitize()
+        .map_err(|e| anyhow!("invalid transaction: {}", e))?;
+    Ok(tx)
+}
+
+/// Static account keys followed by writable and readonly lookup table keys.
+async fn account_roles(
+    client: &RpcClient,
+    message: &VersionedMessage,
+) -> Result<Vec<AccountRole>, CommandError> {
+    let header = message.header();
+    let keys = message.static_account_keys();
+    let signed = header.num_required_signatures as usize;
+    let mut roles = keys
+        .iter()
+        .enumerate()
+        .map(|(i, pubkey)| {
+            let writable = if i < signed {
+                i < signed - header.num_readonly_signed_accounts as usize
+            } else {
+                i < keys.len() - header.num_readonly_unsigned_accounts as usize
+            };
+            AccountRole {
+                pubkey: *pubkey,
+                signer: i < signed,
+                writable,
+                lookup: false,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    if let Some(lookups) = message.address_table_lookups() {
+        let mut writable = Vec::new();
+        let mut readonly = Vec::new();
+        for lookup in lookups {
+            let account = client.get_account(&lookup.account_key).await.map_err(|e| {
+                anyhow!("failed to fetch lookup table {}: {}", lookup.account_key, e)
+            })?;
+            let table = AddressLookupTable::deserialize(&account.data)
+                .map_err(|e| anyhow!("invalid lookup table {}: {}", lookup.account_key, e))?;
+            let get = |index: &u8| {
+                table
+                    .addresses
+                    .get(*index as usize)
+                    .copied()
+                    .ok_or_else(|| {
+                        anyhow!("index {} out of range in {}", index, lookup.account_key)
+                    })
+            };
+            for index in &lookup.writable_indexes {
+                writable.push(get(index)?);
+            }
+            for index in &lookup.readonly_indexes {
+                readonly.push(get(index)?);
+            }
+        }
+        let lookup_role = |writable| {
+            move |pubkey| AccountRole {
+                pubkey,
+                signer: false,
+                writable,
+                lookup: true,
+            }
+        };
+        roles.extend(writable.into_iter().map(lookup_role(true)));
+        roles.extend(readonly.into_iter().map(lookup_role(false)));
+    }
+
+    Ok(roles)
+}
+
+/// Split an externally tagged enum serialized to JSON into variant name and fields.
+fn split_variant(json: JsonValue) -> (String, Option<Value>) {
+    match json {
+        JsonValue::String(name) => (to_snake_case(&name), None),
+        JsonValue::Object(map) if map.len() == 1 => {
+            let (name, args) = map.into_iter().next().unwrap();
+            (to_snake_case(&name), Some(args.into()))
+        }
+        json => ("unknown".to_owned(), Some(json.into())),
+    }
+}
+
+fn decode_compute_budget(data: &[u8]) -> Result<(String, Option<Value>), CommandError> {
+    let (name, args) = match ComputeBudgetInstruction::try_from_slice(data)? {
+        ComputeBudgetInstruction::RequestHeapFrame(bytes) => {
+            ("request_heap_frame", value::map! { "bytes" => bytes })
+        }
+        ComputeBudgetInstruction::SetComputeUnitLimit(units) => {
+            ("set_compute_unit_limit", value::map! { "units" => units })
+        }
+        ComputeBudgetInstruction::SetComputeUnitPrice(micro_lamports) => (
+            "set_compute_unit_price",
+            value::map! { "micro_lamports" => micro_lamports },
+        ),
+        ComputeBudgetInstruction::SetLoadedAccountsDataSizeLimit(bytes) => (
+            "set_loaded_accounts_data_size_limit",
+            value::map! { "bytes" => bytes },
+        ),
+        _ => bail!("unknown compute budget instruction"),
+    };
+    Ok((name.to_owned(), Some(Value::Map(args))))
+}
+
+/// Decode with an Anchor IDL, returns the instruction name, args and account names.
+fn decode_anchor(
+    idl: &Idl,
+    data: &[u8],
+) -> Result<(String, Option<Value>, Vec<String>), CommandError> {
+    let ix = idl
+        .instructions
+        .iter()
+        .find(|ix| data.starts_with(&ix.discriminator()))
+        .ok_or_else(|| anyhow!("data does not match any instruction in the IDL"))?;
+    let mut rest = &data[ix.discriminator().len()..];
+    let decoder = Decoder::new(idl);
+    let args = ix
+        .args
+        .iter()
+        .map(|field| Ok((field.name.clone(), decoder.decode(&field.ty, &mut rest)?)))
+        .collect::<Result<value::Map, CommandError>>()?;
+    let names = ix
+        .flat_accounts()
+        .into_iter()
+        .map(|a| a.name.clone())
+        .collect();
+    Ok((ix.name.clone(), Some(Value::Map(args)), names))
+}
+
+fn decode_instruction(
+    idls: &[(Pubkey, Idl)],
+    program_id: &Pubkey,
+    data: &[u8],
+    accounts: &mut [InstructionAccount],
+) -> (Option<String>, Option<String>, Option<Value>) {
+    let (program, result) = if *program_id == solana_sdk_ids::system_program::ID {
+        let result = bincode::deserialize::<SystemInstruction>(data)
+            .map_err(CommandError::from)
+            .and_then(|ix| Ok(split_variant(serde_json::to_value(ix)?)));
+        ("system", result)
+    } else if *program_id == spl_token_interface::ID || *program_id == spl_token_2022_interface::ID
+    {
+        // token-2022 instructions are a superset of token instructions
+        let result = spl_token_2022_interface::instruction::TokenInstruction::unpack(data)
+            .map_err(CommandError::from)
+            .and_then(|ix| Ok(split_variant(serde_json::to_value(ix)?)));
+        let program = if *program_id == spl_token_interface::ID {
+            "spl_token"
+        } else {
+            "spl_token_2022"
+        };
+        (program, result)
+    } else if *program_id == solana_sdk_ids::compute_budget::ID {
+        ("compute_budget", decode_compute_budget(data))
+    } else if *program_id == spl_memo_interface::v3::ID || *program_id == spl_memo_interface::v1::ID
+    {
+        let result = std::str::from_utf8(data)
+            .map(|memo| {
+                (
+                    "memo".to_owned(),
+                    Some(Value::Map(value::map! { "memo" => memo })),
+                )
+            })
+            .map_err(CommandError::from);
+        ("spl_memo", result)
+    } else if let Some((_, idl)) = idls.iter().find(|(id, _)| id == program_id) {
+        let result = decode_anchor(idl, data).map(|(name, args, names)| {
+            for (account, name) in accounts.iter_mut().zip(names) {
+                account.name = Some(name);
+            }
+            (name, args)
+        });
+        ("anchor", result)
+    } else {
+        return (None, None, None);
+    };
+    match result {
+        Ok((name, args)) => (Some(program.to_owned()), Some(name), args),
+        Err(error) => {
+            tracing::warn!("could not decode {} instruction: {}", program, error);
+            (Some(program.to_owned()), None, None)
+        }
+    }
+}
+
+async fn simulate(
+    client: &RpcClient,
+    tx: &VersionedTransaction,
+    roles: &[AccountRole],
+) -> Result<Simulation, CommandError> {
+    let pubkeys = roles.iter().map(|r| r.pubkey).collect::<Vec<_>>();
+    let mut pre = Vec::with_capacity(pubkeys.len());
+    // lookup tables can load more keys than one call accepts
+    for chunk in pubkeys.chunks(MAX_MULTIPLE_ACCOUNTS) {
+        pre.extend(client.get_multiple_accounts(chunk).await?);
+    }
+    let result = client
+        .simulate_transaction_with_config(
+            tx,
+            RpcSimulateTransactionConfig {
+                sig_verify: false,
+                replace_recent_blockhash: true,
+                accounts: Some(RpcSimulateTransactionAccountsConfig {
+                    encoding: Some(UiAccountEncoding::Base64),
+                    addresses: pubkeys.iter().map(|p| p.to_string()).collect(),
+                }),
+                ..<_>::default()
+            },
+        )
+        .await?
+        .value;
+    let post = result.accounts.unwrap_or_default();
+    let balance_changes = pubkeys
+        .iter()
+        .zip(pre)
+        .zip(post.into_iter().chain(std::iter::repeat(None)))
+        .filter_map(|((pubkey, pre), post)| {
+            let pre = pre.map(|a| a.lamports).unwrap_or(0);
+            let post = post.map(|a| a.lamports).unwrap_or(0);
+            (pre != post).then(|| BalanceChange {
+                pubkey: *pubkey,
+                pre,
+                post,
+                change: post as i64 - pre as i64,
+            })
+        })
+        .collect();
+    Ok(Simulation {
+        err: result.err.map(|e| e.to_string()),
+        logs: result.logs.unwrap_or_default(),
+        units_consumed: result.units_consumed,
+        balance_changes,
+    })
+}
+
+async fn run(ctx: CommandContext, input: Input) -> Result<Output, CommandError> {
+    let client = ctx.solana_client();
+    let tx = parse_transaction(&input.transaction)?;
+    let idls = input
+        .idls
+        .into_iter()
+        .map(|idl| {
+            let idl = Idl::parse(idl)?;
+            let program_id = idl
+                .program_id()
+                .ok_or_else(|| anyhow!("IDL does not declare an address"))?;
+            Ok((program_id, idl))
+        })
+        .collect::<Result<Vec<_>, CommandError>>()?;
+
+    let message = &tx.message;
+    let roles = account_roles(client, message).await?;
+    let role = |index: u8| {
+        roles
+            .get(index as usize)
+            .ok_or_else(|| anyhow!("account index {} out of range", index))
+    };
+
+    let mut instructions = Vec::new();
+    for ix in message.instructions() {
+        let program_id = role(ix.program_id_index)?.pubkey;
+        let mut accounts = ix
+            .accounts
+            .iter()
+            .map(|index| {
+                let role = role(*index)?;
+                Ok(InstructionAccount {
+                    pubkey: role.pubkey,
+                    signer: role.signer,
+                    writable: role.writable,
+                    name: None,
+                })
+            })
+            .collect::<Result<Vec<_>, CommandError>>()?;
+        let (program, name, args) = decode_instruction(&idls, &program_id, &ix.data, &mut accounts);
+        instructions.push(DecodedInstruction {
+            program_id,
+            program,
+            name,
+            args,
+            accounts,
+            data: ix.data.clone().into(),
+        });
+    }
+
+    let simulation = if input.simulate {
+        Some(simulate(client, &tx, &roles).await?)
+    } else {
+        None
+    };
+
+    Ok(Output {
+        version: match message {
+            VersionedMessage::Legacy(_) => "legacy".to_owned(),
+            VersionedMessage::V0(_) => "0".to_owned(),
+        },
+        fee_payer: roles
+            .first()
+            .ok_or_else(|| anyhow!("transaction has no accounts"))?
+            .pubkey,
+        recent_blockhash: message.recent_blockhash().to_string(),
+        signatures: tx.signatures.iter().map(|s| s.to_string()).collect(),
+        accounts: roles,
+        instructions,
+        simulation,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::svm::TestSvm;
+    use solana_message::Message;
+    use solana_transaction::Transaction;
+
+    #[test]
+    fn test_build() {
+        build().unwrap();
+    }
+
+    #[test]
+    fn test_malformed_header() {
+        let payer = Pubkey::new_unique();
+        let mut message = Message::new(
+            &[solana_system_interface::instruction::transfer(
+                &payer,
+                &Pubkey::new_unique(),
+                5000,
+            )],
+            Some(&payer),
+        );
+        message.header.num_readonly_signed_accounts = 2;
+        let tx = VersionedTransaction::from(Transaction::new_unsigned(message));
+        let transaction = base64::encode(bincode::serialize(&tx).unwrap());
+        let error = parse_transaction(&transaction).unwrap_err();
+        assert!(error.to_string().contains("invalid transaction"), "{error}");
+    }
+
+    #[test]
+    fn test_parse_base58() {
+        let payer = Pubkey::new_unique();
+        let recipient = Pubkey::new_unique();
+        // a base58 encoding that is also valid base64
+        let (tx, encoded) = (0..)
+            .map(|lamports| {
+                let message = Message::new(
+                    &[solana_system_interface::instruction::transfer(
+                        &payer, &recipient, lamports,
+                    )],
+                    Some(&payer),
+                );
+                let tx = VersionedTransaction::from(Transaction::new_unsigned(message));
+                let encoded = bs58::encode(bincode::serialize(&tx).unwrap()).into_string();
+                (tx, encoded)
+            })
+            .find(|(_, encoded)| base64::decode(encoded).is_ok())
+            .unwrap();
+        assert_eq!(parse_transaction(&encoded).unwrap(), tx);
+
+        let encoded = base64::encode(bincode::serialize(&tx).unwrap());
+        assert_eq!(parse_transaction(&encoded).unwrap(), tx);
+    }
+
+    #[tokio::test]
+    async fn test_simulate_large_lookup_table() {
+        let svm = TestSvm::new();
+        let payer = svm.funded_wallet(1_000_000_000);
+        let recipient = Pubkey::new_unique();
+        let addresses = (0..120).map(|_| Pubkey::new_unique()).collect::<Vec<_>>();
+        let table_key = Pubkey::new_unique();
+        let table = AddressLookupTable {
+            meta: <_>::default(),
+            addresses: addresses.clone().into(),
+        };
+        svm.set_account(
+            table_key,
+            solana_account::Account {
+                lamports: 1_000_000_000,
+                data: table.serialize_for_tests().unwrap(),
+                owner: solana_address_lookup_table_interface::program::ID,
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+        let mut transfer =
+            solana_system_interface::instruction::transfer(&payer.pubkey(), &recipient, 5000);
+        transfer.accounts.extend(
+            addresses
+                .iter()
+                .map(|pubkey| solana_instruction::AccountMeta::new_readonly(*pubkey, false)),
+        );
+        let message = solana_message::v0::Message::try_compile(
+            &payer.pubkey(),
+            &[transfer],
+            &[solana_message::AddressLookupTableAccount {
+                key: table_key,
+                addresses,
+            }],
+            svm.rpc_client().get_latest_blockhash().await.unwrap(),
+        )
+        .unwrap();
+        let tx = VersionedTransaction {
+            signatures: vec![Default::default()],
+            message: VersionedMessage::V0(message),
+        };
+        let transaction = base64::encode(bincode::serialize(&tx).unwrap());
+
+        let output = run(
+            svm.context(),
+            Input {
+                transaction,
+                idls: Vec::new(),
+                simulate: true,
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(output.version, "0");
+        assert!(output.accounts.len() > MAX_MULTIPLE_ACCOUNTS);
+        assert!(output.simulation.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_decode() {
+        let svm = TestSvm::new();
+        let payer = svm.funded_wallet(1_000_000_000);
+        let recipient = Pubkey::new_unique();
+        let message = Message::new(
+            &[
+                ComputeBudgetInstruction::set_compute_unit_price(1000),
+                solana_system_interface::instruction::transfer(&payer.pubkey(), &recipient, 5000),
+                spl_memo_interface::instruction::build_memo(
+                    &spl_memo_interface::v3::ID,
+                    b"hello",
+                    &[],
+                ),
+            ],
+            Some(&payer.pubkey()),
+        );
+        let tx = VersionedTransaction::from(Transaction::new_unsigned(message));
+        let transaction = base64::encode(bincode::serialize(&tx).unwrap());
+
+        let output = run(
+            svm.context(),
+            Input {
+                transaction,
+                idls: Vec::new(),
+                simulate: false,
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(output.version, "legacy");
+        assert_eq!(output.fee_payer, payer.pubkey());
+        let names = output
+            .instructions
+            .iter()
+            .map(|ix| (ix.program.as_deref(), ix.name.as_deref()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                (Some("compute_budget"), Some("set_compute_unit_price")),
+                (Some("system"), Some("transfer")),
+                (Some("spl_memo"), Some("memo")),
+            ]
+        );
+        let transfer = &output.instructions[1];
+        assert!(transfer.accounts[0].signer && transfer.accounts[0].writable);
+        assert!(!transfer.accounts[1].signer && transfer.accounts[1].writable);
+        assert_eq!(
+            output.instructions[0].args,
+            Some(Value::Map(value::map! { "micro_lamports" => 1000u64 }))
+        );
+    }
+
+    #[test]
+    fn test_decode_anchor() {
+        let idl = Idl::parse(serde_json::json!({
+            "address": "11111111111111111111111111111111",
+            "instructions": [{
+                "name": "increment",
+                "discriminator": [1, 2, 3, 4, 5, 6, 7, 8],
+                "accounts": [{ "name": "counter", "writable": true }],
+                "args": [{ "name": "amount", "type": "u64" }],
+            }],
+        }))
+        .unwrap();
+        let mut data = vec![1, 2, 3, 4, 5, 6, 7, 8];
+        data.extend(7u64.to_le_bytes());
+
+        let (name, args, accounts) = decode_anchor(&idl, &data).unwrap();
+        assert_eq!(name, "increment");
+        assert_eq!(args, Some(Value::Map(value::map! { "amount" => 7u64 })));
+        assert_eq!(accounts, ["counter"]);
+    }
+}
@@ -33,8 +33,8 @@ pub enum Error {
     AccountNotFound(solana_program::pubkey::Pubkey),
     #[error("Invalid account data for {0}")]
     InvalidAccountData(solana_program::pubkey::Pubkey),
-    #[error("bundlr isn't available on solana testnet")]
-    BundlrNotAvailableOnTestnet,
+    #[error("bundlr isn't available on solana {}", .0.as_str())]
+    BundlrNotAvailable(flow_lib::SolanaNet),
     #[error("bundlr api returned an invalid response: {0}")]
     BundlrApiInvalidResponse(String),
     #[error("failed to register funding tx to bundlr. tx_id={0};")]
@@ -8,6 +8,7 @@ use tracing::info;
 
 pub mod error;
 
+pub mod anchor;
 pub mod ardrive;
 pub mod arweave;
 pub mod attestation_service;
@@ -16,6 +17,7 @@ pub mod auctioneer;
 pub mod bridge;
 pub mod damm_v2;
 pub mod das;
+pub mod decode_transaction;
 pub mod dflow;
 pub mod dynamic_bonding_curve;
 pub mod escrow;
@@ -57,6 +59,7 @@ pub mod switchboard;
 pub mod system_program;
 pub mod tuktuk;
 pub mod utils;
+pub mod wait_for_account;
 pub mod wormhole;
 pub mod yvaults;
 pub mod zk_compression;
@@ -18,7 +18,9 @@ pub const RECORD_DEVNET: Pubkey = pubkey!("recr1L3PCGKLbckBqMNcJhuuyU1zgo8nBhfLV
 
 pub const fn record_program_id(net: SolanaNet) -> Pubkey {
     match net {
-        SolanaNet::Mainnet => crate::spl_record::RECORD_MAINNET,
+        SolanaNet::Mainnet | SolanaNet::Localnet | SolanaNet::Custom => {
+            crate::spl_record::RECORD_MAINNET
+        }
         SolanaNet::Devnet => crate::spl_record::RECORD_DEVNET,
         // TODO testnet not deployed yet
         SolanaNet::Testnet => crate::spl_record::RECORD_DEVNET,
@@ -13,7 +13,9 @@ pub const STREAMFLOW_DEVNET_PROGRAM_ID: Pubkey =
 
 pub const fn streamflow_program_id(net: SolanaNet) -> Pubkey {
     match net {
-        SolanaNet::Mainnet => crate::streamflow::STREAMFLOW_PROGRAM_ID,
+        SolanaNet::Mainnet | SolanaNet::Localnet | SolanaNet::Custom => {
+            crate::streamflow::STREAMFLOW_PROGRAM_ID
+        }
         // TODO testnet not deployed yet
         SolanaNet::Testnet => crate::streamflow::STREAMFLOW_DEVNET_PROGRAM_ID,
         SolanaNet::Devnet => crate::streamflow::STREAMFLOW_DEVNET_PROGRAM_ID,
@@ -77,6 +77,29 @@ mod tests {
         build().unwrap();
     }
 
+    #[tokio::test]
+    async fn test_transfer_sol_svm() {
+        let svm = test_utils::svm::TestSvm::new();
+        let sender = svm.funded_wallet(1_000_000_000);
+        let recipient = Keypair::new().pubkey();
+
+        let output = run(
+            svm.context(),
+            Input {
+                fee_payer: None,
+                sender,
+                recipient,
+                amount: rust_decimal_macros::dec!(0.001),
+                submit: true,
+            },
+        )
+        .await
+        .unwrap();
+
+        assert!(output.signature.is_some());
+        assert_eq!(svm.get_balance(&recipient), 1_000_000);
+    }
+
     #[tokio::test]
     #[ignore = "requires funded wallet and network access"]
     async fn test_transfer_sol() {
@@ -7,6 +7,8 @@
 //!
 //! - `TEST_WALLET_KEYPAIR` - Base58-encoded keypair for test wallet (required for integration tests)
 //! - `SOLANA_DEVNET_URL` - Custom devnet RPC URL (optional, defaults to public devnet)
+//!
+//! For offline tests, see [`svm::TestSvm`].
 
 use flow_lib::config::Endpoints;
 use flow_lib::flow_run_events::NodeLogSender;
@@ -30,6 +32,8 @@ use std::collections::HashMap;
 use std::sync::Arc;
 use tower::service_fn;
 
+pub mod svm;
+
 /// Load the test wallet from env var (base58 keypair).
 ///
 /// Checks `TEST_WALLET_KEYPAIR` first, then falls back to `keypair`.
@@ -88,20 +92,16 @@ pub fn test_context() -> CommandContext {
             let instructions = req.instructions;
 
             if instructions.instructions.is_empty() {
-                return Ok(execute::Response { signature: None });
+                return Ok(execute::Response::default());
             }
 
             let config = ExecutionConfig::default();
             let signer = build_keypair_signer(&instructions);
 
-            let signature = instructions
+            instructions
                 .execute(&rpc, None, network, signer, None, config)
                 .await
-                .map_err(|e| ExecuteError::msg(e.to_string()))?;
-
-            Ok(execute::Response {
-                signature: Some(signature),
-            })
+                .map_err(|e| ExecuteError::msg(e.to_string()))
         }
     }));
 
@@ -0,0 +1,475 @@
+//! Offline test harness backed by an in-process SVM.
+//!
+//! [`TestSvm`] provides both the `solana_client` and the `execute::Svc` of a
+//! [`CommandContext`], so a command can run end to end without network access:
+//!
+//! ```ignore
+//! let svm = TestSvm::new();
+//! svm.load_program(program_id, "my_program");
+//! let payer = svm.funded_wallet(LAMPORTS_PER_SOL);
+//! let output = run(svm.context(), Input { .. }).await.unwrap();
+//! assert!(svm.get_account(&output.account).is_some());
+//! ```
+//!
+//! Program binaries are loaded from `tests/fixtures/programs/<name>.so`.
+
+use async_trait::async_trait;
+use flow_lib::{
+    context::{
+        CommandContext,
+        execute::{self, Error as ExecuteError},
+    },
+    solana::{Instructions, Signature, Wallet},
+    utils::tower_client::{CommonErrorExt, TowerClient},
+};
+use litesvm::{LiteSVM, types::TransactionResult};
+use serde_json::{Value as JsonValue, json};
+use solana_account::Account;
+use solana_account_decoder::{UiAccountEncoding, encode_ui_account};
+use solana_address_lookup_table_interface::state::AddressLookupTable;
+use solana_commitment_config::CommitmentConfig;
+use solana_keypair::Keypair;
+use solana_message::{AddressLookupTableAccount, Message, VersionedMessage, v0};
+use solana_program::{clock::Clock, hash::Hash};
+use solana_pubkey::Pubkey;
+use solana_rpc_client::{
+    nonblocking::rpc_client::RpcClient,
+    rpc_client::RpcClientConfig,
+    rpc_sender::{RpcSender, RpcTransportStats},
+};
+use solana_rpc_client_api::{
+    client_error::{Error as ClientError, Result as ClientResult},
+    request::{RpcError, RpcRequest},
+};
+use solana_signer::Signer;
+use solana_transaction::versioned::VersionedTransaction;
+use solana_transaction_status::{TransactionConfirmationStatus, TransactionStatus};
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+use tower::service_fn;
+
+/// Keys accepted by `getMultipleAccounts`, as on a real RPC node.
+pub const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+/// Shared in-process SVM, cheap to clone.
+#[derive(Clone)]
+pub struct TestSvm {
+    svm: Arc<Mutex<LiteSVM>>,
+}
+
+impl Default for TestSvm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TestSvm {
+    pub fn new() -> Self {
+        Self {
+            svm: Arc::new(Mutex::new(LiteSVM::new())),
+        }
+    }
+
+    /// Load `tests/fixtures/programs/<name>.so` as `program_id`.
+    pub fn load_program(&self, program_id: Pubkey, name: &str) {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/programs")
+            .join(name)
+            .with_extension("so");
+        self.svm
+            .lock()
+            .unwrap()
+            .add_program_from_file(program_id, &path)
+            .unwrap_or_else(|error| panic!("failed to load {}: {}", path.display(), error));
+    }
+
+    pub fn airdrop(&self, pubkey: &Pubkey, lamports: u64) {
+        self.svm
+            .lock()
+            .unwrap()
+            .airdrop(pubkey, lamports)
+            .unwrap_or_else(|error| panic!("airdrop failed: {:?}", error.err));
+    }
+
+    /// A new wallet holding `lamports`.
+    pub fn funded_wallet(&self, lamports: u64) -> Wallet {
+        let keypair = Keypair::new();
+        self.airdrop(&keypair.pubkey(), lamports);
+        keypair.into()
+    }
+
+    pub fn get_account(&self, pubkey: &Pubkey) -> Option<Account> {
+        self.svm.lock().unwrap().get_account(pubkey)
+    }
+
+    pub fn set_account(&self, pubkey: Pubkey, account: Account) {
+        self.svm
+            .lock()
+            .unwrap()
+            .set_account(pubkey, account)
+            .expect("set_account");
+    }
+
+    pub fn get_balance(&self, pubkey: &Pubkey) -> u64 {
+        self.svm.lock().unwrap().get_balance(pubkey).unwrap_or(0)
+    }
+
+    /// RPC client answering from the SVM state.
+    pub fn rpc_client(&self) -> Arc<RpcClient> {
+        Arc::new(RpcClient::new_sender(
+            SvmSender { svm: self.clone() },
+            RpcClientConfig::with_commitment(CommitmentConfig::confirmed()),
+        ))
+    }
+
+    /// Execute service signing with the keypairs in `Instructions` and
+    /// processing the transaction in the SVM.
+    pub fn execute_svc(&self) -> execute::Svc {
+        let svm = self.clone();
+        TowerClient::new(service_fn(move |req: execute::Request| {
+            let result = svm.process(req.instructions);
+            std::future::ready(result)
+        }))
+    }
+
+    /// `CommandContext::test_context` wired to this SVM.
+    pub fn context(&self) -> CommandContext {
+        CommandContext::test_context_with(self.rpc_client(), self.execute_svc())
+    }
+
+    fn process(&self, instructions: Instructions) -> Result<execute::Response, ExecuteError> {
+        if instructions.instructions.is_empty() {
+            return Ok(execute::Response::default());
+        }
+        let keypairs = instructions
+            .signers
+            .iter()
+            .map(|w| {
+                w.keypair()
+                    .ok_or_else(|| ExecuteError::msg(format!("no keypair for {}", w.pubkey())))
+            })
+            .collect::<Result<Vec<&Keypair>, _>>()?;
+        let mut svm = self.svm.lock().unwrap();
+        let blockhash = svm.latest_blockhash();
+        let lookups = instructions
+            .lookup_tables
+            .iter()
+            .flatten()
+            .map(|key| {
+                let account = svm
+                    .get_account(key)
+                    .ok_or_else(|| ExecuteError::msg(format!("lookup table {key} not found")))?;
+                let table = AddressLookupTable::deserialize(&account.data)
+                    .map_err(|error| ExecuteError::msg(error.to_string()))?;
+                Ok(AddressLookupTableAccount {
+                    key: *key,
+                    addresses: table.addresses.to_vec(),
+                })
+            })
+            .collect::<Result<Vec<_>, ExecuteError>>()?;
+        let message = if lookups.is_empty() {
+            VersionedMessage::Legacy(Message::new_with_blockhash(
+                &instructions.instructions,
+                Some(&instructions.fee_payer),
+                &blockhash,
+            ))
+        } else {
+            VersionedMessage::V0(
+                v0::Message::try_compile(
+                    &instructions.fee_payer,
+                    &instructions.instructions,
+                    &lookups,
+                    blockhash,
+                )
+                .map_err(|error| ExecuteError::msg(error.to_string()))?,
+            )
+        };
+        let tx = VersionedTransaction::try_new(message, &keypairs)
+            .map_err(|error| ExecuteError::msg(error.to_string()))?;
+        let signature = tx.signatures[0];
+        match svm.send_transaction(tx) {
+            Ok(meta) => {
+                tracing::debug!("{}", meta.logs.join("\n"));
+                Ok(execute::Response::signature(signature))
+            }
+            Err(failed) => {
+                tracing::warn!("{}", failed.meta.logs.join("\n"));
+                Err(ExecuteError::solana(failed.err.into(), 0))
+            }
+        }
+    }
+}
+
+struct SvmSender {
+    svm: TestSvm,
+}
+
+fn rpc_error(msg: impl Into<String>) -> ClientError {
+    RpcError::ForUser(msg.into()).into()
+}
+
+fn param<T: serde::de::DeserializeOwned>(params: &JsonValue, index: usize) -> ClientResult<T> {
+    serde_json::from_value(params.get(index).cloned().unwrap_or(JsonValue::Null))
+        .map_err(|error| rpc_error(format!("invalid param {}: {}", index, error)))
+}
+
+fn encoding(params: &JsonValue, index: usize) -> UiAccountEncoding {
+    params
+        .get(index)
+        .and_then(|config| config.get("encoding"))
+        .and_then(|e| serde_json::from_value(e.clone()).ok())
+        .unwrap_or(UiAccountEncoding::Base64)
+}
+
+fn decode_transaction(params: &JsonValue) -> ClientResult<VersionedTransaction> {
+    let encoded = param::<String>(params, 0)?;
+    let bytes = base64::decode(encoded).map_err(|error| rpc_error(error.to_string()))?;
+    bincode::deserialize(&bytes).map_err(|error| rpc_error(error.to_string()))
+}
+
+fn status(slot: u64, result: &TransactionResult) -> TransactionStatus {
+    let err = result.as_ref().err().map(|failed| failed.err.clone());
+    TransactionStatus {
+        slot,
+        confirmations: None,
+        status: err.clone().map_or(Ok(()), Err),
+        err,
+        confirmation_status: Some(TransactionConfirmationStatus::Finalized),
+    }
+}
+
+#[async_trait]
+impl RpcSender for SvmSender {
+    async fn send(&self, request: RpcRequest, params: JsonValue) -> ClientResult<JsonValue> {
+        let mut svm = self.svm.svm.lock().unwrap();
+        let slot = svm.get_sysvar::<Clock>().slot;
+        let with_context =
+            |value: JsonValue| json!({ "context": { "slot": slot }, "value": value });
+        let account = |svm: &LiteSVM, pubkey: &Pubkey, encoding| {
+            svm.get_account(pubkey)
+                .map(|account| encode_ui_account(pubkey, &account, encoding, None, None))
+        };
+        Ok(match request {
+            RpcRequest::GetAccountInfo => {
+                let pubkey = param::<String>(&params, 0)?
+                    .parse::<Pubkey>()
+                    .map_err(|error| rpc_error(error.to_string()))?;
+                with_context(json!(account(&svm, &pubkey, encoding(&params, 1))))
+            }
+            RpcRequest::GetMultipleAccounts => {
+                let encoding = encoding(&params, 1);
+                let keys = param::<Vec<String>>(&params, 0)?;
+                if keys.len() > MAX_MULTIPLE_ACCOUNTS {
+                    return Err(rpc_error(format!(
+                        "Too many inputs provided; max {}",
+                        MAX_MULTIPLE_ACCOUNTS
+                    )));
+                }
+                let accounts = keys
+                    .iter()
+                    .map(|key| {
+                        let pubkey = key
+                            .parse::<Pubkey>()
+                            .map_err(|error| rpc_error(error.to_string()))?;
+                        Ok(account(&svm, &pubkey, encoding))
+                    })
+                    .collect::<ClientResult<Vec<_>>>()?;
+                with_context(json!(accounts))
+            }
+            RpcRequest::GetBalance => {
+                let pubkey = param::<String>(&params, 0)?
+                    .parse::<Pubkey>()
+                    .map_err(|error| rpc_error(error.to_string()))?;
+                with_context(json!(svm.get_balance(&pubkey).unwrap_or(0)))
+            }
+            RpcRequest::GetLatestBlockhash => with_context(json!({
+                "blockhash": svm.latest_blockhash().to_string(),
+                "lastValidBlockHeight": slot + 150,
+            })),
+            RpcRequest::IsBlockhashValid => {
+                let blockhash = param::<String>(&params, 0)?
+                    .parse::<Hash>()
+                    .map_err(|error| rpc_error(error.to_string()))?;
+                with_context(json!(blockhash == svm.latest_blockhash()))
+            }
+            RpcRequest::GetSlot | RpcRequest::GetBlockHeight => json!(slot),
+            RpcRequest::GetMinimumBalanceForRentExemption => {
+                json!(svm.minimum_balance_for_rent_exemption(param(&params, 0)?))
+            }
+            RpcRequest::GetRecentPrioritizationFees => json!([]),
+            RpcRequest::SendTransaction => {
+                let tx = decode_transaction(&params)?;
+                let signature = tx.signatures[0];
+                svm.send_transaction(tx).map_err(|failed| {
+                    tracing::warn!("{}", failed.meta.logs.join("\n"));
+                    ClientError::from(failed.err)
+                })?;
+                json!(signature.to_string())
+            }
+            RpcRequest::SimulateTransaction => {
+                let tx = decode_transaction(&params)?;
+                let addresses = params
+                    .get(1)
+                    .and_then(|config| config.get("accounts"))
+                    .and_then(|accounts| accounts.get("addresses"))
+                    .map(|addresses| serde_json::from_value::<Vec<String>>(addresses.clone()))
+                    .transpose()
+                    .map_err(|error| rpc_error(error.to_string()))?;
+                let (err, meta, post_accounts) = match svm.simulate_transaction(tx) {
+                    Ok(info) => (None, info.meta, info.post_accounts),
+                    Err(failed) => (Some(failed.err), failed.meta, Vec::new()),
+                };
+                // accounts the transaction did not touch keep their state
+                let accounts = addresses
+                    .map(|addresses| {
+                        addresses
+                            .iter()
+                            .map(|key| {
+                                let pubkey = key
+                                    .parse::<Pubkey>()
+                                    .map_err(|error| rpc_error(error.to_string()))?;
+                                Ok(match post_accounts.iter().find(|(k, _)| *k == pubkey) {
+                                    Some((_, post)) => Some(encode_ui_account(
+                                        &pubkey,
+                                        post,
+                                        UiAccountEncoding::Base64,
+                                        None,
+                                        None,
+                                    )),
+                                    None => account(&svm, &pubkey, UiAccountEncoding::Base64),
+                                })
+                            })
+                            .collect::<ClientResult<Vec<_>>>()
+                    })
+                    .transpose()?;
+                with_context(json!({
+                    "err": err,
+                    "logs": meta.logs,
+                    "accounts": accounts,
+                    "unitsConsumed": meta.compute_units_consumed,
+                }))
+            }
+            RpcRequest::GetSignatureStatuses => {
+                let statuses = param::<Vec<String>>(&params, 0)?
+                    .iter()
+                    .map(|s| {
+                        let signature = s
+                            .parse::<Signature>()
+                            .map_err(|error| rpc_error(error.to_string()))?;
+                        Ok(svm
+                            .get_transaction(&signature)
+                            .map(|result| status(slot, result)))
+                    })
+                    .collect::<ClientResult<Vec<_>>>()?;
+                with_context(json!(statuses))
+            }
+            _ => {
+                return Err(rpc_error(format!(
+                    "{} is not supported by TestSvm",
+                    request
+                )));
+            }
+        })
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        RpcTransportStats::default()
+    }
+
+    fn url(&self) -> String {
+        "litesvm".to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_program::native_token::LAMPORTS_PER_SOL;
+
+    #[tokio::test]
+    async fn test_rpc_client() {
+        let svm = TestSvm::new();
+        let wallet = svm.funded_wallet(LAMPORTS_PER_SOL);
+        let rpc = svm.rpc_client();
+        assert_eq!(
+            rpc.get_balance(&wallet.pubkey()).await.unwrap(),
+            LAMPORTS_PER_SOL
+        );
+        assert!(rpc.get_account(&wallet.pubkey()).await.is_ok());
+        assert!(rpc.get_account(&Pubkey::new_unique()).await.is_err());
+        rpc.get_latest_blockhash().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_execute() {
+        let svm = TestSvm::new();
+        let sender = svm.funded_wallet(LAMPORTS_PER_SOL);
+        let recipient = Pubkey::new_unique();
+        let mut ctx = svm.context();
+        let instructions = Instructions {
+            lookup_tables: None,
+            fee_payer: sender.pubkey(),
+            instructions: [solana_system_interface::instruction::transfer(
+                &sender.pubkey(),
+                &recipient,
+                1_000_000,
+            )]
+            .into(),
+            signers: [sender].into(),
+        };
+        let signature = ctx
+            .execute(instructions, <_>::default())
+            .await
+            .unwrap()
+            .signature
+            .unwrap();
+        assert_eq!(svm.get_balance(&recipient), 1_000_000);
+        let status = ctx
+            .solana_client()
+            .get_signature_status(&signature)
+            .await
+            .unwrap();
+        assert_eq!(status, Some(Ok(())));
+    }
+
+    #[tokio::test]
+    async fn test_execute_with_lookup_table() {
+        let svm = TestSvm::new();
+        let sender = svm.funded_wallet(LAMPORTS_PER_SOL);
+        let recipient = Pubkey::new_unique();
+        let table_key = Pubkey::new_unique();
+        let table = AddressLookupTable {
+            meta: <_>::default(),
+            addresses: vec![recipient].into(),
+        };
+        svm.set_account(
+            table_key,
+            Account {
+                lamports: LAMPORTS_PER_SOL,
+                data: table.serialize_for_tests().unwrap(),
+                owner: solana_address_lookup_table_interface::program::ID,
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+        // addresses are usable in the slot after they were added
+        svm.svm.lock().unwrap().warp_to_slot(1);
+
+        let mut ctx = svm.context();
+        let instructions = Instructions {
+            lookup_tables: Some([table_key].into()),
+            fee_payer: sender.pubkey(),
+            instructions: [solana_system_interface::instruction::transfer(
+                &sender.pubkey(),
+                &recipient,
+                1_000_000,
+            )]
+            .into(),
+            signers: [sender].into(),
+        };
+        ctx.execute(instructions, <_>::default()).await.unwrap();
+        assert_eq!(svm.get_balance(&recipient), 1_000_000);
+    }
+}
@@ -0,0 +1,388 @@
+use crate::{
+    anchor::{decode_account, idl::Idl},
+    prelude::*,
+};
+use anyhow::anyhow;
+use flow_lib::command::condition::{Operator, evaluate};
+use futures::StreamExt;
+use solana_account::Account;
+use solana_account_decoder::UiAccountEncoding;
+use solana_program::program_option::COption;
+use solana_pubsub_client::nonblocking::pubsub_client::PubsubClient;
+use solana_rpc_client_api::config::RpcAccountInfoConfig;
+use spl_token_2022_interface::{
+    extension::StateWithExtensions,
+    state::{Account as TokenAccount, AccountState, Mint},
+};
+use std::time::Duration;
+use tokio::time::{Instant, sleep, timeout_at};
+use tokio_util::sync::CancellationToken;
+
+const NAME: &str = "wait_for_account";
+
+const DEFINITION: &str = flow_lib::node_definition!("wait_for_account.jsonc");
+
+flow_lib::submit!(CommandDescription::new(NAME, |_| build()));
+
+fn build() -> BuildResult {
+    static CACHE: BuilderCache =
+        BuilderCache::new(|| CmdBuilder::new(DEFINITION)?.check_name(NAME));
+    Ok(CACHE.clone()?.build(run))
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountDecoder {
+    /// Token account or mint if owned by a token program, IDL if one is given,
+    /// raw data otherwise.
+    #[default]
+    Auto,
+    Raw,
+    TokenAccount,
+    Mint,
+    Idl,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Input {
+    #[serde(with = "value::pubkey")]
+    account: Pubkey,
+    #[serde(default)]
+    decoder: AccountDecoder,
+    #[serde(default)]
+    idl: Option<JsonValue>,
+    /// Path in the decoded account, e.g. `data.amount`.
+    #[serde(default)]
+    field: Option<String>,
+    operator: Operator,
+    #[serde(default)]
+    compare_to: Option<Value>,
+    #[serde(default = "default_timeout_secs")]
+    timeout_secs: u64,
+    #[serde(default = "default_poll_interval_ms")]
+    poll_interval_ms: u64,
+}
+
+fn default_timeout_secs() -> u64 {
+    60
+}
+
+fn default_poll_interval_ms() -> u64 {
+    2000
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Output {
+    /// Decoded account, `null` if it does not exist.
+    account: Value,
+}
+
+fn optional<T: Into<Value>>(value: COption<T>) -> Value {
+    Option::from(value).map(Into::into).unwrap_or(Value::Null)
+}
+
+fn decode_token_account(data: &[u8]) -> Result<Value, CommandError> {
+    let account = StateWithExtensions::<TokenAccount>::unpack(data)?.base;
+    let state = match account.state {
+        AccountState::Uninitialized => "uninitialized",
+        AccountState::Initialized => "initialized",
+        AccountState::Frozen => "frozen",
+    };
+    Ok(Value::Map(value::map! {
+        "mint" => account.mint,
+        "owner" => account.owner,
+        "amount" => account.amount,
+        "delegate" => optional(account.delegate),
+        "state" => state,
+        "is_native" => optional(account.is_native),
+        "delegated_amount" => account.delegated_amount,
+        "close_authority" => optional(account.close_authority),
+    }))
+}
+
+fn decode_mint(data: &[u8]) -> Result<Value, CommandError> {
+    let mint = StateWithExtensions::<Mint>::unpack(data)?.base;
+    Ok(Value::Map(value::map! {
+        "mint_authority" => optional(mint.mint_authority),
+        "supply" => mint.supply,
+        "decimals" => mint.decimals as u64,
+        "is_initialized" => mint.is_initialized,
+        "freeze_authority" => optional(mint.freeze_authority),
+    }))
+}
+
+fn is_token_program(owner: &Pubkey) -> bool {
+    *owner == spl_token_interface::ID || *owner == spl_token_2022_interface::ID
+}
+
+/// Decode an account into `{ lamports, owner, executable, data }`.
+///
+/// `idl` caches the IDL of the account owner between updates.
+async fn decode(
+    client: &RpcClient,
+    input: &Input,
+    idl: &mut Option<(Pubkey, Idl)>,
+    account: Option<&Account>,
+) -> Result<Value, CommandError> {
+    let Some(account) = account else {
+        return Ok(Value::Null);
+    };
+    let decoder = match input.decoder {
+        AccountDecoder::Auto if input.idl.is_some() => AccountDecoder::Idl,
+        AccountDecoder::Auto if is_token_program(&account.owner) => {
+            if decode_token_account(&account.data).is_ok() {
+                AccountDecoder::TokenAccount
+            } else {
+                AccountDecoder::Mint
+            }
+        }
+        AccountDecoder::Auto => AccountDecoder::Raw,
+        decoder => decoder,
+    };
+    let data = match decoder {
+        AccountDecoder::Auto | AccountDecoder::Raw => Value::Bytes(account.data.clone().into()),
+        AccountDecoder::TokenAccount => decode_token_account(&account.data)?,
+        AccountDecoder::Mint => decode_mint(&account.data)?,
+        AccountDecoder::Idl => {
+            if idl
+                .as_ref()
+                .is_none_or(|(owner, _)| *owner != account.owner)
+            {
+                let (loaded, _) =
+                    crate::anchor::load_idl(client, input.idl.clone(), Some(account.owner)).await?;
+                *idl = Some((account.owner, loaded));
+            }
+            let (_, idl) = idl.as_ref().expect("IDL is loaded above");
+            value::to_value(&decode_account::decode(idl, None, &account.data)?)?
+        }
+    };
+    Ok(Value::Map(value::map! {
+        "lamports" => account.lamports,
+        "owner" => account.owner,
+        "executable" => account.executable,
+        "data" => data,
+    }))
+}
+
+fn ws_url(url: &str) -> String {
+    if let Some(rest) = url.strip_prefix("https://") {
+        format!("wss://{rest}")
+    } else if let Some(rest) = url.strip_prefix("http://") {
+        format!("ws://{rest}")
+    } else {
+        url.to_owned()
+    }
+}
+
+/// Wait for the account to satisfy the condition, or fail when the timeout
+/// expires or the run is canceled.
+async fn run(ctx: CommandContext, input: Input) -> Result<Output, CommandError> {
+    let cancel_token = ctx.get::<CancellationToken>().cloned();
+    let deadline = Instant::now() + Duration::from_secs(input.timeout_secs);
+    tokio::select! {
+        _ = async {
+            match &cancel_token {
+                Some(cancel_token) => cancel_token.cancelled().await,
+                None => std::future::pending().await,
+            }
+        } => Err(anyhow!("waiting for account {} canceled", input.account)),
+        result = timeout_at(deadline, wait(ctx.solana_client(), &input)) => match result {
+            Ok(result) => result,
+            Err(_) => Err(anyhow!(
+                "timeout waiting for account {} after {}s",
+                input.account,
+                input.timeout_secs
+            )),
+        },
+    }
+}
+
+/// Updates come from an account subscription, with RPC polling if it cannot
+/// be opened or is closed.
+async fn wait(client: &RpcClient, input: &Input) -> Result<Output, CommandError> {
+    let poll_interval = Duration::from_millis(input.poll_interval_ms.max(100));
+
+    let pubsub = PubsubClient::new(&ws_url(&client.url()))
+        .await
+        .inspect_err(|error| tracing::warn!("pubsub unavailable, polling: {}", error))
+        .ok();
+    let mut updates = match &pubsub {
+        Some(pubsub) => pubsub
+            .account_subscribe(
+                &input.account,
+                Some(RpcAccountInfoConfig {
+                    encoding: Some(UiAccountEncoding::Base64),
+                    commitment: Some(client.commitment()),
+                    ..<_>::default()
+                }),
+            )
+            .await
+            .inspect_err(|error| tracing::warn!("account subscription failed, polling: {}", error))
+            .ok()
+            .map(|(stream, _)| stream),
+        None => None,
+    };
+
+    // the subscription only sends changes, check the current state first
+    let mut account = client
+        .get_account_with_commitment(&input.account, client.commitment())
+        .await?
+        .value;
+    let mut idl = None;
+    loop {
+        let decoded = decode(client, input, &mut idl, account.as_ref()).await?;
+        if evaluate(
+            &decoded,
+            input.field.as_deref(),
+            &input.operator,
+            input.compare_to.as_ref(),
+        )? {
+            return Ok(Output { account: decoded });
+        }
+
+        account = tokio::select! {
+            update = async {
+                match &mut updates {
+                    Some(stream) => stream.next().await,
+                    None => std::future::pending().await,
+                }
+            } => match update {
+                Some(update) => update.value.decode::<Account>(),
+                None => {
+                    tracing::warn!("account subscription closed, polling");
+                    updates = None;
+                    continue;
+                }
+            },
+            _ = sleep(poll_interval), if updates.is_none() => {
+                client
+                    .get_account_with_commitment(&input.account, client.commitment())
+                    .await?
+                    .value
+            }
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::svm::TestSvm;
+    use solana_program::program_pack::Pack;
+
+    #[test]
+    fn test_build() {
+        build().unwrap();
+    }
+
+    #[test]
+    fn test_ws_url() {
+        assert_eq!(
+            ws_url("https://api.devnet.solana.com"),
+            "wss://api.devnet.solana.com"
+        );
+        assert_eq!(ws_url("http://127.0.0.1:8899"), "ws://127.0.0.1:8899");
+    }
+
+    fn input(account: Pubkey, field: &str, operator: Operator, compare_to: Value) -> Input {
+        Input {
+            account,
+            decoder: AccountDecoder::Auto,
+            idl: None,
+            field: Some(field.to_owned()),
+            operator,
+            compare_to: Some(compare_to),
+            timeout_secs: 1,
+            poll_interval_ms: 100,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_token_account() {
+        let svm = TestSvm::new();
+        let account = Pubkey::new_unique();
+        let mut data = vec![0; TokenAccount::LEN];
+        TokenAccount::pack(
+            TokenAccount {
+                mint: Pubkey::new_unique(),
+                owner: Pubkey::new_unique(),
+                amount: 500,
+                state: AccountState::Initialized,
+                ..<_>::default()
+            },
+            &mut data,
+        )
+        .unwrap();
+        svm.set_account(
+            account,
+            Account {
+                lamports: 2_039_280,
+                data,
+                owner: spl_token_interface::ID,
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+
+        let output = run(
+            svm.context(),
+            input(account, "data.amount", Operator::Gte, Value::U64(500)),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            value::crud::get(&output.account, &["data", "state"]),
+            Some(&Value::String("initialized".to_owned()))
+        );
+
+        let error = run(
+            svm.context(),
+            input(account, "data.amount", Operator::Gt, Value::U64(500)),
+        )
+        .await
+        .unwrap_err();
+        assert!(error.to_string().contains("timeout"));
+    }
+
+    #[tokio::test]
+    async fn test_account_created() {
+        let svm = TestSvm::new();
+        let account = Pubkey::new_unique();
+        let ctx = svm.context();
+        let task = tokio::spawn(run(
+            ctx,
+            input(account, "lamports", Operator::Eq, Value::U64(1_000_000)),
+        ));
+        sleep(Duration::from_millis(300)).await;
+        svm.airdrop(&account, 1_000_000);
+        let output = task.await.unwrap().unwrap();
+        assert_eq!(
+            value::crud::get(&output.account, &["lamports"]),
+            Some(&Value::U64(1_000_000))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_canceled() {
+        let svm = TestSvm::new();
+        let cancel_token = CancellationToken::new();
+        let mut ctx = svm.context();
+        ctx.extensions_mut().unwrap().insert(cancel_token.clone());
+        let mut input = input(
+            Pubkey::new_unique(),
+            "lamports",
+            Operator::Gt,
+            Value::U64(0),
+        );
+        input.timeout_secs = 60;
+        let task = tokio::spawn(run(ctx, input));
+        sleep(Duration::from_millis(300)).await;
+        cancel_token.cancel();
+        let error = tokio::time::timeout(Duration::from_secs(5), task)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap_err();
+        assert!(error.to_string().contains("canceled"));
+    }
+}
@@ -32,8 +32,7 @@ pub struct Output {
 
 async fn run(ctx: CommandContext, input: Input) -> Result<Output, CommandError> {
     let wormhole_endpoint = match ctx.solana_config().cluster {
-        SolanaNet::Mainnet => "",
-        SolanaNet::Testnet => "",
+        SolanaNet::Mainnet | SolanaNet::Testnet | SolanaNet::Localnet | SolanaNet::Custom => "",
         SolanaNet::Devnet => "https://api.testnet.wormscan.io",
     }
     .to_owned();
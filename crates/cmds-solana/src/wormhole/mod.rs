@@ -15,7 +15,9 @@ pub const WORMHOLE_CORE_DEVNET: Pubkey = pubkey!("3u8hJUVTA4jH1wYAyUur7FFZVQ8H63
 
 pub const fn wormhole_core_program_id(net: SolanaNet) -> Pubkey {
     match net {
-        SolanaNet::Mainnet => crate::wormhole::WORMHOLE_CORE_MAINNET,
+        SolanaNet::Mainnet | SolanaNet::Localnet | SolanaNet::Custom => {
+            crate::wormhole::WORMHOLE_CORE_MAINNET
+        }
         // TODO testnet not deployed yet
         SolanaNet::Testnet => crate::wormhole::WORMHOLE_CORE_TESTNET,
         SolanaNet::Devnet => crate::wormhole::WORMHOLE_CORE_DEVNET,
@@ -28,7 +30,7 @@ pub const TOKEN_BRIDGE_DEVNET: Pubkey = pubkey!("DZnkkTmCiFWfYTfT41X3Rd1kDgozqzx
 
 pub const fn token_bridge_program_id(net: SolanaNet) -> Pubkey {
     match net {
-        SolanaNet::Mainnet => TOKEN_BRIDGE_MAINNET,
+        SolanaNet::Mainnet | SolanaNet::Localnet | SolanaNet::Custom => TOKEN_BRIDGE_MAINNET,
         // TODO testnet not deployed yet
         SolanaNet::Testnet => TOKEN_BRIDGE_TESTNET,
         SolanaNet::Devnet => TOKEN_BRIDGE_DEVNET,
@@ -41,7 +43,7 @@ pub const NFT_BRIDGE_DEVNET: Pubkey = pubkey!("2rHhojZ7hpu1zA91nvZmT8TqWWvMcKmmN
 
 pub const fn nft_bridge_program_id(net: SolanaNet) -> Pubkey {
     match net {
-        SolanaNet::Mainnet => NFT_BRIDGE_MAINNET,
+        SolanaNet::Mainnet | SolanaNet::Localnet | SolanaNet::Custom => NFT_BRIDGE_MAINNET,
         // TODO testnet not deployed yet
         SolanaNet::Testnet => NFT_BRIDGE_TESTNET,
         SolanaNet::Devnet => NFT_BRIDGE_DEVNET,
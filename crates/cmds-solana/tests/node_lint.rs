@@ -0,0 +1,9 @@
+use cmds_solana as _;
+
+#[test]
+fn node_lint() {
+    flow_lib::command::builder::lint::assert_lint(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/node-definitions"
+    ));
+}
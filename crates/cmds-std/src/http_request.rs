@@ -1,8 +1,16 @@
 use crate::prelude::*;
 use anyhow::anyhow;
-use flow_lib::command::builder::{BuildResult, BuilderCache};
-use reqwest::{Url, dns::Name as DomainName};
-use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use bytes::Bytes;
+use flow_lib::{
+    command::builder::{BuildResult, BuilderCache},
+    utils::net::{check_url, guarded_client},
+};
+use reqwest::{
+    Method, StatusCode, Url,
+    header::{CONTENT_TYPE, HeaderMap, RETRY_AFTER},
+};
+use serde_json::Value as JsonValue;
+use std::time::Duration;
 
 const HTTP_REQUEST: &str = "http_request";
 
@@ -25,6 +33,65 @@ pub struct BasicAuth {
     pub password: Option<String>,
 }
 
+/// A part of a multipart body, either `text` or `bytes`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct MultipartPart {
+    pub name: String,
+    #[serde(default)]
+    pub text: Option<String>,
+    #[serde(default)]
+    pub bytes: Option<Bytes>,
+    #[serde(default)]
+    pub filename: Option<String>,
+    #[serde(default)]
+    pub content_type: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ResponseType {
+    /// JSON or text depending on content-type, bytes otherwise.
+    #[default]
+    Auto,
+    Json,
+    Text,
+    Bytes,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Pagination {
+    /// Follow the `rel="next"` URL of the `Link` header.
+    LinkHeader,
+    /// Read the next cursor from the response and send it as a query parameter.
+    Cursor { cursor_path: String, param: String },
+    /// Increment a page number query parameter until a page has no items.
+    Page {
+        param: String,
+        #[serde(default = "Pagination::default_start")]
+        start: u64,
+    },
+}
+
+impl Pagination {
+    fn default_start() -> u64 {
+        1
+    }
+}
+
+fn default_max_pages() -> u32 {
+    10
+}
+
+fn default_true() -> bool {
+    true
+}
+
+const MAX_PAGES: u32 = 100;
+const MAX_RETRIES: u32 = 5;
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+const INITIAL_RETRY_DELAY: Duration = Duration::from_millis(500);
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Input {
     pub url: Url,
@@ -39,216 +106,366 @@ pub struct Input {
     pub body: Option<serde_json::Value>,
     #[serde(default)]
     pub form: Option<Vec<(String, String)>>,
+    #[serde(default)]
+    pub multipart: Option<Vec<MultipartPart>>,
+    /// Binary body, sent as-is.
+    #[serde(default)]
+    pub raw_body: Option<Bytes>,
+    #[serde(default)]
+    pub response_type: ResponseType,
+    /// Return an error on non-2xx responses; otherwise output the status and body.
+    #[serde(default = "default_true")]
+    pub error_on_status: bool,
+    /// Retries on 429 responses and connection errors, honoring `Retry-After`.
+    /// 5xx responses and timeouts are only retried for idempotent methods.
+    /// At most [`MAX_RETRIES`].
+    #[serde(default)]
+    pub max_retries: u32,
+    #[serde(default)]
+    pub pagination: Option<Pagination>,
+    /// Dot-separated path to the array of items in a page, e.g. `data.items`.
+    /// The whole body is used if not set.
+    #[serde(default)]
+    pub items_path: Option<String>,
+    #[serde(default = "default_max_pages")]
+    pub max_pages: u32,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Output {
     body: Value,
     headers: HashMap<String, String>,
+    status: u16,
+    /// Items accumulated from all pages.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    items: Option<Vec<Value>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pages: Option<u32>,
 }
 
-struct Resolver;
-
-fn is_global(ip: &IpAddr) -> bool {
-    match ip {
-        IpAddr::V4(ip) => Ipv4Ext::is_global(ip),
-        IpAddr::V6(ip) => Ipv6Ext::is_global(ip),
+/// Build a new request for every attempt, so that bodies can be re-sent on retries.
+fn build_request(
+    client: &reqwest::Client,
+    input: &Input,
+    url: &Url,
+    query: &[(String, String)],
+) -> Result<reqwest::RequestBuilder, CommandError> {
+    let mut req = client.request(input.method.parse()?, url.clone());
+
+    if !query.is_empty() {
+        req = req.query(query);
     }
-}
-
-impl Resolver {
-    fn resolve_impl(&self, name: String) -> reqwest::dns::Resolving {
-        Box::pin(async move {
-            tracing::debug!("resolving {}", name.as_str());
-            let host = name + ":0";
 
-            let addrs = tokio::net::lookup_host(host).await?.collect::<Vec<_>>();
-            if let Some(addr) = addrs.iter().find(|addr| !is_global(&addr.ip())) {
-                return Err(format!("IP address not allowed: {}", addr.ip()).into());
-            }
-            let addrs: Box<dyn Iterator<Item = SocketAddr> + Send> = Box::new(addrs.into_iter());
-            Ok(addrs)
-        })
+    for (k, v) in &input.headers {
+        req = req.header(k, v);
     }
-}
 
-impl reqwest::dns::Resolve for Resolver {
-    fn resolve(&self, name: DomainName) -> reqwest::dns::Resolving {
-        self.resolve_impl(name.as_str().to_owned())
+    if let Some(basic) = &input.basic_auth {
+        let passwd = basic.password.as_ref().filter(|p| !p.is_empty());
+        req = req.basic_auth(&basic.user, passwd);
     }
-}
-
-// copied from nightly rust
-trait Ipv4Ext {
-    fn is_global(&self) -> bool;
-    fn is_shared(&self) -> bool;
-    fn is_benchmarking(&self) -> bool;
-    fn is_reserved(&self) -> bool;
-}
 
-impl Ipv4Ext for Ipv4Addr {
-    fn is_global(&self) -> bool {
-        !(self.octets()[0] == 0 // "This network"
-            || self.is_private()
-            || Ipv4Ext::is_shared(self)
-            || self.is_loopback()
-            || self.is_link_local()
-            // addresses reserved for future protocols (`192.0.0.0/24`)
-            ||(self.octets()[0] == 192 && self.octets()[1] == 0 && self.octets()[2] == 0)
-            || self.is_documentation()
-            || Ipv4Ext::is_benchmarking(self)
-            || Ipv4Ext::is_reserved(self)
-            || self.is_broadcast())
+    if let Some(body) = &input.body {
+        req = req.json(body);
     }
 
-    fn is_shared(&self) -> bool {
-        self.octets()[0] == 100 && (self.octets()[1] & 0b1100_0000 == 0b0100_0000)
+    if let Some(form) = &input.form {
+        let mut multiform = reqwest::multipart::Form::new();
+        for (k, v) in form {
+            multiform = multiform.text(k.clone(), v.clone());
+        }
+        req = req.multipart(multiform);
     }
 
-    fn is_benchmarking(&self) -> bool {
-        self.octets()[0] == 198 && (self.octets()[1] & 0xfe) == 18
+    if let Some(parts) = &input.multipart {
+        let mut multiform = reqwest::multipart::Form::new();
+        for part in parts {
+            let mut p = match (&part.text, &part.bytes) {
+                (Some(text), None) => reqwest::multipart::Part::text(text.clone()),
+                (None, Some(bytes)) => reqwest::multipart::Part::stream(bytes.clone()),
+                _ => {
+                    return Err(anyhow!(
+                        "multipart part {:?} must have either text or bytes",
+                        part.name
+                    ));
+                }
+            };
+            if let Some(filename) = &part.filename {
+                p = p.file_name(filename.clone());
+            }
+            if let Some(content_type) = &part.content_type {
+                p = p.mime_str(content_type)?;
+            }
+            multiform = multiform.part(part.name.clone(), p);
+        }
+        req = req.multipart(multiform);
     }
 
-    fn is_reserved(&self) -> bool {
-        self.octets()[0] & 240 == 240 && !self.is_broadcast()
+    if let Some(bytes) = &input.raw_body {
+        let has_content_type = input
+            .headers
+            .iter()
+            .any(|(k, _)| k.eq_ignore_ascii_case(CONTENT_TYPE.as_str()));
+        if !has_content_type {
+            req = req.header(CONTENT_TYPE, "application/octet-stream");
+        }
+        req = req.body(bytes.clone());
     }
-}
 
-trait Ipv6Ext {
-    fn is_global(&self) -> bool;
-    fn is_documentation(&self) -> bool;
-    fn is_unique_local(&self) -> bool;
-    fn is_unicast_link_local(&self) -> bool;
+    Ok(req)
 }
 
-impl Ipv6Ext for Ipv6Addr {
-    fn is_global(&self) -> bool {
-        !(self.is_unspecified()
-            || self.is_loopback()
-            // IPv4-mapped Address (`::ffff:0:0/96`)
-            || matches!(self.segments(), [0, 0, 0, 0, 0, 0xffff, _, _])
-            // IPv4-IPv6 Translat. (`64:ff9b:1::/48`)
-            || matches!(self.segments(), [0x64, 0xff9b, 1, _, _, _, _, _])
-            // Discard-Only Address Block (`100::/64`)
-            || matches!(self.segments(), [0x100, 0, 0, 0, _, _, _, _])
-            // IETF Protocol Assignments (`2001::/23`)
-            || (matches!(self.segments(), [0x2001, b, _, _, _, _, _, _] if b < 0x200)
-                && !(
-                    // Port Control Protocol Anycast (`2001:1::1`)
-                    u128::from_be_bytes(self.octets()) == 0x2001_0001_0000_0000_0000_0000_0000_0001
-                    // Traversal Using Relays around NAT Anycast (`2001:1::2`)
-                    || u128::from_be_bytes(self.octets()) == 0x2001_0001_0000_0000_0000_0000_0000_0002
-                    // AMT (`2001:3::/32`)
-                    || matches!(self.segments(), [0x2001, 3, _, _, _, _, _, _])
-                    // AS112-v6 (`2001:4:112::/48`)
-                    || matches!(self.segments(), [0x2001, 4, 0x112, _, _, _, _, _])
-                    // ORCHIDv2 (`2001:20::/28`)
-                    || matches!(self.segments(), [0x2001, b, _, _, _, _, _, _] if (0x20..=0x2F).contains(&b))
-                ))
-            || Ipv6Ext::is_documentation(self)
-            || Ipv6Ext::is_unique_local(self)
-            || Ipv6Ext::is_unicast_link_local(self))
-    }
-
-    fn is_documentation(&self) -> bool {
-        (self.segments()[0] == 0x2001) && (self.segments()[1] == 0xdb8)
-    }
+/// A 5xx or a timeout may come after the request took effect, only safe to
+/// repeat for idempotent methods.
+fn is_idempotent(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::GET | Method::HEAD | Method::PUT | Method::DELETE | Method::OPTIONS
+    )
+}
 
-    fn is_unique_local(&self) -> bool {
-        (self.segments()[0] & 0xfe00) == 0xfc00
-    }
+fn is_retryable(status: StatusCode, idempotent: bool) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || (idempotent && status.is_server_error())
+}
 
-    fn is_unicast_link_local(&self) -> bool {
-        (self.segments()[0] & 0xffc0) == 0xfe80
+/// Parse `Retry-After`, either delay-seconds or an HTTP date.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
     }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let delay = date.with_timezone(&chrono::Utc) - chrono::Utc::now();
+    Some(delay.to_std().unwrap_or_default())
 }
 
-async fn run(ctx: CommandContext, input: Input) -> Result<Output, CommandError> {
-    match input.url.host() {
-        Some(url::Host::Domain(domain)) => {
-            let _ = Resolver
-                .resolve_impl(domain.to_owned())
-                .await
-                .map_err(|error| anyhow!(error))?;
-        }
-        Some(url::Host::Ipv4(ip)) => {
-            if !Ipv4Ext::is_global(&ip) {
-                return Err(anyhow::anyhow!("IP address not allowed: {}", ip));
-            }
-        }
-        Some(url::Host::Ipv6(ip)) => {
-            if !Ipv6Ext::is_global(&ip) {
-                return Err(anyhow::anyhow!("IP address not allowed: {}", ip));
-            }
+async fn send(
+    client: &reqwest::Client,
+    input: &Input,
+    url: &Url,
+    query: &[(String, String)],
+) -> Result<reqwest::Response, CommandError> {
+    let max_retries = input.max_retries.min(MAX_RETRIES);
+    let idempotent = is_idempotent(&input.method.parse()?);
+    let mut attempt = 0;
+    loop {
+        let result = build_request(client, input, url, query)?.send().await;
+        let delay = match &result {
+            Ok(resp) if is_retryable(resp.status(), idempotent) => retry_after(resp.headers()),
+            Err(error) if error.is_connect() || (idempotent && error.is_timeout()) => None,
+            _ => return Ok(result?),
+        };
+        if attempt >= max_retries {
+            return Ok(result?);
         }
-        None => return Err(anyhow::anyhow!("URL has no host")),
+        let delay = delay
+            .unwrap_or_else(|| INITIAL_RETRY_DELAY.saturating_mul(1 << attempt.min(16)))
+            .min(MAX_RETRY_DELAY);
+        tracing::debug!("retrying {} in {:?}", url, delay);
+        tokio::time::sleep(delay).await;
+        attempt += 1;
     }
+}
 
-    let client = ctx.http();
-
-    let mut req = client.request(input.method.parse()?, input.url);
+struct Page {
+    status: StatusCode,
+    headers: HashMap<String, String>,
+    body: Value,
+    /// Parsed body, if it is JSON.
+    json: Option<JsonValue>,
+}
 
-    if !input.query_params.is_empty() {
-        req = req.query(&input.query_params);
+async fn read_response(
+    resp: reqwest::Response,
+    response_type: ResponseType,
+    error_on_status: bool,
+) -> Result<Page, CommandError> {
+    let status = resp.status();
+    if !status.is_success() && error_on_status {
+        let body = resp.text().await.ok();
+        return Err(anyhow::anyhow!(
+            "status code: {}\n{}",
+            status.as_u16(),
+            body.unwrap_or_default()
+        ));
     }
 
-    for (k, v) in &input.headers {
-        req = req.header(k, v);
-    }
+    let headers = resp
+        .headers()
+        .iter()
+        .map(|(k, v)| {
+            (
+                k.as_str().to_lowercase(),
+                String::from_utf8_lossy(v.as_bytes()).into_owned(),
+            )
+        })
+        .collect::<HashMap<String, String>>();
+
+    let ct = headers
+        .get("content-type")
+        .map(String::as_str)
+        .unwrap_or("text/plain");
+    let response_type = match response_type {
+        ResponseType::Auto if ct.starts_with("text/") => ResponseType::Text,
+        ResponseType::Auto if ct.contains("json") => ResponseType::Json,
+        ResponseType::Auto => ResponseType::Bytes,
+        other => other,
+    };
+    let (body, json) = match response_type {
+        ResponseType::Json => {
+            let json = resp.json::<JsonValue>().await?;
+            (json.clone().into(), Some(json))
+        }
+        ResponseType::Text => (resp.text().await?.into(), None),
+        _ => (resp.bytes().await?.into(), None),
+    };
+
+    Ok(Page {
+        status,
+        headers,
+        body,
+        json,
+    })
+}
 
-    if let Some(basic) = &input.basic_auth {
-        let passwd = basic.password.as_ref().filter(|p| !p.is_empty());
-        req = req.basic_auth(&basic.user, passwd);
-    }
+/// Find the `rel="next"` URL in a `Link` header.
+fn next_link(header: &str) -> Option<&str> {
+    header.split(',').find_map(|link| {
+        let mut parts = link.split(';');
+        let url = parts.next()?.trim().strip_prefix('<')?.strip_suffix('>')?;
+        parts
+            .any(|param| {
+                let param = param.trim().replace(' ', "");
+                param == "rel=\"next\"" || param == "rel=next"
+            })
+            .then_some(url)
+    })
+}
 
-    if let Some(body) = input.body {
-        req = req.json(&body);
-    }
+/// Look up a dot-separated path, array elements are selected by index.
+fn json_path<'a>(json: &'a JsonValue, path: &str) -> Option<&'a JsonValue> {
+    path.split('.')
+        .filter(|segment| !segment.is_empty())
+        .try_fold(json, |value, segment| match value {
+            JsonValue::Object(map) => map.get(segment),
+            JsonValue::Array(array) => array.get(segment.parse::<usize>().ok()?),
+            _ => None,
+        })
+}
 
-    if let Some(form) = input.form {
-        let mut multiform = reqwest::multipart::Form::new();
-        for (k, v) in form {
-            multiform = multiform.text(k, v);
-        }
-        req = req.multipart(multiform);
+async fn run(_: CommandContext, input: Input) -> Result<Output, CommandError> {
+    check_url(&input.url).await?;
+
+    let bodies = [
+        input.body.is_some(),
+        input.form.is_some(),
+        input.multipart.is_some(),
+        input.raw_body.is_some(),
+    ];
+    if bodies.iter().filter(|set| **set).count() > 1 {
+        return Err(anyhow!(
+            "only one of body, form, multipart and raw_body can be set"
+        ));
     }
 
-    let resp = req.send().await?;
+    let client = guarded_client();
+
+    let Some(pagination) = &input.pagination else {
+        let resp = send(client, &input, &input.url, &input.query_params).await?;
+        let page = read_response(resp, input.response_type, input.error_on_status).await?;
+        return Ok(Output {
+            body: page.body,
+            headers: page.headers,
+            status: page.status.as_u16(),
+            items: None,
+            pages: None,
+        });
+    };
+
+    let max_pages = input.max_pages.clamp(1, MAX_PAGES);
+    let mut url = input.url.clone();
+    let mut query = input.query_params.clone();
+    let mut page_number = match pagination {
+        Pagination::Page { param, start } => {
+            query.push((param.clone(), start.to_string()));
+            *start
+        }
+        _ => 0,
+    };
+    let mut items = Vec::new();
+    let mut pages = 0;
+    loop {
+        let resp = send(client, &input, &url, &query).await?;
+        let page = read_response(resp, input.response_type, input.error_on_status).await?;
+        pages += 1;
+
+        let done = |page: Page, items: Vec<Value>| Output {
+            body: page.body,
+            headers: page.headers,
+            status: page.status.as_u16(),
+            items: Some(items),
+            pages: Some(pages),
+        };
 
-    let status = resp.status();
+        if !page.status.is_success() {
+            return Ok(done(page, items));
+        }
+        let json = page
+            .json
+            .as_ref()
+            .ok_or_else(|| anyhow!("pagination requires a JSON response"))?;
+        let page_items = match &input.items_path {
+            Some(path) => json_path(json, path),
+            None => Some(json),
+        };
+        let count = match page_items {
+            Some(JsonValue::Array(array)) => {
+                items.extend(array.iter().cloned().map(Value::from));
+                array.len()
+            }
+            None | Some(JsonValue::Null) => 0,
+            Some(_) => return Err(anyhow!("page items are not an array")),
+        };
 
-    if status.is_success() {
-        let headers = resp
-            .headers()
-            .iter()
-            .map(|(k, v)| {
-                (
-                    k.as_str().to_lowercase(),
-                    String::from_utf8_lossy(v.as_bytes()).into_owned(),
-                )
-            })
-            .collect::<HashMap<String, String>>();
-
-        let ct = headers
-            .get("content-type")
-            .map(String::as_str)
-            .unwrap_or("text/plain");
-        let body: Value = if ct.starts_with("text/") {
-            resp.text().await?.into()
-        } else if ct.contains("json") {
-            resp.json::<serde_json::Value>().await?.into()
-        } else {
-            resp.bytes().await?.into()
+        let has_next = match pagination {
+            Pagination::LinkHeader => {
+                match page.headers.get("link").and_then(|h| next_link(h)) {
+                    Some(next) => {
+                        // the next URL already contains the query
+                        url = url.join(next)?;
+                        query.clear();
+                        true
+                    }
+                    None => false,
+                }
+            }
+            Pagination::Cursor { cursor_path, param } => {
+                let cursor = match json_path(json, cursor_path) {
+                    Some(JsonValue::String(s)) if !s.is_empty() => Some(s.clone()),
+                    Some(JsonValue::Number(n)) => Some(n.to_string()),
+                    _ => None,
+                };
+                match cursor {
+                    Some(cursor) => {
+                        query = input.query_params.clone();
+                        query.push((param.clone(), cursor));
+                        true
+                    }
+                    None => false,
+                }
+            }
+            Pagination::Page { param, .. } => {
+                page_number += 1;
+                query = input.query_params.clone();
+                query.push((param.clone(), page_number.to_string()));
+                count > 0
+            }
         };
 
-        Ok(Output { headers, body })
-    } else {
-        let body = resp.text().await.ok();
-        Err(anyhow::anyhow!(
-            "status code: {}\n{}",
-            status.as_u16(),
-            body.unwrap_or_default()
-        ))
+        if !has_next || pages >= max_pages {
+            return Ok(done(page, items));
+        }
+        check_url(&url).await?;
     }
 }
 
@@ -278,4 +495,69 @@ mod tests {
         test("http://169.254.169.254/latest/api/token").await;
         test("http://255.255.255.255").await;
     }
+
+    #[tokio::test]
+    async fn test_multiple_bodies() {
+        let input = value::from_map(value::map! {
+            "url" => "http://93.184.215.14",
+            "body" => value::map! { "a" => 1 },
+            "raw_body" => Value::Bytes(Bytes::from_static(b"a")),
+        })
+        .unwrap();
+        let e = run(CommandContext::default(), input)
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(e.contains("only one of"));
+    }
+
+    #[test]
+    fn test_next_link() {
+        let header = r#"<https://api.example.com/items?page=1>; rel="prev", <https://api.example.com/items?page=3>; rel="next""#;
+        assert_eq!(
+            next_link(header),
+            Some("https://api.example.com/items?page=3")
+        );
+        assert_eq!(
+            next_link(r#"<https://api.example.com/items?page=1>; rel="first""#),
+            None
+        );
+    }
+
+    #[test]
+    fn test_json_path() {
+        let json = serde_json::json!({ "data": { "items": [1, 2], "next": null }, "pages": [{ "cursor": "a" }] });
+        assert_eq!(
+            json_path(&json, "data.items"),
+            Some(&serde_json::json!([1, 2]))
+        );
+        assert_eq!(
+            json_path(&json, "pages.0.cursor"),
+            Some(&serde_json::json!("a"))
+        );
+        assert_eq!(json_path(&json, "data.missing"), None);
+    }
+
+    #[test]
+    fn test_retry_after() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+        headers.insert(RETRY_AFTER, "3".parse().unwrap());
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(3)));
+        headers.insert(
+            RETRY_AFTER,
+            "Wed, 21 Oct 2015 07:28:00 GMT".parse().unwrap(),
+        );
+        assert_eq!(retry_after(&headers), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        let post = is_idempotent(&Method::POST);
+        let put = is_idempotent(&Method::PUT);
+        assert!(is_retryable(StatusCode::TOO_MANY_REQUESTS, post));
+        assert!(!is_retryable(StatusCode::BAD_GATEWAY, post));
+        assert!(is_retryable(StatusCode::BAD_GATEWAY, put));
+        assert!(!is_retryable(StatusCode::NOT_FOUND, put));
+    }
 }
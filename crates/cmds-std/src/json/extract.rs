@@ -1,14 +1,15 @@
 use flow_lib::command::prelude::*;
+use schemars::JsonSchema;
 
 const JSON_EXTRACT: &str = "json_extract";
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, JsonSchema, Debug)]
 struct Input {
     json_input: Value,
     field_path: String,
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, JsonSchema, Debug)]
 struct Output {
     value: Value,
     trimmed_json: Value,
@@ -58,6 +59,7 @@ fn build() -> BuildResult {
 }
 
 flow_lib::submit!(CommandDescription::new(JSON_EXTRACT, |_| build()));
+flow_lib::submit!(PortSchema::new::<Input, Output>(JSON_EXTRACT));
 
 #[cfg(test)]
 mod tests {
@@ -1,15 +1,16 @@
 use flow_lib::command::prelude::*;
+use schemars::JsonSchema;
 
 const JSON_INSERT: &str = "json_insert";
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, JsonSchema, Debug)]
 struct Input {
     json_input: Value,
     path: String,
     value: Value,
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, JsonSchema, Debug)]
 struct Output {
     updated_json: Value,
 }
@@ -32,6 +33,7 @@ fn build() -> BuildResult {
 }
 
 flow_lib::submit!(CommandDescription::new(JSON_INSERT, |_| build()));
+flow_lib::submit!(PortSchema::new::<Input, Output>(JSON_INSERT));
 
 #[cfg(test)]
 mod tests {
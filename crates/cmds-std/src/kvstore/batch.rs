@@ -0,0 +1,95 @@
+use crate::supabase_error;
+use anyhow::anyhow;
+use flow_lib::command::prelude::*;
+use reqwest::{StatusCode, header::AUTHORIZATION};
+
+pub const NAME: &str = "kv_batch";
+
+const DEFINITION: &str = flow_lib::node_definition!("kvstore/batch.jsonc");
+
+fn build() -> BuildResult {
+    static CACHE: BuilderCache = BuilderCache::new(|| {
+        Ok(CmdBuilder::new(DEFINITION)?
+            .check_name(NAME)?
+            .permissions(Permissions { user_tokens: true })
+            .read_capability(ReadCapability::Mutating))
+    });
+
+    Ok(CACHE.clone()?.build(run))
+}
+
+flow_lib::submit!(CommandDescription::new(NAME, |_| build()));
+
+/// Operations are passed through to the server, see `db::connection::ItemOp`.
+#[derive(Serialize, Deserialize)]
+struct Input {
+    store: String,
+    ops: Vec<JsonValue>,
+}
+
+#[derive(Serialize, Default)]
+struct Output {
+    committed: bool,
+    results: Vec<JsonValue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    failed_index: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    failed_key: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+enum SuccessBody {
+    Committed { results: Vec<JsonValue> },
+    ConditionFailed { index: u64, key: String },
+}
+
+async fn run(mut ctx: CommandContext, input: Input) -> Result<Output, CommandError> {
+    let mut req = ctx
+        .http()
+        .post(format!("{}/kv/batch", ctx.endpoints().flow_server))
+        .json(&input);
+    req = req.header(AUTHORIZATION, ctx.get_jwt_header().await?);
+    let resp = req.send().await.map_err(|e| anyhow!("HTTP error: {}", e))?;
+    match resp.status() {
+        StatusCode::OK => Ok(match resp.json::<SuccessBody>().await? {
+            SuccessBody::Committed { results } => Output {
+                committed: true,
+                results,
+                ..<_>::default()
+            },
+            SuccessBody::ConditionFailed { index, key } => Output {
+                committed: false,
+                failed_index: Some(index),
+                failed_key: Some(key),
+                ..<_>::default()
+            },
+        }),
+        code => Err(supabase_error(code, resp).await),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build() {
+        build().unwrap();
+    }
+
+    #[test]
+    fn test_parse_body() {
+        let body: SuccessBody = serde_json::from_value(serde_json::json!({
+            "status": "condition_failed",
+            "index": 1,
+            "key": "a",
+            "current_version": 3,
+        }))
+        .unwrap();
+        assert!(matches!(
+            body,
+            SuccessBody::ConditionFailed { index: 1, .. }
+        ));
+    }
+}
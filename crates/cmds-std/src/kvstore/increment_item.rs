@@ -0,0 +1,64 @@
+use crate::supabase_error;
+use anyhow::anyhow;
+use flow_lib::command::prelude::*;
+use reqwest::{StatusCode, header::AUTHORIZATION};
+
+pub const NAME: &str = "kv_increment_item";
+
+const DEFINITION: &str = flow_lib::node_definition!("kvstore/increment_item.jsonc");
+
+fn build() -> BuildResult {
+    static CACHE: BuilderCache = BuilderCache::new(|| {
+        Ok(CmdBuilder::new(DEFINITION)?
+            .check_name(NAME)?
+            .permissions(Permissions { user_tokens: true })
+            .read_capability(ReadCapability::Mutating))
+    });
+
+    Ok(CACHE.clone()?.build(run))
+}
+
+flow_lib::submit!(CommandDescription::new(NAME, |_| build()));
+
+fn default_by() -> i64 {
+    1
+}
+
+#[derive(Serialize, Deserialize)]
+struct Input {
+    store: String,
+    key: String,
+    #[serde(default = "default_by")]
+    by: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ttl_seconds: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Output {
+    value: Value,
+    version: i64,
+}
+
+async fn run(mut ctx: CommandContext, input: Input) -> Result<Output, CommandError> {
+    let mut req = ctx
+        .http()
+        .post(format!("{}/kv/increment_item", ctx.endpoints().flow_server))
+        .json(&input);
+    req = req.header(AUTHORIZATION, ctx.get_jwt_header().await?);
+    let resp = req.send().await.map_err(|e| anyhow!("HTTP error: {}", e))?;
+    match resp.status() {
+        StatusCode::OK => Ok(resp.json::<Output>().await?),
+        code => Err(supabase_error(code, resp).await),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build() {
+        build().unwrap();
+    }
+}
@@ -0,0 +1,80 @@
+use crate::supabase_error;
+use anyhow::anyhow;
+use flow_lib::command::prelude::*;
+use reqwest::{StatusCode, header::AUTHORIZATION};
+
+pub const NAME: &str = "kv_list_items";
+
+const DEFINITION: &str = flow_lib::node_definition!("kvstore/list_items.jsonc");
+
+fn build() -> BuildResult {
+    static CACHE: BuilderCache = BuilderCache::new(|| {
+        Ok(CmdBuilder::new(DEFINITION)?
+            .check_name(NAME)?
+            .permissions(Permissions { user_tokens: true }))
+    });
+
+    Ok(CACHE.clone()?.build(run))
+}
+
+flow_lib::submit!(CommandDescription::new(NAME, |_| build()));
+
+#[derive(Serialize, Deserialize)]
+struct Input {
+    store: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    prefix: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    start: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    end: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    limit: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Item {
+    key: String,
+    value: Value,
+    version: i64,
+}
+
+#[derive(Serialize)]
+struct Output {
+    keys: Vec<String>,
+    items: Vec<Item>,
+}
+
+#[derive(Deserialize)]
+struct SuccessBody {
+    items: Vec<Item>,
+}
+
+async fn run(mut ctx: CommandContext, input: Input) -> Result<Output, CommandError> {
+    let mut req = ctx
+        .http()
+        .post(format!("{}/kv/list_items", ctx.endpoints().flow_server))
+        .json(&input);
+    req = req.header(AUTHORIZATION, ctx.get_jwt_header().await?);
+    let resp = req.send().await.map_err(|e| anyhow!("HTTP error: {}", e))?;
+    match resp.status() {
+        StatusCode::OK => {
+            let body = resp.json::<SuccessBody>().await?;
+            Ok(Output {
+                keys: body.items.iter().map(|item| item.key.clone()).collect(),
+                items: body.items,
+            })
+        }
+        code => Err(supabase_error(code, resp).await),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build() {
+        build().unwrap();
+    }
+}
@@ -1,5 +1,8 @@
+pub mod batch;
 pub mod create_store;
 pub mod delete_store;
 pub mod explorer;
+pub mod increment_item;
+pub mod list_items;
 pub mod read_item;
 pub mod write_item;
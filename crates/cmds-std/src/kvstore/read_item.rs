@@ -31,11 +31,14 @@ struct Input {
 struct Output {
     value: Value,
     found: bool,
+    version: i64,
 }
 
 #[derive(Deserialize)]
 pub struct SuccessBody {
     pub value: Value,
+    #[serde(default)]
+    pub version: i64,
 }
 
 async fn run(mut ctx: CommandContext, input: Input) -> Result<Output, CommandError> {
@@ -51,12 +54,14 @@ async fn run(mut ctx: CommandContext, input: Input) -> Result<Output, CommandErr
             Ok(Output {
                 value: body.value,
                 found: true,
+                version: body.version,
             })
         }
         StatusCode::NOT_FOUND => match input.default {
             Some(default) => Ok(Output {
                 value: default,
                 found: false,
+                version: 0,
             }),
             None => Err(CommandError::msg("not found")),
         },
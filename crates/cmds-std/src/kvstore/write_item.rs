@@ -25,17 +25,25 @@ struct Input {
     store: String,
     key: String,
     value: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expected_version: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ttl_seconds: Option<i64>,
 }
 
 #[derive(Serialize)]
 struct Output {
     #[serde(skip_serializing_if = "Option::is_none")]
     old_value: Option<Value>,
+    version: i64,
+    applied: bool,
 }
 
 #[derive(Deserialize)]
 struct SuccessBody {
     old_value: Option<Value>,
+    version: i64,
+    applied: bool,
 }
 
 async fn run(mut ctx: CommandContext, input: Input) -> Result<Output, CommandError> {
@@ -50,6 +58,8 @@ async fn run(mut ctx: CommandContext, input: Input) -> Result<Output, CommandErr
             let body = resp.json::<SuccessBody>().await?;
             Ok(Output {
                 old_value: body.old_value,
+                version: body.version,
+                applied: body.applied,
             })
         }
         code => Err(supabase_error(code, resp).await),
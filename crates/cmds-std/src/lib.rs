@@ -7,6 +7,7 @@ pub mod http_request;
 pub mod json;
 pub mod kvstore;
 pub mod polars;
+pub mod postgres;
 pub mod postgrest;
 pub mod std;
 pub mod storage;
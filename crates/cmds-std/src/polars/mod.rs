@@ -98,3 +98,7 @@ pub mod polars_rank;
 pub mod polars_rolling_mean;
 pub mod polars_rolling_sum;
 pub mod polars_shift;
+
+// Phase 5: Query
+pub mod polars_lazy_plan;
+pub mod polars_sql;
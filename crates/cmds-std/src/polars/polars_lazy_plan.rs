@@ -0,0 +1,215 @@
+use crate::polars::types::{df_from_ipc, dual_output, unwrap_json_input};
+use flow_lib::command::prelude::*;
+use polars::prelude::*;
+use polars::sql::sql_expr;
+use std::collections::BTreeMap;
+
+pub const NAME: &str = "polars_lazy_plan";
+const DEFINITION: &str = flow_lib::node_definition!("polars/polars_lazy_plan.jsonc");
+
+fn build() -> BuildResult {
+    static CACHE: BuilderCache =
+        BuilderCache::new(|| CmdBuilder::new(DEFINITION)?.check_name(NAME));
+    Ok(CACHE.clone()?.build(run))
+}
+
+flow_lib::submit!(CommandDescription::new(NAME, |_| build()));
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Input {
+    pub dataframe: String,
+    pub operations: JsonValue,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Output {
+    pub dataframe: String,
+    pub dataframe_json: JsonValue,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// One step of the lazy plan. Expressions are written in SQL syntax
+/// (e.g., `"age > 30"`, `"price * qty AS total"`, `"SUM(amount) AS total"`).
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Operation {
+    Filter {
+        predicate: String,
+    },
+    Select {
+        exprs: Vec<String>,
+    },
+    WithColumns {
+        exprs: Vec<String>,
+    },
+    Drop {
+        columns: Vec<String>,
+    },
+    Rename {
+        mapping: BTreeMap<String, String>,
+    },
+    Sort {
+        by: Vec<String>,
+        #[serde(default)]
+        descending: bool,
+        #[serde(default = "default_true")]
+        nulls_last: bool,
+    },
+    GroupBy {
+        by: Vec<String>,
+        aggs: Vec<String>,
+    },
+    Unique {
+        #[serde(default)]
+        subset: Option<Vec<String>>,
+    },
+    Limit {
+        n: u32,
+    },
+}
+
+fn parse_exprs(exprs: &[String]) -> Result<Vec<Expr>, CommandError> {
+    exprs
+        .iter()
+        .map(|e| {
+            sql_expr(e).map_err(|err| CommandError::msg(format!("Invalid expression '{e}': {err}")))
+        })
+        .collect()
+}
+
+fn parse_operations(value: &JsonValue) -> Result<Vec<Operation>, CommandError> {
+    let value = unwrap_json_input(value)?;
+    serde_json::from_value(value.into_owned())
+        .map_err(|e| CommandError::msg(format!("Invalid operations: {e}")))
+}
+
+fn apply(lf: LazyFrame, op: Operation) -> Result<LazyFrame, CommandError> {
+    Ok(match op {
+        Operation::Filter { predicate } => {
+            let predicate = sql_expr(&predicate)
+                .map_err(|e| CommandError::msg(format!("Invalid predicate '{predicate}': {e}")))?;
+            lf.filter(predicate)
+        }
+        Operation::Select { exprs } => lf.select(parse_exprs(&exprs)?),
+        Operation::WithColumns { exprs } => lf.with_columns(parse_exprs(&exprs)?),
+        Operation::Drop { columns } => lf.drop(columns),
+        Operation::Rename { mapping } => {
+            let (existing, new): (Vec<_>, Vec<_>) = mapping.into_iter().unzip();
+            lf.rename(existing, new, true)
+        }
+        Operation::Sort {
+            by,
+            descending,
+            nulls_last,
+        } => {
+            let options = SortMultipleOptions::new()
+                .with_order_descending(descending)
+                .with_nulls_last(nulls_last);
+            lf.sort_by_exprs(parse_exprs(&by)?, options)
+        }
+        Operation::GroupBy { by, aggs } => lf
+            .group_by_stable(parse_exprs(&by)?)
+            .agg(parse_exprs(&aggs)?),
+        Operation::Unique { subset } => lf.unique_stable(
+            subset.map(|cols| cols.into_iter().map(PlSmallStr::from).collect()),
+            UniqueKeepStrategy::First,
+        ),
+        Operation::Limit { n } => lf.limit(n as IdxSize),
+    })
+}
+
+async fn run(_ctx: CommandContext, input: Input) -> Result<Output, CommandError> {
+    let df = df_from_ipc(&input.dataframe)?;
+    let operations = parse_operations(&input.operations)?;
+
+    let lf = operations.into_iter().try_fold(df.lazy(), apply)?;
+    let mut result = lf
+        .collect()
+        .map_err(|e| CommandError::msg(format!("Lazy plan error: {e}")))?;
+
+    let (ipc, json) = dual_output(&mut result)?;
+    Ok(Output {
+        dataframe: ipc,
+        dataframe_json: json,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::polars::types::df_to_ipc;
+
+    #[test]
+    fn test_build() {
+        build().unwrap();
+    }
+
+    fn test_df_ipc() -> String {
+        let mut df = DataFrame::new(vec![
+            Series::new("name".into(), &["Alice", "Bob", "Charlie", "Alice", "Bob"]).into_column(),
+            Series::new("age".into(), &[30i64, 25, 35, 30, 25]).into_column(),
+            Series::new("score".into(), &[88.5f64, 92.0, 75.3, 91.0, 60.0]).into_column(),
+        ])
+        .unwrap();
+        df_to_ipc(&mut df).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_run_filter_select_sort() {
+        let output = run(
+            CommandContext::default(),
+            Input {
+                dataframe: test_df_ipc(),
+                operations: serde_json::json!([
+                    { "op": "filter", "predicate": "score > 70" },
+                    { "op": "with_columns", "exprs": ["score * 2 AS double_score"] },
+                    { "op": "select", "exprs": ["name", "double_score"] },
+                    { "op": "sort", "by": ["double_score"], "descending": true },
+                    { "op": "limit", "n": 2 },
+                ]),
+            },
+        )
+        .await
+        .unwrap();
+        let df = df_from_ipc(&output.dataframe).unwrap();
+        assert_eq!(df.shape(), (2, 2));
+        let scores = df.column("double_score").unwrap().f64().unwrap();
+        assert_eq!(scores.get(0), Some(184.0));
+    }
+
+    #[tokio::test]
+    async fn test_run_group_by() {
+        let output = run(
+            CommandContext::default(),
+            Input {
+                dataframe: test_df_ipc(),
+                operations: serde_json::json!([
+                    { "op": "group_by", "by": ["name"], "aggs": ["SUM(score) AS total", "COUNT(age) AS n"] },
+                    { "op": "rename", "mapping": { "name": "person" } },
+                ]),
+            },
+        )
+        .await
+        .unwrap();
+        let df = df_from_ipc(&output.dataframe).unwrap();
+        assert_eq!(df.height(), 3);
+        let people = df.column("person").unwrap().str().unwrap();
+        assert_eq!(people.get(0), Some("Alice"));
+    }
+
+    #[tokio::test]
+    async fn test_run_invalid_operation() {
+        let result = run(
+            CommandContext::default(),
+            Input {
+                dataframe: test_df_ipc(),
+                operations: serde_json::json!([{ "op": "explode_everything" }]),
+            },
+        )
+        .await;
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,188 @@
+use crate::polars::types::{df_from_ipc, dual_output, unwrap_json_input};
+use flow_lib::command::prelude::*;
+use polars::prelude::*;
+use polars::sql::SQLContext;
+
+pub const NAME: &str = "polars_sql";
+const DEFINITION: &str = flow_lib::node_definition!("polars/polars_sql.jsonc");
+
+fn build() -> BuildResult {
+    static CACHE: BuilderCache =
+        BuilderCache::new(|| CmdBuilder::new(DEFINITION)?.check_name(NAME));
+    Ok(CACHE.clone()?.build(run))
+}
+
+flow_lib::submit!(CommandDescription::new(NAME, |_| build()));
+
+/// Table name used for the `dataframe` input.
+const DEFAULT_TABLE: &str = "df";
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Input {
+    pub query: String,
+    #[serde(default)]
+    pub dataframe: Option<String>,
+    #[serde(default)]
+    pub tables: Option<JsonValue>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Output {
+    pub dataframe: String,
+    pub dataframe_json: JsonValue,
+}
+
+/// Parse the `tables` input: a JSON object mapping table names to DataFrames in IPC format.
+fn parse_tables(value: &JsonValue) -> Result<Vec<(String, DataFrame)>, CommandError> {
+    let value = unwrap_json_input(value)?;
+    let map = value.as_object().ok_or_else(|| {
+        CommandError::msg("tables must be a JSON object mapping table names to DataFrames (IPC)")
+    })?;
+    map.iter()
+        .map(|(name, ipc)| {
+            let ipc = ipc.as_str().ok_or_else(|| {
+                CommandError::msg(format!("Table '{name}' must be a DataFrame in IPC format"))
+            })?;
+            Ok((name.clone(), df_from_ipc(ipc)?))
+        })
+        .collect()
+}
+
+async fn run(_ctx: CommandContext, input: Input) -> Result<Output, CommandError> {
+    let mut tables = match &input.tables {
+        Some(tables) => parse_tables(tables)?,
+        None => Vec::new(),
+    };
+    if let Some(ipc) = &input.dataframe {
+        if tables.iter().any(|(name, _)| name == DEFAULT_TABLE) {
+            return Err(CommandError::msg(format!(
+                "Table '{DEFAULT_TABLE}' is already used by the 'dataframe' input"
+            )));
+        }
+        tables.push((DEFAULT_TABLE.to_owned(), df_from_ipc(ipc)?));
+    }
+    if tables.is_empty() {
+        return Err(CommandError::msg(
+            "At least one table must be provided via 'dataframe' or 'tables'",
+        ));
+    }
+
+    let mut sql = SQLContext::new();
+    for (name, df) in tables {
+        sql.register(&name, df.lazy());
+    }
+
+    let mut result = sql
+        .execute(&input.query)
+        .and_then(|lf| lf.collect())
+        .map_err(|e| CommandError::msg(format!("SQL error: {e}")))?;
+
+    let (ipc, json) = dual_output(&mut result)?;
+    Ok(Output {
+        dataframe: ipc,
+        dataframe_json: json,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::polars::types::df_to_ipc;
+
+    #[test]
+    fn test_build() {
+        build().unwrap();
+    }
+
+    fn people_ipc() -> String {
+        let mut df = DataFrame::new(vec![
+            Series::new("id".into(), &[1i64, 2, 3, 4]).into_column(),
+            Series::new("name".into(), &["Alice", "Bob", "Charlie", "Dave"]).into_column(),
+            Series::new("age".into(), &[30i64, 25, 35, 40]).into_column(),
+        ])
+        .unwrap();
+        df_to_ipc(&mut df).unwrap()
+    }
+
+    fn orders_ipc() -> String {
+        let mut df = DataFrame::new(vec![
+            Series::new("person_id".into(), &[1i64, 1, 3, 4, 4, 4]).into_column(),
+            Series::new("amount".into(), &[10.0f64, 5.0, 7.5, 1.0, 2.0, 3.0]).into_column(),
+        ])
+        .unwrap();
+        df_to_ipc(&mut df).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_run_single_dataframe() {
+        let output = run(
+            CommandContext::default(),
+            Input {
+                query: "SELECT name FROM df WHERE age > 28 ORDER BY age DESC".into(),
+                dataframe: Some(people_ipc()),
+                tables: None,
+            },
+        )
+        .await
+        .unwrap();
+        let df = df_from_ipc(&output.dataframe).unwrap();
+        assert_eq!(df.height(), 3);
+        let names = df.column("name").unwrap().str().unwrap();
+        assert_eq!(names.get(0), Some("Dave"));
+    }
+
+    #[tokio::test]
+    async fn test_run_join_and_aggregate() {
+        let tables = serde_json::json!({
+            "people": people_ipc(),
+            "orders": orders_ipc(),
+        });
+        let output = run(
+            CommandContext::default(),
+            Input {
+                query: "SELECT p.name, SUM(o.amount) AS total \
+                        FROM people p JOIN orders o ON p.id = o.person_id \
+                        GROUP BY p.name ORDER BY total DESC"
+                    .into(),
+                dataframe: None,
+                tables: Some(tables),
+            },
+        )
+        .await
+        .unwrap();
+        let df = df_from_ipc(&output.dataframe).unwrap();
+        assert_eq!(df.height(), 3);
+        let names = df.column("name").unwrap().str().unwrap();
+        assert_eq!(names.get(0), Some("Alice"));
+        let totals = df.column("total").unwrap().f64().unwrap();
+        assert_eq!(totals.get(0), Some(15.0));
+    }
+
+    #[tokio::test]
+    async fn test_run_no_tables() {
+        let result = run(
+            CommandContext::default(),
+            Input {
+                query: "SELECT 1".into(),
+                dataframe: None,
+                tables: None,
+            },
+        )
+        .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_run_invalid_query() {
+        let result = run(
+            CommandContext::default(),
+            Input {
+                query: "SELECT * FROM missing".into(),
+                dataframe: Some(people_ipc()),
+                tables: None,
+            },
+        )
+        .await;
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,131 @@
+use super::{connect, param_refs, params};
+use flow_lib::command::prelude::*;
+
+pub const NAME: &str = "postgres_execute";
+
+const DEFINITION: &str = flow_lib::node_definition!("postgres/execute.jsonc");
+
+fn build() -> BuildResult {
+    static CACHE: BuilderCache = BuilderCache::new(|| {
+        Ok(CmdBuilder::new(DEFINITION)?
+            .check_name(NAME)?
+            .read_capability(ReadCapability::Mutating))
+    });
+
+    Ok(CACHE.clone()?.build(run))
+}
+
+flow_lib::submit!(CommandDescription::new(NAME, |_| build()));
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Statement {
+    query: String,
+    #[serde(default)]
+    params: Vec<Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Input {
+    connection_string: String,
+    #[serde(default)]
+    query: Option<String>,
+    #[serde(default)]
+    params: Vec<Value>,
+    /// Run several statements in one transaction instead of `query`.
+    #[serde(default)]
+    statements: Option<Vec<Statement>>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Output {
+    rows_affected: u64,
+    /// Rows affected by each statement.
+    results: Vec<u64>,
+}
+
+fn statements(input: Input) -> Result<Vec<Statement>, CommandError> {
+    match (input.query, input.statements) {
+        (Some(query), None) => Ok(vec![Statement {
+            query,
+            params: input.params,
+        }]),
+        (None, Some(statements)) if !statements.is_empty() => Ok(statements),
+        (Some(_), Some(_)) => Err(CommandError::msg(
+            "only one of query and statements can be set",
+        )),
+        _ => Err(CommandError::msg("query or statements is required")),
+    }
+}
+
+async fn run(ctx: CommandContext, input: Input) -> Result<Output, CommandError> {
+    if ctx.is_read_only() {
+        return Err(CommandError::msg(
+            "postgres_execute is not allowed in read mode",
+        ));
+    }
+
+    let mut client = connect(&input.connection_string).await?;
+    let statements = statements(input)?;
+
+    let tx = client.transaction().await?;
+    let mut results = Vec::with_capacity(statements.len());
+    for statement in statements {
+        let params = params(statement.params);
+        results.push(tx.execute(&statement.query, &param_refs(&params)).await?);
+    }
+    tx.commit().await?;
+
+    Ok(Output {
+        rows_affected: results.iter().sum(),
+        results,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build() {
+        build().unwrap();
+    }
+
+    #[tokio::test]
+    async fn read_mode_rejects_execute() {
+        let mut ctx = CommandContext::test_context();
+        ctx.set_read_only(true);
+
+        let error = run(
+            ctx,
+            Input {
+                connection_string: "postgres://localhost/db".to_owned(),
+                query: Some("DELETE FROM items".to_owned()),
+                params: Vec::new(),
+                statements: None,
+            },
+        )
+        .await
+        .expect_err("execute should be rejected in read mode");
+
+        assert!(error.to_string().contains("not allowed in read mode"));
+    }
+
+    #[test]
+    fn test_statements() {
+        let input: Input = serde_json::from_value(serde_json::json!({
+            "connection_string": "postgres://localhost/db",
+            "statements": [
+                { "query": "INSERT INTO t VALUES ($1)", "params": [{ "S": "a" }] },
+                { "query": "DELETE FROM t" },
+            ],
+        }))
+        .unwrap();
+        assert_eq!(statements(input).unwrap().len(), 2);
+
+        let input: Input = serde_json::from_value(serde_json::json!({
+            "connection_string": "postgres://localhost/db",
+        }))
+        .unwrap();
+        assert!(statements(input).is_err());
+    }
+}
@@ -0,0 +1,383 @@
+//! Commands running SQL on a user-supplied Postgres database.
+//!
+//! Parameters and result columns are converted between [`Value`] and the
+//! types Postgres reports for the prepared statement.
+
+use anyhow::anyhow;
+use bytes::BytesMut;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use flow_lib::{
+    command::prelude::*,
+    utils::net::{is_global, lookup_global},
+};
+use once_cell::sync::Lazy;
+use std::{error::Error as StdError, str::FromStr, time::Duration};
+use tokio_postgres::{
+    Client, Row,
+    config::Host,
+    types::{FromSql, IsNull, Kind, ToSql, Type, to_sql_checked},
+};
+use tokio_postgres_rustls::MakeRustlsConnect;
+
+pub mod execute;
+pub mod query;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+static TLS: Lazy<Result<MakeRustlsConnect, String>> = Lazy::new(|| {
+    let mut roots = rustls::RootCertStore::empty();
+    let certs = rustls_native_certs::load_native_certs().map_err(|e| e.to_string())?;
+    for cert in certs {
+        roots
+            .add(&rustls::Certificate(cert.0))
+            .map_err(|e| e.to_string())?;
+    }
+    let config = rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    Ok(MakeRustlsConnect::new(config))
+});
+
+/// Reject hosts that are not globally routable.
+///
+/// Domain names are resolved here and the connection is pinned to the checked
+/// address with `hostaddr`, so that a second lookup can not point elsewhere.
+async fn check_hosts(config: &mut tokio_postgres::Config) -> Result<(), CommandError> {
+    if !config.get_hostaddrs().is_empty() {
+        // `hostaddr` takes precedence over `host` when connecting
+        if let Some(ip) = config.get_hostaddrs().iter().find(|ip| !is_global(ip)) {
+            return Err(anyhow!("IP address not allowed: {}", ip));
+        }
+        return Ok(());
+    }
+    let mut addrs = Vec::new();
+    for host in config.get_hosts() {
+        match host {
+            Host::Tcp(name) => {
+                let addr = lookup_global(name, 0).await?[0];
+                addrs.push(addr.ip());
+            }
+            #[cfg(unix)]
+            Host::Unix(_) => return Err(anyhow!("unix socket hosts are not allowed")),
+        }
+    }
+    for ip in addrs {
+        config.hostaddr(ip);
+    }
+    Ok(())
+}
+
+/// Connect using a libpq-style connection string or URL.
+///
+/// TLS is used according to `sslmode`, which defaults to `prefer`. Only
+/// globally routable hosts are allowed.
+pub async fn connect(connection_string: &str) -> Result<Client, CommandError> {
+    let mut config = tokio_postgres::Config::from_str(connection_string)
+        .map_err(|e| anyhow!("invalid connection string: {}", e))?;
+    check_hosts(&mut config).await?;
+    if config.get_connect_timeout().is_none() {
+        config.connect_timeout(CONNECT_TIMEOUT);
+    }
+    let tls = TLS
+        .as_ref()
+        .map_err(|e| anyhow!("failed to load certificates: {}", e))?
+        .clone();
+    let (client, connection) = config.connect(tls).await?;
+    tokio::spawn(async move {
+        if let Err(error) = connection.await {
+            tracing::warn!("postgres connection error: {}", error);
+        }
+    });
+    Ok(client)
+}
+
+type BoxError = Box<dyn StdError + Sync + Send>;
+
+/// Query parameter, encoded according to the type Postgres expects.
+#[derive(Debug)]
+pub struct Param(pub Value);
+
+fn mismatch(value: &Value, ty: &Type) -> BoxError {
+    format!("can not convert {value:?} to {ty}").into()
+}
+
+fn as_i64(value: &Value) -> Option<i64> {
+    match value {
+        Value::I64(n) => Some(*n),
+        Value::U64(n) => i64::try_from(*n).ok(),
+        Value::I128(n) => i64::try_from(*n).ok(),
+        Value::U128(n) => i64::try_from(*n).ok(),
+        Value::Decimal(d) if d.fract().is_zero() => i64::try_from(*d).ok(),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+fn as_f64(value: &Value) -> Option<f64> {
+    match value {
+        Value::F64(f) => Some(*f),
+        Value::I64(n) => Some(*n as f64),
+        Value::U64(n) => Some(*n as f64),
+        Value::I128(n) => Some(*n as f64),
+        Value::U128(n) => Some(*n as f64),
+        Value::Decimal(d) => f64::try_from(*d).ok(),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+fn as_decimal(value: &Value) -> Option<Decimal> {
+    match value {
+        Value::Decimal(d) => Some(*d),
+        Value::I64(n) => Some((*n).into()),
+        Value::U64(n) => Some((*n).into()),
+        Value::I128(n) => Decimal::try_from_i128_with_scale(*n, 0).ok(),
+        Value::U128(n) => i128::try_from(*n)
+            .ok()
+            .and_then(|n| Decimal::try_from_i128_with_scale(n, 0).ok()),
+        Value::F64(f) => Decimal::try_from(*f).ok(),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+fn as_text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Bool(b) => Some(b.to_string()),
+        Value::I64(n) => Some(n.to_string()),
+        Value::U64(n) => Some(n.to_string()),
+        Value::I128(n) => Some(n.to_string()),
+        Value::U128(n) => Some(n.to_string()),
+        Value::F64(f) => Some(f.to_string()),
+        Value::Decimal(d) => Some(d.to_string()),
+        _ => None,
+    }
+}
+
+fn as_bytes(value: &Value) -> Option<&[u8]> {
+    match value {
+        Value::Bytes(b) => Some(&b[..]),
+        Value::B32(b) => Some(&b[..]),
+        Value::B64(b) => Some(&b[..]),
+        _ => None,
+    }
+}
+
+impl ToSql for Param {
+    fn to_sql(&self, ty: &Type, out: &mut BytesMut) -> Result<IsNull, BoxError> {
+        let value = &self.0;
+        if matches!(value, Value::Null) {
+            return Ok(IsNull::Yes);
+        }
+        let err = || mismatch(value, ty);
+        match ty.kind() {
+            Kind::Array(_) => {
+                let Value::Array(items) = value else {
+                    return Err(err());
+                };
+                let items = items.iter().cloned().map(Param).collect::<Vec<_>>();
+                return items.to_sql(ty, out);
+            }
+            Kind::Domain(inner) => return self.to_sql(inner, out),
+            Kind::Enum(_) => {
+                return as_text(value).ok_or_else(err)?.as_str().to_sql(ty, out);
+            }
+            _ => {}
+        }
+        match *ty {
+            Type::BOOL => match value {
+                Value::Bool(b) => b.to_sql(ty, out),
+                _ => Err(err()),
+            },
+            Type::INT2 => i16::try_from(as_i64(value).ok_or_else(err)?)?.to_sql(ty, out),
+            Type::INT4 => i32::try_from(as_i64(value).ok_or_else(err)?)?.to_sql(ty, out),
+            Type::INT8 => as_i64(value).ok_or_else(err)?.to_sql(ty, out),
+            Type::OID => u32::try_from(as_i64(value).ok_or_else(err)?)?.to_sql(ty, out),
+            Type::FLOAT4 => (as_f64(value).ok_or_else(err)? as f32).to_sql(ty, out),
+            Type::FLOAT8 => as_f64(value).ok_or_else(err)?.to_sql(ty, out),
+            Type::NUMERIC => as_decimal(value).ok_or_else(err)?.to_sql(ty, out),
+            Type::TEXT | Type::VARCHAR | Type::BPCHAR | Type::NAME | Type::UNKNOWN => {
+                as_text(value).ok_or_else(err)?.to_sql(ty, out)
+            }
+            Type::JSON | Type::JSONB => JsonValue::from(value.clone()).to_sql(ty, out),
+            Type::BYTEA => as_bytes(value).ok_or_else(err)?.to_sql(ty, out),
+            Type::UUID => match value {
+                Value::String(s) => uuid::Uuid::parse_str(s)?.to_sql(ty, out),
+                _ => Err(err()),
+            },
+            Type::TIMESTAMPTZ => match value {
+                Value::String(s) => DateTime::parse_from_rfc3339(s)?
+                    .with_timezone(&Utc)
+                    .to_sql(ty, out),
+                _ => Err(err()),
+            },
+            Type::TIMESTAMP => match value {
+                Value::String(s) => NaiveDateTime::from_str(s)?.to_sql(ty, out),
+                _ => Err(err()),
+            },
+            Type::DATE => match value {
+                Value::String(s) => NaiveDate::from_str(s)?.to_sql(ty, out),
+                _ => Err(err()),
+            },
+            Type::TIME => match value {
+                Value::String(s) => NaiveTime::from_str(s)?.to_sql(ty, out),
+                _ => Err(err()),
+            },
+            _ => Err(format!("unsupported parameter type {ty}, cast the parameter to text").into()),
+        }
+    }
+
+    fn accepts(_: &Type) -> bool {
+        true
+    }
+
+    to_sql_checked!();
+}
+
+/// Result column, decoded according to its Postgres type.
+#[derive(Debug)]
+pub struct Cell(pub Value);
+
+impl<'a> FromSql<'a> for Cell {
+    fn from_sql(ty: &Type, raw: &'a [u8]) -> Result<Self, BoxError> {
+        match ty.kind() {
+            Kind::Array(_) => {
+                let items = Vec::<Cell>::from_sql(ty, raw)?;
+                return Ok(Cell(Value::Array(
+                    items.into_iter().map(|cell| cell.0).collect(),
+                )));
+            }
+            Kind::Domain(inner) => return Self::from_sql(inner, raw),
+            Kind::Enum(_) => return Ok(Cell(<&str>::from_sql(ty, raw)?.into())),
+            _ => {}
+        }
+        let value = match *ty {
+            Type::BOOL => bool::from_sql(ty, raw)?.into(),
+            Type::CHAR => i8::from_sql(ty, raw)?.into(),
+            Type::INT2 => i16::from_sql(ty, raw)?.into(),
+            Type::INT4 => i32::from_sql(ty, raw)?.into(),
+            Type::INT8 => i64::from_sql(ty, raw)?.into(),
+            Type::OID => u32::from_sql(ty, raw)?.into(),
+            Type::FLOAT4 => f32::from_sql(ty, raw)?.into(),
+            Type::FLOAT8 => f64::from_sql(ty, raw)?.into(),
+            Type::NUMERIC => Decimal::from_sql(ty, raw)?.into(),
+            Type::TEXT | Type::VARCHAR | Type::BPCHAR | Type::NAME | Type::UNKNOWN => {
+                <&str>::from_sql(ty, raw)?.into()
+            }
+            Type::JSON | Type::JSONB => JsonValue::from_sql(ty, raw)?.into(),
+            Type::BYTEA => Value::Bytes(raw.to_vec().into()),
+            Type::UUID => uuid::Uuid::from_sql(ty, raw)?.to_string().into(),
+            Type::TIMESTAMPTZ => DateTime::<Utc>::from_sql(ty, raw)?.to_rfc3339().into(),
+            Type::TIMESTAMP => NaiveDateTime::from_sql(ty, raw)?.to_string().into(),
+            Type::DATE => NaiveDate::from_sql(ty, raw)?.to_string().into(),
+            Type::TIME => NaiveTime::from_sql(ty, raw)?.to_string().into(),
+            _ => {
+                return Err(
+                    format!("unsupported column type {ty}, cast the column to text").into(),
+                );
+            }
+        };
+        Ok(Cell(value))
+    }
+
+    fn from_sql_null(_: &Type) -> Result<Self, BoxError> {
+        Ok(Cell(Value::Null))
+    }
+
+    fn accepts(_: &Type) -> bool {
+        true
+    }
+}
+
+pub fn params(values: Vec<Value>) -> Vec<Param> {
+    values.into_iter().map(Param).collect()
+}
+
+pub fn param_refs(params: &[Param]) -> Vec<&(dyn ToSql + Sync)> {
+    params.iter().map(|p| p as &(dyn ToSql + Sync)).collect()
+}
+
+pub fn row_to_map(row: &Row) -> Result<value::Map, CommandError> {
+    row.columns()
+        .iter()
+        .enumerate()
+        .map(|(i, column)| {
+            let cell = row
+                .try_get::<_, Cell>(i)
+                .map_err(|e| anyhow!("column {:?}: {}", column.name(), e))?;
+            Ok((column.name().to_owned(), cell.0))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(value: Value, ty: Type) -> Value {
+        let mut buf = BytesMut::new();
+        match Param(value).to_sql(&ty, &mut buf).unwrap() {
+            IsNull::Yes => Cell::from_sql_null(&ty).unwrap().0,
+            IsNull::No => Cell::from_sql(&ty, &buf).unwrap().0,
+        }
+    }
+
+    #[test]
+    fn test_param_roundtrip() {
+        assert_eq!(roundtrip(Value::U64(5), Type::INT4), Value::I64(5));
+        assert_eq!(
+            roundtrip(Value::String("42".to_owned()), Type::INT8),
+            Value::I64(42)
+        );
+        assert_eq!(roundtrip(Value::I64(1), Type::TEXT), Value::from("1"));
+        assert_eq!(roundtrip(Value::Null, Type::INT4), Value::Null);
+        assert_eq!(
+            roundtrip(Value::Decimal(Decimal::new(125, 2)), Type::NUMERIC),
+            Value::Decimal(Decimal::new(125, 2))
+        );
+        assert_eq!(
+            roundtrip(
+                Value::Array(vec![Value::I64(1), Value::Null]),
+                Type::INT8_ARRAY
+            ),
+            Value::Array(vec![Value::I64(1), Value::Null])
+        );
+        assert_eq!(
+            roundtrip(
+                Value::String("2024-01-02T03:04:05Z".to_owned()),
+                Type::TIMESTAMPTZ
+            ),
+            Value::from("2024-01-02T03:04:05+00:00")
+        );
+    }
+
+    #[test]
+    fn test_param_mismatch() {
+        let mut buf = BytesMut::new();
+        assert!(
+            Param(Value::String("a".to_owned()))
+                .to_sql(&Type::INT4, &mut buf)
+                .is_err()
+        );
+        assert!(
+            Param(Value::I64(i64::MAX))
+                .to_sql(&Type::INT2, &mut buf)
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_private_hosts() {
+        for conn in [
+            "postgres://localhost/db",
+            "postgres://10.0.0.1/db",
+            "host=db.example.com hostaddr=127.0.0.1 dbname=db",
+            "host=/var/run/postgresql dbname=db",
+        ] {
+            let error = connect(conn).await.expect_err(conn);
+            assert!(error.to_string().contains("not allowed"), "{error}");
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use super::{connect, param_refs, params, row_to_map};
+use crate::polars::types::{df_from_json_value, dual_output};
+use flow_lib::command::prelude::*;
+use polars::prelude::{Column, DataFrame, DataType};
+
+pub const NAME: &str = "postgres_query";
+
+const DEFINITION: &str = flow_lib::node_definition!("postgres/query.jsonc");
+
+fn build() -> BuildResult {
+    static CACHE: BuilderCache = BuilderCache::new(|| {
+        Ok(CmdBuilder::new(DEFINITION)?
+            .check_name(NAME)?
+            .read_capability(ReadCapability::Snapshot))
+    });
+
+    Ok(CACHE.clone()?.build(run))
+}
+
+flow_lib::submit!(CommandDescription::new(NAME, |_| build()));
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Input {
+    connection_string: String,
+    query: String,
+    #[serde(default)]
+    params: Vec<Value>,
+    /// Return a DataFrame instead of a list of rows.
+    #[serde(default)]
+    as_dataframe: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct Output {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rows: Option<Vec<value::Map>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dataframe: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dataframe_json: Option<JsonValue>,
+    row_count: u64,
+}
+
+fn rows_to_dataframe(columns: &[String], rows: Vec<value::Map>) -> Result<DataFrame, CommandError> {
+    if rows.is_empty() {
+        let columns = columns
+            .iter()
+            .map(|name| Column::new_empty(name.as_str().into(), &DataType::Null))
+            .collect();
+        return DataFrame::new(columns)
+            .map_err(|e| CommandError::msg(format!("DataFrame error: {e}")));
+    }
+    let rows = rows
+        .into_iter()
+        .map(|row| JsonValue::from(Value::Map(row)))
+        .collect::<Vec<_>>();
+    df_from_json_value(&JsonValue::Array(rows))
+}
+
+async fn run(ctx: CommandContext, input: Input) -> Result<Output, CommandError> {
+    let mut client = connect(&input.connection_string).await?;
+    let tx = client
+        .build_transaction()
+        .read_only(ctx.is_read_only())
+        .start()
+        .await?;
+    let stmt = tx.prepare(&input.query).await?;
+    let params = params(input.params);
+    let rows = tx.query(&stmt, &param_refs(&params)).await?;
+    tx.commit().await?;
+
+    let row_count = rows.len() as u64;
+    let rows = rows.iter().map(row_to_map).collect::<Result<Vec<_>, _>>()?;
+    if input.as_dataframe {
+        let columns = stmt
+            .columns()
+            .iter()
+            .map(|c| c.name().to_owned())
+            .collect::<Vec<_>>();
+        let mut df = rows_to_dataframe(&columns, rows)?;
+        let (ipc, json) = dual_output(&mut df)?;
+        Ok(Output {
+            dataframe: Some(ipc),
+            dataframe_json: Some(json),
+            row_count,
+            ..<_>::default()
+        })
+    } else {
+        Ok(Output {
+            rows: Some(rows),
+            row_count,
+            ..<_>::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build() {
+        build().unwrap();
+    }
+
+    #[test]
+    fn test_rows_to_dataframe() {
+        let columns = vec!["id".to_owned(), "name".to_owned()];
+        let df = rows_to_dataframe(&columns, Vec::new()).unwrap();
+        assert_eq!(df.shape(), (0, 2));
+
+        let rows = vec![
+            value::map! { "id" => 1i64, "name" => "a" },
+            value::map! { "id" => 2i64, "name" => Value::Null },
+        ];
+        let df = rows_to_dataframe(&columns, rows).unwrap();
+        assert_eq!(df.shape(), (2, 2));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_connection_string() {
+        let result = run(
+            CommandContext::default(),
+            Input {
+                connection_string: "postgres://host:notaport".to_owned(),
+                query: "SELECT 1".to_owned(),
+                params: Vec::new(),
+                as_dataframe: false,
+            },
+        )
+        .await;
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,450 @@
+use super::{
+    local::LocalStorage,
+    s3::{S3Config, S3Storage},
+};
+use crate::supabase_error;
+use anyhow::anyhow;
+use bytes::Bytes;
+use flow_lib::command::prelude::*;
+use reqwest::{
+    StatusCode,
+    header::{AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE, HeaderName, LAST_MODIFIED},
+};
+use serde_json::{Value as JsonValue, json};
+use std::collections::HashMap;
+
+/// Flow environment variable selecting the backend: `supabase` (default), `s3` or `local`.
+pub const BACKEND_ENV: &str = "STORAGE_BACKEND";
+pub const S3_ENDPOINT_ENV: &str = "STORAGE_S3_ENDPOINT";
+pub const S3_REGION_ENV: &str = "STORAGE_S3_REGION";
+pub const S3_ACCESS_KEY_ID_ENV: &str = "STORAGE_S3_ACCESS_KEY_ID";
+pub const S3_SECRET_ACCESS_KEY_ENV: &str = "STORAGE_S3_SECRET_ACCESS_KEY";
+
+/// Backend selected in node config, takes priority over the flow's environment.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BackendConfig {
+    Supabase,
+    S3(S3Options),
+    Local,
+}
+
+/// S3 backend in node config.
+///
+/// Node config is stored in the flow and sent in events, so credentials are
+/// only read from the flow's environment.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct S3Options {
+    /// Defaults to `STORAGE_S3_ENDPOINT`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+    /// Defaults to `STORAGE_S3_REGION`, then `us-east-1`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    /// Rejected, see above.
+    #[serde(default, skip_serializing)]
+    access_key_id: Option<JsonValue>,
+    /// Rejected, see above.
+    #[serde(default, skip_serializing)]
+    secret_access_key: Option<JsonValue>,
+}
+
+impl S3Options {
+    pub fn config(&self, env: &HashMap<String, String>) -> Result<S3Config, CommandError> {
+        if self.access_key_id.is_some() || self.secret_access_key.is_some() {
+            return Err(anyhow!(
+                "S3 credentials can not be set in node config, use the {} and {} environment variables",
+                S3_ACCESS_KEY_ID_ENV,
+                S3_SECRET_ACCESS_KEY_ENV
+            ));
+        }
+        let var = |name: &str| {
+            env.get(name)
+                .cloned()
+                .ok_or_else(|| anyhow!("missing environment variable: {}", name))
+        };
+        Ok(S3Config {
+            endpoint: match &self.endpoint {
+                Some(endpoint) => endpoint.clone(),
+                None => var(S3_ENDPOINT_ENV)?,
+            },
+            region: self
+                .region
+                .clone()
+                .or_else(|| env.get(S3_REGION_ENV).cloned())
+                .unwrap_or_else(S3Config::default_region),
+            access_key_id: var(S3_ACCESS_KEY_ID_ENV)?,
+            secret_access_key: var(S3_SECRET_ACCESS_KEY_ENV)?,
+        })
+    }
+}
+
+impl BackendConfig {
+    /// Backend in the `backend` field of node config, if set.
+    pub fn from_node(config: &JsonValue) -> Result<Option<Self>, CommandError> {
+        let value = config
+            .get("backend")
+            .map(|json| flow_lib::command::parse_value_tagged_or_json(json.clone()));
+        match value {
+            None | Some(Value::Null) => Ok(None),
+            Some(value) => Ok(Some(value::from_value(value)?)),
+        }
+    }
+
+    pub fn from_env(env: &HashMap<String, String>) -> Result<Self, CommandError> {
+        let Some(backend) = env.get(BACKEND_ENV) else {
+            return Ok(Self::Supabase);
+        };
+        match backend.as_str() {
+            "supabase" => Ok(Self::Supabase),
+            "local" => Ok(Self::Local),
+            "s3" => Ok(Self::S3(S3Options::default())),
+            other => Err(anyhow!("unknown storage backend: {}", other)),
+        }
+    }
+}
+
+pub struct Object {
+    pub content: Bytes,
+    pub content_type: String,
+}
+
+pub struct ObjectMetadata {
+    pub content_type: String,
+    pub last_modified: String,
+    pub size: Option<u64>,
+}
+
+/// Storage used by `storage_*` commands.
+///
+/// Objects are addressed by keys in the form `bucket/path`, as produced by [`super::FileSpec::key`].
+#[derive(Debug, Clone)]
+pub enum StorageBackend {
+    /// Supabase Storage, authenticated with the flow owner's JWT.
+    Supabase,
+    S3(S3Storage),
+    Local(LocalStorage),
+}
+
+impl StorageBackend {
+    pub fn new(ctx: &CommandContext, config: Option<BackendConfig>) -> Result<Self, CommandError> {
+        let config = match config {
+            Some(config) => config,
+            None => BackendConfig::from_env(ctx.environment())?,
+        };
+        Ok(match config {
+            BackendConfig::Supabase => Self::Supabase,
+            BackendConfig::S3(options) => {
+                Self::S3(S3Storage::new(options.config(ctx.environment())?)?)
+            }
+            BackendConfig::Local => Self::Local(LocalStorage::from_env(&ctx.flow_owner().id)?),
+        })
+    }
+
+    pub async fn upload(
+        &self,
+        ctx: &mut CommandContext,
+        key: &str,
+        content: Bytes,
+        content_type: &str,
+        overwrite: bool,
+    ) -> Result<String, CommandError> {
+        match self {
+            Self::Supabase => supabase_upload(ctx, key, content, content_type, overwrite).await,
+            Self::S3(s3) => s3.upload(ctx, key, content, content_type, overwrite).await,
+            Self::Local(local) => local.upload(key, content, overwrite).await,
+        }
+    }
+
+    pub async fn download(
+        &self,
+        ctx: &mut CommandContext,
+        key: &str,
+    ) -> Result<Object, CommandError> {
+        match self {
+            Self::Supabase => supabase_download(ctx, key).await,
+            Self::S3(s3) => s3.download(ctx, key).await,
+            Self::Local(local) => local.download(key).await,
+        }
+    }
+
+    pub async fn delete(&self, ctx: &mut CommandContext, key: &str) -> Result<(), CommandError> {
+        match self {
+            Self::Supabase => supabase_delete(ctx, key).await,
+            Self::S3(s3) => s3.delete(ctx, key).await,
+            Self::Local(local) => local.delete(key).await,
+        }
+    }
+
+    pub async fn metadata(
+        &self,
+        ctx: &mut CommandContext,
+        key: &str,
+    ) -> Result<ObjectMetadata, CommandError> {
+        match self {
+            Self::Supabase => supabase_metadata(ctx, key).await,
+            Self::S3(s3) => s3.metadata(ctx, key).await,
+            Self::Local(local) => local.metadata(key).await,
+        }
+    }
+
+    /// List entries directly under `prefix` in `bucket`, returning names relative to `prefix`.
+    pub async fn list(
+        &self,
+        ctx: &mut CommandContext,
+        bucket: &str,
+        prefix: &str,
+    ) -> Result<Vec<String>, CommandError> {
+        match self {
+            Self::Supabase => supabase_list(ctx, bucket, prefix).await,
+            Self::S3(s3) => s3.list(ctx, bucket, prefix).await,
+            Self::Local(local) => local.list(bucket, prefix).await,
+        }
+    }
+}
+
+async fn supabase_upload(
+    ctx: &mut CommandContext,
+    key: &str,
+    content: Bytes,
+    content_type: &str,
+    overwrite: bool,
+) -> Result<String, CommandError> {
+    #[derive(Deserialize)]
+    #[allow(non_snake_case)]
+    struct SuccessBody {
+        Key: String,
+    }
+
+    let url = format!("{}/storage/v1/object/{}", ctx.endpoints().supabase, key);
+    let mut req = ctx
+        .http()
+        .post(url)
+        .header(AUTHORIZATION, ctx.get_jwt_header().await?)
+        .header(CONTENT_TYPE, content_type)
+        .body(content);
+
+    if overwrite {
+        req = req.header("x-upsert", "true");
+    }
+
+    let resp = req.send().await?;
+
+    match resp.status() {
+        StatusCode::OK => Ok(resp.json::<SuccessBody>().await?.Key),
+        code => Err(supabase_error(code, resp).await),
+    }
+}
+
+async fn supabase_download(ctx: &mut CommandContext, key: &str) -> Result<Object, CommandError> {
+    let url = format!(
+        "{}/storage/v1/object/authenticated/{}",
+        ctx.endpoints().supabase,
+        key
+    );
+    tracing::debug!("using URL: {}", url);
+    let resp = ctx
+        .http()
+        .get(url)
+        .header(AUTHORIZATION, ctx.get_jwt_header().await?)
+        .send()
+        .await?;
+
+    match resp.status() {
+        StatusCode::OK => {
+            let content_type = resp
+                .headers()
+                .get(CONTENT_TYPE)
+                .and_then(|t| t.to_str().ok())
+                .unwrap_or("application/octet-stream")
+                .to_owned();
+            let content = resp.bytes().await?;
+            Ok(Object {
+                content,
+                content_type,
+            })
+        }
+        code => Err(supabase_error(code, resp).await),
+    }
+}
+
+async fn supabase_delete(ctx: &mut CommandContext, key: &str) -> Result<(), CommandError> {
+    let url = format!("{}/storage/v1/object/{}", ctx.endpoints().supabase, key);
+    tracing::debug!("using URL: {}", url);
+    let resp = ctx
+        .http()
+        .delete(url)
+        .header(AUTHORIZATION, ctx.get_jwt_header().await?)
+        .send()
+        .await?;
+
+    match resp.status() {
+        StatusCode::OK => Ok(()),
+        code => Err(supabase_error(code, resp).await),
+    }
+}
+
+async fn supabase_metadata(
+    ctx: &mut CommandContext,
+    key: &str,
+) -> Result<ObjectMetadata, CommandError> {
+    let url = format!(
+        "{}/storage/v1/object/info/authenticated/{}",
+        ctx.endpoints().supabase,
+        key
+    );
+    tracing::debug!("using URL: {}", url);
+    let resp = ctx
+        .http()
+        .head(url)
+        .header(AUTHORIZATION, ctx.get_jwt_header().await?)
+        .send()
+        .await?;
+
+    match resp.status() {
+        StatusCode::OK => {
+            let headers = resp.headers();
+            let header = |name: HeaderName| {
+                headers
+                    .get(name)
+                    .map(|v| String::from_utf8_lossy(v.as_bytes()).into_owned())
+            };
+            Ok(ObjectMetadata {
+                content_type: header(CONTENT_TYPE)
+                    .ok_or_else(|| anyhow!("missing header: content-type"))?,
+                last_modified: header(LAST_MODIFIED)
+                    .ok_or_else(|| anyhow!("missing header: last-modified"))?,
+                size: header(CONTENT_LENGTH).and_then(|v| v.parse().ok()),
+            })
+        }
+        code => Err(supabase_error(code, resp).await),
+    }
+}
+
+async fn supabase_list(
+    ctx: &mut CommandContext,
+    bucket: &str,
+    prefix: &str,
+) -> Result<Vec<String>, CommandError> {
+    let url = format!(
+        "{}/storage/v1/object/list/{}",
+        ctx.endpoints().supabase,
+        bucket,
+    );
+    tracing::debug!("using URL: {}", url);
+    tracing::debug!("using prefix: {}", prefix);
+    let req = ctx
+        .http()
+        .post(url)
+        .header(AUTHORIZATION, ctx.get_jwt_header().await?);
+
+    let body = json!({
+        "prefix": prefix,
+        "limit": 1000,
+        "offset": 0,
+        "sortBy": {
+            "column": "name",
+            "order": "asc"
+        },
+    });
+
+    let resp = req.json(&body).send().await?;
+
+    match resp.status() {
+        StatusCode::OK => {
+            #[derive(Deserialize)]
+            struct File {
+                name: String,
+            }
+            let files = resp.json::<Vec<File>>().await?;
+            Ok(files.into_iter().map(|f| f.name).collect())
+        }
+        code => Err(supabase_error(code, resp).await),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_from_env() {
+        let mut env = HashMap::new();
+        assert!(matches!(
+            BackendConfig::from_env(&env).unwrap(),
+            BackendConfig::Supabase
+        ));
+
+        env.insert(BACKEND_ENV.to_owned(), "s3".to_owned());
+        let BackendConfig::S3(options) = BackendConfig::from_env(&env).unwrap() else {
+            panic!("expected S3 backend");
+        };
+        assert!(options.config(&env).is_err());
+
+        env.insert(
+            S3_ENDPOINT_ENV.to_owned(),
+            "http://localhost:9000".to_owned(),
+        );
+        env.insert(S3_ACCESS_KEY_ID_ENV.to_owned(), "minio".to_owned());
+        env.insert(S3_SECRET_ACCESS_KEY_ENV.to_owned(), "minio123".to_owned());
+        let config = options.config(&env).unwrap();
+        assert_eq!(config.endpoint, "http://localhost:9000");
+        assert_eq!(config.region, "us-east-1");
+
+        env.insert(BACKEND_ENV.to_owned(), "ftp".to_owned());
+        assert!(BackendConfig::from_env(&env).is_err());
+    }
+
+    #[test]
+    fn test_config_from_node() {
+        assert!(BackendConfig::from_node(&json!({})).unwrap().is_none());
+        assert!(
+            BackendConfig::from_node(&json!({ "backend": null }))
+                .unwrap()
+                .is_none()
+        );
+        let config = json!({ "backend": { "type": "s3", "region": "eu-west-1" } });
+        let Some(BackendConfig::S3(options)) = BackendConfig::from_node(&config).unwrap() else {
+            panic!("expected S3 backend");
+        };
+        assert_eq!(options.region.as_deref(), Some("eu-west-1"));
+        assert!(BackendConfig::from_node(&json!({ "backend": { "type": "ftp" } })).is_err());
+    }
+
+    #[test]
+    fn test_config_deserialize() {
+        let env = HashMap::from([
+            (S3_ACCESS_KEY_ID_ENV.to_owned(), "minio".to_owned()),
+            (S3_SECRET_ACCESS_KEY_ENV.to_owned(), "minio123".to_owned()),
+        ]);
+        let config: BackendConfig = serde_json::from_value(json!({
+            "type": "s3",
+            "endpoint": "http://localhost:9000",
+            "region": "eu-west-1",
+        }))
+        .unwrap();
+        let BackendConfig::S3(options) = config else {
+            panic!("expected S3 backend");
+        };
+        let config = options.config(&env).unwrap();
+        assert_eq!(config.endpoint, "http://localhost:9000");
+        assert_eq!(config.region, "eu-west-1");
+        assert_eq!(config.secret_access_key, "minio123");
+
+        let config: BackendConfig = serde_json::from_value(json!({
+            "type": "s3",
+            "endpoint": "http://localhost:9000",
+            "access_key_id": "minio",
+            "secret_access_key": "minio123",
+        }))
+        .unwrap();
+        let BackendConfig::S3(options) = config else {
+            panic!("expected S3 backend");
+        };
+        assert!(options.config(&env).is_err());
+        assert_eq!(
+            serde_json::to_value(&options).unwrap(),
+            json!({ "endpoint": "http://localhost:9000" })
+        );
+    }
+}
@@ -1,13 +1,11 @@
-use super::FileSpec;
-use crate::supabase_error;
+use super::{BackendConfig, FileInput, StorageBackend};
 use flow_lib::command::prelude::*;
-use reqwest::{StatusCode, header::AUTHORIZATION};
 
 pub const NAME: &str = "storage_delete";
 
 const DEFINITION: &str = flow_lib::node_definition!("storage/delete.jsonc");
 
-fn build() -> BuildResult {
+fn build(backend: Option<BackendConfig>) -> BuildResult {
     static CACHE: BuilderCache = BuilderCache::new(|| {
         Ok(CmdBuilder::new(DEFINITION)?
             .check_name(NAME)?
@@ -15,31 +13,29 @@ fn build() -> BuildResult {
             .read_capability(ReadCapability::Mutating))
     });
 
-    Ok(CACHE.clone()?.build(run))
+    Ok(CACHE
+        .clone()?
+        .build(move |ctx, input| run(ctx, input, backend.clone())))
 }
 
-flow_lib::submit!(CommandDescription::new(NAME, |_| build()));
+flow_lib::submit!(CommandDescription::new(NAME, |nd: &NodeData| {
+    build(BackendConfig::from_node(&nd.config)?)
+}));
 
 #[derive(Serialize)]
 struct Output {
     key: String,
 }
 
-async fn run(mut ctx: CommandContext, input: FileSpec) -> Result<Output, CommandError> {
-    let key = input.key(&ctx.flow_owner().id);
-    let url = format!("{}/storage/v1/object/{}", ctx.endpoints().supabase, key);
-    tracing::debug!("using URL: {}", url);
-    let resp = ctx
-        .http()
-        .delete(url)
-        .header(AUTHORIZATION, ctx.get_jwt_header().await?)
-        .send()
-        .await?;
-
-    match resp.status() {
-        StatusCode::OK => Ok(Output { key }),
-        code => Err(supabase_error(code, resp).await),
-    }
+async fn run(
+    mut ctx: CommandContext,
+    input: FileInput,
+    backend: Option<BackendConfig>,
+) -> Result<Output, CommandError> {
+    let backend = StorageBackend::new(&ctx, backend)?;
+    let key = input.file.key(&ctx.flow_owner().id);
+    backend.delete(&mut ctx, &key).await?;
+    Ok(Output { key })
 }
 
 #[cfg(test)]
@@ -48,6 +44,6 @@ mod tests {
 
     #[test]
     fn test_build() {
-        build().unwrap();
+        build(None).unwrap();
     }
 }
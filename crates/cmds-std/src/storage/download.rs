@@ -1,26 +1,25 @@
-use super::FileSpec;
-use crate::supabase_error;
+use super::{BackendConfig, FileInput, StorageBackend};
 use flow_lib::command::prelude::*;
-use reqwest::{
-    StatusCode,
-    header::{AUTHORIZATION, CONTENT_TYPE},
-};
 
 pub const NAME: &str = "storage_download";
 
 const DEFINITION: &str = flow_lib::node_definition!("storage/download.jsonc");
 
-fn build() -> BuildResult {
+fn build(backend: Option<BackendConfig>) -> BuildResult {
     static CACHE: BuilderCache = BuilderCache::new(|| {
         Ok(CmdBuilder::new(DEFINITION)?
             .check_name(NAME)?
             .permissions(Permissions { user_tokens: true }))
     });
 
-    Ok(CACHE.clone()?.build(run))
+    Ok(CACHE
+        .clone()?
+        .build(move |ctx, input| run(ctx, input, backend.clone())))
 }
 
-flow_lib::submit!(CommandDescription::new(NAME, |_| build()));
+flow_lib::submit!(CommandDescription::new(NAME, |nd: &NodeData| {
+    build(BackendConfig::from_node(&nd.config)?)
+}));
 
 #[derive(Serialize)]
 struct Output {
@@ -29,43 +28,26 @@ struct Output {
     content_type: String,
 }
 
-async fn run(mut ctx: CommandContext, input: FileSpec) -> Result<Output, CommandError> {
-    let key = input.key(&ctx.flow_owner().id);
-    let url = format!(
-        "{}/storage/v1/object/authenticated/{}",
-        ctx.endpoints().supabase,
-        key
-    );
-    tracing::debug!("using URL: {}", url);
-    let resp = ctx
-        .http()
-        .get(url)
-        .header(AUTHORIZATION, ctx.get_jwt_header().await?)
-        .send()
-        .await?;
-
-    match resp.status() {
-        StatusCode::OK => {
-            let content_type = resp
-                .headers()
-                .get(CONTENT_TYPE)
-                .and_then(|t| t.to_str().ok())
-                .unwrap_or("application/octet-stream")
-                .to_owned();
-            let bytes = resp.bytes().await?;
-            let size = bytes.len() as u64;
-            let content = match std::str::from_utf8(&bytes) {
-                Ok(_) => Value::String(unsafe { String::from_utf8_unchecked(bytes.into()) }),
-                Err(_) => Value::Bytes(bytes),
-            };
-            Ok(Output {
-                content,
-                size,
-                content_type,
-            })
-        }
-        code => Err(supabase_error(code, resp).await),
-    }
+async fn run(
+    mut ctx: CommandContext,
+    input: FileInput,
+    backend: Option<BackendConfig>,
+) -> Result<Output, CommandError> {
+    let backend = StorageBackend::new(&ctx, backend)?;
+    let key = input.file.key(&ctx.flow_owner().id);
+    let object = backend.download(&mut ctx, &key).await?;
+
+    let bytes = object.content;
+    let size = bytes.len() as u64;
+    let content = match std::str::from_utf8(&bytes) {
+        Ok(_) => Value::String(unsafe { String::from_utf8_unchecked(bytes.into()) }),
+        Err(_) => Value::Bytes(bytes),
+    };
+    Ok(Output {
+        content,
+        size,
+        content_type: object.content_type,
+    })
 }
 
 #[cfg(test)]
@@ -74,6 +56,6 @@ mod tests {
 
     #[test]
     fn test_build() {
-        build().unwrap();
+        build(None).unwrap();
     }
 }
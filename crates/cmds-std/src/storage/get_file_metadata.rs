@@ -1,73 +1,48 @@
-use super::FileSpec;
-use crate::supabase_error;
-use anyhow::anyhow;
+use super::{BackendConfig, FileInput, StorageBackend};
 use flow_lib::command::prelude::*;
-use reqwest::{
-    StatusCode,
-    header::{AUTHORIZATION, CONTENT_TYPE, LAST_MODIFIED},
-};
 
 pub const NAME: &str = "storage_get_file_metadata";
 
 const DEFINITION: &str = flow_lib::node_definition!("storage/get_file_metadata.jsonc");
 
-fn build() -> BuildResult {
+fn build(backend: Option<BackendConfig>) -> BuildResult {
     static CACHE: BuilderCache = BuilderCache::new(|| {
         Ok(CmdBuilder::new(DEFINITION)?
             .check_name(NAME)?
             .permissions(Permissions { user_tokens: true }))
     });
 
-    Ok(CACHE.clone()?.build(run))
+    Ok(CACHE
+        .clone()?
+        .build(move |ctx, input| run(ctx, input, backend.clone())))
 }
 
-flow_lib::submit!(CommandDescription::new(NAME, |_| build()));
+flow_lib::submit!(CommandDescription::new(NAME, |nd: &NodeData| {
+    build(BackendConfig::from_node(&nd.config)?)
+}));
 
 #[derive(Serialize)]
 struct Output {
     key: String,
     content_type: String,
     last_modified: String,
+    size: Option<u64>,
 }
 
-async fn run(mut ctx: CommandContext, input: FileSpec) -> Result<Output, CommandError> {
-    let key = input.key(&ctx.flow_owner().id);
-    let url = format!(
-        "{}/storage/v1/object/info/authenticated/{}",
-        ctx.endpoints().supabase,
-        key
-    );
-    tracing::debug!("using URL: {}", url);
-    let resp = ctx
-        .http()
-        .head(url)
-        .header(AUTHORIZATION, ctx.get_jwt_header().await?)
-        .send()
-        .await?;
-
-    match resp.status() {
-        StatusCode::OK => {
-            let headers = resp.headers();
-            Ok(Output {
-                key,
-                content_type: String::from_utf8_lossy(
-                    headers
-                        .get(CONTENT_TYPE)
-                        .ok_or_else(|| anyhow!("missing header: content-type"))?
-                        .as_bytes(),
-                )
-                .to_string(),
-                last_modified: String::from_utf8_lossy(
-                    headers
-                        .get(LAST_MODIFIED)
-                        .ok_or_else(|| anyhow!("missing header: last-modified"))?
-                        .as_bytes(),
-                )
-                .to_string(),
-            })
-        }
-        code => Err(supabase_error(code, resp).await),
-    }
+async fn run(
+    mut ctx: CommandContext,
+    input: FileInput,
+    backend: Option<BackendConfig>,
+) -> Result<Output, CommandError> {
+    let backend = StorageBackend::new(&ctx, backend)?;
+    let key = input.file.key(&ctx.flow_owner().id);
+    let metadata = backend.metadata(&mut ctx, &key).await?;
+    Ok(Output {
+        key,
+        content_type: metadata.content_type,
+        last_modified: metadata.last_modified,
+        size: metadata.size,
+    })
 }
 
 #[cfg(test)]
@@ -76,6 +51,6 @@ mod tests {
 
     #[test]
     fn test_build() {
-        build().unwrap();
+        build(None).unwrap();
     }
 }
@@ -0,0 +1,203 @@
+//! Storage backed by a directory on the server's file system.
+//!
+//! Every flow owner gets their own sub-directory and keys can not escape it.
+
+use super::{Object, ObjectMetadata};
+use anyhow::anyhow;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use flow_lib::{UserId, command::prelude::*};
+use mime_guess::MimeGuess;
+use std::{
+    io::ErrorKind,
+    path::{Component, Path, PathBuf},
+};
+use tokio::io::AsyncWriteExt;
+
+/// Server environment variable setting the root directory of local storage.
+/// Local storage is disabled when it is not set.
+pub const ROOT_ENV: &str = "STORAGE_LOCAL_ROOT";
+
+#[derive(Debug, Clone)]
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+/// Only allow plain path components, so that a key can not point outside of the sandbox.
+fn relative_path(key: &str) -> Result<PathBuf, CommandError> {
+    let path = Path::new(key);
+    let mut result = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(c) => result.push(c),
+            Component::CurDir => {}
+            _ => return Err(anyhow!("invalid storage key: {:?}", key)),
+        }
+    }
+    if result.as_os_str().is_empty() {
+        return Err(anyhow!("invalid storage key: {:?}", key));
+    }
+    Ok(result)
+}
+
+fn not_found(key: &str) -> impl FnOnce(std::io::Error) -> CommandError + '_ {
+    move |error| match error.kind() {
+        ErrorKind::NotFound => anyhow!("object not found: {}", key),
+        _ => error.into(),
+    }
+}
+
+impl LocalStorage {
+    /// `root` is the directory for this flow owner.
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    /// Use the directory set by [`ROOT_ENV`].
+    pub fn from_env(owner: &UserId) -> Result<Self, CommandError> {
+        let root = std::env::var_os(ROOT_ENV)
+            .ok_or_else(|| anyhow!("local storage is not enabled on this server"))?;
+        Ok(Self::new(PathBuf::from(root).join(owner.to_string())))
+    }
+
+    fn path(&self, key: &str) -> Result<PathBuf, CommandError> {
+        Ok(self.root.join(relative_path(key)?))
+    }
+
+    pub async fn upload(
+        &self,
+        key: &str,
+        content: Bytes,
+        overwrite: bool,
+    ) -> Result<String, CommandError> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let mut options = tokio::fs::OpenOptions::new();
+        options.write(true);
+        if overwrite {
+            options.create(true).truncate(true);
+        } else {
+            options.create_new(true);
+        }
+        let mut file = options
+            .open(&path)
+            .await
+            .map_err(|error| match error.kind() {
+                ErrorKind::AlreadyExists => anyhow!("object already exists: {}", key),
+                _ => error.into(),
+            })?;
+        file.write_all(&content).await?;
+        file.flush().await?;
+        Ok(key.to_owned())
+    }
+
+    pub async fn download(&self, key: &str) -> Result<Object, CommandError> {
+        let path = self.path(key)?;
+        let content = tokio::fs::read(&path).await.map_err(not_found(key))?;
+        let content_type = MimeGuess::from_path(&path)
+            .first_raw()
+            .unwrap_or("application/octet-stream")
+            .to_owned();
+        Ok(Object {
+            content: content.into(),
+            content_type,
+        })
+    }
+
+    pub async fn delete(&self, key: &str) -> Result<(), CommandError> {
+        let path = self.path(key)?;
+        tokio::fs::remove_file(&path)
+            .await
+            .map_err(not_found(key))?;
+        Ok(())
+    }
+
+    pub async fn metadata(&self, key: &str) -> Result<ObjectMetadata, CommandError> {
+        let path = self.path(key)?;
+        let metadata = tokio::fs::metadata(&path).await.map_err(not_found(key))?;
+        let last_modified: DateTime<Utc> = metadata.modified()?.into();
+        Ok(ObjectMetadata {
+            content_type: MimeGuess::from_path(&path)
+                .first_raw()
+                .unwrap_or("application/octet-stream")
+                .to_owned(),
+            last_modified: last_modified
+                .format("%a, %d %b %Y %H:%M:%S GMT")
+                .to_string(),
+            size: Some(metadata.len()),
+        })
+    }
+
+    /// List entries directly under `bucket/prefix`, returning names relative to it.
+    pub async fn list(&self, bucket: &str, prefix: &str) -> Result<Vec<String>, CommandError> {
+        let dir = match relative_path(prefix) {
+            Ok(prefix) => self.path(bucket)?.join(prefix),
+            Err(_) if prefix.trim_matches('/').is_empty() => self.path(bucket)?,
+            Err(error) => return Err(error),
+        };
+        let mut entries = match tokio::fs::read_dir(&dir).await {
+            Ok(entries) => entries,
+            Err(error) if error.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => return Err(error.into()),
+        };
+        let mut names = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            names.push(entry.file_name().to_string_lossy().into_owned());
+        }
+        names.sort();
+        Ok(names)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relative_path() {
+        assert_eq!(
+            relative_path("bucket/./a/b.txt").unwrap(),
+            PathBuf::from("bucket/a/b.txt")
+        );
+        assert!(relative_path("bucket/../../etc/passwd").is_err());
+        assert!(relative_path("/etc/passwd").is_err());
+        assert!(relative_path("").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = LocalStorage::new(dir.path().to_owned());
+
+        let key = "files/dir/hello.txt";
+        storage
+            .upload(key, Bytes::from_static(b"hello"), false)
+            .await
+            .unwrap();
+        assert!(
+            storage
+                .upload(key, Bytes::from_static(b"again"), false)
+                .await
+                .is_err()
+        );
+        storage
+            .upload(key, Bytes::from_static(b"world"), true)
+            .await
+            .unwrap();
+
+        let object = storage.download(key).await.unwrap();
+        assert_eq!(object.content.as_ref(), b"world");
+        assert_eq!(object.content_type, "text/plain");
+
+        let metadata = storage.metadata(key).await.unwrap();
+        assert_eq!(metadata.size, Some(5));
+
+        assert_eq!(storage.list("files", "").await.unwrap(), ["dir"]);
+        assert_eq!(storage.list("files", "dir").await.unwrap(), ["hello.txt"]);
+
+        storage.delete(key).await.unwrap();
+        assert!(storage.download(key).await.is_err());
+    }
+}
@@ -1,6 +1,10 @@
 use flow_lib::UserId;
 use std::path::PathBuf;
 
+pub mod backend;
+pub mod local;
+pub mod s3;
+
 pub mod create_signed_url;
 pub mod delete;
 pub mod download;
@@ -10,6 +14,8 @@ pub mod get_public_url;
 pub mod list;
 pub mod upload;
 
+pub use backend::{BackendConfig, Object, ObjectMetadata, StorageBackend};
+
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(untagged)]
 pub enum FileSpec {
@@ -31,3 +37,10 @@ impl FileSpec {
         }
     }
 }
+
+/// Input of commands that operate on an existing file.
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct FileInput {
+    #[serde(flatten)]
+    pub file: FileSpec,
+}
@@ -1,18 +1,14 @@
-use crate::supabase_error;
+use super::{BackendConfig, FileSpec, StorageBackend};
 use bytes::Bytes;
 use flow_lib::command::prelude::*;
 use mime_guess::MimeGuess;
-use reqwest::{
-    StatusCode,
-    header::{AUTHORIZATION, CONTENT_TYPE},
-};
 use std::{borrow::Cow, path::PathBuf};
 
 pub const NAME: &str = "storage_upload";
 
 const DEFINITION: &str = flow_lib::node_definition!("storage/upload.jsonc");
 
-fn build() -> BuildResult {
+fn build(backend: Option<BackendConfig>) -> BuildResult {
     static CACHE: BuilderCache = BuilderCache::new(|| {
         Ok(CmdBuilder::new(DEFINITION)?
             .check_name(NAME)?
@@ -20,10 +16,14 @@ fn build() -> BuildResult {
             .read_capability(ReadCapability::Mutating))
     });
 
-    Ok(CACHE.clone()?.build(run))
+    Ok(CACHE
+        .clone()?
+        .build(move |ctx, input| run(ctx, input, backend.clone())))
 }
 
-flow_lib::submit!(CommandDescription::new(NAME, |_| build()));
+flow_lib::submit!(CommandDescription::new(NAME, |nd: &NodeData| {
+    build(BackendConfig::from_node(&nd.config)?)
+}));
 
 const fn bool_false() -> bool {
     false
@@ -50,14 +50,12 @@ struct Output {
     content_type: String,
 }
 
-#[derive(Deserialize)]
-#[allow(non_snake_case)]
-struct SuccessBody {
-    Key: String,
-}
-
-async fn run(mut ctx: CommandContext, input: Input) -> Result<Output, CommandError> {
-    let auth = ctx.get_jwt_header().await?;
+async fn run(
+    mut ctx: CommandContext,
+    input: Input,
+    backend: Option<BackendConfig>,
+) -> Result<Output, CommandError> {
+    let backend = StorageBackend::new(&ctx, backend)?;
 
     let content_type = input.content_type.unwrap_or_else(|| {
         MimeGuess::from_path(&input.path)
@@ -68,43 +66,23 @@ async fn run(mut ctx: CommandContext, input: Input) -> Result<Output, CommandErr
             })
             .to_owned()
     });
-    let url = {
-        use std::fmt::Write;
-        let mut url = format!(
-            "{}/storage/v1/object/{}",
-            ctx.endpoints().supabase,
-            input.bucket
-        );
-        if ["user-storages", "user-public-storages"].contains(&input.bucket.as_ref()) {
-            write!(&mut url, "/{}", ctx.flow_owner().id).unwrap();
-        }
-        write!(&mut url, "/{}", input.path.display()).unwrap();
-        url
-    };
-
-    let mut req = ctx
-        .http()
-        .post(url)
-        .header(AUTHORIZATION, auth)
-        .header(CONTENT_TYPE, &content_type)
-        .body(input.content);
-
-    if input.overwrite {
-        req = req.header("x-upsert", "true");
-    }
-
-    let resp = req.send().await?;
-
-    match resp.status() {
-        StatusCode::OK => {
-            let resp = resp.json::<SuccessBody>().await?;
-            Ok(Output {
-                key: resp.Key,
-                content_type,
-            })
-        }
-        code => Err(supabase_error(code, resp).await),
+    let key = FileSpec::BucketPath {
+        bucket: input.bucket.into_owned(),
+        path: input.path,
     }
+    .key(&ctx.flow_owner().id);
+
+    let key = backend
+        .upload(
+            &mut ctx,
+            &key,
+            input.content,
+            &content_type,
+            input.overwrite,
+        )
+        .await?;
+
+    Ok(Output { key, content_type })
 }
 
 #[cfg(test)]
@@ -113,6 +91,6 @@ mod tests {
 
     #[test]
     fn test_build() {
-        build().unwrap();
+        build(None).unwrap();
     }
 }
@@ -0,0 +1,10 @@
+use cmds_std as _;
+
+#[tokio::test]
+async fn node_fixtures() {
+    flow_lib::command::builder::fixture::assert_fixtures(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/node-definitions"
+    ))
+    .await;
+}
@@ -0,0 +1,239 @@
+//! Compiled modules and components keyed by the SHA-256 of their bytes.
+//!
+//! Compilation is the expensive part of running a WASM node, so they are
+//! kept in memory and, when `WASM_MODULE_CACHE_DIR` is set, serialized to disk
+//! so they survive restarts.
+
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    fmt::Write as _,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use wasmtime::{Engine, Module, component::Component};
+
+use crate::component::is_component;
+
+pub const DEFAULT_CAPACITY: usize = 64;
+
+pub type ModuleKey = [u8; 32];
+
+pub fn module_key(bytes: &[u8]) -> ModuleKey {
+    Sha256::digest(bytes).into()
+}
+
+fn hex(key: &ModuleKey) -> String {
+    key.iter().fold(String::with_capacity(64), |mut s, b| {
+        write!(s, "{b:02x}").unwrap();
+        s
+    })
+}
+
+#[derive(Clone)]
+pub enum Compiled {
+    Module(Module),
+    Component(Component),
+}
+
+impl Compiled {
+    fn new(engine: &Engine, bytes: &[u8]) -> anyhow::Result<Self> {
+        Ok(if is_component(bytes) {
+            Self::Component(Component::new(engine, bytes)?)
+        } else {
+            Self::Module(Module::new(engine, bytes)?)
+        })
+    }
+
+    fn serialize(&self) -> anyhow::Result<Vec<u8>> {
+        match self {
+            Self::Module(module) => module.serialize(),
+            Self::Component(component) => component.serialize(),
+        }
+    }
+}
+
+struct Entries {
+    tick: u64,
+    modules: HashMap<ModuleKey, (Compiled, u64)>,
+}
+
+pub struct ModuleCache {
+    capacity: usize,
+    dir: Option<PathBuf>,
+    entries: Mutex<Entries>,
+}
+
+impl ModuleCache {
+    pub fn new(capacity: usize, dir: Option<PathBuf>) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            dir,
+            entries: Mutex::new(Entries {
+                tick: 0,
+                modules: HashMap::new(),
+            }),
+        }
+    }
+
+    /// Read `WASM_MODULE_CACHE_DIR`, in-memory only when unset.
+    pub fn from_env() -> Self {
+        let dir = std::env::var_os("WASM_MODULE_CACHE_DIR")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from);
+        Self::new(DEFAULT_CAPACITY, dir)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().modules.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn get(&self, key: &ModuleKey) -> Option<Compiled> {
+        let mut entries = self.entries.lock().unwrap();
+        entries.tick += 1;
+        let tick = entries.tick;
+        let (module, last_used) = entries.modules.get_mut(key)?;
+        *last_used = tick;
+        Some(module.clone())
+    }
+
+    fn insert(&self, key: ModuleKey, module: Compiled) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.tick += 1;
+        let tick = entries.tick;
+        if entries.modules.len() >= self.capacity && !entries.modules.contains_key(&key) {
+            let oldest = entries
+                .modules
+                .iter()
+                .min_by_key(|(_, (_, last_used))| *last_used)
+                .map(|(key, _)| *key);
+            if let Some(oldest) = oldest {
+                entries.modules.remove(&oldest);
+            }
+        }
+        entries.modules.insert(key, (module, tick));
+    }
+
+    fn load(engine: &Engine, path: &Path, component: bool) -> Option<Compiled> {
+        if !path.is_file() {
+            return None;
+        }
+        // SAFETY: files in the cache directory are only written by `store`
+        // below, and wasmtime rejects artifacts built by another engine
+        // configuration or version.
+        let compiled = if component {
+            unsafe { Component::deserialize_file(engine, path) }.map(Compiled::Component)
+        } else {
+            unsafe { Module::deserialize_file(engine, path) }.map(Compiled::Module)
+        };
+        compiled
+            .inspect_err(|error| tracing::warn!("discarding cached module {:?}: {}", path, error))
+            .ok()
+    }
+
+    fn store(module: &Compiled, dir: &Path, path: &Path) -> anyhow::Result<()> {
+        std::fs::create_dir_all(dir)?;
+        let tmp = path.with_extension(format!("tmp{}", std::process::id()));
+        std::fs::write(&tmp, module.serialize()?)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// Return the compiled module or component of `bytes`, compiling it on a
+    /// cache miss. Modules that fail to compile are not cached.
+    ///
+    /// This can block for a long time, call it from a blocking thread.
+    pub fn get_or_compile(&self, engine: &Engine, bytes: &[u8]) -> anyhow::Result<Compiled> {
+        let key = module_key(bytes);
+        if let Some(module) = self.get(&key) {
+            return Ok(module);
+        }
+
+        let path = self
+            .dir
+            .as_ref()
+            .map(|dir| dir.join(format!("{}.cwasm", hex(&key))));
+        let component = is_component(bytes);
+        let module = match path
+            .as_deref()
+            .and_then(|path| Self::load(engine, path, component))
+        {
+            Some(module) => module,
+            None => {
+                let module = Compiled::new(engine, bytes)?;
+                if let (Some(dir), Some(path)) = (&self.dir, &path) {
+                    if let Err(error) = Self::store(&module, dir, path) {
+                        tracing::warn!("could not write module cache {:?}: {}", path, error);
+                    }
+                }
+                module
+            }
+        };
+        self.insert(key, module.clone());
+        Ok(module)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODULE: &str = r#"(module (func (export "f") (result i32) i32.const 1))"#;
+
+    #[test]
+    fn test_memory_cache() {
+        let engine = Engine::default();
+        let cache = ModuleCache::new(1, None);
+        cache.get_or_compile(&engine, MODULE.as_bytes()).unwrap();
+        cache.get_or_compile(&engine, MODULE.as_bytes()).unwrap();
+        assert_eq!(cache.len(), 1);
+
+        assert!(cache.get_or_compile(&engine, b"not wasm").is_err());
+        assert_eq!(cache.len(), 1);
+
+        let other = r#"(module (func (export "g")))"#;
+        cache.get_or_compile(&engine, other.as_bytes()).unwrap();
+        assert_eq!(cache.len(), 1);
+        assert!(cache.get(&module_key(MODULE.as_bytes())).is_none());
+    }
+
+    #[test]
+    fn test_disk_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = Engine::default();
+        let cache = ModuleCache::new(4, Some(dir.path().to_owned()));
+        cache.get_or_compile(&engine, MODULE.as_bytes()).unwrap();
+        let file = dir
+            .path()
+            .join(format!("{}.cwasm", hex(&module_key(MODULE.as_bytes()))));
+        assert!(file.is_file());
+
+        let cache = ModuleCache::new(4, Some(dir.path().to_owned()));
+        assert!(ModuleCache::load(&engine, &file, false).is_some());
+        cache.get_or_compile(&engine, MODULE.as_bytes()).unwrap();
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_component() {
+        const COMPONENT: &str = r#"(component (core module))"#;
+
+        let dir = tempfile::tempdir().unwrap();
+        let engine = Engine::default();
+        let cache = ModuleCache::new(4, Some(dir.path().to_owned()));
+        let compiled = cache.get_or_compile(&engine, COMPONENT.as_bytes()).unwrap();
+        assert!(matches!(compiled, Compiled::Component(_)));
+
+        let file = dir
+            .path()
+            .join(format!("{}.cwasm", hex(&module_key(COMPONENT.as_bytes()))));
+        assert!(matches!(
+            ModuleCache::load(&engine, &file, true),
+            Some(Compiled::Component(_))
+        ));
+    }
+}
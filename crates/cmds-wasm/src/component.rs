@@ -0,0 +1,91 @@
+//! Component-model guests, with WASI preview 2.
+//!
+//! Components implement the `node` world of `wit/node.wit`: `run` receives
+//! the MessagePack input and returns the MessagePack output, or an error
+//! message. The `host` interface has the same functions as the `env` imports
+//! of core modules.
+
+use crate::host::{self, HostState};
+use space_lib::common::{self, KvRead, KvResult, KvWrite, RequestData};
+use wasmtime::component::{Linker, ResourceTable};
+use wasmtime_wasi::{IoView, WasiCtx, WasiView};
+
+wasmtime::component::bindgen!({
+    world: "node",
+    path: "wit",
+    async: true,
+});
+
+use space_operator::node::host::{Host, HttpRequest, Method};
+
+/// Whether `bytes` is a component, in the binary or text format.
+pub fn is_component(bytes: &[u8]) -> bool {
+    match bytes {
+        // magic, version and layer 1
+        [0, b'a', b's', b'm', _, _, 1, 0, ..] => true,
+        [0, b'a', b's', b'm', ..] => false,
+        _ => {
+            std::str::from_utf8(bytes).is_ok_and(|text| text.trim_start().starts_with("(component"))
+        }
+    }
+}
+
+impl IoView for HostState {
+    fn table(&mut self) -> &mut ResourceTable {
+        self.wasi.table()
+    }
+}
+
+impl WasiView for HostState {
+    fn ctx(&mut self) -> &mut WasiCtx {
+        self.wasi.ctx()
+    }
+}
+
+impl Host for HostState {
+    async fn http_call_request(&mut self, request: HttpRequest) -> Result<Vec<u8>, String> {
+        let pairs = |pairs: Vec<(String, String)>| {
+            pairs
+                .into_iter()
+                .flat_map(|(name, value)| [name, value])
+                .collect()
+        };
+        let request = RequestData {
+            url: request.url,
+            headers: pairs(request.headers),
+            queries: pairs(request.queries),
+            method: match request.method {
+                Method::Get => common::Method::GET,
+                Method::Post => common::Method::POST,
+                Method::Delete => common::Method::DELETE,
+                Method::Head => common::Method::HEAD,
+                Method::Patch => common::Method::PATCH,
+                Method::Put => common::Method::PUT,
+            },
+        };
+        host::http_call_request(request, host::http_timeout(&self.ctx), self.max_http_body())
+            .await
+            .map_err(|error| {
+                self.record_limit(&error);
+                error.to_string()
+            })
+    }
+
+    async fn kv_read(&mut self, store: String, key: String) -> KvResult {
+        host::kv_read(&mut self.ctx, KvRead { store, key })
+            .await
+            .unwrap_or_else(|error| Err(error.to_string()))
+    }
+
+    async fn kv_write(&mut self, store: String, key: String, value: Vec<u8>) -> KvResult {
+        host::kv_write(&mut self.ctx, KvWrite { store, key, value })
+            .await
+            .unwrap_or_else(|error| Err(error.to_string()))
+    }
+}
+
+/// Add WASI preview 2 and the `host` interface to `linker`.
+pub fn add_to_linker(linker: &mut Linker<HostState>) -> anyhow::Result<()> {
+    wasmtime_wasi::add_to_linker_async(linker)?;
+    Node::add_to_linker(linker, |state: &mut HostState| state)
+}
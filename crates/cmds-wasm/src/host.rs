@@ -0,0 +1,320 @@
+//! Host functions imported by modules built with `space-lib`.
+//!
+//! Requests and responses are MessagePack. A host function receives a
+//! pointer and length of the request, and returns an offset to
+//! `[u32 len][bytes]` appended at the end of the guest memory, or `0` when
+//! `http_call_request` fails. A response body larger than the guest memory
+//! limit fails the run with [`Limit::Memory`].
+
+use anyhow::{Context as _, anyhow};
+use flow_lib::{
+    command::limits::{Limit, LimitExceeded},
+    context::CommandContext,
+    utils::net::{check_url, guarded_client},
+    value::Value,
+};
+use reqwest::{StatusCode, Url, header::AUTHORIZATION};
+use serde::{Serialize, de::DeserializeOwned};
+use space_lib::common::{KvRead, KvResult, KvWrite, Method, RequestData};
+use std::time::Duration;
+use wasmtime::{
+    AsContextMut, Caller, Linker, Memory, ResourceLimiter, StoreLimits, StoreLimitsBuilder,
+};
+use wasmtime_wasi::preview1::WasiP1Ctx;
+
+pub const MODULE: &str = "env";
+
+const PAGE_SIZE: usize = 64 * 1024;
+
+pub struct HostState {
+    pub wasi: WasiP1Ctx,
+    pub ctx: CommandContext,
+    limits: StoreLimits,
+    memory_bytes: Option<usize>,
+    /// A memory growth was denied by `limits`.
+    pub memory_denied: bool,
+    /// A host function exceeded a limit, the run fails even if the guest
+    /// handles the error.
+    pub limit_exceeded: Option<LimitExceeded>,
+}
+
+impl HostState {
+    pub fn new(wasi: WasiP1Ctx, ctx: CommandContext, memory_bytes: Option<usize>) -> Self {
+        let mut limits = StoreLimitsBuilder::new();
+        if let Some(bytes) = memory_bytes {
+            limits = limits.memory_size(bytes);
+        }
+        Self {
+            wasi,
+            ctx,
+            limits: limits.build(),
+            memory_bytes,
+            memory_denied: false,
+            limit_exceeded: None,
+        }
+    }
+
+    /// Largest HTTP response body read for the guest, it can not be larger
+    /// than the guest memory.
+    pub fn max_http_body(&self) -> usize {
+        self.memory_bytes.unwrap_or(u32::MAX as usize)
+    }
+
+    /// Remember a [`LimitExceeded`] in the chain of `error`.
+    pub fn record_limit(&mut self, error: &anyhow::Error) {
+        if let Some(exceeded) = error.downcast_ref::<LimitExceeded>() {
+            self.limit_exceeded = Some(exceeded.clone());
+        }
+    }
+}
+
+impl ResourceLimiter for HostState {
+    fn memory_growing(
+        &mut self,
+        current: usize,
+        desired: usize,
+        maximum: Option<usize>,
+    ) -> anyhow::Result<bool> {
+        let allowed = self.limits.memory_growing(current, desired, maximum)?;
+        if !allowed {
+            self.memory_denied = true;
+        }
+        Ok(allowed)
+    }
+
+    fn table_growing(
+        &mut self,
+        current: usize,
+        desired: usize,
+        maximum: Option<usize>,
+    ) -> anyhow::Result<bool> {
+        self.limits.table_growing(current, desired, maximum)
+    }
+}
+
+/// Append `[u32 len][bytes]` to `memory`, growing it as needed, and return
+/// the offset.
+pub fn write_bytes(
+    memory: &Memory,
+    mut store: impl AsContextMut,
+    bytes: &[u8],
+) -> anyhow::Result<u32> {
+    let len = u32::try_from(bytes.len()).context("data is too large")?;
+    let offset = memory.data_size(&store);
+    let pages = (4 + bytes.len()).div_ceil(PAGE_SIZE);
+    memory
+        .grow(&mut store, pages as u64)
+        .context("could not grow memory")?;
+    memory.write(&mut store, offset, &len.to_le_bytes())?;
+    memory.write(&mut store, offset + 4, bytes)?;
+    u32::try_from(offset).context("memory offset out of range")
+}
+
+fn memory(caller: &mut Caller<'_, HostState>) -> anyhow::Result<Memory> {
+    caller
+        .get_export("memory")
+        .and_then(|export| export.into_memory())
+        .ok_or_else(|| anyhow!("module does not export memory"))
+}
+
+/// Deserialize a request in place, `ptr` and `len` are checked against the
+/// guest memory before anything is read.
+fn read_request<T: DeserializeOwned>(
+    caller: &mut Caller<'_, HostState>,
+    ptr: u32,
+    len: u32,
+) -> anyhow::Result<T> {
+    let memory = memory(caller)?;
+    let (start, len) = (ptr as usize, len as usize);
+    let bytes = start
+        .checked_add(len)
+        .and_then(|end| memory.data(&*caller).get(start..end))
+        .ok_or_else(|| anyhow!("request out of bounds: {len} bytes at {start}"))?;
+    Ok(rmp_serde::from_slice(bytes)?)
+}
+
+fn write_response<T: Serialize>(
+    caller: &mut Caller<'_, HostState>,
+    value: &T,
+) -> anyhow::Result<u64> {
+    let memory = memory(caller)?;
+    let bytes = rmp_serde::to_vec_named(value)?;
+    Ok(write_bytes(&memory, caller, &bytes)?.into())
+}
+
+/// Timeout of `http_call_request`, from the flow's HTTP client config.
+pub(crate) fn http_timeout(ctx: &CommandContext) -> Duration {
+    Duration::from_secs(ctx.raw().data.flow.set.http.timeout_in_secs.get())
+}
+
+/// Send the request and read the response body, failing with
+/// [`Limit::Memory`] when it is larger than `max_body` bytes.
+pub(crate) async fn http_call_request(
+    request: RequestData,
+    timeout: Duration,
+    max_body: usize,
+) -> anyhow::Result<Vec<u8>> {
+    let url = Url::parse(&request.url)?;
+    check_url(&url).await?;
+    let method = match request.method {
+        Method::GET => reqwest::Method::GET,
+        Method::POST => reqwest::Method::POST,
+        Method::DELETE => reqwest::Method::DELETE,
+        Method::HEAD => reqwest::Method::HEAD,
+        Method::PATCH => reqwest::Method::PATCH,
+        Method::PUT => reqwest::Method::PUT,
+    };
+    // the guarded client can not reach private addresses, also after redirects
+    let mut builder = guarded_client().request(method, url).timeout(timeout);
+    for chunk in request.headers.chunks_exact(2) {
+        builder = builder.header(&chunk[0], &chunk[1]);
+    }
+    let queries = request
+        .queries
+        .chunks_exact(2)
+        .map(|chunk| (&chunk[0], &chunk[1]))
+        .collect::<Vec<_>>();
+    let mut resp = builder.query(&queries).send().await?.error_for_status()?;
+    let too_large = || {
+        LimitExceeded::new(
+            Limit::Memory,
+            format!("HTTP response is larger than {max_body} bytes"),
+        )
+    };
+    if resp
+        .content_length()
+        .is_some_and(|len| len > max_body as u64)
+    {
+        return Err(too_large().into());
+    }
+    let mut body = Vec::new();
+    while let Some(chunk) = resp.chunk().await? {
+        if body.len() + chunk.len() > max_body {
+            return Err(too_large().into());
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
+}
+
+/// Convert a MessagePack value written by the guest to a flow value.
+fn value_from_guest(bytes: &[u8]) -> anyhow::Result<Value> {
+    Ok(rmp_serde::from_slice::<serde_json::Value>(bytes)?.into())
+}
+
+fn value_to_guest(value: Value) -> anyhow::Result<Vec<u8>> {
+    Ok(rmp_serde::to_vec_named(&serde_json::Value::from(value))?)
+}
+
+async fn kv_error(code: StatusCode, resp: reqwest::Response) -> String {
+    let body = resp.text().await.unwrap_or_default();
+    format!("KV request failed ({code}): {body}")
+}
+
+pub(crate) async fn kv_read(ctx: &mut CommandContext, request: KvRead) -> anyhow::Result<KvResult> {
+    #[derive(serde::Deserialize)]
+    struct SuccessBody {
+        value: Value,
+    }
+
+    let resp = ctx
+        .http()
+        .post(format!("{}/kv/read_item", ctx.endpoints().flow_server))
+        .header(AUTHORIZATION, ctx.get_jwt_header().await?)
+        .json(&serde_json::json!({ "store": request.store, "key": request.key }))
+        .send()
+        .await?;
+    Ok(match resp.status() {
+        StatusCode::OK => Ok(Some(value_to_guest(
+            resp.json::<SuccessBody>().await?.value,
+        )?)),
+        StatusCode::NOT_FOUND => Ok(None),
+        code => Err(kv_error(code, resp).await),
+    })
+}
+
+pub(crate) async fn kv_write(
+    ctx: &mut CommandContext,
+    request: KvWrite,
+) -> anyhow::Result<KvResult> {
+    #[derive(serde::Deserialize)]
+    struct SuccessBody {
+        old_value: Option<Value>,
+    }
+
+    let value = value_from_guest(&request.value)?;
+    let resp = ctx
+        .http()
+        .post(format!("{}/kv/write_item", ctx.endpoints().flow_server))
+        .header(AUTHORIZATION, ctx.get_jwt_header().await?)
+        .json(&serde_json::json!({ "store": request.store, "key": request.key, "value": value }))
+        .send()
+        .await?;
+    Ok(match resp.status() {
+        StatusCode::OK => Ok(resp
+            .json::<SuccessBody>()
+            .await?
+            .old_value
+            .map(value_to_guest)
+            .transpose()?),
+        code => Err(kv_error(code, resp).await),
+    })
+}
+
+/// Add WASI preview 1 and the `space-lib` host functions to `linker`.
+pub fn add_to_linker(linker: &mut Linker<HostState>) -> anyhow::Result<()> {
+    wasmtime_wasi::preview1::add_to_linker_async(linker, |state: &mut HostState| &mut state.wasi)?;
+
+    linker.func_wrap_async(
+        MODULE,
+        "http_call_request",
+        |mut caller: Caller<'_, HostState>, (ptr, len): (u32, u32)| {
+            Box::new(async move {
+                let request = read_request::<RequestData>(&mut caller, ptr, len)?;
+                let timeout = http_timeout(&caller.data().ctx);
+                let max_body = caller.data().max_http_body();
+                match http_call_request(request, timeout, max_body).await {
+                    Ok(body) => write_response(&mut caller, &body),
+                    Err(error) if error.is::<LimitExceeded>() => {
+                        caller.data_mut().record_limit(&error);
+                        Err(error)
+                    }
+                    Err(error) => {
+                        tracing::warn!("http_call_request: {}", error);
+                        Ok(0)
+                    }
+                }
+            })
+        },
+    )?;
+
+    linker.func_wrap_async(
+        MODULE,
+        "kv_read",
+        |mut caller: Caller<'_, HostState>, (ptr, len): (u32, u32)| {
+            Box::new(async move {
+                let request = read_request::<KvRead>(&mut caller, ptr, len)?;
+                let result = kv_read(&mut caller.data_mut().ctx, request)
+                    .await
+                    .unwrap_or_else(|error| Err(error.to_string()));
+                write_response(&mut caller, &result)
+            })
+        },
+    )?;
+
+    linker.func_wrap_async(
+        MODULE,
+        "kv_write",
+        |mut caller: Caller<'_, HostState>, (ptr, len): (u32, u32)| {
+            Box::new(async move {
+                let request = read_request::<KvWrite>(&mut caller, ptr, len)?;
+                let result = kv_write(&mut caller.data_mut().ctx, request)
+                    .await
+                    .unwrap_or_else(|error| Err(error.to_string()));
+                write_response(&mut caller, &result)
+            })
+        },
+    )?;
+
+    Ok(())
+}
@@ -0,0 +1,588 @@
+//! WASM nodes, run with wasmtime.
+//!
+//! Core modules are built with `space-lib`: the exported function receives a
+//! pointer to `[u32 len][MessagePack input]` and returns a pointer to a
+//! `SpaceSlice` holding the MessagePack output. Components implement the
+//! world in [`component`]. WASI preview 1 and preview 2 imports are provided
+//! by `wasmtime-wasi`, with the flow environment as environment variables,
+//! and stdout/stderr are forwarded to the node logs.
+//!
+//! CPU is metered with fuel, memory with store limits and the wall clock
+//! with epoch deadlines, all derived from the deployment's [`ScriptLimits`].
+//! WASM runs are never unlimited: limits the deployment leaves unset fall
+//! back to hard defaults, which operators can raise with `WASM_LIMIT_CPU_SECS`,
+//! `WASM_LIMIT_MEMORY_MB` and `WASM_LIMIT_WALL_CLOCK_SECS` but not remove.
+
+use anyhow::anyhow;
+use bytes::Bytes;
+use flow_lib::{
+    CmdInputDescription, CmdOutputDescription, CommandType, Name, ValueSet,
+    command::{
+        CommandDescription, CommandError, CommandTrait, MatchCommand, MatchName,
+        limits::{Limit, LimitExceeded, ScriptLimits},
+        prelude::{Either, Permissions, async_trait},
+    },
+    config::client::NodeData,
+    context::CommandContext,
+    utils::LocalBoxFuture,
+};
+use host::HostState;
+use serde_json::Value as JsonValue;
+use std::{sync::LazyLock, time::Duration};
+use wasmtime::{Config, Engine, Linker, Module, Store, Trap, component::Component};
+use wasmtime_wasi::{WasiCtxBuilder, pipe::MemoryOutputPipe};
+
+pub mod cache;
+pub mod component;
+pub mod host;
+
+/// Fuel is roughly one unit per WASM instruction.
+pub const FUEL_PER_CPU_SEC: u64 = 1_000_000_000;
+/// Yield to the executor after this much fuel, so that timeouts and
+/// cancellation can interrupt long computations.
+const FUEL_YIELD_INTERVAL: u64 = 10_000_000;
+const DEFAULT_LOG_BYTES: u64 = 1024 * 1024;
+const MB: u64 = 1024 * 1024;
+
+const DEFAULT_CPU_SECS: u64 = 60;
+const DEFAULT_MEMORY_MB: u64 = 512;
+const DEFAULT_WALL_CLOCK_SECS: u64 = 300;
+
+/// The engine's epoch is incremented every tick.
+const EPOCH_TICK: Duration = Duration::from_millis(100);
+
+static ENGINE: LazyLock<Engine> = LazyLock::new(|| {
+    let mut config = Config::new();
+    config
+        .async_support(true)
+        .consume_fuel(true)
+        .epoch_interruption(true)
+        .wasm_component_model(true);
+    let engine = Engine::new(&config).expect("valid engine config");
+    std::thread::Builder::new()
+        .name("wasm-epoch".to_owned())
+        .spawn({
+            let engine = engine.clone();
+            move || {
+                loop {
+                    std::thread::sleep(EPOCH_TICK);
+                    engine.increment_epoch();
+                }
+            }
+        })
+        .expect("spawn epoch thread");
+    engine
+});
+
+/// Deployment limits with the hard defaults filled in.
+fn deployment_limits() -> &'static ScriptLimits {
+    static LIMITS: LazyLock<ScriptLimits> =
+        LazyLock::new(|| with_hard_defaults(ScriptLimits::deployment()));
+    &LIMITS
+}
+
+fn with_hard_defaults(limits: &ScriptLimits) -> ScriptLimits {
+    let default = |name: &str, default: u64| {
+        std::env::var(name)
+            .ok()
+            .and_then(|s| s.trim().parse::<u64>().ok())
+            .map_or(default, |value| value.max(default))
+    };
+    ScriptLimits {
+        cpu_secs: limits
+            .cpu_secs
+            .or_else(|| Some(default("WASM_LIMIT_CPU_SECS", DEFAULT_CPU_SECS))),
+        memory_mb: limits
+            .memory_mb
+            .or_else(|| Some(default("WASM_LIMIT_MEMORY_MB", DEFAULT_MEMORY_MB))),
+        wall_clock_secs: limits.wall_clock_secs.or_else(|| {
+            Some(default(
+                "WASM_LIMIT_WALL_CLOCK_SECS",
+                DEFAULT_WALL_CLOCK_SECS,
+            ))
+        }),
+        ..*limits
+    }
+}
+
+static LINKER: LazyLock<Linker<HostState>> = LazyLock::new(|| {
+    let mut linker = Linker::new(&ENGINE);
+    host::add_to_linker(&mut linker).expect("no duplicate imports");
+    linker
+});
+
+static COMPONENT_LINKER: LazyLock<wasmtime::component::Linker<HostState>> = LazyLock::new(|| {
+    let mut linker = wasmtime::component::Linker::new(&ENGINE);
+    component::add_to_linker(&mut linker).expect("no duplicate imports");
+    linker
+});
+
+static MODULES: LazyLock<cache::ModuleCache> = LazyLock::new(cache::ModuleCache::from_env);
+
+async fn compile(bytes: Bytes) -> Result<cache::Compiled, CommandError> {
+    tokio::task::spawn_blocking(move || MODULES.get_or_compile(&ENGINE, &bytes)).await?
+}
+
+pub struct WasmCommand {
+    node_data: NodeData,
+    module: cache::Compiled,
+    function: String,
+    limits: ScriptLimits,
+}
+
+impl WasmCommand {
+    pub async fn new(nd: NodeData) -> Result<Self, CommandError> {
+        let bytes = nd
+            .wasm
+            .as_ref()
+            .and_then(|wasm| wasm.bytes.clone())
+            .ok_or_else(|| CommandError::msg("WASM module bytes not loaded"))?;
+        let function = nd
+            .config
+            .get("function")
+            .and_then(JsonValue::as_str)
+            .unwrap_or("main")
+            .to_owned();
+        let limits = deployment_limits().for_node(&nd.config);
+        Ok(Self {
+            module: compile(bytes).await?,
+            node_data: nd,
+            function,
+            limits,
+        })
+    }
+
+    fn input(params: ValueSet) -> Result<JsonValue, CommandError> {
+        match params.first() {
+            _ if params.len() > 1 => Ok(JsonValue::Object(
+                params
+                    .into_iter()
+                    .map(|(key, value)| (key, value.into()))
+                    .collect(),
+            )),
+            Some((_, input)) => Ok(input.clone().into()),
+            None => Err(CommandError::msg("Expected some input, got none")),
+        }
+    }
+
+    fn output(&self, output: JsonValue) -> Result<ValueSet, CommandError> {
+        let output = match output {
+            JsonValue::Object(mut object) => {
+                if let Some(error) = object.remove("Err") {
+                    let message = match error.get("description") {
+                        Some(JsonValue::String(s)) => s.clone(),
+                        Some(description) => description.to_string(),
+                        None => error.to_string(),
+                    };
+                    return Err(CommandError::msg(message));
+                }
+                match object.remove("Ok") {
+                    Some(JsonValue::Object(ok)) => ok,
+                    Some(value) => return self.single_output(value),
+                    None => object,
+                }
+            }
+            output => return self.single_output(output),
+        };
+        Ok(output
+            .into_iter()
+            .map(|(key, value)| (key, value.into()))
+            .collect())
+    }
+
+    fn single_output(&self, value: JsonValue) -> Result<ValueSet, CommandError> {
+        let name = self
+            .node_data
+            .outputs
+            .first()
+            .ok_or_else(|| CommandError::msg("Expected 1 output, got 0"))?
+            .name
+            .clone();
+        Ok(ValueSet::from([(name, value.into())]))
+    }
+
+    async fn call_module(
+        &self,
+        store: &mut Store<HostState>,
+        module: &Module,
+        input: &JsonValue,
+    ) -> anyhow::Result<JsonValue> {
+        let instance = LINKER.instantiate_async(&mut *store, module).await?;
+        // WASI reactors must be initialized before calling their exports
+        if let Ok(init) = instance.get_typed_func::<(), ()>(&mut *store, "_initialize") {
+            init.call_async(&mut *store, ()).await?;
+        }
+        let memory = instance
+            .get_memory(&mut *store, "memory")
+            .ok_or_else(|| anyhow!("module does not export memory"))?;
+        let ptr = host::write_bytes(&memory, &mut *store, &rmp_serde::to_vec(input)?)?;
+        let func = instance.get_typed_func::<u32, u32>(&mut *store, &self.function)?;
+        let slice = func.call_async(&mut *store, ptr).await?;
+
+        let mut header = [0; 8];
+        memory.read(&*store, slice as usize, &mut header)?;
+        let [l0, l1, l2, l3, p0, p1, p2, p3] = header;
+        let len = u32::from_le_bytes([l0, l1, l2, l3]) as usize;
+        let data = u32::from_le_bytes([p0, p1, p2, p3]) as usize;
+        if data.saturating_add(len) > memory.data_size(&*store) {
+            return Err(anyhow!("output is out of bounds"));
+        }
+        let mut bytes = vec![0; len];
+        memory.read(&*store, data, &mut bytes)?;
+        Ok(rmp_serde::from_slice(&bytes)?)
+    }
+
+    async fn call_component(
+        store: &mut Store<HostState>,
+        component: &Component,
+        input: &JsonValue,
+    ) -> anyhow::Result<JsonValue> {
+        let node =
+            component::Node::instantiate_async(&mut *store, component, &COMPONENT_LINKER).await?;
+        let output = node
+            .call_run(&mut *store, &rmp_serde::to_vec(input)?)
+            .await?
+            .map_err(|error| anyhow!(error))?;
+        Ok(rmp_serde::from_slice(&output)?)
+    }
+
+    async fn call(
+        &self,
+        ctx: CommandContext,
+        input: &JsonValue,
+    ) -> Result<JsonValue, CommandError> {
+        // one more byte than allowed, to tell a full pipe from an exceeded limit
+        let log_capacity = self.limits.log_bytes.unwrap_or(DEFAULT_LOG_BYTES) as usize + 1;
+        let stdout = MemoryOutputPipe::new(log_capacity);
+        let stderr = MemoryOutputPipe::new(log_capacity);
+        let env = ctx.environment().iter().collect::<Vec<_>>();
+        let wasi = WasiCtxBuilder::new()
+            .stdout(stdout.clone())
+            .stderr(stderr.clone())
+            .envs(&env)
+            .build_p1();
+
+        let memory_bytes = self.limits.memory_mb.map(|mb| (mb * MB) as usize);
+        let mut store = Store::new(&ENGINE, HostState::new(wasi, ctx, memory_bytes));
+        store.limiter(|state| state);
+        let fuel = self
+            .limits
+            .cpu_secs
+            .map_or(u64::MAX, |secs| secs.saturating_mul(FUEL_PER_CPU_SEC));
+        store.set_fuel(fuel)?;
+        store.fuel_async_yield_interval(Some(FUEL_YIELD_INTERVAL))?;
+        let wall_clock = self
+            .limits
+            .wall_clock()
+            .unwrap_or(Duration::from_secs(DEFAULT_WALL_CLOCK_SECS));
+        store.set_epoch_deadline(wall_clock.as_millis().div_ceil(EPOCH_TICK.as_millis()) as u64);
+        store.epoch_deadline_trap();
+
+        let result = match &self.module {
+            cache::Compiled::Module(module) => self.call_module(&mut store, module, input).await,
+            cache::Compiled::Component(component) => {
+                Self::call_component(&mut store, component, input).await
+            }
+        };
+
+        let (stdout, stderr) = (stdout.contents(), stderr.contents());
+        for line in String::from_utf8_lossy(&stdout).lines() {
+            tracing::info!("{}", line);
+        }
+        for line in String::from_utf8_lossy(&stderr).lines() {
+            tracing::warn!("{}", line);
+        }
+        let logged = (stdout.len() + stderr.len()) as u64;
+
+        if let Some(exceeded) = store.data_mut().limit_exceeded.take() {
+            return Err(exceeded.into());
+        }
+        match result {
+            Err(error) if matches!(error.downcast_ref::<Trap>(), Some(Trap::OutOfFuel)) => {
+                Err(LimitExceeded::new(Limit::Cpu, format!("used more than {fuel} fuel")).into())
+            }
+            Err(error) if matches!(error.downcast_ref::<Trap>(), Some(Trap::Interrupt)) => {
+                Err(LimitExceeded::new(
+                    Limit::WallClock,
+                    format!("run took longer than {}s", wall_clock.as_secs()),
+                )
+                .into())
+            }
+            Err(_) if store.data().memory_denied => Err(LimitExceeded::new(
+                Limit::Memory,
+                format!(
+                    "memory is limited to {} MB",
+                    self.limits.memory_mb.unwrap_or(0)
+                ),
+            )
+            .into()),
+            _ if self.limits.log_bytes.is_some_and(|max| logged > max) => Err(LimitExceeded::new(
+                Limit::Log,
+                format!(
+                    "logged more than {} bytes",
+                    self.limits.log_bytes.unwrap_or(0)
+                ),
+            )
+            .into()),
+            result => result,
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl CommandTrait for WasmCommand {
+    fn r#type(&self) -> CommandType {
+        CommandType::Wasm
+    }
+
+    fn name(&self) -> Name {
+        self.node_data.node_id.clone()
+    }
+
+    fn inputs(&self) -> Vec<CmdInputDescription> {
+        self.node_data.cmd_inputs()
+    }
+
+    fn outputs(&self) -> Vec<CmdOutputDescription> {
+        self.node_data.cmd_outputs()
+    }
+
+    /// Needed by the KV host functions.
+    fn permissions(&self) -> Permissions {
+        Permissions { user_tokens: true }
+    }
+
+    async fn run(&self, ctx: CommandContext, params: ValueSet) -> Result<ValueSet, CommandError> {
+        let input = Self::input(params)?;
+        let output = match self.limits.wall_clock() {
+            Some(limit) => tokio::time::timeout(limit, self.call(ctx, &input))
+                .await
+                .map_err(|_| {
+                    LimitExceeded::new(
+                        Limit::WallClock,
+                        format!("run took longer than {}s", limit.as_secs()),
+                    )
+                })??,
+            None => self.call(ctx, &input).await?,
+        };
+        let output = self.output(output)?;
+        self.limits.check_output(&output)?;
+        Ok(output)
+    }
+}
+
+pub fn new(nd: &NodeData) -> LocalBoxFuture<'static, Result<Box<dyn CommandTrait>, CommandError>> {
+    let nd = nd.clone();
+    Box::pin(async move { Ok(Box::new(WasmCommand::new(nd).await?) as Box<dyn CommandTrait>) })
+}
+
+flow_lib::submit!(CommandDescription {
+    matcher: MatchCommand {
+        r#type: CommandType::Wasm,
+        name: MatchName::Regex(std::borrow::Cow::Borrowed("")),
+    },
+    fn_new: Either::Right(new),
+});
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flow_lib::{config::client::WasmNode, value::Value};
+
+    // echo the input back: the returned slice points into the input buffer
+    const ECHO: &str = r#"(module
+        (memory (export "memory") 1)
+        (func (export "main") (param $ptr i32) (result i32)
+            (i32.store (i32.const 0) (i32.load (local.get $ptr)))
+            (i32.store (i32.const 4) (i32.add (local.get $ptr) (i32.const 4)))
+            (i32.const 0)))"#;
+
+    const SPIN: &str = r#"(module
+        (memory (export "memory") 1)
+        (func (export "main") (param i32) (result i32)
+            (loop (br 0))
+            (i32.const 0)))"#;
+
+    // echo the input back: the result points to the lowered input
+    const ECHO_COMPONENT: &str = r#"(component
+        (core module $m
+            (memory (export "memory") 1)
+            (global $next (mut i32) (i32.const 1024))
+            (func (export "realloc") (param i32 i32 i32 i32) (result i32)
+                (local $ptr i32)
+                (local.set $ptr (global.get $next))
+                (global.set $next (i32.add (global.get $next) (local.get 3)))
+                (local.get $ptr))
+            (func (export "run") (param $ptr i32) (param $len i32) (result i32)
+                (i32.store8 (i32.const 0) (i32.const 0))
+                (i32.store (i32.const 4) (local.get $ptr))
+                (i32.store (i32.const 8) (local.get $len))
+                (i32.const 0)))
+        (core instance $i (instantiate $m))
+        (alias core export $i "memory" (core memory $memory))
+        (alias core export $i "realloc" (core func $realloc))
+        (alias core export $i "run" (core func $run))
+        (func (export "run") (param "input" (list u8)) (result (result (list u8) (error string)))
+            (canon lift (core func $run) (memory $memory) (realloc $realloc))))"#;
+
+    const GROW: &str = r#"(module
+        (memory (export "memory") 1)
+        (func (export "main") (param i32) (result i32)
+            (drop (memory.grow (i32.const 1024)))
+            (unreachable)))"#;
+
+    // ask the host to read far more than the guest memory
+    const OVERSIZED_REQUEST: &str = r#"(module
+        (import "env" "kv_read" (func $kv_read (param i32 i32) (result i64)))
+        (memory (export "memory") 1)
+        (func (export "main") (param i32) (result i32)
+            (drop (call $kv_read (i32.const 16) (i32.const -1)))
+            (i32.const 0)))"#;
+
+    fn node_data(wat: &str, limits: JsonValue) -> NodeData {
+        let mut nd: NodeData = serde_json::from_value(serde_json::json!({
+            "type": "WASM",
+            "node_id": "wasm_test",
+            "inputs": [
+                { "id": "00000000-0000-0000-0000-000000000001", "name": "a" },
+                { "id": "00000000-0000-0000-0000-000000000002", "name": "b" },
+            ],
+            "outputs": [
+                { "id": "00000000-0000-0000-0000-000000000003", "name": "a" },
+                { "id": "00000000-0000-0000-0000-000000000004", "name": "b" },
+            ],
+            "config": { "limits": limits },
+        }))
+        .unwrap();
+        nd.wasm = Some(WasmNode {
+            supabase_id: 0,
+            bytes: Some(Bytes::copy_from_slice(wat.as_bytes())),
+        });
+        nd
+    }
+
+    fn params() -> ValueSet {
+        ValueSet::from([
+            ("a".to_owned(), Value::String("hello".to_owned())),
+            ("b".to_owned(), Value::I64(-1)),
+        ])
+    }
+
+    #[tokio::test]
+    async fn test_echo() {
+        let cmd = WasmCommand::new(node_data(ECHO, serde_json::json!({})))
+            .await
+            .unwrap();
+        let output = cmd
+            .run(CommandContext::test_context(), params())
+            .await
+            .unwrap();
+        assert_eq!(output["a"], Value::String("hello".to_owned()));
+        assert_eq!(output["b"], Value::I64(-1));
+    }
+
+    #[tokio::test]
+    async fn test_component() {
+        let cmd = WasmCommand::new(node_data(ECHO_COMPONENT, serde_json::json!({})))
+            .await
+            .unwrap();
+        assert!(matches!(cmd.module, cache::Compiled::Component(_)));
+        let output = cmd
+            .run(CommandContext::test_context(), params())
+            .await
+            .unwrap();
+        assert_eq!(output["a"], Value::String("hello".to_owned()));
+        assert_eq!(output["b"], Value::I64(-1));
+    }
+
+    #[tokio::test]
+    async fn test_http_private_address() {
+        for url in [
+            "http://127.0.0.1:8080/",
+            "http://[::1]/",
+            "http://localhost/",
+        ] {
+            let request = space_lib::common::RequestData {
+                url: url.to_owned(),
+                headers: Vec::new(),
+                queries: Vec::new(),
+                method: space_lib::common::Method::GET,
+            };
+            let error =
+                host::http_call_request(request, std::time::Duration::from_secs(1), MB as usize)
+                    .await
+                    .unwrap_err();
+            assert!(error.to_string().contains("not allowed"), "{url}: {error}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fuel() {
+        let cmd = WasmCommand::new(node_data(SPIN, serde_json::json!({ "cpu_secs": 0 })))
+            .await
+            .unwrap();
+        let error = cmd
+            .run(CommandContext::test_context(), params())
+            .await
+            .unwrap_err();
+        let error = error.downcast_ref::<LimitExceeded>().unwrap();
+        assert_eq!(error.limit, Limit::Cpu);
+    }
+
+    #[tokio::test]
+    async fn test_oversized_request() {
+        let cmd = WasmCommand::new(node_data(OVERSIZED_REQUEST, serde_json::json!({})))
+            .await
+            .unwrap();
+        let error = cmd
+            .run(CommandContext::test_context(), params())
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("out of bounds"), "{error:#}");
+    }
+
+    #[test]
+    fn test_hard_defaults() {
+        let limits = with_hard_defaults(&ScriptLimits::default());
+        assert_eq!(limits.cpu_secs, Some(DEFAULT_CPU_SECS));
+        assert_eq!(limits.memory_mb, Some(DEFAULT_MEMORY_MB));
+        assert_eq!(limits.wall_clock_secs, Some(DEFAULT_WALL_CLOCK_SECS));
+
+        let deployment = ScriptLimits {
+            cpu_secs: Some(1000),
+            ..Default::default()
+        };
+        assert_eq!(with_hard_defaults(&deployment).cpu_secs, Some(1000));
+        // a node can only lower them
+        let node = with_hard_defaults(&ScriptLimits::default())
+            .for_node(&serde_json::json!({ "limits": { "cpu_secs": 100000 } }));
+        assert_eq!(node.cpu_secs, Some(DEFAULT_CPU_SECS));
+    }
+
+    #[tokio::test]
+    async fn test_epoch_deadline() {
+        let cmd = WasmCommand::new(node_data(SPIN, serde_json::json!({ "wall_clock_secs": 1 })))
+            .await
+            .unwrap();
+        // without the timeout of `run`, only the epoch deadline stops it
+        let error = cmd
+            .call(CommandContext::test_context(), &serde_json::json!({}))
+            .await
+            .unwrap_err();
+        let error = error.downcast_ref::<LimitExceeded>().unwrap();
+        assert_eq!(error.limit, Limit::WallClock);
+    }
+
+    #[tokio::test]
+    async fn test_memory() {
+        let cmd = WasmCommand::new(node_data(GROW, serde_json::json!({ "memory_mb": 1 })))
+            .await
+            .unwrap();
+        let error = cmd
+            .run(CommandContext::test_context(), params())
+            .await
+            .unwrap_err();
+        let error = error.downcast_ref::<LimitExceeded>().unwrap();
+        assert_eq!(error.limit, Limit::Memory);
+    }
+}
@@ -325,69 +325,21 @@ impl AdminConn {
         key: &str,
         value: &Value,
     ) -> crate::Result<Option<Value>> {
-        let json = serde_json::value::to_raw_value(value).map_err(Error::json("json serialize"))?;
-        let mut conn = self.pool.get_conn().await?;
-        let tx = conn
-            .transaction()
-            .await
-            .map_err(Error::exec("insert_item start"))?;
-
-        let stmt = "SELECT LENGTH(value::TEXT) + LENGTH(key), value FROM kvstore
-                    WHERE user_id = $1
-                        AND store_name = $2
-                        AND key = $3";
-        let (old_size, old_value) = tx
-            .do_query_opt(stmt, &[user_id, &store_name, &key])
-            .await
-            .map_err(Error::exec("get existing value"))?
-            .map(|row| {
-                (
-                    row.try_get::<_, i32>(0),
-                    row.try_get::<_, Json<Value>>(1).map(|v| Some(v.0)),
-                )
-            })
-            .map(|(r1, r2)| r1.and_then(|r1| Ok((r1, r2?))))
-            .transpose()
-            .map_err(Error::data("parse value"))?
-            .unwrap_or((0, None));
-
-        let stmt = "INSERT INTO kvstore (user_id, store_name, key, value)
-                    VALUES ($1, $2, $3, $4)
-                    ON CONFLICT (user_id, store_name, key)
-                    DO UPDATE SET value = $4
-                    RETURNING LENGTH(value::text) + LENGTH(key)";
-        let new_size: i32 = tx
-            .do_query_one(stmt, &[user_id, &store_name, &key, &Json(&json)])
-            .await
-            .map_err(Error::exec("update kvstore"))?
-            .try_get::<_, i32>(0)
-            .map_err(Error::data("INTEGER"))?;
-
-        let changed = (new_size - old_size) as i64;
-
-        let stmt = "UPDATE user_quotas
-                    SET kvstore_size = kvstore_size + $2
-                    WHERE
-                        user_id = $1
-                        AND kvstore_size + $2 < kvstore_size_limit
-                    RETURNING 0";
-        tx.do_query_one(stmt, &[user_id, &changed])
-            .await
-            .map_err(Error::exec("update user_quotas"))?;
-
-        let stmt = "UPDATE kvstore_metadata
-                    SET stats_size = stats_size + $3
-                    WHERE
-                        user_id = $1 AND store_name = $2
-                    RETURNING 0";
-        tx.do_query_one(stmt, &[user_id, &store_name, &changed])
-            .await
-            .map_err(Error::exec("update kvstore_metadata"))?;
-
-        tx.commit()
-            .await
-            .map_err(Error::exec("insert_item commit"))?;
-        Ok(old_value)
+        let op = super::ItemOp::Write {
+            key: key.to_owned(),
+            value: value.clone(),
+            expected_version: None,
+            ttl_seconds: None,
+        };
+        match self.write_items(user_id, store_name, &[op]).await? {
+            super::BatchOutcome::Committed { mut results } => Ok(results
+                .pop()
+                .ok_or_else(|| Error::LogicError(anyhow!("empty batch result")))?
+                .old_value),
+            super::BatchOutcome::ConditionFailed { .. } => {
+                Err(Error::LogicError(anyhow!("unconditional write failed")))
+            }
+        }
     }
 
     pub async fn remove_item(
@@ -402,21 +354,30 @@ impl AdminConn {
             .await
             .map_err(Error::exec("remove_item start"))?;
 
+        super::kvstore::lock_items(&tx, user_id, store_name, [key]).await?;
+
         let stmt = "DELETE FROM kvstore
                 WHERE user_id = $1
                     AND store_name = $2
                     AND key = $3
-                RETURNING LENGTH(value::TEXT) + LENGTH(key), value";
-        let (old_size, old_value) = tx
-            .do_query_one(stmt, &[user_id, &store_name, &key])
+                RETURNING
+                    LENGTH(value::TEXT) + LENGTH(key),
+                    value,
+                    expires_at IS NOT NULL AND expires_at <= NOW()";
+        let (old_size, old_value, expired) = tx
+            .do_query_opt(stmt, &[user_id, &store_name, &key])
             .await
-            .and_then(|row| {
-                Ok((
+            .map_err(Error::exec("remove_item"))?
+            .map(|row| {
+                Ok::<_, tokio_postgres::Error>((
                     row.try_get::<_, i32>(0)?,
                     row.try_get::<_, Json<Value>>(1).map(|v| v.0)?,
+                    row.try_get::<_, bool>(2)?,
                 ))
             })
-            .map_err(Error::exec("remove_item"))?;
+            .transpose()
+            .map_err(Error::data("kvstore"))?
+            .ok_or_else(|| Error::not_found("item", key))?;
 
         let old_size = old_size as i64;
 
@@ -441,6 +402,10 @@ impl AdminConn {
         tx.commit()
             .await
             .map_err(Error::exec("remove_item commit"))?;
+        if expired {
+            // the row is purged all the same, but an expired item does not exist
+            return Err(Error::not_found("item", key));
+        }
         Ok(old_value)
     }
 
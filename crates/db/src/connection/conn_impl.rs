@@ -492,8 +492,12 @@ impl UserConnectionTrait for UserConnection {
         self.save_signature_impl(id, signature, new_message).await
     }
 
-    async fn read_item(&self, store: &str, key: &str) -> crate::Result<Option<Value>> {
-        self.read_item(store, key).await
+    async fn read_item(&self, store: &str, key: &str) -> crate::Result<Option<KvItem>> {
+        self.read_item_impl(store, key).await
+    }
+
+    async fn list_items(&self, store: &str, range: &KeyRange) -> crate::Result<Vec<KvItem>> {
+        self.list_items_impl(store, range).await
     }
 
     async fn export_user_data(&mut self) -> crate::Result<ExportedUserData> {
@@ -536,26 +540,6 @@ impl UserConnection {
         result
     }
 
-    async fn read_item(&self, store: &str, key: &str) -> crate::Result<Option<Value>> {
-        let conn = self.pool.get_conn().await?;
-        let opt = conn
-            .do_query_opt(
-                "SELECT value FROM kvstore
-                WHERE user_id = $1 AND store_name = $2 AND key = $3",
-                &[&self.user_id, &store, &key],
-            )
-            .await
-            .map_err(Error::exec("read item kvstore"))?;
-        match opt {
-            Some(row) => Ok(Some(
-                row.try_get::<_, Json<Value>>(0)
-                    .map_err(Error::data("kvstore.value"))?
-                    .0,
-            )),
-            None => Ok(None),
-        }
-    }
-
     async fn export_user_data(&mut self) -> crate::Result<ExportedUserData> {
         let mut conn = self.pool.get_conn().await?;
         let tx = conn.transaction().await.map_err(Error::exec("start"))?;
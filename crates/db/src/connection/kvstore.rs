@@ -0,0 +1,537 @@
+use super::{AdminConn, DbClient, UserConnection};
+use crate::Error;
+use anyhow::anyhow;
+use chrono::{DateTime, Utc};
+use deadpool_postgres::Transaction;
+use flow_lib::UserId;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use tokio_postgres::types::Json;
+use value::Value;
+
+/// An item in a KV store.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct KvItem {
+    pub key: String,
+    pub value: Value,
+    /// Starts at 1 and is incremented on every write.
+    pub version: i64,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Operation in a [`AdminConn::write_items`] batch.
+///
+/// `expected_version` makes the operation conditional: `0` means the key must not exist,
+/// any other value must match the current version of the item.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum ItemOp {
+    Write {
+        key: String,
+        value: Value,
+        #[serde(default)]
+        expected_version: Option<i64>,
+        #[serde(default)]
+        ttl_seconds: Option<i64>,
+    },
+    Increment {
+        key: String,
+        #[serde(default = "ItemOp::default_increment")]
+        by: i64,
+        #[serde(default)]
+        ttl_seconds: Option<i64>,
+    },
+    Delete {
+        key: String,
+        #[serde(default)]
+        expected_version: Option<i64>,
+    },
+}
+
+impl ItemOp {
+    fn default_increment() -> i64 {
+        1
+    }
+
+    pub fn key(&self) -> &str {
+        match self {
+            ItemOp::Write { key, .. }
+            | ItemOp::Increment { key, .. }
+            | ItemOp::Delete { key, .. } => key,
+        }
+    }
+
+    fn expected_version(&self) -> Option<i64> {
+        match self {
+            ItemOp::Write {
+                expected_version, ..
+            }
+            | ItemOp::Delete {
+                expected_version, ..
+            } => *expected_version,
+            ItemOp::Increment { .. } => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ItemOpResult {
+    pub key: String,
+    pub old_value: Option<Value>,
+    pub new_value: Option<Value>,
+    /// Version after the operation, `0` if the item was deleted.
+    pub version: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum BatchOutcome {
+    /// All operations were applied.
+    Committed { results: Vec<ItemOpResult> },
+    /// A condition did not hold, nothing was applied.
+    ConditionFailed {
+        index: usize,
+        key: String,
+        current_version: i64,
+    },
+}
+
+/// Key range for [`UserConnection::list_items`].
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct KeyRange {
+    pub prefix: Option<String>,
+    /// Inclusive lower bound.
+    pub start: Option<String>,
+    /// Exclusive upper bound.
+    pub end: Option<String>,
+    pub limit: Option<i64>,
+}
+
+impl KeyRange {
+    pub const MAX_LIMIT: i64 = 1000;
+}
+
+fn increment(key: &str, old: Option<&Value>, by: i64) -> crate::Result<Value> {
+    let overflow = || Error::LogicError(anyhow!("increment of key {:?} overflows", key));
+    Ok(match old {
+        None => Value::I64(by),
+        Some(Value::I64(n)) => Value::I64(n.checked_add(by).ok_or_else(overflow)?),
+        Some(Value::U64(n)) => {
+            let sum = *n as i128 + by as i128;
+            match u64::try_from(sum) {
+                Ok(sum) => Value::U64(sum),
+                Err(_) => Value::I64(i64::try_from(sum).map_err(|_| overflow())?),
+            }
+        }
+        Some(Value::I128(n)) => Value::I128(n.checked_add(by as i128).ok_or_else(overflow)?),
+        Some(Value::U128(n)) => Value::U128(
+            if by >= 0 {
+                n.checked_add(by as u128)
+            } else {
+                n.checked_sub(by.unsigned_abs() as u128)
+            }
+            .ok_or_else(overflow)?,
+        ),
+        Some(Value::F64(f)) => Value::F64(f + by as f64),
+        Some(Value::Decimal(d)) => {
+            Value::Decimal(d.checked_add(Decimal::from(by)).ok_or_else(overflow)?)
+        }
+        Some(_) => {
+            return Err(Error::LogicError(anyhow!(
+                "value of key {:?} is not a number",
+                key
+            )));
+        }
+    })
+}
+
+struct CurrentItem {
+    size: i64,
+    /// `None` if the item has expired.
+    value: Option<Value>,
+    version: i64,
+}
+
+/// Take the advisory locks of `keys` and then the store row, so that all writers
+/// of a store lock in the same order: keys are sorted and deduplicated, making
+/// batches that touch the same keys in a different order unable to deadlock.
+/// The store row lock also serializes the size accounting in [`update_size`].
+pub(crate) async fn lock_items<'a>(
+    tx: &Transaction<'_>,
+    user_id: &UserId,
+    store_name: &str,
+    keys: impl IntoIterator<Item = &'a str>,
+) -> crate::Result<()> {
+    // also serializes writers of keys that do not exist yet
+    for key in keys.into_iter().collect::<BTreeSet<_>>() {
+        let lock = format!("{user_id}/{store_name}/{key}");
+        tx.do_execute(
+            "SELECT pg_advisory_xact_lock(hashtextextended($1, 0))",
+            &[&lock],
+        )
+        .await
+        .map_err(Error::exec("lock kvstore item"))?;
+    }
+
+    let stmt = "SELECT 0 FROM kvstore_metadata
+                WHERE user_id = $1 AND store_name = $2
+                FOR UPDATE";
+    tx.do_query_opt(stmt, &[user_id, &store_name])
+        .await
+        .map_err(Error::exec("lock kvstore_metadata"))?
+        .ok_or_else(|| Error::not_found("store", store_name))?;
+    Ok(())
+}
+
+/// Read an item, its lock must be held.
+async fn current_item(
+    tx: &Transaction<'_>,
+    user_id: &UserId,
+    store_name: &str,
+    key: &str,
+) -> crate::Result<Option<CurrentItem>> {
+    let stmt = "SELECT
+                    LENGTH(value::TEXT) + LENGTH(key),
+                    value,
+                    version,
+                    expires_at IS NOT NULL AND expires_at <= NOW()
+                FROM kvstore
+                WHERE user_id = $1
+                    AND store_name = $2
+                    AND key = $3";
+    let Some(row) = tx
+        .do_query_opt(stmt, &[user_id, &store_name, &key])
+        .await
+        .map_err(Error::exec("get existing value"))?
+    else {
+        return Ok(None);
+    };
+    let size = row.try_get::<_, i32>(0).map_err(Error::data("size"))? as i64;
+    let value = row
+        .try_get::<_, Json<Value>>(1)
+        .map_err(Error::data("kvstore.value"))?
+        .0;
+    let version = row
+        .try_get::<_, i64>(2)
+        .map_err(Error::data("kvstore.version"))?;
+    let expired = row
+        .try_get::<_, bool>(3)
+        .map_err(Error::data("kvstore.expires_at"))?;
+    Ok(Some(if expired {
+        CurrentItem {
+            size,
+            value: None,
+            version: 0,
+        }
+    } else {
+        CurrentItem {
+            size,
+            value: Some(value),
+            version,
+        }
+    }))
+}
+
+async fn update_size(
+    tx: &Transaction<'_>,
+    user_id: &UserId,
+    store_name: &str,
+    changed: i64,
+) -> crate::Result<()> {
+    let stmt = if changed > 0 {
+        "UPDATE user_quotas
+        SET kvstore_size = kvstore_size + $2
+        WHERE
+            user_id = $1
+            AND kvstore_size + $2 < kvstore_size_limit
+        RETURNING 0"
+    } else {
+        "UPDATE user_quotas
+        SET kvstore_size = kvstore_size + $2
+        WHERE
+            user_id = $1
+        RETURNING 0"
+    };
+    tx.do_query_one(stmt, &[user_id, &changed])
+        .await
+        .map_err(Error::exec("update user_quotas"))?;
+
+    let stmt = "UPDATE kvstore_metadata
+                SET stats_size = stats_size + $3
+                WHERE
+                    user_id = $1 AND store_name = $2
+                RETURNING 0";
+    tx.do_query_one(stmt, &[user_id, &store_name, &changed])
+        .await
+        .map_err(Error::exec("update kvstore_metadata"))?;
+    Ok(())
+}
+
+/// Delete the expired items of a store and return their total size.
+///
+/// Every writer holds the store row lock, so it is enough to hold it here.
+async fn purge_expired(
+    tx: &Transaction<'_>,
+    user_id: &UserId,
+    store_name: &str,
+) -> crate::Result<i64> {
+    let stmt = "WITH deleted AS (
+                    DELETE FROM kvstore
+                    WHERE user_id = $1
+                        AND store_name = $2
+                        AND expires_at <= NOW()
+                    RETURNING LENGTH(value::TEXT) + LENGTH(key) AS size
+                )
+                SELECT COALESCE(SUM(size), 0)::BIGINT FROM deleted";
+    tx.do_query_one(stmt, &[user_id, &store_name])
+        .await
+        .map_err(Error::exec("purge expired items"))?
+        .try_get::<_, i64>(0)
+        .map_err(Error::data("size"))
+}
+
+impl AdminConn {
+    /// Apply `ops` atomically: either all operations are applied or none are.
+    pub async fn write_items(
+        &mut self,
+        user_id: &UserId,
+        store_name: &str,
+        ops: &[ItemOp],
+    ) -> crate::Result<BatchOutcome> {
+        for op in ops {
+            if let ItemOp::Write {
+                ttl_seconds: Some(ttl),
+                ..
+            }
+            | ItemOp::Increment {
+                ttl_seconds: Some(ttl),
+                ..
+            } = op
+                && *ttl <= 0
+            {
+                return Err(Error::LogicError(anyhow!(
+                    "ttl_seconds of key {:?} must be positive",
+                    op.key()
+                )));
+            }
+        }
+
+        let mut conn = self.pool.get_conn().await?;
+        let tx = conn
+            .transaction()
+            .await
+            .map_err(Error::exec("write_items start"))?;
+
+        lock_items(&tx, user_id, store_name, ops.iter().map(ItemOp::key)).await?;
+
+        let mut results = Vec::with_capacity(ops.len());
+        let mut changed = 0i64;
+        for (index, op) in ops.iter().enumerate() {
+            let key = op.key();
+            let current = current_item(&tx, user_id, store_name, key).await?;
+            let (old_size, old_value, current_version) = match current {
+                Some(item) => (item.size, item.value, item.version),
+                None => (0, None, 0),
+            };
+
+            if let Some(expected) = op.expected_version()
+                && expected != current_version
+            {
+                // dropping `tx` rolls back
+                return Ok(BatchOutcome::ConditionFailed {
+                    index,
+                    key: key.to_owned(),
+                    current_version,
+                });
+            }
+
+            let (new_value, ttl_seconds) = match op {
+                ItemOp::Write {
+                    value, ttl_seconds, ..
+                } => (Some(value.clone()), *ttl_seconds),
+                ItemOp::Increment {
+                    by, ttl_seconds, ..
+                } => (Some(increment(key, old_value.as_ref(), *by)?), *ttl_seconds),
+                ItemOp::Delete { .. } => (None, None),
+            };
+
+            let (new_size, version) = match &new_value {
+                Some(value) => {
+                    let json = serde_json::value::to_raw_value(value)
+                        .map_err(Error::json("json serialize"))?;
+                    let version = current_version + 1;
+                    let stmt = "INSERT INTO kvstore
+                                    (user_id, store_name, key, value, version, expires_at)
+                                VALUES
+                                    ($1, $2, $3, $4, $5, NOW() + $6::BIGINT * INTERVAL '1 second')
+                                ON CONFLICT (user_id, store_name, key)
+                                DO UPDATE SET
+                                    value = EXCLUDED.value,
+                                    version = EXCLUDED.version,
+                                    expires_at = EXCLUDED.expires_at
+                                RETURNING LENGTH(value::text) + LENGTH(key)";
+                    let new_size = tx
+                        .do_query_one(
+                            stmt,
+                            &[
+                                user_id,
+                                &store_name,
+                                &key,
+                                &Json(&json),
+                                &version,
+                                &ttl_seconds,
+                            ],
+                        )
+                        .await
+                        .map_err(Error::exec("update kvstore"))?
+                        .try_get::<_, i32>(0)
+                        .map_err(Error::data("INTEGER"))?;
+                    (new_size as i64, version)
+                }
+                None => {
+                    let stmt = "DELETE FROM kvstore
+                                WHERE user_id = $1
+                                    AND store_name = $2
+                                    AND key = $3";
+                    tx.do_execute(stmt, &[user_id, &store_name, &key])
+                        .await
+                        .map_err(Error::exec("delete kvstore"))?;
+                    (0, 0)
+                }
+            };
+
+            changed += new_size - old_size;
+            results.push(ItemOpResult {
+                key: key.to_owned(),
+                old_value,
+                new_value,
+                version,
+            });
+        }
+
+        // reclaim the quota of expired items while the store is locked,
+        // the keys of `ops` are no longer expired at this point
+        changed -= purge_expired(&tx, user_id, store_name).await?;
+
+        if changed != 0 {
+            update_size(&tx, user_id, store_name, changed).await?;
+        }
+
+        tx.commit()
+            .await
+            .map_err(Error::exec("write_items commit"))?;
+        Ok(BatchOutcome::Committed { results })
+    }
+}
+
+fn parse_item(row: &tokio_postgres::Row) -> crate::Result<KvItem> {
+    Ok(KvItem {
+        key: row.try_get("key").map_err(Error::data("kvstore.key"))?,
+        value: row
+            .try_get::<_, Json<Value>>("value")
+            .map_err(Error::data("kvstore.value"))?
+            .0,
+        version: row
+            .try_get("version")
+            .map_err(Error::data("kvstore.version"))?,
+        expires_at: row
+            .try_get("expires_at")
+            .map_err(Error::data("kvstore.expires_at"))?,
+    })
+}
+
+impl UserConnection {
+    pub(crate) async fn read_item_impl(
+        &self,
+        store: &str,
+        key: &str,
+    ) -> crate::Result<Option<KvItem>> {
+        let conn = self.pool.get_conn().await?;
+        let opt = conn
+            .do_query_opt(
+                "SELECT key, value, version, expires_at FROM kvstore
+                WHERE user_id = $1 AND store_name = $2 AND key = $3
+                    AND (expires_at IS NULL OR expires_at > NOW())",
+                &[&self.user_id, &store, &key],
+            )
+            .await
+            .map_err(Error::exec("read item kvstore"))?;
+        opt.as_ref().map(parse_item).transpose()
+    }
+
+    pub(crate) async fn list_items_impl(
+        &self,
+        store: &str,
+        range: &KeyRange,
+    ) -> crate::Result<Vec<KvItem>> {
+        let limit = range
+            .limit
+            .unwrap_or(KeyRange::MAX_LIMIT)
+            .clamp(0, KeyRange::MAX_LIMIT);
+        let conn = self.pool.get_conn().await?;
+        let rows = conn
+            .do_query(
+                r#"SELECT key, value, version, expires_at FROM kvstore
+                WHERE user_id = $1 AND store_name = $2
+                    AND (expires_at IS NULL OR expires_at > NOW())
+                    AND ($3::TEXT IS NULL OR STARTS_WITH(key, $3))
+                    AND ($4::TEXT IS NULL OR key COLLATE "C" >= $4)
+                    AND ($5::TEXT IS NULL OR key COLLATE "C" < $5)
+                ORDER BY key COLLATE "C"
+                LIMIT $6"#,
+                &[
+                    &self.user_id,
+                    &store,
+                    &range.prefix,
+                    &range.start,
+                    &range.end,
+                    &limit,
+                ],
+            )
+            .await
+            .map_err(Error::exec("list items kvstore"))?;
+        rows.iter().map(parse_item).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_increment() {
+        assert_eq!(increment("k", None, 5).unwrap(), Value::I64(5));
+        assert_eq!(
+            increment("k", Some(&Value::I64(1)), -3).unwrap(),
+            Value::I64(-2)
+        );
+        assert_eq!(
+            increment("k", Some(&Value::U64(1)), -3).unwrap(),
+            Value::I64(-2)
+        );
+        assert_eq!(
+            increment("k", Some(&Value::U64(u64::MAX - 1)), 1).unwrap(),
+            Value::U64(u64::MAX)
+        );
+        assert!(increment("k", Some(&Value::I64(i64::MAX)), 1).is_err());
+        assert_eq!(
+            increment("k", Some(&Value::Decimal(Decimal::new(15, 1))), 1).unwrap(),
+            Value::Decimal(Decimal::new(25, 1))
+        );
+        assert!(increment("k", Some(&Value::String("a".to_owned())), 1).is_err());
+    }
+
+    #[test]
+    fn test_parse_ops() {
+        let ops: Vec<ItemOp> = serde_json::from_value(serde_json::json!([
+            { "op": "write", "key": "a", "value": { "S": "hello" }, "expected_version": 0 },
+            { "op": "increment", "key": "counter" },
+            { "op": "delete", "key": "b", "expected_version": 3 },
+        ]))
+        .unwrap();
+        assert_eq!(ops[0].expected_version(), Some(0));
+        assert!(matches!(ops[1], ItemOp::Increment { by: 1, .. }));
+        assert_eq!(ops[2].key(), "b");
+    }
+}
@@ -32,6 +32,9 @@ pub mod csv_export;
 mod admin;
 pub use admin::*;
 
+mod kvstore;
+pub use kvstore::*;
+
 #[derive(Clone)]
 pub struct UserConnection {
     pub local: LocalStorage,
@@ -258,7 +261,10 @@ pub trait UserConnectionTrait: Any + 'static {
         new_msg: Option<&Bytes>,
     ) -> crate::Result<()>;
 
-    async fn read_item(&self, store: &str, key: &str) -> crate::Result<Option<Value>>;
+    /// Expired items are treated as absent.
+    async fn read_item(&self, store: &str, key: &str) -> crate::Result<Option<KvItem>>;
+
+    async fn list_items(&self, store: &str, range: &KeyRange) -> crate::Result<Vec<KvItem>>;
 
     async fn export_user_data(&mut self) -> crate::Result<ExportedUserData>;
 }
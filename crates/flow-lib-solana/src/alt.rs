@@ -0,0 +1,697 @@
+//! Automatic address lookup tables, see [`ExecutionConfig::auto_lookup_table`].
+//!
+//! When a transaction would be too large, its accounts are moved to lookup
+//! tables owned by the fee payer. Tables are created and extended as needed,
+//! and reused by later transactions of the same fee payer.
+
+use crate::{execute_current_machine, utils::fetch_address_lookup_table};
+use flow_lib::{
+    FlowRunId, SolanaNet,
+    context::{
+        execute::{self, Error},
+        signer,
+    },
+    solana::{DurableNonce, ExecutionConfig, Instructions, Wallet},
+    utils::tower_client::CommonErrorExt,
+};
+use futures::future::BoxFuture;
+use solana_account_decoder::UiAccountEncoding;
+use solana_address_lookup_table_interface::{
+    instruction::{create_lookup_table, extend_lookup_table},
+    program::ID as ADDRESS_LOOKUP_TABLE_PROGRAM,
+    state::{AddressLookupTable, LOOKUP_TABLE_MAX_ADDRESSES},
+};
+use solana_commitment_config::CommitmentConfig;
+use solana_message::{AddressLookupTableAccount, VersionedMessage, v0};
+use solana_program::{hash::Hash, instruction::Instruction};
+use solana_pubkey::Pubkey;
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_rpc_client_api::{
+    config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    filter::{Memcmp, RpcFilterType},
+};
+use solana_signature::Signature;
+use solana_transaction::versioned::VersionedTransaction;
+use spo_helius::Helius;
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::{Arc, LazyLock, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Max size of a serialized transaction.
+pub const PACKET_DATA_SIZE: usize = 1280 - 40 - 8;
+
+/// Room for compute budget and `advance_nonce_account` instructions, which
+/// are inserted after lookup tables are chosen.
+const RESERVED_SIZE: usize = 128;
+
+/// Addresses added by one extend transaction.
+const EXTEND_CHUNK_SIZE: usize = 20;
+
+/// Accounts used at least this many times are added to a table that is being
+/// extended, so that later transactions do not need to extend it again.
+const FREQUENT_USE: u32 = 3;
+
+const CACHE_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// Usage counts are reset after this long, so that old accounts stop being
+/// added to tables.
+const USAGE_TTL: Duration = Duration::from_secs(60 * 60);
+
+const MAX_CACHED_TABLES: usize = 4096;
+
+/// Authorities whose owned tables and usage are remembered.
+const MAX_AUTHORITIES: usize = 1024;
+
+/// Accounts counted per authority, the least used ones are dropped first.
+const MAX_TRACKED_ACCOUNTS: usize = 1024;
+
+const ACTIVATION_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Offset of `Option<Pubkey>` authority in a lookup table account.
+const AUTHORITY_OFFSET: usize = 21;
+
+struct Entry<V> {
+    value: V,
+    inserted: Instant,
+    used: u64,
+}
+
+/// Map keeping at most `capacity` entries, each for at most `ttl` after it was
+/// inserted. When full, the least recently used entry is evicted.
+struct BoundedCache<K, V> {
+    capacity: usize,
+    ttl: Duration,
+    entries: HashMap<K, Entry<V>>,
+    clock: u64,
+}
+
+impl<K: Clone + Eq + std::hash::Hash, V> BoundedCache<K, V> {
+    fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            capacity,
+            ttl,
+            entries: HashMap::new(),
+            clock: 0,
+        }
+    }
+
+    fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        if self.entries.get(key)?.inserted.elapsed() >= self.ttl {
+            self.entries.remove(key);
+            return None;
+        }
+        self.clock += 1;
+        let entry = self.entries.get_mut(key)?;
+        entry.used = self.clock;
+        Some(&mut entry.value)
+    }
+
+    fn insert(&mut self, key: K, value: V) -> &mut V {
+        if !self.entries.contains_key(&key) && self.entries.len() >= self.capacity {
+            self.evict();
+        }
+        self.clock += 1;
+        let entry = Entry {
+            value,
+            inserted: Instant::now(),
+            used: self.clock,
+        };
+        &mut self.entries.entry(key).insert_entry(entry).into_mut().value
+    }
+
+    fn get_or_insert_with(&mut self, key: K, f: impl FnOnce() -> V) -> &mut V {
+        if self.get_mut(&key).is_none() {
+            return self.insert(key, f());
+        }
+        self.get_mut(&key).expect("checked above")
+    }
+
+    fn evict(&mut self) {
+        let ttl = self.ttl;
+        self.entries.retain(|_, e| e.inserted.elapsed() < ttl);
+        if self.entries.len() >= self.capacity
+            && let Some(key) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, e)| e.used)
+                .map(|(key, _)| key.clone())
+        {
+            self.entries.remove(&key);
+        }
+    }
+}
+
+/// RPC URL and address of an account, the same address can be a different
+/// account on another cluster.
+type Key = (String, Pubkey);
+
+fn cache_key(rpc: &RpcClient, pubkey: &Pubkey) -> Key {
+    (rpc.url(), *pubkey)
+}
+
+static TABLES: LazyLock<Mutex<BoundedCache<Key, AddressLookupTableAccount>>> =
+    LazyLock::new(|| Mutex::new(BoundedCache::new(MAX_CACHED_TABLES, CACHE_TTL)));
+
+/// Tables owned by an authority, filled on first use.
+static OWNED: LazyLock<Mutex<BoundedCache<Key, Vec<Pubkey>>>> =
+    LazyLock::new(|| Mutex::new(BoundedCache::new(MAX_AUTHORITIES, CACHE_TTL)));
+
+/// How many transactions of an authority used each account.
+static USAGE: LazyLock<Mutex<BoundedCache<Key, HashMap<Pubkey, u32>>>> =
+    LazyLock::new(|| Mutex::new(BoundedCache::new(MAX_AUTHORITIES, USAGE_TTL)));
+
+/// Tables of an authority are updated by one transaction at a time.
+static LOCKS: LazyLock<Mutex<HashMap<Key, Arc<tokio::sync::Mutex<()>>>>> =
+    LazyLock::new(Default::default);
+
+fn lock_of(authority: &Key) -> Arc<tokio::sync::Mutex<()>> {
+    let mut locks = LOCKS.lock().unwrap();
+    if locks.len() >= MAX_AUTHORITIES {
+        // only drop locks that nobody holds or waits for
+        locks.retain(|_, lock| Arc::strong_count(lock) > 1);
+    }
+    locks.entry(authority.clone()).or_default().clone()
+}
+
+fn cache_table(rpc: &RpcClient, table: AddressLookupTableAccount) {
+    TABLES
+        .lock()
+        .unwrap()
+        .insert(cache_key(rpc, &table.key), table);
+}
+
+/// [`fetch_address_lookup_table`], cached for a few minutes.
+///
+/// Addresses are never removed from a table, so a cached table can only
+/// miss recently added addresses, see [`refresh_lookup_tables`].
+pub async fn get_lookup_table(
+    rpc: &RpcClient,
+    pubkey: &Pubkey,
+) -> Result<AddressLookupTableAccount, Error> {
+    if let Some(table) = TABLES.lock().unwrap().get_mut(&cache_key(rpc, pubkey)) {
+        return Ok(table.clone());
+    }
+    let table = fetch_address_lookup_table(rpc, pubkey).await?;
+    cache_table(rpc, table.clone());
+    Ok(table)
+}
+
+/// Fetch `tables` again, bypassing the cache.
+pub async fn refresh_lookup_tables(
+    rpc: &RpcClient,
+    tables: &mut [AddressLookupTableAccount],
+) -> Result<(), Error> {
+    for table in tables {
+        *table = fetch_address_lookup_table(rpc, &table.key).await?;
+        cache_table(rpc, table.clone());
+    }
+    Ok(())
+}
+
+/// Size of a serialized transaction with this message.
+pub fn transaction_size(message: &v0::Message) -> usize {
+    let tx = VersionedTransaction {
+        signatures: vec![Signature::default(); message.header.num_required_signatures as usize],
+        message: VersionedMessage::V0(message.clone()),
+    };
+    bincode1::serialized_size(&tx).map_or(usize::MAX, |size| size as usize)
+}
+
+fn fits(i: &Instructions, lookups: &[AddressLookupTableAccount]) -> bool {
+    match v0::Message::try_compile(&i.fee_payer, &i.instructions, lookups, Hash::default()) {
+        Ok(message) => transaction_size(&message) + RESERVED_SIZE <= PACKET_DATA_SIZE,
+        // too many accounts
+        Err(_) => false,
+    }
+}
+
+/// Accounts that can be loaded from a lookup table, signers and invoked
+/// programs have to be in the message.
+pub fn lookup_candidates(i: &Instructions) -> Vec<Pubkey> {
+    let programs = i
+        .instructions
+        .iter()
+        .map(|ix| ix.program_id)
+        .collect::<BTreeSet<_>>();
+    let signers = i
+        .instructions
+        .iter()
+        .flat_map(|ix| &ix.accounts)
+        .filter(|meta| meta.is_signer)
+        .map(|meta| meta.pubkey)
+        .chain([i.fee_payer])
+        .collect::<BTreeSet<_>>();
+    let mut seen = BTreeSet::new();
+    i.instructions
+        .iter()
+        .flat_map(|ix| &ix.accounts)
+        .map(|meta| meta.pubkey)
+        .filter(|pk| !programs.contains(pk) && !signers.contains(pk) && seen.insert(*pk))
+        .collect()
+}
+
+fn record_usage(authority: &Key, accounts: &[Pubkey]) {
+    let mut usage = USAGE.lock().unwrap();
+    let counts = usage.get_or_insert_with(authority.clone(), HashMap::new);
+    for account in accounts {
+        *counts.entry(*account).or_default() += 1;
+    }
+    if counts.len() > MAX_TRACKED_ACCOUNTS {
+        let mut sorted = counts.iter().map(|(pk, c)| (*c, *pk)).collect::<Vec<_>>();
+        sorted.sort_unstable_by(|a, b| b.cmp(a));
+        sorted.truncate(MAX_TRACKED_ACCOUNTS / 2);
+        *counts = sorted.into_iter().map(|(c, pk)| (pk, c)).collect();
+    }
+}
+
+/// Frequently used accounts, most used first.
+fn frequent_accounts(authority: &Key) -> Vec<Pubkey> {
+    let mut usage = USAGE.lock().unwrap();
+    let mut accounts = usage
+        .get_mut(authority)
+        .map(|counts| &*counts)
+        .into_iter()
+        .flatten()
+        .filter(|(_, count)| **count >= FREQUENT_USE)
+        .map(|(pk, count)| (*pk, *count))
+        .collect::<Vec<_>>();
+    accounts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    accounts.into_iter().map(|(pk, _)| pk).collect()
+}
+
+/// Active lookup tables owned by `authority`.
+async fn owned_tables(
+    rpc: &RpcClient,
+    authority: &Pubkey,
+) -> Result<Vec<AddressLookupTableAccount>, Error> {
+    let known = OWNED
+        .lock()
+        .unwrap()
+        .get_mut(&cache_key(rpc, authority))
+        .cloned();
+    let keys = match known {
+        Some(keys) => keys,
+        None => {
+            let mut bytes = vec![1];
+            bytes.extend_from_slice(authority.as_ref());
+            let accounts = rpc
+                .get_program_ui_accounts_with_config(
+                    &ADDRESS_LOOKUP_TABLE_PROGRAM,
+                    RpcProgramAccountsConfig {
+                        filters: Some(vec![RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+                            AUTHORITY_OFFSET,
+                            bytes,
+                        ))]),
+                        account_config: RpcAccountInfoConfig {
+                            encoding: Some(UiAccountEncoding::Base64),
+                            ..<_>::default()
+                        },
+                        ..<_>::default()
+                    },
+                )
+                .await
+                .map_err(|error| Error::solana(error, 0))?;
+            let mut keys = Vec::new();
+            for (key, account) in accounts {
+                let Some(data) = account.data.decode() else {
+                    continue;
+                };
+                let Ok(table) = AddressLookupTable::deserialize(&data) else {
+                    continue;
+                };
+                if table.meta.deactivation_slot != u64::MAX {
+                    continue;
+                }
+                cache_table(
+                    rpc,
+                    AddressLookupTableAccount {
+                        key,
+                        addresses: table.addresses.to_vec(),
+                    },
+                );
+                keys.push(key);
+            }
+            tracing::info!("found {} lookup table(s) of {}", keys.len(), authority);
+            OWNED
+                .lock()
+                .unwrap()
+                .insert(cache_key(rpc, authority), keys.clone());
+            keys
+        }
+    };
+    let mut tables = Vec::with_capacity(keys.len());
+    for key in keys {
+        tables.push(get_lookup_table(rpc, &key).await?);
+    }
+    Ok(tables)
+}
+
+struct Payer<'a> {
+    wallet: Wallet,
+    rpc: &'a RpcClient,
+    helius: Option<&'a Helius>,
+    network: SolanaNet,
+    signer: signer::Svc,
+    flow_run_id: Option<FlowRunId>,
+    config: ExecutionConfig,
+}
+
+impl Payer<'_> {
+    async fn send(&self, instructions: Vec<Instruction>) -> Result<(), Error> {
+        let i = Instructions {
+            fee_payer: self.wallet.pubkey(),
+            signers: vec![self.wallet.clone()],
+            instructions,
+            lookup_tables: None,
+        };
+        execute_boxed(
+            i,
+            self.rpc,
+            self.helius,
+            self.network,
+            self.signer.clone(),
+            self.flow_run_id,
+            &self.config,
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+/// Boxed because `execute_current_machine` calls `add_lookup_tables`.
+fn execute_boxed<'a>(
+    i: Instructions,
+    rpc: &'a RpcClient,
+    helius: Option<&'a Helius>,
+    network: SolanaNet,
+    signer: signer::Svc,
+    flow_run_id: Option<FlowRunId>,
+    config: &'a ExecutionConfig,
+) -> BoxFuture<'a, Result<execute::Response, Error>> {
+    Box::pin(execute_current_machine(
+        i,
+        rpc,
+        helius,
+        network,
+        signer,
+        flow_run_id,
+        config,
+    ))
+}
+
+/// Wait until addresses added to `tables` can be used.
+async fn wait_for_activation(
+    rpc: &RpcClient,
+    tables: &[Pubkey],
+    commitment: CommitmentConfig,
+) -> Result<(), Error> {
+    let started = Instant::now();
+    for key in tables {
+        let account = rpc
+            .get_account_with_commitment(key, commitment)
+            .await
+            .map_err(|error| Error::solana(error, 0))?
+            .value
+            .ok_or_else(|| Error::msg(format!("lookup table {key} not found")))?;
+        let table = AddressLookupTable::deserialize(&account.data)?;
+        let last_extended_slot = table.meta.last_extended_slot;
+        cache_table(
+            rpc,
+            AddressLookupTableAccount {
+                key: *key,
+                addresses: table.addresses.to_vec(),
+            },
+        );
+        loop {
+            let slot = rpc
+                .get_slot_with_commitment(commitment)
+                .await
+                .map_err(|error| Error::solana(error, 0))?;
+            if slot > last_extended_slot {
+                break;
+            }
+            if started.elapsed() > ACTIVATION_TIMEOUT {
+                return Err(Error::Timeout);
+            }
+            tokio::time::sleep(Duration::from_millis(400)).await;
+        }
+    }
+    Ok(())
+}
+
+/// Add lookup tables to `i` if its transaction would be too large.
+///
+/// Tables of the fee payer are used first, missing accounts are added to a
+/// table with free space, or to a new table. The fee payer pays and signs for
+/// creating and extending tables.
+#[allow(clippy::too_many_arguments)]
+pub async fn add_lookup_tables(
+    i: &mut Instructions,
+    lookups: Vec<AddressLookupTableAccount>,
+    rpc: &RpcClient,
+    helius: Option<&Helius>,
+    network: SolanaNet,
+    signer: signer::Svc,
+    flow_run_id: Option<FlowRunId>,
+    config: &ExecutionConfig,
+) -> Result<(), Error> {
+    let authority = i.fee_payer;
+    let authority_key = cache_key(rpc, &authority);
+    let candidates = lookup_candidates(i);
+    record_usage(&authority_key, &candidates);
+
+    if fits(i, &lookups) {
+        return Ok(());
+    }
+    tracing::info!("transaction is too large, using lookup tables");
+
+    let wallet = i
+        .signers
+        .iter()
+        .find(|w| w.pubkey() == authority)
+        .cloned()
+        .ok_or_else(|| Error::msg("fee payer is not in signers"))?;
+
+    let _guard = lock_of(&authority_key).lock_owned().await;
+
+    let mut owned = owned_tables(rpc, &authority).await?;
+    let mut all = lookups.clone();
+    all.extend(owned.iter().cloned());
+    let use_tables = |i: &mut Instructions, tables: &[AddressLookupTableAccount]| {
+        let keys = i.lookup_tables.get_or_insert_with(Vec::new);
+        for table in tables {
+            if !lookups.iter().any(|t| t.key == table.key)
+                && table.addresses.iter().any(|pk| candidates.contains(pk))
+                && !keys.contains(&table.key)
+            {
+                keys.push(table.key);
+            }
+        }
+    };
+    if fits(i, &all) {
+        use_tables(i, &owned);
+        return Ok(());
+    }
+
+    let in_tables = all
+        .iter()
+        .flat_map(|t| t.addresses.iter().copied())
+        .collect::<BTreeSet<_>>();
+    let mut missing = candidates
+        .iter()
+        .copied()
+        .filter(|pk| !in_tables.contains(pk))
+        .collect::<Vec<_>>();
+    let free = owned
+        .iter()
+        .map(|t| LOOKUP_TABLE_MAX_ADDRESSES - t.addresses.len())
+        .sum::<usize>();
+    let room = free.saturating_sub(missing.len());
+    let frequent = frequent_accounts(&authority_key)
+        .into_iter()
+        .filter(|pk| !in_tables.contains(pk) && !missing.contains(pk))
+        .take(room)
+        .collect::<Vec<_>>();
+    missing.extend(frequent);
+
+    let payer = Payer {
+        wallet,
+        rpc,
+        helius,
+        network,
+        signer,
+        flow_run_id,
+        config: ExecutionConfig {
+            auto_lookup_table: false,
+            durable_nonce: DurableNonce::No,
+            ..config.clone()
+        },
+    };
+    let commitment = CommitmentConfig {
+        commitment: config.tx_commitment_level,
+    };
+
+    let mut updated = Vec::new();
+    let mut pending = missing.as_slice();
+    while !pending.is_empty() {
+        let (key, free) = match owned
+            .iter()
+            .find(|t| t.addresses.len() < LOOKUP_TABLE_MAX_ADDRESSES)
+        {
+            Some(table) => (
+                table.key,
+                LOOKUP_TABLE_MAX_ADDRESSES - table.addresses.len(),
+            ),
+            None => {
+                let slot = rpc
+                    .get_slot_with_commitment(CommitmentConfig::finalized())
+                    .await
+                    .map_err(|error| Error::solana(error, 0))?;
+                let (create, key) = create_lookup_table(authority, authority, slot);
+                tracing::info!("creating lookup table {}", key);
+                payer.send(vec![create]).await?;
+                // if it was evicted, the next lookup finds the new table
+                if let Some(keys) = OWNED.lock().unwrap().get_mut(&authority_key) {
+                    keys.push(key);
+                }
+                owned.push(AddressLookupTableAccount {
+                    key,
+                    addresses: Vec::new(),
+                });
+                (key, LOOKUP_TABLE_MAX_ADDRESSES)
+            }
+        };
+        let (now, rest) = pending.split_at(free.min(pending.len()));
+        for chunk in now.chunks(EXTEND_CHUNK_SIZE) {
+            tracing::info!("adding {} address(es) to lookup table {}", chunk.len(), key);
+            payer
+                .send(vec![extend_lookup_table(
+                    key,
+                    authority,
+                    Some(authority),
+                    chunk.to_vec(),
+                )])
+                .await?;
+        }
+        let table = owned
+            .iter_mut()
+            .find(|t| t.key == key)
+            .expect("table is in owned");
+        table.addresses.extend_from_slice(now);
+        cache_table(rpc, table.clone());
+        updated.push(key);
+        pending = rest;
+    }
+
+    wait_for_activation(rpc, &updated, commitment).await?;
+
+    use_tables(i, &owned);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_program::instruction::AccountMeta;
+
+    fn instructions(accounts: usize) -> Instructions {
+        let fee_payer = Pubkey::new_unique();
+        let program = Pubkey::new_unique();
+        Instructions {
+            fee_payer,
+            signers: vec![],
+            instructions: vec![Instruction::new_with_bytes(
+                program,
+                &[],
+                std::iter::once(AccountMeta::new(fee_payer, true))
+                    .chain((0..accounts).map(|_| AccountMeta::new(Pubkey::new_unique(), false)))
+                    .collect(),
+            )],
+            lookup_tables: None,
+        }
+    }
+
+    #[test]
+    fn test_lookup_candidates() {
+        let mut i = instructions(3);
+        let signer = Pubkey::new_unique();
+        let program = i.instructions[0].program_id;
+        i.instructions[0].accounts.extend([
+            AccountMeta::new(signer, true),
+            AccountMeta::new(program, false),
+        ]);
+        let candidates = lookup_candidates(&i);
+        assert_eq!(candidates.len(), 3);
+        assert!(!candidates.contains(&i.fee_payer));
+        assert!(!candidates.contains(&signer));
+        assert!(!candidates.contains(&program));
+    }
+
+    #[test]
+    fn test_fits() {
+        assert!(fits(&instructions(10), &[]));
+
+        let i = instructions(40);
+        assert!(!fits(&i, &[]));
+        let table = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: lookup_candidates(&i),
+        };
+        assert!(fits(&i, &[table]));
+    }
+
+    #[test]
+    fn test_frequent_accounts() {
+        let authority = ("http://localhost:8899".to_owned(), Pubkey::new_unique());
+        let [a, b, c] = [
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        ];
+        for _ in 0..FREQUENT_USE {
+            record_usage(&authority, &[a, b]);
+        }
+        record_usage(&authority, &[b, c]);
+        assert_eq!(frequent_accounts(&authority), [b, a]);
+
+        let accounts = (0..MAX_TRACKED_ACCOUNTS)
+            .map(|_| Pubkey::new_unique())
+            .collect::<Vec<_>>();
+        record_usage(&authority, &accounts);
+        assert!(USAGE.lock().unwrap().get_mut(&authority).unwrap().len() <= MAX_TRACKED_ACCOUNTS);
+        assert_eq!(frequent_accounts(&authority), [b, a]);
+    }
+
+    #[test]
+    fn test_cache_per_cluster() {
+        let devnet = RpcClient::new("https://api.devnet.solana.com".to_owned());
+        let mainnet = RpcClient::new("https://api.mainnet-beta.solana.com".to_owned());
+        let table = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: vec![Pubkey::new_unique()],
+        };
+        cache_table(&devnet, table.clone());
+        let mut tables = TABLES.lock().unwrap();
+        assert!(tables.get_mut(&cache_key(&devnet, &table.key)).is_some());
+        assert!(tables.get_mut(&cache_key(&mainnet, &table.key)).is_none());
+    }
+
+    #[test]
+    fn test_bounded_cache() {
+        let mut cache = BoundedCache::new(2, Duration::from_secs(60));
+        cache.insert(1, "a");
+        cache.insert(2, "b");
+        cache.get_mut(&1);
+        cache.insert(3, "c");
+        assert_eq!(cache.get_mut(&1), Some(&mut "a"));
+        assert_eq!(cache.get_mut(&2), None);
+        assert_eq!(cache.get_mut(&3), Some(&mut "c"));
+
+        let mut cache = BoundedCache::new(2, Duration::ZERO);
+        cache.insert(1, "a");
+        assert_eq!(cache.get_mut(&1), None);
+        assert_eq!(*cache.get_or_insert_with(1, || "b"), "b");
+    }
+}
@@ -0,0 +1,190 @@
+//! Priority fee estimation, see [`ExecutionConfig::priority_fee_estimator`].
+
+use crate::{get_priority_fee, unique_accounts};
+use anyhow::anyhow;
+use flow_lib::solana::{ExecutionConfig, FeeEstimator, Instructions};
+use solana_pubkey::Pubkey;
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use spo_helius::Helius;
+use std::{collections::BTreeSet, future::Future};
+
+/// Used when estimation fails, in micro-lamports per compute unit.
+pub const DEFAULT_PRIORITY_FEE: u64 = 100;
+
+pub const DEFAULT_PERCENTILE: u8 = 75;
+
+/// `getRecentPrioritizationFees` accepts at most this many accounts.
+const MAX_RPC_ACCOUNTS: usize = 128;
+
+pub trait PriorityFeeEstimator {
+    /// Compute unit price for a transaction, in micro-lamports.
+    fn estimate(&self, i: &Instructions)
+    -> impl Future<Output = Result<u64, anyhow::Error>> + Send;
+}
+
+pub struct HeliusEstimator<'a>(pub &'a Helius);
+
+impl PriorityFeeEstimator for HeliusEstimator<'_> {
+    async fn estimate(&self, i: &Instructions) -> Result<u64, anyhow::Error> {
+        get_priority_fee(self.0, &unique_accounts(i)).await
+    }
+}
+
+pub struct RpcEstimator<'a> {
+    pub rpc: &'a RpcClient,
+    pub percentile: u8,
+}
+
+impl PriorityFeeEstimator for RpcEstimator<'_> {
+    async fn estimate(&self, i: &Instructions) -> Result<u64, anyhow::Error> {
+        let accounts = writable_accounts(i);
+        let fees = self
+            .rpc
+            .get_recent_prioritization_fees(&accounts)
+            .await?
+            .into_iter()
+            .map(|fee| fee.prioritization_fee)
+            .collect();
+        percentile(fees, self.percentile).ok_or_else(|| anyhow!("no recent prioritization fees"))
+    }
+}
+
+pub struct StaticEstimator(pub u64);
+
+impl PriorityFeeEstimator for StaticEstimator {
+    async fn estimate(&self, _: &Instructions) -> Result<u64, anyhow::Error> {
+        Ok(self.0)
+    }
+}
+
+/// Fee payer, then writable accounts in instruction order, at most
+/// [`MAX_RPC_ACCOUNTS`].
+fn writable_accounts(i: &Instructions) -> Vec<Pubkey> {
+    let mut seen = BTreeSet::new();
+    std::iter::once(i.fee_payer)
+        .chain(
+            i.instructions
+                .iter()
+                .flat_map(|ix| &ix.accounts)
+                .filter(|meta| meta.is_writable)
+                .map(|meta| meta.pubkey),
+        )
+        .filter(|pubkey| seen.insert(*pubkey))
+        .take(MAX_RPC_ACCOUNTS)
+        .collect()
+}
+
+/// Nearest-rank percentile: the smallest value such that at least
+/// `percentile`% of `values` are less than or equal to it.
+/// `None` if `values` is empty.
+fn percentile(mut values: Vec<u64>, percentile: u8) -> Option<u64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_unstable();
+    let rank = (values.len() * percentile.min(100) as usize).div_ceil(100);
+    Some(values[rank.max(1) - 1])
+}
+
+/// Estimate with the estimator selected in `config`, capped at
+/// [`ExecutionConfig::max_priority_fee`].
+pub async fn estimate_priority_fee(
+    i: &Instructions,
+    rpc: &RpcClient,
+    helius: Option<&Helius>,
+    config: &ExecutionConfig,
+) -> u64 {
+    let rpc_estimator = RpcEstimator {
+        rpc,
+        percentile: config.priority_fee_percentile.unwrap_or(DEFAULT_PERCENTILE),
+    };
+    let result = match (config.priority_fee_estimator, helius) {
+        (FeeEstimator::Static(fee), _) => StaticEstimator(fee).estimate(i).await,
+        (FeeEstimator::Auto | FeeEstimator::Helius, Some(helius)) => {
+            HeliusEstimator(helius).estimate(i).await
+        }
+        (FeeEstimator::Helius, None) => {
+            tracing::warn!("no Helius client, using RPC");
+            rpc_estimator.estimate(i).await
+        }
+        (FeeEstimator::Auto | FeeEstimator::Rpc, _) => rpc_estimator.estimate(i).await,
+    };
+    let fee = result
+        .inspect_err(|error| tracing::warn!("priority fee estimation error: {}", error))
+        .unwrap_or(DEFAULT_PRIORITY_FEE);
+    match config.max_priority_fee {
+        Some(max) if fee > max => {
+            tracing::info!("capping priority fee {} to {}", fee, max);
+            max
+        }
+        _ => fee,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_program::instruction::{AccountMeta, Instruction};
+
+    #[test]
+    fn test_writable_accounts() {
+        let fee_payer = Pubkey::new_unique();
+        let accounts = (0..MAX_RPC_ACCOUNTS + 10)
+            .map(|_| Pubkey::new_unique())
+            .collect::<Vec<_>>();
+        let i = Instructions {
+            fee_payer,
+            signers: vec![],
+            instructions: vec![Instruction::new_with_bytes(
+                Pubkey::new_unique(),
+                &[],
+                std::iter::once(AccountMeta::new_readonly(Pubkey::new_unique(), false))
+                    .chain(accounts.iter().rev().map(|a| AccountMeta::new(*a, false)))
+                    .chain([AccountMeta::new(fee_payer, true)])
+                    .collect(),
+            )],
+            lookup_tables: None,
+        };
+        let writable = writable_accounts(&i);
+        assert_eq!(writable.len(), MAX_RPC_ACCOUNTS);
+        assert_eq!(writable[0], fee_payer);
+        assert!(
+            writable[1..]
+                .iter()
+                .eq(accounts.iter().rev().take(MAX_RPC_ACCOUNTS - 1))
+        );
+    }
+
+    #[test]
+    fn test_percentile() {
+        assert_eq!(percentile(vec![], 50), None);
+        assert_eq!(percentile(vec![7], 90), Some(7));
+        let fees = vec![50, 10, 40, 20, 30];
+        assert_eq!(percentile(fees.clone(), 0), Some(10));
+        assert_eq!(percentile(fees.clone(), 50), Some(30));
+        assert_eq!(percentile(fees.clone(), 75), Some(40));
+        assert_eq!(percentile(fees, 100), Some(50));
+
+        let fees = vec![40, 10, 30, 20];
+        assert_eq!(percentile(fees.clone(), 25), Some(10));
+        assert_eq!(percentile(fees.clone(), 50), Some(20));
+        assert_eq!(percentile(fees.clone(), 75), Some(30));
+        assert_eq!(percentile(fees, 76), Some(40));
+    }
+
+    #[tokio::test]
+    async fn test_static_and_cap() {
+        let rpc = RpcClient::new("http://127.0.0.1:1".to_owned());
+        let i = Instructions::default();
+        let config = ExecutionConfig {
+            priority_fee_estimator: FeeEstimator::Static(5000),
+            ..<_>::default()
+        };
+        assert_eq!(estimate_priority_fee(&i, &rpc, None, &config).await, 5000);
+        let config = ExecutionConfig {
+            max_priority_fee: Some(1000),
+            ..config
+        };
+        assert_eq!(estimate_priority_fee(&i, &rpc, None, &config).await, 1000);
+    }
+}
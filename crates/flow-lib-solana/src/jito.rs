@@ -0,0 +1,529 @@
+//! Send transactions as one atomic [Jito bundle](https://docs.jito.wtf/lowlatencytxnsend/#bundles).
+//!
+//! Either every transaction of a bundle lands, in order and in the same slot,
+//! or none of them does.
+
+use crate::{build_and_sign_tx, commitment};
+use base64::prelude::*;
+use flow_lib::{
+    FlowRunId, SolanaNet,
+    context::{
+        execute::{self, Error, TxOutcome},
+        signer,
+    },
+    solana::{ExecutionConfig, InsertionBehavior, Instructions},
+    utils::{
+        net::{check_endpoint, endpoint_client, private_endpoints_allowed},
+        tower_client::CommonErrorExt,
+    },
+};
+use serde::{Deserialize, de::DeserializeOwned};
+use serde_json::{Value as JsonValue, json};
+use solana_pubkey::Pubkey;
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_system_interface::instruction as system_instruction;
+use solana_transaction::versioned::VersionedTransaction;
+use spo_helius::Helius;
+use std::time::{Duration, Instant};
+
+pub const MAINNET_BLOCK_ENGINE_URL: &str = "https://mainnet.block-engine.jito.wtf";
+
+pub const TESTNET_BLOCK_ENGINE_URL: &str = "https://ny.testnet.block-engine.jito.wtf";
+
+/// Block engine of `network`, if Jito runs one there.
+pub fn default_block_engine_url(network: SolanaNet) -> Option<&'static str> {
+    match network {
+        SolanaNet::Mainnet => Some(MAINNET_BLOCK_ENGINE_URL),
+        SolanaNet::Testnet => Some(TESTNET_BLOCK_ENGINE_URL),
+        SolanaNet::Devnet | SolanaNet::Localnet | SolanaNet::Custom => None,
+    }
+}
+
+/// Lamports.
+pub const DEFAULT_TIP: u64 = 10_000;
+
+pub const MAX_BUNDLE_SIZE: usize = 5;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BundleStatus {
+    /// Not found, or older than 5 minutes.
+    Invalid,
+    Pending,
+    Failed,
+    Landed,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct InflightBundleStatus {
+    pub bundle_id: String,
+    pub status: BundleStatus,
+    pub landed_slot: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct RpcResponse<T> {
+    result: Option<T>,
+    error: Option<RpcError>,
+}
+
+#[derive(Deserialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct WithContext<T> {
+    value: T,
+}
+
+/// JSON-RPC client of a block engine.
+#[derive(Clone)]
+pub struct BlockEngine {
+    http: reqwest::Client,
+    url: String,
+}
+
+impl BlockEngine {
+    pub fn new(http: reqwest::Client, url: &str) -> Self {
+        Self {
+            http,
+            url: format!("{}/api/v1/bundles", url.trim_end_matches('/')),
+        }
+    }
+
+    /// Use the configured block engine, or the one of `network`.
+    ///
+    /// Private and loopback addresses are only allowed if the deployment opts
+    /// in with [`ALLOW_PRIVATE_ENDPOINTS`][flow_lib::utils::net::ALLOW_PRIVATE_ENDPOINTS_ENV].
+    pub async fn from_config(config: &ExecutionConfig, network: SolanaNet) -> Result<Self, Error> {
+        let url = match &config.jito_block_engine_url {
+            Some(url) => url.as_str(),
+            None => default_block_engine_url(network).ok_or_else(|| {
+                Error::BundleNotLanded(format!(
+                    "no Jito block engine on {}, set jito_block_engine_url",
+                    network.as_str()
+                ))
+            })?,
+        };
+        let allow_private = private_endpoints_allowed();
+        let parsed = reqwest::Url::parse(url)
+            .map_err(|error| Error::msg(format!("invalid block engine URL: {error}")))?;
+        check_endpoint(&parsed, allow_private)
+            .await
+            .map_err(|error| Error::msg(format!("block engine not allowed: {error}")))?;
+        Ok(Self::new(endpoint_client(allow_private).clone(), url))
+    }
+
+    /// A JSON-RPC error means the request was rejected, and is returned as
+    /// [`Error::BundleNotLanded`].
+    async fn call<T: DeserializeOwned>(&self, method: &str, params: JsonValue) -> Result<T, Error> {
+        let resp = self
+            .http
+            .post(&self.url)
+            .json(&json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": method,
+                "params": params,
+            }))
+            .send()
+            .await
+            .map_err(|error| Error::msg(format!("{method}: {error}")))?
+            .json::<RpcResponse<T>>()
+            .await
+            .map_err(|error| Error::msg(format!("{method}: {error}")))?;
+        match (resp.result, resp.error) {
+            (_, Some(error)) => Err(Error::BundleNotLanded(format!(
+                "{method}: {} ({})",
+                error.message, error.code
+            ))),
+            (Some(result), None) => Ok(result),
+            (None, None) => Err(Error::msg(format!("{method}: empty response"))),
+        }
+    }
+
+    pub async fn get_tip_accounts(&self) -> Result<Vec<Pubkey>, Error> {
+        let accounts = self
+            .call::<Vec<String>>("getTipAccounts", json!([]))
+            .await?;
+        accounts
+            .iter()
+            .map(|s| {
+                s.parse()
+                    .map_err(|_| Error::msg(format!("invalid tip account: {s}")))
+            })
+            .collect()
+    }
+
+    /// Returns the bundle ID.
+    pub async fn send_bundle(&self, txs: &[VersionedTransaction]) -> Result<String, Error> {
+        let encoded = txs
+            .iter()
+            .map(|tx| {
+                bincode1::serialize(tx)
+                    .map(|bytes| BASE64_STANDARD.encode(bytes))
+                    .map_err(|error| Error::msg(error.to_string()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        self.call("sendBundle", json!([encoded, { "encoding": "base64" }]))
+            .await
+    }
+
+    pub async fn get_inflight_bundle_status(
+        &self,
+        bundle_id: &str,
+    ) -> Result<Option<InflightBundleStatus>, Error> {
+        let resp = self
+            .call::<WithContext<Vec<InflightBundleStatus>>>(
+                "getInflightBundleStatuses",
+                json!([[bundle_id]]),
+            )
+            .await?;
+        Ok(resp.value.into_iter().next())
+    }
+}
+
+/// Build and sign every transaction, add a tip to the last one, then send
+/// them as a bundle and wait until all of them are confirmed.
+///
+/// Returns [`Error::BundleNotLanded`] if it is known that none of the
+/// transactions has landed or can still land, they can then be sent again by
+/// other means.
+pub async fn execute_bundle(
+    mut txs: Vec<Instructions>,
+    rpc: &RpcClient,
+    helius: Option<&Helius>,
+    network: SolanaNet,
+    signer: signer::Svc,
+    flow_run_id: Option<FlowRunId>,
+    config: &ExecutionConfig,
+) -> Result<Vec<execute::Response>, Error> {
+    if txs.len() > MAX_BUNDLE_SIZE {
+        return Err(Error::BundleNotLanded(format!(
+            "{} transactions, a bundle can have at most {}",
+            txs.len(),
+            MAX_BUNDLE_SIZE
+        )));
+    }
+    let engine = BlockEngine::from_config(config, network).await?;
+
+    let tip_accounts = engine.get_tip_accounts().await?;
+    if tip_accounts.is_empty() {
+        return Err(Error::BundleNotLanded("no tip account".to_owned()));
+    }
+    // spread the load, as Jito recommends
+    let tip_account = tip_accounts[rand_index(tip_accounts.len())];
+    let tip = config.jito_tip.unwrap_or(DEFAULT_TIP);
+    let last = txs.last_mut().ok_or_else(|| Error::msg("empty bundle"))?;
+    tracing::info!("tipping {} lamports to {}", tip, tip_account);
+    last.instructions.push(system_instruction::transfer(
+        &last.fee_payer,
+        &tip_account,
+        tip,
+    ));
+
+    let mut signed = Vec::with_capacity(txs.len());
+    for i in txs {
+        let config = without_simulation(config, &i);
+        signed.push(
+            build_and_sign_tx(
+                i,
+                rpc,
+                helius,
+                network,
+                signer.clone(),
+                flow_run_id,
+                &config,
+            )
+            .await?,
+        );
+    }
+    let transactions = signed.iter().map(|s| s.tx.clone()).collect::<Vec<_>>();
+    let signatures = transactions
+        .iter()
+        .map(|tx| tx.signatures[0])
+        .collect::<Vec<_>>();
+
+    let bundle_id = engine.send_bundle(&transactions).await?;
+    tracing::info!("submitted bundle {}", bundle_id);
+
+    let commitment = commitment(config.wait_commitment_level);
+    let started = Instant::now();
+    loop {
+        tokio::time::sleep(POLL_INTERVAL).await;
+
+        let statuses = rpc
+            .get_signature_statuses(&signatures)
+            .await
+            .map_err(|error| Error::solana(error, 0))?
+            .value;
+        if let Some((error, inserted)) = statuses.iter().zip(&signed).find_map(|(status, s)| {
+            status
+                .as_ref()
+                .and_then(|status| status.err.clone())
+                .map(|error| (error, s.inserted))
+        }) {
+            return Err(Error::solana(error.into(), inserted));
+        }
+        if statuses.iter().all(|status| {
+            status
+                .as_ref()
+                .is_some_and(|s| s.satisfies_commitment(commitment))
+        }) {
+            tracing::info!("bundle {} landed", bundle_id);
+            return Ok(signatures
+                .iter()
+                .zip(statuses.iter().flatten())
+                .zip(&signed)
+                .map(|((signature, status), s)| execute::Response {
+                    signature: Some(*signature),
+                    outcome: Some(TxOutcome {
+                        attempts: 1,
+                        rebuilds: 0,
+                        slot: status.slot,
+                        priority_fee: s.priority_fee,
+                    }),
+                })
+                .collect());
+        }
+        if statuses.iter().any(Option::is_some) {
+            // landed, waiting for the commitment level
+            continue;
+        }
+
+        match engine.get_inflight_bundle_status(&bundle_id).await {
+            // the block engine may still forward it, wait until it can no
+            // longer land before sending the transactions again
+            Ok(Some(InflightBundleStatus {
+                status: BundleStatus::Failed,
+                ..
+            })) => tracing::warn!("bundle {} failed", bundle_id),
+            Ok(status) => tracing::debug!("bundle {}: {:?}", bundle_id, status),
+            Err(error) => tracing::warn!("getInflightBundleStatuses failed: {}", error),
+        }
+
+        // the bundle is atomic, it can not land once any transaction expired
+        let mut expired = None;
+        for s in &signed {
+            expired = s
+                .lifetime
+                .expiry(rpc)
+                .await
+                .map_err(|error| Error::solana(error, 0))?;
+            if expired.is_some() {
+                break;
+            }
+        }
+        if let Some(reason) = expired {
+            // it may have landed after the status request
+            let landed = rpc
+                .get_signature_statuses(&signatures)
+                .await
+                .map_err(|error| Error::solana(error, 0))?
+                .value
+                .iter()
+                .any(Option::is_some);
+            if !landed {
+                return Err(Error::BundleNotLanded(format!(
+                    "bundle {bundle_id} expired: {reason}"
+                )));
+            }
+            continue;
+        }
+
+        if signed.iter().all(|s| {
+            s.lifetime
+                .wait_limit()
+                .is_some_and(|limit| started.elapsed() >= limit)
+        }) {
+            // only nonce transactions, they may still land
+            return Err(Error::TxUnconfirmed {
+                signature: signatures[0],
+                attempts: 1,
+            });
+        }
+    }
+}
+
+/// Transactions of a bundle can depend on each other, so they can not be
+/// simulated one by one to estimate compute units. The fallback budget is
+/// used instead.
+fn without_simulation(config: &ExecutionConfig, i: &Instructions) -> ExecutionConfig {
+    let mut config = config.clone();
+    if config.compute_budget == InsertionBehavior::Auto {
+        let compute_units = config
+            .fallback_compute_budget
+            .unwrap_or(200_000 * i.instructions.len() as u64)
+            .min(1_400_000);
+        config.compute_budget = InsertionBehavior::Value(compute_units);
+    }
+    config
+}
+
+fn rand_index(len: usize) -> usize {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_nanos();
+    nanos as usize % len
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    /// Answer `n` JSON-RPC requests with `respond(method)`.
+    async fn block_engine(n: usize, respond: fn(&str) -> JsonValue) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            for _ in 0..n {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut buf = Vec::new();
+                let body = loop {
+                    let mut chunk = [0u8; 4096];
+                    let len = stream.read(&mut chunk).await.unwrap();
+                    buf.extend_from_slice(&chunk[..len]);
+                    let text = String::from_utf8_lossy(&buf);
+                    if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                        let content_length = head
+                            .lines()
+                            .find_map(|l| {
+                                l.to_ascii_lowercase()
+                                    .strip_prefix("content-length:")
+                                    .map(|v| v.trim().parse::<usize>().unwrap())
+                            })
+                            .unwrap_or(0);
+                        if body.len() >= content_length {
+                            break body.to_owned();
+                        }
+                    }
+                };
+                let req: JsonValue = serde_json::from_str(&body).unwrap();
+                let resp = respond(req["method"].as_str().unwrap()).to_string();
+                stream
+                    .write_all(
+                        format!(
+                            "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                            resp.len(),
+                            resp
+                        )
+                        .as_bytes(),
+                    )
+                    .await
+                    .unwrap();
+            }
+        });
+        url
+    }
+
+    #[tokio::test]
+    async fn test_block_engine() {
+        let url = block_engine(3, |method| match method {
+            "getTipAccounts" => json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "result": ["96gYZGLnJYVFmbjzopPSU6QiEV5fGqZNyN9nmNhvrZU5"],
+            }),
+            "sendBundle" => json!({ "jsonrpc": "2.0", "id": 1, "result": "bundle-1" }),
+            "getInflightBundleStatuses" => json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "result": {
+                    "context": { "slot": 100 },
+                    "value": [{ "bundle_id": "bundle-1", "status": "Landed", "landed_slot": 99 }],
+                },
+            }),
+            _ => unreachable!(),
+        })
+        .await;
+        let engine = BlockEngine::new(reqwest::Client::new(), &url);
+
+        assert_eq!(
+            engine.get_tip_accounts().await.unwrap(),
+            ["96gYZGLnJYVFmbjzopPSU6QiEV5fGqZNyN9nmNhvrZU5"
+                .parse::<Pubkey>()
+                .unwrap()]
+        );
+        assert_eq!(engine.send_bundle(&[]).await.unwrap(), "bundle-1");
+        assert_eq!(
+            engine.get_inflight_bundle_status("bundle-1").await.unwrap(),
+            Some(InflightBundleStatus {
+                bundle_id: "bundle-1".to_owned(),
+                status: BundleStatus::Landed,
+                landed_slot: Some(99),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_from_config() {
+        let engine = BlockEngine::from_config(&ExecutionConfig::default(), SolanaNet::Mainnet)
+            .await
+            .unwrap();
+        assert_eq!(
+            engine.url,
+            format!("{MAINNET_BLOCK_ENGINE_URL}/api/v1/bundles")
+        );
+
+        let error = BlockEngine::from_config(&ExecutionConfig::default(), SolanaNet::Devnet)
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(error, Error::BundleNotLanded(_)), "{error}");
+
+        let config = ExecutionConfig {
+            jito_block_engine_url: Some("http://127.0.0.1:8080".to_owned()),
+            ..Default::default()
+        };
+        assert!(
+            BlockEngine::from_config(&config, SolanaNet::Localnet)
+                .await
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_without_simulation() {
+        let i = Instructions {
+            instructions: vec![system_instruction::transfer(
+                &Pubkey::new_unique(),
+                &Pubkey::new_unique(),
+                1,
+            )],
+            ..Default::default()
+        };
+        let config = without_simulation(&ExecutionConfig::default(), &i);
+        assert_eq!(config.compute_budget, InsertionBehavior::Value(200_000));
+
+        let config = ExecutionConfig {
+            compute_budget: InsertionBehavior::No,
+            ..Default::default()
+        };
+        let config = without_simulation(&config, &i);
+        assert_eq!(config.compute_budget, InsertionBehavior::No);
+    }
+
+    #[tokio::test]
+    async fn test_rejected_bundle() {
+        let url = block_engine(1, |_| {
+            json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "error": { "code": -32602, "message": "bundle contains an already processed transaction" },
+            })
+        })
+        .await;
+        let engine = BlockEngine::new(reqwest::Client::new(), &url);
+
+        let error = engine.send_bundle(&[]).await.unwrap_err();
+        assert!(matches!(error, Error::BundleNotLanded(_)), "{error}");
+    }
+}
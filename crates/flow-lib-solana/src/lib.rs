@@ -3,16 +3,20 @@ use bytes::Bytes;
 use chrono::Utc;
 use flow_lib::{
     FlowRunId, SolanaNet,
-    context::{execute::Error, signer},
+    context::{
+        execute::{self, Error, TxOutcome},
+        signer,
+    },
     solana::{
-        ExecuteOn, ExecutionConfig, InsertionBehavior, Instructions, SolanaActionConfig, Wallet,
+        DurableNonce, ExecuteOn, ExecutionConfig, InsertionBehavior, Instructions,
+        SolanaActionConfig, Wallet,
     },
     utils::tower_client::CommonErrorExt,
 };
 use futures::{FutureExt, TryStreamExt, future::Either};
 use solana_commitment_config::{CommitmentConfig, CommitmentLevel};
 use solana_compute_budget_interface::ComputeBudgetInstruction;
-use solana_message::{VersionedMessage, v0};
+use solana_message::{AddressLookupTableAccount, VersionedMessage, v0};
 use solana_presigner::Presigner as SdkPresigner;
 use solana_program::instruction::{AccountMeta, Instruction};
 use solana_pubkey::Pubkey;
@@ -39,6 +43,18 @@ pub use watcher::*;
 
 pub mod multi_watcher;
 
+pub mod nonce;
+use nonce::{NonceInfo, NonceLease};
+
+pub mod sender;
+use sender::{SendConfig, SendError};
+
+pub mod jito;
+
+pub mod alt;
+
+pub mod fee;
+
 pub mod spl_memo {
     pub const ID: solana_pubkey::Pubkey =
         solana_pubkey::pubkey!("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr");
@@ -60,12 +76,15 @@ fn is_set_compute_unit_limit(ix: &Instruction) -> Option<()> {
     }
 }
 
-fn is_set_compute_unit_price(ix: &Instruction) -> Option<()> {
+fn is_set_compute_unit_price(ix: &Instruction) -> Option<u64> {
     if solana_compute_budget_interface::check_id(&ix.program_id) {
         let data = ComputeBudgetInstruction::try_from_slice(&ix.data)
             .map_err(|error| tracing::error!("could not decode instruction: {}", error))
             .ok()?;
-        matches!(data, ComputeBudgetInstruction::SetComputeUnitPrice(_)).then_some(())
+        match data {
+            ComputeBudgetInstruction::SetComputeUnitPrice(price) => Some(price),
+            _ => None,
+        }
     } else {
         None
     }
@@ -200,8 +219,9 @@ async fn insert_priority_fee(
             if simulation_commitment_level != config.tx_commitment_level {
                 tracing::warn!("tx_commitment_level is not Finalized, simulation can fail");
             }
-            let message =
-                build_message(i, rpc, network, config, simulation_commitment_level).await?;
+            let message = build_message(i, rpc, network, config, simulation_commitment_level, None)
+                .await?
+                .0;
             let result = rpc
                 .simulate_transaction(&VersionedTransaction {
                     message: VersionedMessage::V0(message.clone()),
@@ -259,16 +279,8 @@ async fn insert_priority_fee(
     if config.priority_fee != InsertionBehavior::No && !contains_set_compute_unit_price(i) {
         let fee = if let InsertionBehavior::Value(x) = config.priority_fee {
             x
-        } else if let Some(helius) = helius {
-            get_priority_fee(helius, &unique_accounts(i))
-                .await
-                .inspect_err(|error| {
-                    tracing::warn!("get_priority_fee error: {}", error);
-                })
-                .unwrap_or(100)
         } else {
-            tracing::warn!("no Helius client");
-            100
+            fee::estimate_priority_fee(i, rpc, helius, config).await
         };
         tracing::info!("adding priority fee {}", fee);
         i.instructions
@@ -283,40 +295,87 @@ fn commitment(commitment: CommitmentLevel) -> CommitmentConfig {
     CommitmentConfig { commitment }
 }
 
-async fn build_message(
+/// Lookup tables of `i` and the config-level table.
+async fn fetch_lookup_tables(
     i: &Instructions,
     rpc: &RpcClient,
     network: SolanaNet,
     config: &ExecutionConfig,
-    commitment_level: CommitmentLevel,
-) -> Result<v0::Message, Error> {
+) -> Result<Vec<AddressLookupTableAccount>, Error> {
     let mut lookups = Vec::new();
     for pubkey in i.lookup_tables.iter().flatten() {
-        let table = fetch_address_lookup_table(rpc, pubkey).await?;
+        let table = alt::get_lookup_table(rpc, pubkey).await?;
         lookups.push(table);
     }
     if let Some(pubkey) = config.lookup_table(network).as_ref()
         && !i.lookup_tables.iter().flatten().any(|pk| pk == pubkey)
     {
         tracing::warn!("build_message: adding config-level ALT: {}", pubkey);
-        let table = fetch_address_lookup_table(rpc, pubkey).await?;
+        let table = alt::get_lookup_table(rpc, pubkey).await?;
         lookups.push(table);
     }
+    Ok(lookups)
+}
+
+/// Compile a message, using the durable nonce if set, or the latest blockhash otherwise.
+async fn build_message(
+    i: &Instructions,
+    rpc: &RpcClient,
+    network: SolanaNet,
+    config: &ExecutionConfig,
+    commitment_level: CommitmentLevel,
+    nonce: Option<&NonceInfo>,
+) -> Result<(v0::Message, TransactionLifetime), Error> {
+    let mut lookups = fetch_lookup_tables(i, rpc, network, config).await?;
     if lookups.is_empty() {
         tracing::info!("build_message: no address lookup tables");
     } else {
         tracing::info!("build_message: {} lookup table(s)", lookups.len());
     }
 
-    let blockhash = rpc
-        .get_latest_blockhash_with_commitment(commitment(commitment_level))
-        .await
-        .map_err(|error| Error::solana(error, 0))? // TODO: better handling of "inserted"
-        .0;
+    let lifetime = match nonce {
+        Some(nonce) => TransactionLifetime::Nonce {
+            account: nonce.account,
+            nonce: nonce.blockhash,
+        },
+        None => {
+            let (blockhash, last_valid_block_height) = rpc
+                .get_latest_blockhash_with_commitment(commitment(commitment_level))
+                .await
+                .map_err(|error| Error::solana(error, 0))?; // TODO: better handling of "inserted"
+            TransactionLifetime::Blockhash {
+                blockhash,
+                last_valid_block_height: Some(last_valid_block_height),
+            }
+        }
+    };
 
-    let message = v0::Message::try_compile(&i.fee_payer, &i.instructions, &lookups, blockhash)?;
+    let compile = |lookups: &[AddressLookupTableAccount]| {
+        v0::Message::try_compile(&i.fee_payer, &i.instructions, lookups, lifetime.blockhash())
+    };
+    let mut message = compile(&lookups);
+    // cached tables can miss recently added addresses
+    let too_large = message
+        .as_ref()
+        .is_ok_and(|message| alt::transaction_size(message) > alt::PACKET_DATA_SIZE);
+    if !lookups.is_empty() && (message.is_err() || too_large) {
+        tracing::info!("build_message: refreshing lookup tables");
+        alt::refresh_lookup_tables(rpc, &mut lookups).await?;
+        message = compile(&lookups);
+    }
+
+    Ok((message?, lifetime))
+}
 
-    Ok(message)
+struct SignedTransaction {
+    tx: VersionedTransaction,
+    inserted: usize,
+    /// Compute unit price of the transaction.
+    priority_fee: Option<u64>,
+    simulation: Option<RpcResult<RpcSimulateTransactionResult>>,
+    lifetime: TransactionLifetime,
+    /// Keeps the nonce account locked until the transaction is confirmed.
+    _nonce: Option<NonceLease>,
 }
 
 async fn build_and_sign_tx(
@@ -327,14 +386,7 @@ async fn build_and_sign_tx(
     mut signer: signer::Svc,
     flow_run_id: Option<FlowRunId>,
     config: &ExecutionConfig,
-) -> Result<
-    (
-        VersionedTransaction,
-        usize,
-        Option<RpcResult<RpcSimulateTransactionResult>>,
-    ),
-    Error,
-> {
+) -> Result<SignedTransaction, Error> {
     // Log pre-priority-fee instruction state
     tracing::info!(
         "build_and_sign_tx: {} instruction(s) before priority fee, fee_payer={}, lookup_tables={:?}",
@@ -352,16 +404,47 @@ async fn build_and_sign_tx(
         );
     }
 
+    if config.auto_lookup_table {
+        let lookups = fetch_lookup_tables(&i, rpc, network, config).await?;
+        alt::add_lookup_tables(
+            &mut i,
+            lookups,
+            rpc,
+            helius,
+            network,
+            signer.clone(),
+            flow_run_id,
+            config,
+        )
+        .await?;
+    }
+
+    // before the simulation, so that the compute unit limit includes its instructions
+    let (nonce, nonce_inserted) = nonce::prepare_nonce(&mut i, rpc, config).await?;
+
     let (inserted, simulate_result) =
         insert_priority_fee(&mut i, rpc, helius, network, config).await?;
 
+    // compute budget instructions are inserted first, `advance_nonce_account` has to be first
+    let inserted = inserted + nonce_inserted;
+    i.instructions[..inserted].rotate_right(nonce_inserted);
+
     tracing::info!(
         "build_and_sign_tx: {} instruction(s) after priority fee ({} inserted)",
         i.instructions.len(),
         inserted
     );
 
-    let mut message = build_message(&i, rpc, network, config, config.tx_commitment_level).await?;
+    let nonce_info = nonce.as_ref().and_then(|lease| lease.info);
+    let (mut message, mut lifetime) = build_message(
+        &i,
+        rpc,
+        network,
+        config,
+        config.tx_commitment_level,
+        nonce_info.as_ref(),
+    )
+    .await?;
 
     // Log the compiled V0 message details for debugging #51
     tracing::info!(
@@ -413,6 +496,12 @@ async fn build_and_sign_tx(
             if let Some(new) = resp.new_message {
                 let new_message = is_same_message_logic(&data, &new).map_err(Error::from_anyhow)?;
                 tracing::info!("updating transaction");
+                if new_message.recent_blockhash != lifetime.blockhash() {
+                    lifetime = TransactionLifetime::Blockhash {
+                        blockhash: new_message.recent_blockhash,
+                        last_valid_block_height: None,
+                    };
+                }
                 message = new_message;
                 data = new;
             }
@@ -492,9 +581,20 @@ async fn build_and_sign_tx(
         VersionedTransaction::try_new(VersionedMessage::V0(message), &signers)?
     };
 
-    Ok((tx, inserted, simulate_result))
+    Ok(SignedTransaction {
+        tx,
+        inserted,
+        priority_fee: i.instructions.iter().find_map(is_set_compute_unit_price),
+        simulation: simulate_result,
+        lifetime,
+        _nonce: nonce,
+    })
 }
 
+/// Send and confirm, see [`sender::send_until_confirmed`].
+///
+/// An expired transaction is built and signed again, up to
+/// [`ExecutionConfig::max_rebuilds`] times.
 async fn execute_current_machine(
     i: Instructions,
     rpc: &RpcClient,
@@ -503,39 +603,82 @@ async fn execute_current_machine(
     signer: signer::Svc,
     flow_run_id: Option<FlowRunId>,
     config: &ExecutionConfig,
-) -> Result<Signature, Error> {
-    let (tx, inserted, simulate_result) =
-        build_and_sign_tx(i, rpc, helius, network, signer, flow_run_id, config).await?;
-
-    let skip_preflight = match simulate_result {
-        Some(Ok(resp)) => resp.value.err.is_none(),
-        Some(Err(_)) => false,
-        None => false,
+) -> Result<execute::Response, Error> {
+    let rebroadcast_interval = match config.rebroadcast_interval {
+        None => Some(sender::DEFAULT_REBROADCAST_INTERVAL),
+        Some(0) => None,
+        Some(secs) => Some(Duration::from_secs(secs)),
     };
+    let mut attempts = 0;
+    let mut rebuilds = 0;
+    loop {
+        let signed = build_and_sign_tx(
+            i.clone(),
+            rpc,
+            helius,
+            network,
+            signer.clone(),
+            flow_run_id,
+            config,
+        )
+        .await?;
 
-    let signature = rpc
-        .send_transaction_with_config(
-            &tx,
-            RpcSendTransactionConfig {
+        let skip_preflight = match &signed.simulation {
+            Some(Ok(resp)) => resp.value.err.is_none(),
+            Some(Err(_)) => false,
+            None => false,
+        };
+        let send_config = SendConfig {
+            send: RpcSendTransactionConfig {
                 skip_preflight,
                 preflight_commitment: Some(config.tx_commitment_level),
                 ..<_>::default()
             },
-        )
-        .await
-        .map_err(move |error| Error::solana(error, inserted))?;
-    tracing::info!("submitted {}", signature);
-
-    confirm_transaction(
-        rpc,
-        &signature,
-        tx.message.recent_blockhash(),
-        commitment(config.wait_commitment_level),
-    )
-    .await
-    .map_err(move |error| Error::solana(error, inserted))?;
+            commitment: commitment(config.wait_commitment_level),
+            rebroadcast_interval,
+        };
 
-    Ok(signature)
+        let inserted = signed.inserted;
+        match sender::send_until_confirmed(rpc, &signed.tx, &signed.lifetime, send_config).await {
+            Ok(sent) => {
+                return Ok(execute::Response {
+                    signature: Some(sent.signature),
+                    outcome: Some(TxOutcome {
+                        attempts: attempts + sent.attempts,
+                        rebuilds,
+                        slot: sent.slot,
+                        priority_fee: signed.priority_fee,
+                    }),
+                });
+            }
+            Err(SendError::Expired {
+                reason,
+                attempts: sent,
+            }) => {
+                attempts += sent;
+                if rebuilds >= config.max_rebuilds {
+                    return Err(Error::TxExpired { reason, attempts });
+                }
+                rebuilds += 1;
+                tracing::warn!(
+                    "transaction expired ({}), rebuilding {}/{}",
+                    reason,
+                    rebuilds,
+                    config.max_rebuilds
+                );
+            }
+            Err(SendError::Unconfirmed {
+                signature,
+                attempts: sent,
+            }) => {
+                return Err(Error::TxUnconfirmed {
+                    signature,
+                    attempts: attempts + sent,
+                });
+            }
+            Err(SendError::Client(error)) => return Err(Error::solana(error, inserted)),
+        }
+    }
 }
 
 async fn execute_solana_action(
@@ -621,7 +764,7 @@ pub trait InstructionsExt: Sized {
         signer: signer::Svc,
         flow_run_id: Option<FlowRunId>,
         config: ExecutionConfig,
-    ) -> impl Future<Output = Result<Signature, Error>>;
+    ) -> impl Future<Output = Result<execute::Response, Error>>;
 }
 
 impl InstructionsExt for Instructions {
@@ -679,6 +822,9 @@ impl InstructionsExt for Instructions {
     ) -> Result<(PartialVersionedTransaction, usize, Option<String>), Error> {
         let (inserted, _simulate_result) =
             insert_priority_fee(&mut self, rpc, helius, network, config).await?;
+        if config.durable_nonce != DurableNonce::No {
+            tracing::warn!("durable nonce is not supported for Solana Actions");
+        }
         let memo = if let Some(action_identity) = action_identity {
             if !self
                 .instructions
@@ -715,7 +861,16 @@ impl InstructionsExt for Instructions {
         };
 
         let message = VersionedMessage::V0(
-            build_message(&self, rpc, network, config, config.tx_commitment_level).await?,
+            build_message(
+                &self,
+                rpc,
+                network,
+                config,
+                config.tx_commitment_level,
+                None,
+            )
+            .await?
+            .0,
         );
 
         // Sign all signatures except for action_signer
@@ -790,25 +945,24 @@ impl InstructionsExt for Instructions {
         signer: signer::Svc,
         flow_run_id: Option<FlowRunId>,
         config: ExecutionConfig,
-    ) -> Result<Signature, Error> {
+    ) -> Result<execute::Response, Error> {
         match &config.execute_on {
             ExecuteOn::CurrentMachine => {
                 execute_current_machine(self, rpc, helius, network, signer, flow_run_id, &config)
                     .await
             }
-            ExecuteOn::SolanaAction(action_config) => {
-                execute_solana_action(
-                    self,
-                    rpc,
-                    helius,
-                    network,
-                    signer,
-                    flow_run_id,
-                    &config,
-                    action_config,
-                )
-                .await
-            }
+            ExecuteOn::SolanaAction(action_config) => execute_solana_action(
+                self,
+                rpc,
+                helius,
+                network,
+                signer,
+                flow_run_id,
+                &config,
+                action_config,
+            )
+            .await
+            .map(execute::Response::signature),
         }
     }
 }
@@ -925,6 +1079,7 @@ mod tests {
             SolanaNet::Devnet,
             &<_>::default(),
             CommitmentLevel::Confirmed,
+            None,
         )
         .await
         .unwrap();
@@ -0,0 +1,263 @@
+//! Durable nonce support, see [`ExecutionConfig::durable_nonce`].
+//!
+//! A transaction using a durable nonce has the nonce value as its blockhash and
+//! `advance_nonce_account` as its first instruction. It stays valid until the
+//! nonce is advanced, so waiting for signatures can take as long as needed.
+
+use flow_lib::{
+    context::execute::Error,
+    solana::{DurableNonce, ExecutionConfig, Instructions},
+    utils::tower_client::CommonErrorExt,
+};
+use solana_commitment_config::CommitmentConfig;
+use solana_nonce::{state::State, versions::Versions};
+use solana_program::hash::Hash;
+use solana_pubkey::Pubkey;
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_system_interface::instruction as system_instruction;
+use std::{
+    collections::HashMap,
+    sync::{Arc, LazyLock, Mutex},
+};
+use tokio::sync::OwnedMutexGuard;
+
+/// Max number of nonce accounts created for a wallet with [`DurableNonce::Pool`].
+pub const POOL_SIZE: u32 = 4;
+
+const POOL_SEED_PREFIX: &str = "spo-nonce-";
+
+/// A nonce account can only be used by one transaction at a time.
+///
+/// Entries are evicted once no lease or waiter holds them.
+static LOCKS: LazyLock<Mutex<HashMap<Pubkey, Arc<tokio::sync::Mutex<()>>>>> =
+    LazyLock::new(Default::default);
+
+fn lock_of(account: &Pubkey) -> Arc<tokio::sync::Mutex<()>> {
+    let mut locks = LOCKS.lock().unwrap();
+    locks.retain(|_, lock| Arc::strong_count(lock) > 1);
+    locks.entry(*account).or_default().clone()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NonceInfo {
+    pub account: Pubkey,
+    pub authority: Pubkey,
+    /// Current nonce value, used as the transaction's blockhash.
+    pub blockhash: Hash,
+}
+
+/// Exclusive use of a nonce account, held until the transaction is confirmed or expired.
+#[derive(Debug)]
+pub struct NonceLease {
+    /// `None` if the transaction creates the nonce account.
+    pub info: Option<NonceInfo>,
+    _guard: OwnedMutexGuard<()>,
+}
+
+/// Address of the `index`-th nonce account in the pool of `authority`.
+pub fn pool_address(authority: &Pubkey, index: u32) -> Pubkey {
+    Pubkey::create_with_seed(
+        authority,
+        &format!("{POOL_SEED_PREFIX}{index}"),
+        &solana_system_interface::program::ID,
+    )
+    .expect("seed is shorter than MAX_SEED_LEN")
+}
+
+/// Parse nonce account data, returns `None` if the account is not initialized.
+pub fn parse_nonce_account(account: &Pubkey, data: &[u8]) -> Option<NonceInfo> {
+    let versions = bincode1::deserialize::<Versions>(data).ok()?;
+    match versions.state() {
+        State::Uninitialized => None,
+        State::Initialized(data) => Some(NonceInfo {
+            account: *account,
+            authority: data.authority,
+            blockhash: data.blockhash(),
+        }),
+    }
+}
+
+pub async fn fetch_nonce(
+    rpc: &RpcClient,
+    account: &Pubkey,
+    commitment: CommitmentConfig,
+) -> Result<Option<NonceInfo>, Error> {
+    let account_data = rpc
+        .get_account_with_commitment(account, commitment)
+        .await
+        .map_err(|error| Error::solana(error, 0))?
+        .value;
+    Ok(account_data.and_then(|a| parse_nonce_account(account, &a.data)))
+}
+
+fn is_signer(i: &Instructions, pubkey: &Pubkey) -> bool {
+    i.signers.iter().any(|w| w.pubkey() == *pubkey)
+}
+
+/// Pick a nonce account according to `config` and insert `advance_nonce_account`
+/// as the first instruction.
+///
+/// With [`DurableNonce::Pool`], a free nonce account of the fee payer is used.
+/// If there is none and the pool is not full, instructions to create one are
+/// appended instead, and this transaction uses a recent blockhash.
+///
+/// Returns the lease and the number of instructions inserted before the user's instructions.
+pub async fn prepare_nonce(
+    i: &mut Instructions,
+    rpc: &RpcClient,
+    config: &ExecutionConfig,
+) -> Result<(Option<NonceLease>, usize), Error> {
+    let commitment = CommitmentConfig {
+        commitment: config.tx_commitment_level,
+    };
+    let (info, guard) = match config.durable_nonce {
+        DurableNonce::No => return Ok((None, 0)),
+        DurableNonce::Account(account) => {
+            let guard = lock_of(&account).lock_owned().await;
+            let info = fetch_nonce(rpc, &account, commitment)
+                .await?
+                .ok_or_else(|| Error::msg(format!("{account} is not a nonce account")))?;
+            if !is_signer(i, &info.authority) {
+                return Err(Error::msg(format!(
+                    "nonce authority {} is not a signer",
+                    info.authority
+                )));
+            }
+            (info, guard)
+        }
+        DurableNonce::Pool => {
+            let authority = i.fee_payer;
+            let mut missing = None;
+            let mut found = None;
+            let mut busy = None;
+            for index in 0..POOL_SIZE {
+                let account = pool_address(&authority, index);
+                let Ok(guard) = lock_of(&account).try_lock_owned() else {
+                    busy.get_or_insert(index);
+                    continue;
+                };
+                match fetch_nonce(rpc, &account, commitment).await? {
+                    Some(info) if info.authority == authority => {
+                        found = Some((info, guard));
+                        break;
+                    }
+                    Some(_) => continue,
+                    None => {
+                        if missing.is_none() {
+                            missing = Some((index, guard));
+                        }
+                    }
+                }
+            }
+            match (found, missing) {
+                (Some(found), _) => found,
+                (None, Some((index, guard))) => {
+                    let account = pool_address(&authority, index);
+                    tracing::info!("creating nonce account {}", account);
+                    let lamports = rpc
+                        .get_minimum_balance_for_rent_exemption(State::size())
+                        .await
+                        .map_err(|error| Error::solana(error, 0))?;
+                    // appended, so instruction indexes in errors stay the same
+                    i.instructions
+                        .extend(system_instruction::create_nonce_account_with_seed(
+                            &authority,
+                            &account,
+                            &authority,
+                            &format!("{POOL_SEED_PREFIX}{index}"),
+                            &authority,
+                            lamports,
+                        ));
+                    let lease = NonceLease {
+                        info: None,
+                        _guard: guard,
+                    };
+                    return Ok((Some(lease), 0));
+                }
+                (None, None) => {
+                    // every nonce account is in use, wait for the first busy one
+                    let index = busy.ok_or_else(|| {
+                        Error::msg(format!("no nonce account of {authority} can be used"))
+                    })?;
+                    let account = pool_address(&authority, index);
+                    let guard = lock_of(&account).lock_owned().await;
+                    let info = fetch_nonce(rpc, &account, commitment)
+                        .await?
+                        .filter(|info| info.authority == authority)
+                        .ok_or_else(|| {
+                            Error::msg(format!("{account} is not a nonce account of {authority}"))
+                        })?;
+                    (info, guard)
+                }
+            }
+        }
+    };
+
+    tracing::info!(
+        "using nonce account {}, nonce {}",
+        info.account,
+        info.blockhash
+    );
+    i.instructions.insert(
+        0,
+        system_instruction::advance_nonce_account(&info.account, &info.authority),
+    );
+    let lease = NonceLease {
+        info: Some(info),
+        _guard: guard,
+    };
+    Ok((Some(lease), 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_nonce::state::{Data, DurableNonce as NonceValue};
+
+    #[test]
+    fn test_parse_nonce_account() {
+        let account = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let nonce = NonceValue::from_blockhash(&Hash::new_unique());
+        let data = Data::new(authority, nonce, 5000);
+        let blockhash = data.blockhash();
+        let bytes = bincode1::serialize(&Versions::new(State::Initialized(data))).unwrap();
+        assert_eq!(
+            parse_nonce_account(&account, &bytes),
+            Some(NonceInfo {
+                account,
+                authority,
+                blockhash,
+            })
+        );
+
+        let bytes = bincode1::serialize(&Versions::new(State::Uninitialized)).unwrap();
+        assert_eq!(parse_nonce_account(&account, &bytes), None);
+    }
+
+    #[test]
+    fn test_pool_address() {
+        let authority = Pubkey::new_unique();
+        assert_ne!(pool_address(&authority, 0), pool_address(&authority, 1));
+        assert_eq!(pool_address(&authority, 0), pool_address(&authority, 0));
+    }
+
+    #[test]
+    fn test_locks_evicted() {
+        let leased = Pubkey::new_unique();
+        let released = Pubkey::new_unique();
+        let guard = lock_of(&leased).try_lock_owned().unwrap();
+        drop(lock_of(&released));
+
+        drop(lock_of(&Pubkey::new_unique()));
+        {
+            let locks = LOCKS.lock().unwrap();
+            assert!(locks.contains_key(&leased));
+            assert!(!locks.contains_key(&released));
+        }
+
+        drop(guard);
+        drop(lock_of(&Pubkey::new_unique()));
+        assert!(!LOCKS.lock().unwrap().contains_key(&leased));
+    }
+}
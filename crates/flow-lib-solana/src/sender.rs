@@ -0,0 +1,160 @@
+//! Send a signed transaction and re-send it until it is confirmed or expired.
+//!
+//! RPC nodes drop transactions under load, re-sending the same signed
+//! transaction is safe and is the main way to improve landing rates.
+
+use crate::watcher::TransactionLifetime;
+use flow_lib::context::execute::ExpiryReason;
+use solana_clock::MAX_HASH_AGE_IN_SECONDS;
+use solana_commitment_config::CommitmentConfig;
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_rpc_client_api::{client_error, config::RpcSendTransactionConfig, request::RpcError};
+use solana_signature::Signature;
+use solana_transaction::versioned::VersionedTransaction;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_REBROADCAST_INTERVAL: Duration = Duration::from_secs(2);
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug)]
+pub enum SendError {
+    /// The transaction can no longer land, it is safe to rebuild and sign again.
+    Expired {
+        reason: ExpiryReason,
+        attempts: u32,
+    },
+    /// A nonce transaction was not found after
+    /// [`TransactionLifetime::wait_limit`], it may still land so it must not be
+    /// sent again with a different signature.
+    Unconfirmed {
+        signature: Signature,
+        attempts: u32,
+    },
+    Client(client_error::Error),
+}
+
+impl From<client_error::Error> for SendError {
+    fn from(value: client_error::Error) -> Self {
+        SendError::Client(value)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Sent {
+    pub signature: Signature,
+    /// Number of times the transaction was sent.
+    pub attempts: u32,
+    pub slot: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SendConfig {
+    /// Config of the first send, re-sends always skip preflight.
+    pub send: RpcSendTransactionConfig,
+    pub commitment: CommitmentConfig,
+    /// `None` to send only once.
+    pub rebroadcast_interval: Option<Duration>,
+}
+
+/// Send `tx` and wait until it reaches `config.commitment`.
+///
+/// While the transaction is not found, it is re-sent every
+/// `rebroadcast_interval` until `lifetime` says it has expired or its wait
+/// limit is reached.
+pub async fn send_until_confirmed(
+    rpc: &RpcClient,
+    tx: &VersionedTransaction,
+    lifetime: &TransactionLifetime,
+    config: SendConfig,
+) -> Result<Sent, SendError> {
+    let signature = rpc.send_transaction_with_config(tx, config.send).await?;
+    tracing::info!("submitted {}", signature);
+
+    let resend_config = RpcSendTransactionConfig {
+        skip_preflight: true,
+        // we are retrying ourselves
+        max_retries: Some(0),
+        ..config.send
+    };
+    let started = Instant::now();
+    let mut last_sent = Instant::now();
+    let mut attempts = 1;
+    loop {
+        tokio::time::sleep(POLL_INTERVAL).await;
+
+        let status = rpc
+            .get_signature_statuses(&[signature])
+            .await?
+            .value
+            .pop()
+            .flatten();
+        if let Some(status) = status {
+            if let Some(error) = status.err {
+                return Err(SendError::Client(error.into()));
+            }
+            if status.satisfies_commitment(config.commitment) {
+                tracing::info!(
+                    "{} landed in slot {} after {} attempt(s)",
+                    signature,
+                    status.slot,
+                    attempts
+                );
+                return Ok(Sent {
+                    signature,
+                    attempts,
+                    slot: status.slot,
+                });
+            }
+            if started.elapsed().as_secs() >= 2 * MAX_HASH_AGE_IN_SECONDS as u64 {
+                return Err(client_error::Error::from(RpcError::ForUser(
+                    "transaction not finalized. \
+                     This can happen when a transaction lands in an abandoned fork. \
+                     Please retry."
+                        .to_owned(),
+                ))
+                .into());
+            }
+            // landed, waiting for the commitment level
+            continue;
+        }
+
+        if let Some(reason) = lifetime.expiry(rpc).await? {
+            // it may have landed after the status request
+            let landed = rpc
+                .get_signature_statuses(&[signature])
+                .await?
+                .value
+                .pop()
+                .flatten()
+                .is_some();
+            if !landed {
+                tracing::warn!("{} expired: {}", signature, reason);
+                return Err(SendError::Expired { reason, attempts });
+            }
+            continue;
+        }
+
+        if lifetime
+            .wait_limit()
+            .is_some_and(|limit| started.elapsed() >= limit)
+        {
+            tracing::warn!("{} not confirmed in time", signature);
+            return Err(SendError::Unconfirmed {
+                signature,
+                attempts,
+            });
+        }
+
+        if let Some(interval) = config.rebroadcast_interval
+            && last_sent.elapsed() >= interval
+        {
+            tracing::debug!("re-sending {}", signature);
+            match rpc.send_transaction_with_config(tx, resend_config).await {
+                Ok(_) => attempts += 1,
+                Err(error) => tracing::warn!("re-sending {} failed: {}", signature, error),
+            }
+            last_sent = Instant::now();
+        }
+    }
+}
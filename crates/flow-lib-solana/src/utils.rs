@@ -74,20 +74,16 @@ pub fn simple_execute_svc(
         let config = config.clone();
         let helius = helius.clone();
         async move {
-            Ok(execute::Response {
-                signature: Some(
-                    req.instructions
-                        .execute(
-                            &rpc,
-                            helius.as_deref(),
-                            network,
-                            signer,
-                            flow_run_id,
-                            config,
-                        )
-                        .await?,
-                ),
-            })
+            req.instructions
+                .execute(
+                    &rpc,
+                    helius.as_deref(),
+                    network,
+                    signer,
+                    flow_run_id,
+                    config,
+                )
+                .await
         }
     };
     execute::Svc::new(tower::service_fn(handle))
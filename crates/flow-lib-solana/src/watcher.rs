@@ -1,6 +1,7 @@
 use super::{Pubkey, Signature};
 use super::{parse_action_memo, parse_rpc_memo_field};
 use anyhow::{anyhow, ensure};
+use flow_lib::context::execute::ExpiryReason;
 use solana_clock::MAX_HASH_AGE_IN_SECONDS;
 use solana_commitment_config::{CommitmentConfig, CommitmentLevel};
 use solana_program::hash::Hash;
@@ -12,6 +13,81 @@ use std::time::{Duration, Instant};
 
 pub const ACTION_CONFIRM_TIMEOUT: Duration = Duration::from_secs(60 * 3);
 
+/// What makes a transaction expire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransactionLifetime {
+    /// Expires when the blockhash is too old, `last_valid_block_height` is
+    /// unknown if a wallet replaced the blockhash.
+    Blockhash {
+        blockhash: Hash,
+        last_valid_block_height: Option<u64>,
+    },
+    /// Expires when the nonce account is advanced by another transaction.
+    Nonce { account: Pubkey, nonce: Hash },
+}
+
+impl TransactionLifetime {
+    /// The transaction's `recent_blockhash`.
+    pub fn blockhash(&self) -> Hash {
+        match self {
+            TransactionLifetime::Blockhash { blockhash, .. } => *blockhash,
+            TransactionLifetime::Nonce { nonce, .. } => *nonce,
+        }
+    }
+
+    /// How long to wait for a transaction that is not found.
+    ///
+    /// A nonce transaction never expires on its own, so we stop waiting after
+    /// the time a blockhash transaction would have. It may still land after
+    /// that.
+    pub fn wait_limit(&self) -> Option<Duration> {
+        match self {
+            TransactionLifetime::Blockhash { .. } => None,
+            TransactionLifetime::Nonce { .. } => {
+                Some(Duration::from_secs(MAX_HASH_AGE_IN_SECONDS as u64))
+            }
+        }
+    }
+
+    /// Check if a transaction that is not found can no longer land.
+    pub async fn expiry(
+        &self,
+        rpc: &RpcClient,
+    ) -> Result<Option<ExpiryReason>, client_error::Error> {
+        Ok(match self {
+            TransactionLifetime::Blockhash {
+                last_valid_block_height: Some(last_valid_block_height),
+                ..
+            } => {
+                let block_height = rpc
+                    .get_block_height_with_commitment(CommitmentConfig::processed())
+                    .await?;
+                (block_height > *last_valid_block_height).then_some(
+                    ExpiryReason::BlockHeightExceeded {
+                        last_valid_block_height: *last_valid_block_height,
+                        block_height,
+                    },
+                )
+            }
+            TransactionLifetime::Blockhash { blockhash, .. } => (!rpc
+                .is_blockhash_valid(blockhash, CommitmentConfig::processed())
+                .await?)
+                .then_some(ExpiryReason::BlockhashNotFound),
+            TransactionLifetime::Nonce { account, nonce } => {
+                let data = rpc
+                    .get_account_with_commitment(account, CommitmentConfig::processed())
+                    .await?
+                    .value;
+                let current =
+                    data.and_then(|a| crate::nonce::parse_nonce_account(account, &a.data));
+                current
+                    .is_none_or(|info| info.blockhash != *nonce)
+                    .then_some(ExpiryReason::NonceAdvanced)
+            }
+        })
+    }
+}
+
 pub async fn confirm_action_transaction(
     rpc: &RpcClient,
     action_identity: Pubkey,
@@ -73,7 +149,7 @@ pub async fn confirm_action_transaction(
 pub async fn confirm_transaction(
     rpc: &RpcClient,
     signature: &Signature,
-    recent_blockhash: &Hash,
+    lifetime: &TransactionLifetime,
     commitment: CommitmentConfig,
 ) -> Result<(), client_error::Error> {
     let mut confirmations = 0;
@@ -86,10 +162,15 @@ pub async fn confirm_transaction(
             .get_signature_status_with_commitment(signature, CommitmentConfig::processed())
             .await?;
         if status.is_none() {
-            let blockhash_not_found = !rpc
-                .is_blockhash_valid(recent_blockhash, CommitmentConfig::processed())
-                .await?;
-            if blockhash_not_found && now.elapsed() >= confirm_transaction_initial_timeout {
+            let expired = lifetime.expiry(rpc).await?.is_some()
+                || lifetime
+                    .wait_limit()
+                    .is_some_and(|limit| now.elapsed() >= limit);
+            if expired && now.elapsed() >= confirm_transaction_initial_timeout {
+                // it may have landed after the status request
+                let status = rpc
+                    .get_signature_status_with_commitment(signature, CommitmentConfig::processed())
+                    .await?;
                 break (signature, status);
             }
         } else {
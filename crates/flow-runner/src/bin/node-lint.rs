@@ -0,0 +1,72 @@
+#![allow(clippy::print_stderr, clippy::print_stdout)]
+
+use clap::Parser;
+use flow_lib::command::builder::lint::{Level, collect_port_schemas, find_definitions, lint_file};
+use std::{path::PathBuf, process::ExitCode};
+
+// link commands so that their port schemas are registered
+use cmds_bun as _;
+use cmds_deno as _;
+use cmds_image as _;
+use cmds_pdg as _;
+use cmds_python as _;
+use cmds_solana as _;
+use cmds_std as _;
+use cmds_wasm as _;
+
+/// Lint node definitions, and migrate legacy ones to V2.
+///
+/// Exits with 1 if a definition has errors, and with 2 if a file could not be
+/// read or written.
+#[derive(Parser, Debug)]
+#[command(name = "node-lint")]
+struct Args {
+    /// Directory or node definition file
+    #[arg(default_value = ".")]
+    paths: Vec<PathBuf>,
+    /// Rewrite legacy definitions in the V2 format
+    #[arg(long)]
+    fix: bool,
+}
+
+fn run(args: Args) -> Result<bool, anyhow::Error> {
+    let mut definitions = Vec::new();
+    for path in &args.paths {
+        definitions.extend(find_definitions(path)?);
+    }
+
+    let schemas = collect_port_schemas();
+    let (mut errors, mut warnings, mut migrated) = (0, 0, 0);
+    for definition in &definitions {
+        let report = lint_file(&schemas, definition, args.fix)?;
+        errors += report.count(Level::Error);
+        warnings += report.count(Level::Warning);
+        if report.migrated {
+            migrated += 1;
+        }
+        if report.migrated || !report.issues.is_empty() {
+            print!("{report}");
+        }
+    }
+
+    print!(
+        "{} definitions, {errors} errors, {warnings} warnings",
+        definitions.len()
+    );
+    if args.fix {
+        print!(", {migrated} migrated");
+    }
+    println!();
+    Ok(errors == 0)
+}
+
+fn main() -> ExitCode {
+    match run(Args::parse()) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(error) => {
+            eprintln!("error: {error:#}");
+            ExitCode::from(2)
+        }
+    }
+}
@@ -0,0 +1,77 @@
+#![allow(clippy::print_stderr, clippy::print_stdout)]
+
+use clap::Parser;
+use flow_lib::command::{
+    CommandFactory,
+    builder::fixture::{find_definitions, test_definition},
+};
+use std::{path::PathBuf, process::ExitCode};
+use tracing_subscriber::EnvFilter;
+
+// avoid commands being optimized out by the compiler
+use cmds_bun as _;
+use cmds_deno as _;
+use cmds_image as _;
+use cmds_pdg as _;
+use cmds_python as _;
+use cmds_solana as _;
+use cmds_std as _;
+use cmds_wasm as _;
+
+/// Run the test fixtures of node definitions.
+///
+/// Exits with 1 if a case failed, and with 2 if a fixture could not be run.
+#[derive(Parser, Debug)]
+#[command(name = "node-test")]
+struct Args {
+    /// Directory, node definition or fixture file
+    #[arg(default_value = ".")]
+    paths: Vec<PathBuf>,
+}
+
+async fn run(args: Args) -> Result<bool, anyhow::Error> {
+    let mut definitions = Vec::new();
+    for path in &args.paths {
+        definitions.extend(find_definitions(path)?);
+    }
+    if definitions.is_empty() {
+        eprintln!("no fixtures found");
+        return Ok(true);
+    }
+
+    let factory = CommandFactory::collect();
+    let (mut passed, mut failed) = (0, 0);
+    for definition in &definitions {
+        let report = test_definition(&factory, definition).await?;
+        for case in &report.cases {
+            if case.passed() {
+                passed += 1;
+            } else {
+                failed += 1;
+            }
+        }
+        print!("{report}");
+    }
+
+    println!("{passed} passed, {failed} failed");
+    Ok(failed == 0)
+}
+
+#[actix::main]
+async fn main() -> ExitCode {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn")),
+        )
+        .with_writer(std::io::stderr)
+        .init();
+
+    match run(Args::parse()).await {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(error) => {
+            eprintln!("error: {error:#}");
+            ExitCode::from(2)
+        }
+    }
+}
@@ -0,0 +1,345 @@
+//! Run flows from exported flow JSON, without flow-server or a database.
+//!
+//! Nodes run with the commands linked into the binary, signature requests are
+//! signed with local keypairs, and events are passed to the caller as they
+//! happen.
+
+use anyhow::{Context as _, anyhow, bail};
+use flow::{
+    flow_graph::{FlowRunResult, StopSignal},
+    flow_registry::{BackendServices, FlowRegistry, StartFlowOptions, get_flow, new_flow_run},
+};
+use flow_lib::{
+    FlowId, FlowRunId, NodeId, User, Value, ValueSet,
+    command::parse_value_tagged_or_json,
+    config::client::{ClientConfig, ClientConfigV2},
+    context::signer,
+    flow_run_events::Event,
+    solana::{Keypair, KeypairExt, Pubkey},
+    utils::tower_client::unimplemented_svc,
+};
+use futures::{
+    StreamExt,
+    stream::{BoxStream, SelectAll},
+};
+use hashbrown::HashMap;
+use serde_json::Value as JsonValue;
+use solana_signer::Signer;
+use std::{future::ready, path::Path, sync::Arc, time::Duration};
+use tokio::sync::mpsc;
+
+/// How long to wait for the remaining events after the flow finished.
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Parse an exported flow, either `{"flow": {...}}` like the files in
+/// `test_files/` and the output of `spo flow export`, or a bare config.
+///
+/// Both the v1 `ClientConfig` and the `ClientConfigV2` format stored in
+/// `flows_v2` are accepted.
+pub fn parse_flow(json: &str) -> Result<ClientConfig, anyhow::Error> {
+    let json = match serde_json::from_str::<JsonValue>(json).context("invalid JSON")? {
+        JsonValue::Object(mut map) if map.contains_key("flow") => map.remove("flow").unwrap(),
+        json => json,
+    };
+    match serde_json::from_value::<ClientConfig>(json.clone()) {
+        Ok(config) => Ok(config),
+        Err(error) => serde_json::from_value::<ClientConfigV2>(json)
+            .map(Into::into)
+            .map_err(|_| error)
+            .context("invalid flow config"),
+    }
+}
+
+/// Parse a `NAME=VALUE` flow input.
+///
+/// The value is read as JSON, plain or tagged with value types, and used as a
+/// string if it is not valid JSON.
+pub fn parse_input(s: &str) -> Result<(String, Value), anyhow::Error> {
+    let (name, value) = s
+        .split_once('=')
+        .ok_or_else(|| anyhow!("expected NAME=VALUE, got {s:?}"))?;
+    if name.is_empty() {
+        bail!("empty input name in {s:?}");
+    }
+    let value = match serde_json::from_str::<JsonValue>(value) {
+        Ok(json) => parse_value_tagged_or_json(json),
+        Err(_) => Value::String(value.to_owned()),
+    };
+    Ok((name.to_owned(), value))
+}
+
+/// Parse flow inputs from a JSON object.
+pub fn parse_inputs(json: &str) -> Result<ValueSet, anyhow::Error> {
+    let map = serde_json::from_str::<serde_json::Map<String, JsonValue>>(json)
+        .context("inputs must be a JSON object")?;
+    Ok(map
+        .into_iter()
+        .map(|(name, json)| (name, parse_value_tagged_or_json(json)))
+        .collect())
+}
+
+/// Read a keypair file, either a JSON byte array as written by
+/// `solana-keygen` or a base58 string.
+pub fn read_keypair_file(path: &Path) -> Result<Keypair, anyhow::Error> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("could not read {}", path.display()))?;
+    let content = content.trim();
+    let keypair = if content.starts_with('[') {
+        let bytes = serde_json::from_str::<Vec<u8>>(content)
+            .with_context(|| format!("invalid keypair file {}", path.display()))?;
+        Keypair::try_from(&bytes[..])
+            .map_err(|error| anyhow!("invalid keypair file {}: {error}", path.display()))?
+    } else {
+        <Keypair as KeypairExt>::from_str(content)
+            .with_context(|| format!("invalid keypair file {}", path.display()))?
+    };
+    Ok(keypair)
+}
+
+/// A signer service that signs with local keypairs, and refuses requests for
+/// other public keys.
+pub fn keypair_signer(keypairs: impl IntoIterator<Item = Keypair>) -> signer::Svc {
+    let keypairs = Arc::new(
+        keypairs
+            .into_iter()
+            .map(|keypair| (keypair.pubkey(), keypair))
+            .collect::<HashMap<Pubkey, Keypair>>(),
+    );
+    signer::Svc::new(tower::service_fn(move |req: signer::SignatureRequest| {
+        let result = req.validate().and_then(|_| {
+            let keypair = keypairs
+                .get(&req.pubkey)
+                .ok_or_else(|| signer::Error::Pubkey(req.pubkey.to_string()))?;
+            Ok(signer::SignatureResponse {
+                signature: keypair.sign_message(&req.message),
+                new_message: None,
+            })
+        });
+        ready(result)
+    }))
+}
+
+/// Whether a run had node or flow errors.
+pub fn has_errors(result: &FlowRunResult) -> bool {
+    !result.node_errors.is_empty() || !result.flow_errors.is_empty()
+}
+
+/// Names of the nodes of `flows`, to print events.
+pub fn node_names<'a>(
+    flows: impl IntoIterator<Item = &'a ClientConfig>,
+) -> HashMap<NodeId, String> {
+    flows
+        .into_iter()
+        .flat_map(|flow| &flow.nodes)
+        .map(|node| (node.id, node.data.node_id.clone()))
+        .collect()
+}
+
+pub struct LocalRun {
+    pub entrypoint: ClientConfig,
+    /// Flows called by interflow nodes.
+    pub flows: Vec<ClientConfig>,
+    pub inputs: ValueSet,
+    pub keypairs: Vec<Keypair>,
+    /// Stop the flow after this duration.
+    pub timeout: Option<Duration>,
+}
+
+impl LocalRun {
+    /// Run the flow to completion, calling `on_event` with the events of the
+    /// flow and of the flows it calls.
+    ///
+    /// Must be called inside an actix system.
+    pub async fn run(
+        self,
+        mut on_event: impl FnMut(Event),
+    ) -> Result<FlowRunResult, anyhow::Error> {
+        let entrypoint = self.entrypoint.id;
+        let timeout = self.timeout;
+        let owner = User::new(self.entrypoint.user_id);
+        let flows = Arc::new(
+            self.flows
+                .into_iter()
+                .chain([self.entrypoint])
+                .map(|flow| (flow.id, flow))
+                .collect::<HashMap<FlowId, ClientConfig>>(),
+        );
+        let get_flow = tower::service_fn(move |req: get_flow::Request| {
+            ready(
+                flows
+                    .get(&req.flow_id)
+                    .cloned()
+                    .map(|config| get_flow::Response { config })
+                    .ok_or(get_flow::Error::NotFound),
+            )
+        });
+
+        let stop = StopSignal::new();
+        let (streams_tx, mut streams_rx) = mpsc::unbounded_channel::<BoxStream<'static, Event>>();
+        let new_flow_run = new_flow_run::Svc::new(tower::service_fn({
+            let stop = stop.clone();
+            move |req: new_flow_run::Request| {
+                let flow_run_id = FlowRunId::new_v4();
+                streams_tx.send(req.stream).ok();
+                ready(Ok::<_, new_flow_run::Error>(new_flow_run::Response {
+                    flow_run_id,
+                    stop_signal: stop.clone(),
+                    stop_shared_signal: StopSignal::new(),
+                    span: tracing::info_span!("flow_run", %flow_run_id),
+                }))
+            }
+        }));
+
+        let mut registry = FlowRegistry::fetch()
+            .flow_owner(owner)
+            .started_by(owner)
+            .shared_with(Vec::new())
+            .entrypoint(entrypoint)
+            .environment(HashMap::new())
+            .endpoints(<_>::default())
+            .signers_info(JsonValue::Null)
+            .backend(BackendServices {
+                api_input: unimplemented_svc(),
+                signer: keypair_signer(self.keypairs),
+                token: unimplemented_svc(),
+                get_secret: unimplemented_svc(),
+                new_flow_run,
+                get_previous_values: unimplemented_svc(),
+                helius: None,
+            })
+            .get_flow(get_flow)
+            .call()
+            .await
+            .context("could not build flow")?;
+
+        let (_, mut run) = registry
+            .start(entrypoint, self.inputs, StartFlowOptions::default())
+            .await
+            .context("could not start flow")?;
+
+        let mut timeout = std::pin::pin!(async move {
+            match timeout {
+                Some(timeout) => tokio::time::sleep(timeout).await,
+                None => std::future::pending().await,
+            }
+        });
+        let mut stopping = false;
+        let mut events = SelectAll::new();
+        let result = loop {
+            tokio::select! {
+                result = &mut run => break result.context("flow task failed")?,
+                Some(stream) = streams_rx.recv() => events.push(stream),
+                Some(event) = events.next(), if !events.is_empty() => on_event(event),
+                _ = &mut timeout, if !stopping => {
+                    stopping = true;
+                    stop.stop(0, Some("timeout".to_owned()));
+                }
+                _ = tokio::signal::ctrl_c(), if !stopping => {
+                    stopping = true;
+                    stop.stop(0, Some("interrupted".to_owned()));
+                }
+            }
+        };
+
+        while let Ok(stream) = streams_rx.try_recv() {
+            events.push(stream);
+        }
+        tokio::time::timeout(DRAIN_TIMEOUT, async {
+            while let Some(event) = events.next().await {
+                on_event(event);
+            }
+        })
+        .await
+        .ok();
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_input() {
+        assert_eq!(
+            parse_input("amount=1.5").unwrap(),
+            ("amount".to_owned(), Value::Decimal("1.5".parse().unwrap()))
+        );
+        assert_eq!(
+            parse_input("name=hello").unwrap(),
+            ("name".to_owned(), Value::String("hello".to_owned()))
+        );
+        assert_eq!(
+            parse_input(r#"n={"U":"10"}"#).unwrap(),
+            ("n".to_owned(), Value::U64(10))
+        );
+        assert_eq!(
+            parse_input(r#"m={"a":{"S":"b"}}"#).unwrap(),
+            (
+                "m".to_owned(),
+                Value::Map(flow_lib::value::map! { "a" => "b" })
+            )
+        );
+        assert_eq!(
+            parse_input("eq=a=b").unwrap(),
+            ("eq".to_owned(), Value::String("a=b".to_owned()))
+        );
+        assert!(parse_input("amount").is_err());
+        assert!(parse_input("=1").is_err());
+    }
+
+    #[test]
+    fn test_parse_flow() {
+        let json = include_str!("../../../test_files/foreach.json");
+        let wrapped = parse_flow(json).unwrap();
+        let bare = serde_json::from_str::<JsonValue>(json).unwrap()["flow"].to_string();
+        assert_eq!(parse_flow(&bare).unwrap().nodes.len(), wrapped.nodes.len());
+    }
+
+    #[actix::test]
+    async fn test_keypair_signer() {
+        use tower::ServiceExt;
+
+        let keypair = Keypair::new();
+        let pubkey = keypair.pubkey();
+        let signer = keypair_signer([keypair.insecure_clone()]);
+        let request = |pubkey| signer::SignatureRequest {
+            id: None,
+            time: <_>::default(),
+            token: None,
+            pubkey,
+            message: b"hello".to_vec().into(),
+            timeout: Duration::from_secs(1),
+            kind: <_>::default(),
+            flow_run_id: None,
+            signatures: None,
+        };
+        let response = signer.clone().oneshot(request(pubkey)).await.unwrap();
+        assert_eq!(response.signature, keypair.sign_message(b"hello"));
+        assert!(signer.oneshot(request(Pubkey::new_unique())).await.is_err());
+    }
+
+    #[actix::test]
+    async fn test_run_foreach() {
+        let entrypoint = parse_flow(include_str!("../../../test_files/foreach.json")).unwrap();
+        let mut events = 0;
+        let result = LocalRun {
+            entrypoint,
+            flows: Vec::new(),
+            inputs: <_>::default(),
+            keypairs: Vec::new(),
+            timeout: None,
+        }
+        .run(|_| events += 1)
+        .await
+        .unwrap();
+        assert!(!has_errors(&result));
+        assert_eq!(result.output["keypairs"], Value::Array([
+            Value::new_keypair_bs58("3LUpzbebV5SCftt8CPmicbKxNtQhtJegEz4n8s6LBf3b1s4yfjLapgJhbMERhP73xLmWEP2XJ2Rz7Y3TFiYgTpXv").unwrap(),
+            Value::new_keypair_bs58("5WmnrZDv6oM4tkN5SfSTf5MGyPLPV4HjHGQZN4JiBDCxkcetz5LTYYhRwNxKXY5BCWBaVYALZ2GkpBpU5uRr2jMa").unwrap(),
+            Value::new_keypair_bs58("XunqA3LMMvpjD1JH9HMp2eSmvEaSoTdGhnNrseoFW9rMsSRhVefZYcTRDdfgVxoyJJvLwF2gzV4zyYMGiAoJaSS").unwrap(),
+        ].to_vec()));
+        assert!(events > 0);
+    }
+}
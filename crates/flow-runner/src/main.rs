@@ -0,0 +1,179 @@
+#![allow(clippy::print_stderr, clippy::print_stdout)]
+
+use anyhow::Context;
+use clap::Parser;
+use flow_lib::{NodeId, ValueSet, flow_run_events::Event};
+use flow_runner::{
+    LocalRun, has_errors, node_names, parse_flow, parse_input, parse_inputs, read_keypair_file,
+};
+use hashbrown::HashMap;
+use solana_signer::Signer;
+use std::{
+    path::{Path, PathBuf},
+    process::ExitCode,
+    time::Duration,
+};
+use tracing_subscriber::EnvFilter;
+
+// avoid commands being optimized out by the compiler
+use cmds_bun as _;
+use cmds_deno as _;
+use cmds_image as _;
+use cmds_pdg as _;
+use cmds_python as _;
+use cmds_solana as _;
+use cmds_std as _;
+use cmds_wasm as _;
+
+/// Run a flow locally, without flow-server.
+///
+/// Exits with 1 if a node or the flow failed, and with 2 if the flow could
+/// not be run.
+#[derive(Parser, Debug)]
+#[command(name = "flow-runner")]
+struct Args {
+    /// Path to exported flow JSON
+    flow: PathBuf,
+    /// Flow input, value is JSON or a string
+    #[arg(long = "input", short, value_name = "NAME=VALUE")]
+    inputs: Vec<String>,
+    /// JSON file with flow inputs, `--input` takes precedence
+    #[arg(long, value_name = "PATH")]
+    inputs_file: Option<PathBuf>,
+    /// Keypair file to sign with, can be repeated
+    #[arg(long = "keypair", short, value_name = "PATH")]
+    keypairs: Vec<PathBuf>,
+    /// Flow called by interflow nodes, can be repeated
+    #[arg(long = "with-flow", value_name = "PATH")]
+    flows: Vec<PathBuf>,
+    /// Write outputs to a file instead of stdout
+    #[arg(long, short, value_name = "PATH")]
+    output: Option<PathBuf>,
+    /// Print events as JSON lines
+    #[arg(long)]
+    json_events: bool,
+    /// Stop the flow after this many seconds
+    #[arg(long, value_name = "SECS")]
+    timeout: Option<u64>,
+}
+
+fn read_flow(path: &Path) -> Result<flow_lib::config::client::ClientConfig, anyhow::Error> {
+    let json = std::fs::read_to_string(path)
+        .with_context(|| format!("could not read {}", path.display()))?;
+    parse_flow(&json).with_context(|| format!("invalid flow {}", path.display()))
+}
+
+fn print_event(names: &HashMap<NodeId, String>, json: bool, event: &Event) {
+    if json {
+        if let Ok(line) = serde_json::to_string(event) {
+            eprintln!("{line}");
+        }
+        return;
+    }
+    let node = |id: &NodeId, times: u32| {
+        let name = names.get(id).map_or("node", String::as_str);
+        let id = id.simple().to_string();
+        format!("{name}[{}]#{times}", &id[..8])
+    };
+    let time = event.time().format("%H:%M:%S%.3f");
+    match event {
+        Event::FlowStart(_) => eprintln!("{time} flow started"),
+        Event::FlowError(e) => eprintln!("{time} flow error: {}", e.error),
+        Event::FlowLog(e) => eprintln!("{time} {}: {}", e.level, e.content),
+        Event::FlowFinish(e) => {
+            eprintln!("{time} flow finished, {} nodes not run", e.not_run.len())
+        }
+        Event::NodeStart(e) => eprintln!("{time} {} started", node(&e.node_id, e.times)),
+        Event::NodeOutput(_) => {}
+        Event::NodeError(e) => {
+            eprintln!("{time} {} failed: {}", node(&e.node_id, e.times), e.error)
+        }
+        Event::NodeLog(e) => eprintln!(
+            "{time} {} {}: {}",
+            node(&e.node_id, e.times),
+            e.level,
+            e.content
+        ),
+        Event::NodeFinish(e) => eprintln!("{time} {} finished", node(&e.node_id, e.times)),
+        Event::SignatureRequest(e) => eprintln!("{time} signature requested for {}", e.pubkey),
+        Event::ApiInput(e) => eprintln!("{time} waiting for API input at {}", e.url),
+    }
+}
+
+async fn run(args: Args) -> Result<bool, anyhow::Error> {
+    let entrypoint = read_flow(&args.flow)?;
+    let flows = args
+        .flows
+        .iter()
+        .map(|path| read_flow(path))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut inputs = match &args.inputs_file {
+        Some(path) => {
+            let json = std::fs::read_to_string(path)
+                .with_context(|| format!("could not read {}", path.display()))?;
+            parse_inputs(&json).with_context(|| format!("invalid inputs {}", path.display()))?
+        }
+        None => ValueSet::new(),
+    };
+    for input in &args.inputs {
+        let (name, value) = parse_input(input)?;
+        inputs.insert(name, value);
+    }
+
+    let keypairs = args
+        .keypairs
+        .iter()
+        .map(|path| read_keypair_file(path))
+        .collect::<Result<Vec<_>, _>>()?;
+    for keypair in &keypairs {
+        eprintln!("signing with {}", keypair.pubkey());
+    }
+
+    let names = node_names(flows.iter().chain([&entrypoint]));
+    let result = LocalRun {
+        entrypoint,
+        flows,
+        inputs,
+        keypairs,
+        timeout: args.timeout.map(Duration::from_secs),
+    }
+    .run(|event| print_event(&names, args.json_events, &event))
+    .await?;
+
+    let output = serde_json::to_string_pretty(&result.output)?;
+    match &args.output {
+        Some(path) => std::fs::write(path, output + "\n")
+            .with_context(|| format!("could not write {}", path.display()))?,
+        None => println!("{output}"),
+    }
+
+    let success = !has_errors(&result);
+    if !success {
+        eprintln!(
+            "{} node errors, {} flow errors",
+            result.node_errors.len(),
+            result.flow_errors.len()
+        );
+    }
+    Ok(success)
+}
+
+#[actix::main]
+async fn main() -> ExitCode {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn")),
+        )
+        .with_writer(std::io::stderr)
+        .init();
+
+    match run(Args::parse()).await {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(error) => {
+            eprintln!("error: {error:#}");
+            ExitCode::from(2)
+        }
+    }
+}
@@ -0,0 +1,40 @@
+use super::{super::prelude::*, write_item::parse_error};
+use db::connection::{BatchOutcome, ItemOp};
+
+pub fn service(config: &Config) -> impl HttpServiceFactory + 'static {
+    web::resource("/batch")
+        .wrap(config.cors())
+        .route(web::post().to(batch))
+}
+
+/// Maximum number of operations in one batch.
+const MAX_OPS: usize = 100;
+
+#[derive(Deserialize)]
+struct Params {
+    store: String,
+    ops: Vec<ItemOp>,
+}
+
+/// Apply all operations in one transaction.
+///
+/// If a condition does not hold nothing is written, and the response tells which operation failed.
+async fn batch(
+    params: web::Json<Params>,
+    user: Auth<auth_v1::AuthenticatedUser>,
+    db: web::Data<DbPool>,
+) -> Result<web::Json<BatchOutcome>, Error> {
+    if params.ops.len() > MAX_OPS {
+        return Err(Error::custom(
+            StatusCode::BAD_REQUEST,
+            format!("too many operations, maximum is {MAX_OPS}"),
+        ));
+    }
+    let outcome = db
+        .get_admin_conn()
+        .await?
+        .write_items(user.user_id(), &params.store, &params.ops)
+        .await
+        .map_err(parse_error)?;
+    Ok(web::Json(outcome))
+}
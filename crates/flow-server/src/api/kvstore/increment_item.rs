@@ -0,0 +1,65 @@
+use super::{super::prelude::*, write_item::parse_error};
+use db::connection::{BatchOutcome, ItemOp};
+use value::Value;
+
+pub fn service(config: &Config) -> impl HttpServiceFactory + 'static {
+    web::resource("/increment_item")
+        .wrap(config.cors())
+        .route(web::post().to(increment_item))
+}
+
+#[derive(Deserialize)]
+struct Params {
+    store: String,
+    key: String,
+    #[serde(default = "default_by")]
+    by: i64,
+    #[serde(default)]
+    ttl_seconds: Option<i64>,
+}
+
+fn default_by() -> i64 {
+    1
+}
+
+#[derive(Serialize)]
+struct Output {
+    value: Value,
+    version: i64,
+}
+
+async fn increment_item(
+    params: web::Json<Params>,
+    user: Auth<auth_v1::AuthenticatedUser>,
+    db: web::Data<DbPool>,
+) -> Result<web::Json<Output>, Error> {
+    let params = params.into_inner();
+    let op = ItemOp::Increment {
+        key: params.key,
+        by: params.by,
+        ttl_seconds: params.ttl_seconds,
+    };
+    let outcome = db
+        .get_admin_conn()
+        .await?
+        .write_items(user.user_id(), &params.store, &[op])
+        .await
+        .map_err(parse_error)?;
+    match outcome {
+        BatchOutcome::Committed { mut results } => match results.pop() {
+            Some(db::connection::ItemOpResult {
+                new_value: Some(value),
+                version,
+                ..
+            }) => Ok(web::Json(Output { value, version })),
+            _ => Err(Error::custom(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "no result",
+            )),
+        },
+        BatchOutcome::ConditionFailed { .. } => Err(Error::custom(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "unconditional increment failed",
+        )),
+    }
+}
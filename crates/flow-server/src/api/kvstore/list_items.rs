@@ -0,0 +1,33 @@
+use super::super::prelude::*;
+use db::connection::{KeyRange, KvItem};
+
+pub fn service(config: &Config) -> impl HttpServiceFactory + 'static {
+    web::resource("/list_items")
+        .wrap(config.cors())
+        .route(web::post().to(list_items))
+}
+
+#[derive(Deserialize)]
+struct Params {
+    store: String,
+    #[serde(flatten)]
+    range: KeyRange,
+}
+
+#[derive(Serialize)]
+struct Output {
+    items: Vec<KvItem>,
+}
+
+async fn list_items(
+    params: web::Json<Params>,
+    user: Auth<auth_v1::AuthenticatedUser>,
+    db: web::Data<DbPool>,
+) -> Result<web::Json<Output>, Error> {
+    let items = db
+        .get_user_conn(*user.user_id())
+        .await?
+        .list_items(&params.store, &params.range)
+        .await?;
+    Ok(web::Json(Output { items }))
+}
@@ -1,6 +1,9 @@
 pub mod create_store;
 pub mod delete_store;
 
+pub mod batch;
 pub mod delete_item;
+pub mod increment_item;
+pub mod list_items;
 pub mod read_item;
 pub mod write_item;
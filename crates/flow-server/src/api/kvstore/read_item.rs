@@ -1,4 +1,5 @@
 use super::super::prelude::*;
+use chrono::{DateTime, Utc};
 use value::Value;
 
 pub fn service(config: &Config) -> impl HttpServiceFactory + 'static {
@@ -16,6 +17,8 @@ struct Params {
 #[derive(Serialize)]
 struct Output {
     value: Value,
+    version: i64,
+    expires_at: Option<DateTime<Utc>>,
 }
 
 async fn read_item(
@@ -29,7 +32,11 @@ async fn read_item(
         .read_item(&params.store, &params.key)
         .await?;
     match opt {
-        Some(value) => Ok(web::Json(Output { value })),
+        Some(item) => Ok(web::Json(Output {
+            value: item.value,
+            version: item.version,
+            expires_at: item.expires_at,
+        })),
         None => Err(Error::custom(StatusCode::NOT_FOUND, "not found")),
     }
 }
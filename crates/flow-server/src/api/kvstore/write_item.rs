@@ -1,4 +1,5 @@
 use super::super::prelude::*;
+use db::connection::{BatchOutcome, ItemOp};
 use value::Value;
 
 pub fn service(config: &Config) -> impl HttpServiceFactory + 'static {
@@ -12,14 +13,22 @@ struct Params {
     store: String,
     key: String,
     value: Value,
+    /// Only write if the item is at this version, `0` means the item must not exist.
+    #[serde(default)]
+    expected_version: Option<i64>,
+    #[serde(default)]
+    ttl_seconds: Option<i64>,
 }
 
 #[derive(Serialize)]
 struct Output {
     old_value: Option<Value>,
+    /// New version if applied, otherwise the current version.
+    version: i64,
+    applied: bool,
 }
 
-fn parse_error(e: DbError) -> Error {
+pub(super) fn parse_error(e: DbError) -> Error {
     if let DbError::Execute { error, context, .. } = &e {
         let name = error.as_db_error().and_then(|e| e.constraint());
         if name == Some("kvstore_user_id_store_name_fkey") {
@@ -30,6 +39,9 @@ fn parse_error(e: DbError) -> Error {
             return Error::custom(StatusCode::FORBIDDEN, "user's storage limit exceeded");
         }
     }
+    if let DbError::LogicError(error) = &e {
+        return Error::custom(StatusCode::BAD_REQUEST, error);
+    }
     e.into()
 }
 
@@ -38,11 +50,37 @@ async fn write_item(
     user: Auth<auth_v1::AuthenticatedUser>,
     db: web::Data<DbPool>,
 ) -> Result<web::Json<Output>, Error> {
-    let old_value = db
+    let params = params.into_inner();
+    let op = ItemOp::Write {
+        key: params.key,
+        value: params.value,
+        expected_version: params.expected_version,
+        ttl_seconds: params.ttl_seconds,
+    };
+    let outcome = db
         .get_admin_conn()
         .await?
-        .insert_or_replace_item(user.user_id(), &params.store, &params.key, &params.value)
+        .write_items(user.user_id(), &params.store, &[op])
         .await
         .map_err(parse_error)?;
-    Ok(web::Json(Output { old_value }))
+    let output = match outcome {
+        BatchOutcome::Committed { mut results } => {
+            let result = results
+                .pop()
+                .ok_or_else(|| Error::custom(StatusCode::INTERNAL_SERVER_ERROR, "no result"))?;
+            Output {
+                old_value: result.old_value,
+                version: result.version,
+                applied: true,
+            }
+        }
+        BatchOutcome::ConditionFailed {
+            current_version, ..
+        } => Output {
+            old_value: None,
+            version: current_version,
+            applied: false,
+        },
+    };
+    Ok(web::Json(output))
 }
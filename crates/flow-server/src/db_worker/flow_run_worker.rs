@@ -627,6 +627,7 @@ async fn save_to_db(
                     times,
                     error,
                     time,
+                    kind,
                 }) => match new_nodes.get_mut(&(node_id, times)) {
                     Some(node) => {
                         if let Some(errors) = &mut node.errors {
@@ -641,6 +642,7 @@ async fn save_to_db(
                             times,
                             error,
                             time,
+                            kind,
                         }));
                     }
                 },
@@ -648,6 +650,7 @@ async fn save_to_db(
                     time,
                     node_id,
                     times,
+                    ..
                 }) => match new_nodes.get_mut(&(node_id, times)) {
                     Some(node) => {
                         node.end_time = Some(time);
@@ -769,6 +772,7 @@ async fn save_to_db(
                     time,
                     node_id,
                     times,
+                    ..
                 }) => {
                     conn.set_node_finish(&run_id, &node_id, &(times as i32), &time)
                         .await
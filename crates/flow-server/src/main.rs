@@ -38,9 +38,13 @@ use cmds_image as _;
 #[cfg(feature = "commands")]
 use cmds_pdg as _;
 #[cfg(feature = "commands")]
+use cmds_python as _;
+#[cfg(feature = "commands")]
 use cmds_solana as _;
 #[cfg(feature = "commands")]
 use cmds_std as _;
+#[cfg(feature = "commands")]
+use cmds_wasm as _;
 
 #[actix::main]
 async fn main() {
@@ -327,7 +331,10 @@ async fn main() {
             .service(api::kvstore::delete_store::service(&config))
             .service(api::kvstore::write_item::service(&config))
             .service(api::kvstore::delete_item::service(&config))
-            .service(api::kvstore::read_item::service(&config));
+            .service(api::kvstore::read_item::service(&config))
+            .service(api::kvstore::increment_item::service(&config))
+            .service(api::kvstore::batch::service(&config))
+            .service(api::kvstore::list_items::service(&config));
 
         let deployment = web::scope("/deployment")
             .service(api::start_deployment::service(&config))
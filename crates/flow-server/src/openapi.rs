@@ -256,16 +256,56 @@ struct KvWriteParamsDoc {
     store: String,
     key: String,
     value: JsonValue,
+    expected_version: Option<i64>,
+    ttl_seconds: Option<i64>,
 }
 
 #[derive(Serialize, Deserialize, ToSchema)]
 struct KvWriteOutputDoc {
     old_value: Option<JsonValue>,
+    version: i64,
+    applied: bool,
 }
 
 #[derive(Serialize, Deserialize, ToSchema)]
 struct KvReadOutputDoc {
     value: JsonValue,
+    version: i64,
+    expires_at: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+struct KvIncrementParamsDoc {
+    store: String,
+    key: String,
+    by: Option<i64>,
+    ttl_seconds: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+struct KvIncrementOutputDoc {
+    value: JsonValue,
+    version: i64,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+struct KvBatchParamsDoc {
+    store: String,
+    ops: Vec<JsonValue>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+struct KvListParamsDoc {
+    store: String,
+    prefix: Option<String>,
+    start: Option<String>,
+    end: Option<String>,
+    limit: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+struct KvListOutputDoc {
+    items: Vec<JsonValue>,
 }
 
 #[derive(Serialize, Deserialize, ToSchema)]
@@ -599,6 +639,33 @@ fn kv_read_item_doc() {}
 )]
 fn kv_delete_item_doc() {}
 
+#[utoipa::path(
+    post,
+    path = "/kv/increment_item",
+    tag = "kv",
+    request_body = KvIncrementParamsDoc,
+    responses((status = 200, description = "Increment KV item", body = KvIncrementOutputDoc))
+)]
+fn kv_increment_item_doc() {}
+
+#[utoipa::path(
+    post,
+    path = "/kv/batch",
+    tag = "kv",
+    request_body = KvBatchParamsDoc,
+    responses((status = 200, description = "Apply KV operations atomically", body = Value))
+)]
+fn kv_batch_doc() {}
+
+#[utoipa::path(
+    post,
+    path = "/kv/list_items",
+    tag = "kv",
+    request_body = KvListParamsDoc,
+    responses((status = 200, description = "List KV items", body = KvListOutputDoc))
+)]
+fn kv_list_items_doc() {}
+
 #[utoipa::path(
     post,
     path = "/wallets/upsert",
@@ -647,6 +714,9 @@ fn data_export_doc() {}
         kv_write_item_doc,
         kv_read_item_doc,
         kv_delete_item_doc,
+        kv_increment_item_doc,
+        kv_batch_doc,
+        kv_list_items_doc,
         wallet_upsert_doc,
         data_export_doc
     ),
@@ -684,6 +754,11 @@ fn data_export_doc() {}
             KvWriteOutputDoc,
             KvReadOutputDoc,
             KvDeleteOutputDoc,
+            KvIncrementParamsDoc,
+            KvIncrementOutputDoc,
+            KvBatchParamsDoc,
+            KvListParamsDoc,
+            KvListOutputDoc,
             SubmitSignatureDoc
         )
     ),
@@ -1 +1 @@
-pub mod condition;
+pub use flow_lib::command::condition;
@@ -29,4 +29,3 @@ pub mod interflow;
 pub mod interflow_instructions;
 pub mod rhai;
 pub mod wallet;
-// pub mod wasm;
@@ -5,8 +5,8 @@ use crate::{
 use base64::prelude::*;
 use chrono::{DateTime, Utc};
 use flow_lib::{
-    CmdInputDescription, CommandType, FlowConfig, FlowId, FlowRunId, Name, NodeId, UserId,
-    ValueSet, ValueType,
+    CmdInputDescription, CommandType, FlowConfig, FlowId, FlowRunId, Name, NodeId,
+    SolanaClientConfig, UserId, ValueSet, ValueType,
     command::{
         CommandError, CommandFactory, CommandTrait, InstructionInfo, input_accepts_pubkey,
         input_is_required, keypair_outputs, output_is_optional, passthrough_outputs,
@@ -17,11 +17,15 @@ use flow_lib::{
         FlowSetServices, execute, get_jwt,
     },
     flow_run_events::{
-        EventSender, FlowError, FlowFinish, FlowStart, NODE_SPAN_NAME, NodeError, NodeFinish,
-        NodeLogSender, NodeOutput, NodeStart,
+        EventSender, FlowError, FlowFinish, FlowStart, NODE_SPAN_NAME, NodeError, NodeErrorKind,
+        NodeFinish, NodeLogSender, NodeOutput, NodeStart,
+    },
+    solana::{ExecuteOn, ExecutionConfig, Instructions, Pubkey, Wallet},
+    utils::{
+        Extensions, TowerClient,
+        net::{check_endpoint, endpoint_client, private_endpoints_allowed},
+        tower_client::CommonErrorExt,
     },
-    solana::{ExecutionConfig, Instructions, Pubkey, Wallet},
-    utils::{Extensions, TowerClient, tower_client::CommonErrorExt},
 };
 use flow_lib_solana::{InstructionsExt, find_failed_instruction, simple_execute_svc};
 use flow_rpc::flow_side::command_factory::CommandFactoryWithRemotes;
@@ -45,7 +49,7 @@ use std::{
     collections::{BTreeSet, VecDeque},
     ops::ControlFlow,
     sync::{
-        Arc, RwLock,
+        Arc, Mutex, RwLock,
         atomic::{AtomicU32, Ordering},
     },
     task::Poll,
@@ -71,6 +75,7 @@ fn command_type_label(command_type: CommandType) -> &'static str {
         CommandType::Wasm => "wasm",
         CommandType::Deno => "deno",
         CommandType::Bun => "bun",
+        CommandType::Python => "python",
     }
 }
 
@@ -144,6 +149,7 @@ pub struct FlowGraph {
     pub action_identity: Option<Pubkey>,
     pub rhai_permit: Arc<Semaphore>,
     pub bun_permit: Arc<Semaphore>,
+    pub python_permit: Arc<Semaphore>,
     pub tx_exec_config: ExecutionConfig,
     pub parent_flow_execute: Option<execute::Svc>,
     pub fees: Vec<(Pubkey, u64)>,
@@ -163,6 +169,8 @@ pub struct Node {
     pub idx: NodeIndex<u32>,
     /// List of input ports to use previous run's values
     pub use_previous_values: HashMap<Name, UsePreviousValue>,
+    /// RPC to use instead of the flow's, set in the node's config
+    pub solana_client: Option<SolanaClientConfig>,
 }
 
 impl std::fmt::Debug for Node {
@@ -176,6 +184,36 @@ impl std::fmt::Debug for Node {
     }
 }
 
+/// Where to send the result of a node's instructions, `range` is the
+/// position of its instructions in the transaction.
+struct Responder {
+    sender: oneshot::Sender<Result<execute::Response, execute::Error>>,
+    range: std::ops::Range<usize>,
+}
+
+impl Responder {
+    fn respond_all(resp: Vec<Responder>, res: &Result<execute::Response, execute::Error>) {
+        let failed_instruction = res.as_ref().err().and_then(|e| match e {
+            execute::Error::Solana { error, inserted } => {
+                find_failed_instruction(error).and_then(|pos| pos.checked_sub(*inserted))
+            }
+            _ => None,
+        });
+        for resp in resp {
+            if let Some(pos) = failed_instruction {
+                if resp.range.contains(&pos) {
+                    resp.sender.send(res.clone()).ok();
+                } else {
+                    debug_assert!(res.is_err());
+                    resp.sender.send(Err(execute::Error::TxSimFailed)).ok();
+                }
+            } else {
+                resp.sender.send(res.clone()).ok();
+            }
+        }
+    }
+}
+
 pub struct PartialOutput {
     pub node_id: NodeId,
     pub times: u32,
@@ -300,6 +338,7 @@ fn node_error(
     node_id: NodeId,
     times: u32,
     error: String,
+    kind: Option<NodeErrorKind>,
 ) {
     event_tx
         .unbounded_send(
@@ -308,6 +347,7 @@ fn node_error(
                 node_id,
                 times,
                 error: error.clone(),
+                kind,
             }
             .into(),
         )
@@ -387,6 +427,10 @@ pub enum BuildGraphError {
     NoInput((NodeId, String), String),
     #[error("node {:?}:{} has no output {:?}", .0.0, .1, .0.1)]
     NoOutput((NodeId, String), String),
+    #[error("node {0:?} has invalid solana_client config: {1}")]
+    InvalidSolanaClient(NodeId, serde_json::Error),
+    #[error("node {0:?} has solana_client url that is not allowed: {1}")]
+    SolanaClientNotAllowed(NodeId, String),
     #[error("node {node_id}:{input_name} has malformed vault_ref: {reason}")]
     InvalidVaultRef {
         node_id: NodeId,
@@ -544,6 +588,21 @@ async fn resolve_node_vault_refs(
     Ok(config)
 }
 
+/// Per-node RPC overrides are set by the flow author, they can only reach
+/// private addresses if the deployment allows it.
+///
+/// An empty `url` falls back to the cluster's URL, which is checked as well.
+async fn check_node_solana_client(config: &SolanaClientConfig) -> Result<(), String> {
+    let url = match config.url.trim() {
+        "" => config.cluster.url(),
+        url => url.to_owned(),
+    };
+    let url = url::Url::parse(&url).map_err(|error| error.to_string())?;
+    check_endpoint(&url, private_endpoints_allowed())
+        .await
+        .map_err(|error| error.to_string())
+}
+
 impl FlowGraph {
     pub fn validate_read_only(&self) -> crate::Result<()> {
         for node in self.nodes.values() {
@@ -569,6 +628,7 @@ impl FlowGraph {
         let build_started = Instant::now();
         let rhai_permit = registry.rhai_permit.clone();
         let bun_permit = registry.bun_permit.clone();
+        let python_permit = registry.python_permit.clone();
         let flow_owner_id = registry.flow_owner.id;
         let tx_exec_config = ExecutionConfig::from_env(&c.ctx.environment)
             .inspect_err(|error| tracing::error!("error parsing ExecutionConfig: {}", error))
@@ -690,6 +750,13 @@ impl FlowGraph {
         for result in results {
             let (n, command, resolved_config) = result?;
             let id = n.id;
+            let solana_client = SolanaClientConfig::from_node_config(&n.config)
+                .map_err(|error| BuildGraphError::InvalidSolanaClient(id, error))?;
+            if let Some(config) = &solana_client {
+                check_node_solana_client(config)
+                    .await
+                    .map_err(|error| BuildGraphError::SolanaClientNotAllowed(id, error))?;
+            }
             let idx = g.add_node(id);
             let node = Node {
                 id,
@@ -697,6 +764,7 @@ impl FlowGraph {
                 form_inputs: command.read_config(resolved_config),
                 command,
                 use_previous_values: <_>::default(),
+                solana_client,
             };
             nodes.insert(id, node);
         }
@@ -786,6 +854,7 @@ impl FlowGraph {
             fees: Vec::new(),
             rhai_permit,
             bun_permit,
+            python_permit,
             tx_exec_config,
             parent_flow_execute,
         };
@@ -1215,10 +1284,11 @@ impl FlowGraph {
                             info.id,
                             info.times,
                             error.to_owned(),
+                            None,
                         );
                     }
                 } else {
-                    o.resp.send(Ok(execute::Response { signature: None })).ok();
+                    o.resp.send(Ok(execute::Response::default())).ok();
                 }
             }
             Err(error) => {
@@ -1228,8 +1298,16 @@ impl FlowGraph {
                     return;
                 }
                 let err_str = format!("{error:#}");
+                let kind = NodeErrorKind::from_error(&error);
                 o.resp.send(Err(execute::Error::from_anyhow(error))).ok();
-                node_error(&s.event_tx, &mut s.result, info.id, info.times, err_str);
+                node_error(
+                    &s.event_tx,
+                    &mut s.result,
+                    info.id,
+                    info.times,
+                    err_str,
+                    kind,
+                );
             }
         }
     }
@@ -1245,6 +1323,7 @@ impl FlowGraph {
                 times,
                 finished_at,
                 result,
+                transactions,
             }) => {
                 let (resp, _) = oneshot::channel();
                 self.save_outputs(
@@ -1284,6 +1363,7 @@ impl FlowGraph {
                             missing.join(", "),
                             missing.join(", "),
                         ),
+                        None,
                     );
                 }
 
@@ -1294,6 +1374,7 @@ impl FlowGraph {
                             time: finished_at,
                             node_id: node.id,
                             times,
+                            transactions,
                         }
                         .into(),
                     )
@@ -1324,6 +1405,8 @@ impl FlowGraph {
             .into_iter()
             .map(|tx| tx.into_iter().map(|id| (id, None)).collect())
             .collect();
+        let bundle = self.use_bundle();
+        let mut bundled = Vec::new();
         for info in s.running_info.values_mut() {
             let w = match info.waiting.take() {
                 Some(w) => w,
@@ -1359,12 +1442,6 @@ impl FlowGraph {
             if is_complete {
                 let mut tx = tx.into_values().rev().flatten().collect::<Vec<_>>();
                 while let Some(w) = tx.pop() {
-                    use std::ops::Range;
-                    struct Responder {
-                        sender: oneshot::Sender<Result<execute::Response, execute::Error>>,
-                        range: Range<usize>,
-                    }
-
                     let (mut ins, resp) = {
                         let mut ins = w.instructions;
                         if let Some(signer) = self
@@ -1430,90 +1507,12 @@ impl FlowGraph {
                     }
                     metrics::histogram!("flow_instruction_batch_size")
                         .record(ins.instructions.len() as f64);
-                    let res = if s.stop.token.is_cancelled() {
-                        Err(execute::Error::Canceled(s.stop.get_reason()))
-                    } else if s.stop_shared.token.is_cancelled() {
-                        Err(execute::Error::Canceled(s.stop_shared.get_reason()))
-                    } else if ins.instructions.is_empty() {
-                        Ok(execute::Response { signature: None })
-                    } else if let Some(exec) = &self.parent_flow_execute {
-                        self.collect_flow_output(s).await;
-                        let execute_started = Instant::now();
-                        match exec.clone().ready().await {
-                            Ok(exec) => {
-                                let res = s
-                                    .stop
-                                    .race(
-                                        std::pin::pin!(s.stop_shared.race(
-                                            std::pin::pin!(exec.call(execute::Request {
-                                                instructions: ins,
-                                                output: s.result.output.clone(),
-                                            })),
-                                            execute::Error::Canceled,
-                                        )),
-                                        execute::Error::Canceled,
-                                    )
-                                    .await;
-                                metrics::histogram!(
-                                    "flow_instruction_execute_seconds",
-                                    "mode" => "parent",
-                                )
-                                .record(execute_started.elapsed().as_secs_f64());
-                                res
-                            }
-                            Err(error) => Err(error),
-                        }
-                    } else {
-                        tracing::info!("executing instructions");
-                        let config = self.tx_exec_config.clone();
-                        let network = self.ctx_data.set.solana.cluster;
-                        let execute_started = Instant::now();
-                        let res = s
-                            .stop
-                            .race(
-                                std::pin::pin!(s.stop_shared.race(
-                                    std::pin::pin!(ins.execute(
-                                        &self.ctx_svcs.set.solana_client,
-                                        self.ctx_svcs.set.helius.as_deref(),
-                                        network,
-                                        self.ctx_svcs.signer.clone(),
-                                        Some(s.flow_run_id),
-                                        config,
-                                    )),
-                                    execute::Error::Canceled,
-                                )),
-                                execute::Error::Canceled,
-                            )
-                            .await;
-                        metrics::histogram!(
-                            "flow_instruction_execute_seconds",
-                            "mode" => "local",
-                        )
-                        .record(execute_started.elapsed().as_secs_f64());
-                        res.map(|signature| execute::Response {
-                            signature: Some(signature),
-                        })
-                    };
-
-                    let failed_instruction = res.as_ref().err().and_then(|e| match e {
-                        execute::Error::Solana { error, inserted } => {
-                            find_failed_instruction(error)
-                                .and_then(|pos| pos.checked_sub(*inserted))
-                        }
-                        _ => None,
-                    });
-                    for resp in resp {
-                        if let Some(pos) = failed_instruction {
-                            if resp.range.contains(&pos) {
-                                resp.sender.send(res.clone()).ok();
-                            } else {
-                                debug_assert!(res.is_err());
-                                resp.sender.send(Err(execute::Error::TxSimFailed)).ok();
-                            }
-                        } else {
-                            resp.sender.send(res.clone()).ok();
-                        }
+                    if bundle && !ins.instructions.is_empty() {
+                        bundled.push((ins, resp));
+                        continue;
                     }
+                    let res = self.execute_instructions(s, ins).await;
+                    Responder::respond_all(resp, &res);
 
                     if let Err(error) = &res {
                         for w in tx.into_iter().rev() {
@@ -1529,9 +1528,153 @@ impl FlowGraph {
                 }
             }
         }
+        if !bundled.is_empty() {
+            self.execute_bundle(s, bundled).await;
+        }
         ControlFlow::Continue(())
     }
 
+    /// Send all transactions as one Jito bundle, see [`ExecutionConfig::jito_bundle`].
+    fn use_bundle(&self) -> bool {
+        self.tx_exec_config.jito_bundle
+            && self.parent_flow_execute.is_none()
+            && matches!(self.tx_exec_config.execute_on, ExecuteOn::CurrentMachine)
+    }
+
+    async fn execute_bundle(&self, s: &mut State, bundled: Vec<(Instructions, Vec<Responder>)>) {
+        tracing::info!("executing {} transaction(s) as a bundle", bundled.len());
+        let res = if s.stop.token.is_cancelled() {
+            Err(execute::Error::Canceled(s.stop.get_reason()))
+        } else if s.stop_shared.token.is_cancelled() {
+            Err(execute::Error::Canceled(s.stop_shared.get_reason()))
+        } else {
+            let execute_started = Instant::now();
+            let res = s
+                .stop
+                .race(
+                    std::pin::pin!(s.stop_shared.race(
+                        std::pin::pin!(flow_lib_solana::jito::execute_bundle(
+                            bundled.iter().map(|(ins, _)| ins.clone()).collect(),
+                            &self.ctx_svcs.set.solana_client,
+                            self.ctx_svcs.set.helius.as_deref(),
+                            self.ctx_data.set.solana.cluster,
+                            self.ctx_svcs.signer.clone(),
+                            Some(s.flow_run_id),
+                            &self.tx_exec_config,
+                        )),
+                        execute::Error::Canceled,
+                    )),
+                    execute::Error::Canceled,
+                )
+                .await;
+            metrics::histogram!(
+                "flow_instruction_execute_seconds",
+                "mode" => "bundle",
+            )
+            .record(execute_started.elapsed().as_secs_f64());
+            res
+        };
+
+        match res {
+            Ok(responses) => {
+                for ((_, resp), res) in bundled.into_iter().zip(responses) {
+                    Responder::respond_all(resp, &Ok(res));
+                }
+            }
+            // only returned once none of the bundle's transactions can land,
+            // so sending them again can not execute anything twice
+            Err(execute::Error::BundleNotLanded(reason)) if self.tx_exec_config.jito_fallback => {
+                tracing::warn!("bundle did not land ({}), sending transactions", reason);
+                let mut bundled = bundled.into_iter();
+                while let Some((ins, resp)) = bundled.next() {
+                    let res = self.execute_instructions(s, ins).await;
+                    Responder::respond_all(resp, &res);
+                    if let Err(error) = &res {
+                        for (_, resp) in bundled {
+                            Responder::respond_all(resp, &Err(error.clone()));
+                        }
+                        break;
+                    }
+                }
+            }
+            Err(error) => {
+                let res = Err(error);
+                for (_, resp) in bundled {
+                    Responder::respond_all(resp, &res);
+                }
+            }
+        }
+    }
+
+    async fn execute_instructions(
+        &self,
+        s: &mut State,
+        ins: Instructions,
+    ) -> Result<execute::Response, execute::Error> {
+        if s.stop.token.is_cancelled() {
+            Err(execute::Error::Canceled(s.stop.get_reason()))
+        } else if s.stop_shared.token.is_cancelled() {
+            Err(execute::Error::Canceled(s.stop_shared.get_reason()))
+        } else if ins.instructions.is_empty() {
+            Ok(execute::Response::default())
+        } else if let Some(exec) = &self.parent_flow_execute {
+            self.collect_flow_output(s).await;
+            let execute_started = Instant::now();
+            match exec.clone().ready().await {
+                Ok(exec) => {
+                    let res = s
+                        .stop
+                        .race(
+                            std::pin::pin!(s.stop_shared.race(
+                                std::pin::pin!(exec.call(execute::Request {
+                                    instructions: ins,
+                                    output: s.result.output.clone(),
+                                })),
+                                execute::Error::Canceled,
+                            )),
+                            execute::Error::Canceled,
+                        )
+                        .await;
+                    metrics::histogram!(
+                        "flow_instruction_execute_seconds",
+                        "mode" => "parent",
+                    )
+                    .record(execute_started.elapsed().as_secs_f64());
+                    res
+                }
+                Err(error) => Err(error),
+            }
+        } else {
+            tracing::info!("executing instructions");
+            let config = self.tx_exec_config.clone();
+            let network = self.ctx_data.set.solana.cluster;
+            let execute_started = Instant::now();
+            let res = s
+                .stop
+                .race(
+                    std::pin::pin!(s.stop_shared.race(
+                        std::pin::pin!(ins.execute(
+                            &self.ctx_svcs.set.solana_client,
+                            self.ctx_svcs.set.helius.as_deref(),
+                            network,
+                            self.ctx_svcs.signer.clone(),
+                            Some(s.flow_run_id),
+                            config,
+                        )),
+                        execute::Error::Canceled,
+                    )),
+                    execute::Error::Canceled,
+                )
+                .await;
+            metrics::histogram!(
+                "flow_instruction_execute_seconds",
+                "mode" => "local",
+            )
+            .record(execute_started.elapsed().as_secs_f64());
+            res
+        }
+    }
+
     fn supply_partial_run_values(&mut self, fake_node: NodeIndex<u32>, s: &mut State) {
         let out_edges = self
             .out_edges(fake_node)
@@ -1904,9 +2047,13 @@ impl FlowGraph {
         debug_assert_eq!(outputs.len(), times as usize);
         outputs.push(<_>::default());
         let rhai_permit = self.rhai_permit.clone();
-        let bun_permit = self.bun_permit.clone();
         let is_rhai_script = rhai_script::is_rhai_script(&command_name);
-        let is_bun_command = node.command.r#type() == CommandType::Bun;
+        // script runtimes that spawn a subprocess per node share a pool
+        let process_permit = match node.command.r#type() {
+            CommandType::Bun => Some((self.bun_permit.clone(), "bun")),
+            CommandType::Python => Some((self.python_permit.clone(), "python")),
+            _ => None,
+        };
         let span =
             tracing::error_span!(NODE_SPAN_NAME, node_id = node.id.to_string(), times = times);
         let task = run_command()
@@ -1944,12 +2091,12 @@ impl FlowGraph {
                     .record(run_started.elapsed().as_secs_f64());
                     std::mem::drop(p);
                     result
-                } else if is_bun_command {
+                } else if let Some((semaphore, runtime)) = process_permit {
                     let wait_started = Instant::now();
-                    let permit = bun_permit.acquire_owned().await.ok();
+                    let permit = semaphore.acquire_owned().await.ok();
                     metrics::histogram!(
                         "flow_node_permit_wait_seconds",
-                        "runtime" => "bun",
+                        "runtime" => runtime,
                     )
                     .record(wait_started.elapsed().as_secs_f64());
                     let run_started = Instant::now();
@@ -2080,6 +2227,7 @@ struct Finished {
     times: u32,
     finished_at: DateTime<Utc>,
     result: Result<value::Map, CommandError>,
+    transactions: Vec<execute::TxOutcome>,
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -2089,16 +2237,28 @@ async fn run_command(
     flow_run_id: FlowRunId,
     times: u32,
     inputs: value::Map,
-    ctx_data: FlowContextData,
-    ctx_svcs: FlowServices,
+    mut ctx_data: FlowContextData,
+    mut ctx_svcs: FlowServices,
     mut get_jwt: get_jwt::Svc,
     event_tx: EventSender,
     stop: StopSignal,
     stop_shared: StopSignal,
     tx: mpsc::UnboundedSender<PartialOutput>,
-    mode: client::BundlingMode,
+    mut mode: client::BundlingMode,
     tx_exec_config: ExecutionConfig,
 ) -> Finished {
+    if let Some(config) = &node.solana_client {
+        if config.cluster != ctx_data.set.solana.cluster {
+            ctx_svcs.set.helius = None;
+        }
+        // checked when the graph was built, the guarded client also rejects
+        // hosts that resolve to private addresses later on
+        let http = endpoint_client(private_endpoints_allowed()).clone();
+        ctx_svcs.set.solana_client = Arc::new(config.build_client(Some(http)));
+        ctx_data.set.solana = config.clone();
+        // bundles are sent to the flow's RPC
+        mode = client::BundlingMode::Off;
+    }
     let execute = match mode {
         client::BundlingMode::Off => TowerClient::new(ExecuteNoBundling {
             node_id: node.id,
@@ -2124,6 +2284,22 @@ async fn run_command(
             tx: tx.clone(),
         }),
     };
+    // how the node's transactions landed, reported in `NodeFinish`
+    let transactions = Arc::new(Mutex::new(Vec::<execute::TxOutcome>::new()));
+    let execute = {
+        let transactions = transactions.clone();
+        TowerClient::new(service_fn(move |req| {
+            let execute = execute.clone();
+            let transactions = transactions.clone();
+            async move {
+                let resp = execute.oneshot(req).await?;
+                if let Some(outcome) = resp.outcome {
+                    transactions.lock().unwrap().push(outcome);
+                }
+                Ok(resp)
+            }
+        }))
+    };
     if !node.command.permissions().user_tokens {
         get_jwt = get_jwt::Svc::new(service_fn(|_| {
             std::future::ready(Err(get_jwt::Error::NotAllowed))
@@ -2160,11 +2336,13 @@ async fn run_command(
             crate::Error::Canceled(reason).into()
         })
         .await;
+    let transactions = std::mem::take(&mut *transactions.lock().unwrap());
 
     Finished {
         node,
         times,
         result,
+        transactions,
         finished_at: Utc::now(),
     }
 }
@@ -2799,6 +2977,25 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_check_node_solana_client() {
+        let config = |url: &str| SolanaClientConfig {
+            url: url.to_owned(),
+            cluster: flow_lib::SolanaNet::Localnet,
+        };
+        // falls back to the localnet URL, 127.0.0.1:8899 by default
+        check_node_solana_client(&config("")).await.unwrap_err();
+        check_node_solana_client(&config("http://127.0.0.1:8899"))
+            .await
+            .unwrap_err();
+        check_node_solana_client(&config("http://10.0.0.1:8899"))
+            .await
+            .unwrap_err();
+        check_node_solana_client(&config("not a url"))
+            .await
+            .unwrap_err();
+    }
+
     /*
      * // TODO: a node in this flow changed
     #[tokio::test]
@@ -116,6 +116,7 @@ pub struct FlowRegistry {
 
     pub(crate) rhai_permit: Arc<Semaphore>,
     pub(crate) bun_permit: Arc<Semaphore>,
+    pub(crate) python_permit: Arc<Semaphore>,
     rhai_tx: Arc<OnceLock<crossbeam_channel::Sender<run_rhai::ChannelMessage>>>,
 
     pub(crate) rpc_server: Option<actix::Addr<tower_rpc::Server>>,
@@ -140,6 +141,7 @@ impl Default for FlowRegistry {
             backend: BackendServices::unimplemented(),
             rhai_permit: Arc::new(Semaphore::new(rhai_pool_size())),
             bun_permit: Arc::new(Semaphore::new(bun_pool_size())),
+            python_permit: Arc::new(Semaphore::new(python_pool_size())),
             rhai_tx: <_>::default(),
             rpc_server: None, // TODO: try this
             remotes: None,
@@ -277,9 +279,25 @@ pub fn bun_pool_size() -> usize {
     })
 }
 
+pub fn python_pool_size() -> usize {
+    static POOL_SIZE: OnceLock<usize> = OnceLock::new();
+    *POOL_SIZE.get_or_init(|| {
+        std::env::var("PYTHON_POOL_SIZE")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_else(|| {
+                std::thread::available_parallelism()
+                    .map(|parallelism| parallelism.get().min(2))
+                    .unwrap_or(1)
+            })
+            .max(1)
+    })
+}
+
 fn spawn_rhai_thread(rx: crossbeam_channel::Receiver<run_rhai::ChannelMessage>) {
     tokio::task::spawn_blocking(move || {
         let mut engine = rhai_script::setup_engine();
+        let mut cache = rhai_script::AstCache::default();
         while let Ok((req, tx)) = rx.recv() {
             if let Some(tx) = req.ctx.get::<EventSender>() {
                 let tx1 = tx.clone();
@@ -340,7 +358,7 @@ fn spawn_rhai_thread(rx: crossbeam_channel::Receiver<run_rhai::ChannelMessage>)
                     engine.on_progress(|_| None);
                 }
             }
-            let result = req.command.run(&mut engine, req.ctx, req.input);
+            let result = req.command.run(&mut engine, &mut cache, req.ctx, req.input);
             if tx.send(result).is_err() {
                 tracing::debug!("command stopped waiting");
             }
@@ -389,6 +407,7 @@ impl FlowRegistry {
             backend,
             rhai_permit: Arc::new(Semaphore::new(rhai_pool_size())),
             bun_permit: Arc::new(Semaphore::new(bun_pool_size())),
+            python_permit: Arc::new(Semaphore::new(python_pool_size())),
             rhai_tx: <_>::default(),
             rpc_server: tower_rpc::Server::start_http_server()
                 .inspect_err(|error| tracing::error!("tower_rpc error: {}", error))
@@ -473,6 +473,7 @@ impl FlowSet {
             .depth(self.context.depth)
             .rhai_permit(self.context.rhai_permit)
             .bun_permit(self.context.bun_permit)
+            .python_permit(self.context.python_permit)
             .rhai_tx(self.context.rhai_tx)
             .maybe_parent_flow_execute(self.context.parent_flow_execute)
             .maybe_rpc_server(self.context.rpc_server)
@@ -535,6 +536,8 @@ pub struct FlowSetContext {
     rhai_permit: Arc<Semaphore>,
     #[builder(default = Arc::new(Semaphore::new(crate::flow_registry::bun_pool_size())))]
     bun_permit: Arc<Semaphore>,
+    #[builder(default = Arc::new(Semaphore::new(crate::flow_registry::python_pool_size())))]
+    python_permit: Arc<Semaphore>,
     #[builder(default)]
     rhai_tx: Arc<OnceLock<crossbeam_channel::Sender<run_rhai::ChannelMessage>>>,
 
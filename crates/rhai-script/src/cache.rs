@@ -0,0 +1,74 @@
+use rhai::{AST, Engine, EvalAltResult};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+pub const DEFAULT_CAPACITY: usize = 256;
+
+/// Compiled scripts keyed by the SHA-256 of their source.
+///
+/// `AST` is not `Send`, so each worker thread owns its own cache next to its
+/// `Engine`. When full, the least recently used script is evicted.
+pub struct AstCache {
+    capacity: usize,
+    tick: u64,
+    entries: HashMap<[u8; 32], (AST, u64)>,
+}
+
+impl Default for AstCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+impl AstCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            tick: 0,
+            entries: HashMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Return the compiled `AST` of `code`, compiling it on a cache miss.
+    /// Scripts that fail to parse are not cached.
+    pub fn get_or_compile(
+        &mut self,
+        engine: &Engine,
+        code: &str,
+    ) -> Result<&AST, Box<EvalAltResult>> {
+        let key: [u8; 32] = Sha256::digest(code.as_bytes()).into();
+        self.tick += 1;
+        let tick = self.tick;
+
+        if !self.entries.contains_key(&key) {
+            let ast = engine.compile(code)?;
+            if self.entries.len() >= self.capacity {
+                self.evict();
+            }
+            self.entries.insert(key, (ast, tick));
+        }
+
+        let (ast, last_used) = self.entries.get_mut(&key).expect("inserted above");
+        *last_used = tick;
+        Ok(ast)
+    }
+
+    fn evict(&mut self) {
+        let oldest = self
+            .entries
+            .iter()
+            .min_by_key(|(_, (_, last_used))| *last_used)
+            .map(|(key, _)| *key);
+        if let Some(key) = oldest {
+            self.entries.remove(&key);
+        }
+    }
+}
@@ -9,9 +9,12 @@ use rhai::{
 use rhai_rand::RandomPackage;
 use sha2::{Digest, Sha256};
 
+pub mod cache;
 pub mod convert;
 pub mod error;
+pub mod solana;
 
+pub use cache::AstCache;
 pub use error::ScriptError;
 pub use rhai::Engine;
 
@@ -119,6 +122,7 @@ pub fn setup_engine() -> Engine {
     engine
         .register_global_module(StandardPackage::new().as_shared_module())
         .register_static_module("rand", RandomPackage::new().as_shared_module())
+        .register_static_module("solana", solana::module().into())
         .register_fn("utc_now", utc_now)
         .register_fn("Decimal", decimal)
         .register_fn("base58_encode", base58_encode)
@@ -158,6 +162,7 @@ impl Command {
     pub fn run(
         &self,
         engine: &mut Engine,
+        cache: &mut AstCache,
         ctx: CommandContext,
         mut input: ValueSet,
     ) -> Result<ValueSet, CommandError> {
@@ -193,8 +198,9 @@ impl Command {
                 }
             }
         }
-        let eval_result = engine
-            .eval_with_scope::<Dynamic>(&mut scope, &code)
+        let eval_result = cache
+            .get_or_compile(engine, &code)
+            .and_then(|ast| engine.eval_ast_with_scope::<Dynamic>(&mut scope, ast))
             .map_err(|error| anyhow!(ScriptError::from(error)))?;
         let mut outputs = ValueSet::new();
         for o in &self.outputs {
@@ -0,0 +1,489 @@
+//! `solana::*` functions for Rhai scripts.
+//!
+//! Pubkeys are passed around as base58 strings and raw data as blobs.
+//! `u64`, `u128` and `i128` values are returned as `Decimal`, the same way
+//! `U64` flow values are converted to Rhai.
+
+use crate::rhai_err;
+use rhai::{Array, Blob, Dynamic, EvalAltResult, FuncRegistration, INT, Map, Module};
+use rust_decimal::{Decimal, prelude::ToPrimitive};
+use solana_pubkey::Pubkey;
+use solana_signature::Signature;
+use std::str::FromStr;
+
+type RhaiResult<T> = Result<T, Box<EvalAltResult>>;
+
+const LAMPORTS_PER_SOL_DECIMALS: u32 = 9;
+
+const TOKEN_PROGRAM_ID: Pubkey =
+    Pubkey::from_str_const("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+
+const ASSOCIATED_TOKEN_PROGRAM_ID: Pubkey =
+    Pubkey::from_str_const("ATokenGPvbdGVxr1b2hRvp8K7LjPtzcv6FWZ6ow2c1cT");
+
+fn parse_pubkey(s: &str) -> RhaiResult<Pubkey> {
+    Pubkey::from_str(s).map_err(|e| rhai_err(format!("invalid pubkey {s:?}: {e}")))
+}
+
+fn pubkey(s: &str) -> RhaiResult<String> {
+    parse_pubkey(s).map(|pubkey| pubkey.to_string())
+}
+
+fn pubkey_from_bytes(bytes: Blob) -> RhaiResult<String> {
+    let bytes = <[u8; 32]>::try_from(bytes.as_slice())
+        .map_err(|_| rhai_err(format!("pubkey must be 32 bytes, got {}", bytes.len())))?;
+    Ok(Pubkey::new_from_array(bytes).to_string())
+}
+
+fn pubkey_bytes(s: &str) -> RhaiResult<Blob> {
+    parse_pubkey(s).map(|pubkey| pubkey.to_bytes().to_vec())
+}
+
+fn is_pubkey(s: &str) -> bool {
+    Pubkey::from_str(s).is_ok()
+}
+
+fn is_on_curve(s: &str) -> RhaiResult<bool> {
+    parse_pubkey(s).map(|pubkey| pubkey.is_on_curve())
+}
+
+/// Blobs are used as is, strings as their UTF-8 bytes.
+fn seed_bytes(seed: Dynamic) -> RhaiResult<Vec<u8>> {
+    if seed.is_blob() {
+        Ok(seed.cast::<Blob>())
+    } else if seed.is_string() {
+        Ok(seed.cast::<rhai::ImmutableString>().as_bytes().to_vec())
+    } else {
+        Err(rhai_err(format!(
+            "seed must be a blob or a string, got {}",
+            seed.type_name()
+        )))
+    }
+}
+
+fn find_program_address(seeds: Array, program_id: &str) -> RhaiResult<Array> {
+    let program_id = parse_pubkey(program_id)?;
+    let seeds = seeds
+        .into_iter()
+        .map(seed_bytes)
+        .collect::<RhaiResult<Vec<_>>>()?;
+    let seeds = seeds.iter().map(Vec::as_slice).collect::<Vec<_>>();
+    let (address, bump) = Pubkey::try_find_program_address(&seeds, &program_id)
+        .ok_or_else(|| rhai_err("unable to find a viable program address".to_owned()))?;
+    Ok(vec![address.to_string().into(), (bump as INT).into()])
+}
+
+fn associated_token_address_with_program(
+    wallet: &str,
+    mint: &str,
+    token_program: &str,
+) -> RhaiResult<String> {
+    let wallet = parse_pubkey(wallet)?;
+    let mint = parse_pubkey(mint)?;
+    let token_program = parse_pubkey(token_program)?;
+    let (address, _) = Pubkey::find_program_address(
+        &[wallet.as_ref(), token_program.as_ref(), mint.as_ref()],
+        &ASSOCIATED_TOKEN_PROGRAM_ID,
+    );
+    Ok(address.to_string())
+}
+
+fn associated_token_address(wallet: &str, mint: &str) -> RhaiResult<String> {
+    associated_token_address_with_program(wallet, mint, &TOKEN_PROGRAM_ID.to_string())
+}
+
+fn integer(value: &Dynamic) -> RhaiResult<i128> {
+    if let Ok(i) = value.as_int() {
+        Ok(i as i128)
+    } else if let Ok(d) = value.as_decimal() {
+        if !d.fract().is_zero() {
+            return Err(rhai_err(format!("expected an integer, got {d}")));
+        }
+        d.to_i128()
+            .ok_or_else(|| rhai_err(format!("integer out of range: {d}")))
+    } else {
+        Err(rhai_err(format!(
+            "expected an integer, got {}",
+            value.type_name()
+        )))
+    }
+}
+
+fn decimal(value: &Dynamic) -> RhaiResult<Decimal> {
+    if let Ok(i) = value.as_int() {
+        Ok(Decimal::from(i))
+    } else if let Ok(d) = value.as_decimal() {
+        Ok(d)
+    } else if let Ok(f) = value.as_float() {
+        Decimal::from_str(&f.to_string()).map_err(|e| rhai_err(format!("invalid number: {e}")))
+    } else {
+        Err(rhai_err(format!(
+            "expected a number, got {}",
+            value.type_name()
+        )))
+    }
+}
+
+fn decimals(decimals: INT) -> RhaiResult<u32> {
+    u32::try_from(decimals)
+        .ok()
+        .filter(|d| *d <= 28)
+        .ok_or_else(|| rhai_err(format!("invalid decimals: {decimals}")))
+}
+
+/// `amount / 10^decimals`
+fn to_ui_amount(amount: Dynamic, decimals: INT) -> RhaiResult<Decimal> {
+    let amount = u64::try_from(integer(&amount)?)
+        .map_err(|_| rhai_err("amount must be a u64".to_owned()))?;
+    let mut ui_amount = Decimal::from(amount);
+    ui_amount
+        .set_scale(self::decimals(decimals)?)
+        .map_err(|e| rhai_err(e.to_string()))?;
+    Ok(ui_amount.normalize())
+}
+
+/// `ui_amount * 10^decimals`, fails if the result is not a whole `u64`.
+fn to_token_amount(ui_amount: Dynamic, decimals: INT) -> RhaiResult<Decimal> {
+    let ui_amount = decimal(&ui_amount)?;
+    let scale = Decimal::from_i128_with_scale(10i128.pow(self::decimals(decimals)?), 0);
+    let amount = ui_amount
+        .checked_mul(scale)
+        .ok_or_else(|| rhai_err(format!("amount overflow: {ui_amount}")))?;
+    if !amount.fract().is_zero() {
+        return Err(rhai_err(format!(
+            "{ui_amount} has more than {decimals} decimals"
+        )));
+    }
+    amount
+        .to_u64()
+        .map(Decimal::from)
+        .ok_or_else(|| rhai_err(format!("amount must be a u64: {amount}")))
+}
+
+fn lamports_to_sol(lamports: Dynamic) -> RhaiResult<Decimal> {
+    to_ui_amount(lamports, LAMPORTS_PER_SOL_DECIMALS as INT)
+}
+
+fn sol_to_lamports(sol: Dynamic) -> RhaiResult<INT> {
+    let lamports = to_token_amount(sol, LAMPORTS_PER_SOL_DECIMALS as INT)?;
+    lamports
+        .to_i64()
+        .ok_or_else(|| rhai_err(format!("lamports out of range: {lamports}")))
+}
+
+fn parse_signature(signature: &Dynamic) -> RhaiResult<Signature> {
+    let bytes = if signature.is_blob() {
+        signature.clone().cast::<Blob>()
+    } else if signature.is_string() {
+        let s = signature.clone().cast::<rhai::ImmutableString>();
+        bs58::decode(s.as_str())
+            .into_vec()
+            .map_err(|e| rhai_err(format!("invalid signature: {e}")))?
+    } else {
+        return Err(rhai_err(format!(
+            "signature must be a blob or a base58 string, got {}",
+            signature.type_name()
+        )));
+    };
+    Signature::try_from(bytes.as_slice())
+        .map_err(|_| rhai_err(format!("signature must be 64 bytes, got {}", bytes.len())))
+}
+
+/// Verify an ed25519 signature. String messages are signed as UTF-8 bytes.
+fn verify(pubkey: &str, message: Dynamic, signature: Dynamic) -> RhaiResult<bool> {
+    let pubkey = parse_pubkey(pubkey)?;
+    let message = seed_bytes(message)?;
+    let signature = parse_signature(&signature)?;
+    Ok(signature.verify(pubkey.as_ref(), &message))
+}
+
+/// Borsh type of a layout field.
+#[derive(Debug, Clone, PartialEq)]
+enum Ty {
+    U8,
+    U16,
+    U32,
+    U64,
+    U128,
+    I8,
+    I16,
+    I32,
+    I64,
+    I128,
+    Bool,
+    String,
+    Pubkey,
+    Bytes,
+    Option(Box<Ty>),
+    Vec(Box<Ty>),
+}
+
+impl FromStr for Ty {
+    type Err = Box<EvalAltResult>;
+
+    fn from_str(s: &str) -> RhaiResult<Self> {
+        let s = s.trim();
+        let inner = |prefix: &str| {
+            s.strip_prefix(prefix)
+                .and_then(|rest| rest.strip_suffix('>'))
+                .map(Ty::from_str)
+        };
+        if let Some(ty) = inner("option<") {
+            return Ok(Ty::Option(Box::new(ty?)));
+        }
+        if let Some(ty) = inner("vec<") {
+            return Ok(Ty::Vec(Box::new(ty?)));
+        }
+        Ok(match s {
+            "u8" => Ty::U8,
+            "u16" => Ty::U16,
+            "u32" => Ty::U32,
+            "u64" => Ty::U64,
+            "u128" => Ty::U128,
+            "i8" => Ty::I8,
+            "i16" => Ty::I16,
+            "i32" => Ty::I32,
+            "i64" => Ty::I64,
+            "i128" => Ty::I128,
+            "bool" => Ty::Bool,
+            "string" => Ty::String,
+            "pubkey" => Ty::Pubkey,
+            "bytes" => Ty::Bytes,
+            _ => return Err(rhai_err(format!("unknown borsh type: {s:?}"))),
+        })
+    }
+}
+
+/// Layout is an array of `[name, type]` pairs or `#{ name, type }` maps.
+fn parse_layout(layout: Array) -> RhaiResult<Vec<(String, Ty)>> {
+    layout
+        .into_iter()
+        .map(|field| {
+            let (name, ty) = if field.is_array() {
+                let pair = field.cast::<Array>();
+                match <[Dynamic; 2]>::try_from(pair) {
+                    Ok([name, ty]) => (name, ty),
+                    Err(_) => return Err(rhai_err("layout field must be [name, type]".to_owned())),
+                }
+            } else if field.is_map() {
+                let map = field.cast::<Map>();
+                match (map.get("name").cloned(), map.get("type").cloned()) {
+                    (Some(name), Some(ty)) => (name, ty),
+                    _ => {
+                        return Err(rhai_err(
+                            "layout field must have `name` and `type`".to_owned(),
+                        ));
+                    }
+                }
+            } else {
+                return Err(rhai_err(format!(
+                    "invalid layout field: {}",
+                    field.type_name()
+                )));
+            };
+            let name = name
+                .into_string()
+                .map_err(|t| rhai_err(format!("field name must be a string, got {t}")))?;
+            let ty = ty
+                .into_string()
+                .map_err(|t| rhai_err(format!("field type must be a string, got {t}")))?;
+            Ok((name, ty.parse()?))
+        })
+        .collect()
+}
+
+fn encode_int(out: &mut Vec<u8>, value: &Dynamic, ty: &Ty) -> RhaiResult<()> {
+    let i = integer(value)?;
+    let range = || rhai_err(format!("{i} out of range for {ty:?}"));
+    match ty {
+        Ty::U8 => out.extend(u8::try_from(i).map_err(|_| range())?.to_le_bytes()),
+        Ty::U16 => out.extend(u16::try_from(i).map_err(|_| range())?.to_le_bytes()),
+        Ty::U32 => out.extend(u32::try_from(i).map_err(|_| range())?.to_le_bytes()),
+        Ty::U64 => out.extend(u64::try_from(i).map_err(|_| range())?.to_le_bytes()),
+        Ty::U128 => out.extend(u128::try_from(i).map_err(|_| range())?.to_le_bytes()),
+        Ty::I8 => out.extend(i8::try_from(i).map_err(|_| range())?.to_le_bytes()),
+        Ty::I16 => out.extend(i16::try_from(i).map_err(|_| range())?.to_le_bytes()),
+        Ty::I32 => out.extend(i32::try_from(i).map_err(|_| range())?.to_le_bytes()),
+        Ty::I64 => out.extend(i64::try_from(i).map_err(|_| range())?.to_le_bytes()),
+        Ty::I128 => out.extend(i.to_le_bytes()),
+        _ => unreachable!("not an integer type"),
+    }
+    Ok(())
+}
+
+fn encode_len(out: &mut Vec<u8>, len: usize) -> RhaiResult<()> {
+    let len = u32::try_from(len).map_err(|_| rhai_err(format!("length too large: {len}")))?;
+    out.extend(len.to_le_bytes());
+    Ok(())
+}
+
+fn encode_value(out: &mut Vec<u8>, value: Dynamic, ty: &Ty) -> RhaiResult<()> {
+    match ty {
+        Ty::Bool => {
+            let b = value
+                .as_bool()
+                .map_err(|t| rhai_err(format!("expected bool, got {t}")))?;
+            out.push(b as u8);
+        }
+        Ty::String => {
+            let s = value
+                .into_string()
+                .map_err(|t| rhai_err(format!("expected string, got {t}")))?;
+            encode_len(out, s.len())?;
+            out.extend(s.as_bytes());
+        }
+        Ty::Pubkey => {
+            let s = value
+                .into_string()
+                .map_err(|t| rhai_err(format!("expected pubkey, got {t}")))?;
+            out.extend(parse_pubkey(&s)?.to_bytes());
+        }
+        Ty::Bytes => {
+            let bytes = value
+                .try_cast::<Blob>()
+                .ok_or_else(|| rhai_err("expected blob".to_owned()))?;
+            encode_len(out, bytes.len())?;
+            out.extend(bytes);
+        }
+        Ty::Option(inner) => {
+            if value.is_unit() {
+                out.push(0);
+            } else {
+                out.push(1);
+                encode_value(out, value, inner)?;
+            }
+        }
+        Ty::Vec(inner) => {
+            let items = value
+                .try_cast::<Array>()
+                .ok_or_else(|| rhai_err("expected array".to_owned()))?;
+            encode_len(out, items.len())?;
+            for item in items {
+                encode_value(out, item, inner)?;
+            }
+        }
+        int => encode_int(out, &value, int)?,
+    }
+    Ok(())
+}
+
+/// Encode `value` with Borsh, in `layout` order. Missing fields are `()`,
+/// which is only valid for `option<T>`.
+fn borsh_encode(layout: Array, value: Map) -> RhaiResult<Blob> {
+    let mut out = Vec::new();
+    for (name, ty) in parse_layout(layout)? {
+        let field = value.get(name.as_str()).cloned().unwrap_or(Dynamic::UNIT);
+        encode_value(&mut out, field, &ty).map_err(|e| rhai_err(format!("{name}: {e}")))?;
+    }
+    Ok(out)
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> RhaiResult<&'a [u8]> {
+        if self.data.len() < n {
+            return Err(rhai_err("unexpected end of data".to_owned()));
+        }
+        let (head, tail) = self.data.split_at(n);
+        self.data = tail;
+        Ok(head)
+    }
+
+    fn array<const N: usize>(&mut self) -> RhaiResult<[u8; N]> {
+        Ok(self.take(N)?.try_into().expect("length checked"))
+    }
+
+    fn len(&mut self) -> RhaiResult<usize> {
+        Ok(u32::from_le_bytes(self.array()?) as usize)
+    }
+}
+
+fn large_int(i: i128) -> RhaiResult<Dynamic> {
+    Decimal::try_from_i128_with_scale(i, 0)
+        .map(Into::into)
+        .map_err(|_| rhai_err(format!("{i} does not fit in a Decimal")))
+}
+
+fn decode_value(reader: &mut Reader<'_>, ty: &Ty) -> RhaiResult<Dynamic> {
+    Ok(match ty {
+        Ty::U8 => (u8::from_le_bytes(reader.array()?) as INT).into(),
+        Ty::U16 => (u16::from_le_bytes(reader.array()?) as INT).into(),
+        Ty::U32 => (u32::from_le_bytes(reader.array()?) as INT).into(),
+        Ty::U64 => Decimal::from(u64::from_le_bytes(reader.array()?)).into(),
+        Ty::U128 => {
+            let i = u128::from_le_bytes(reader.array()?);
+            large_int(i128::try_from(i).map_err(|_| rhai_err(format!("{i} is too large")))?)?
+        }
+        Ty::I8 => (i8::from_le_bytes(reader.array()?) as INT).into(),
+        Ty::I16 => (i16::from_le_bytes(reader.array()?) as INT).into(),
+        Ty::I32 => (i32::from_le_bytes(reader.array()?) as INT).into(),
+        Ty::I64 => i64::from_le_bytes(reader.array()?).into(),
+        Ty::I128 => large_int(i128::from_le_bytes(reader.array()?))?,
+        Ty::Bool => match reader.array::<1>()? {
+            [0] => false.into(),
+            [1] => true.into(),
+            [b] => return Err(rhai_err(format!("invalid bool: {b}"))),
+        },
+        Ty::String => {
+            let len = reader.len()?;
+            String::from_utf8(reader.take(len)?.to_vec())
+                .map_err(|e| rhai_err(format!("invalid string: {e}")))?
+                .into()
+        }
+        Ty::Pubkey => Pubkey::new_from_array(reader.array()?).to_string().into(),
+        Ty::Bytes => {
+            let len = reader.len()?;
+            Dynamic::from_blob(reader.take(len)?.to_vec())
+        }
+        Ty::Option(inner) => match reader.array::<1>()? {
+            [0] => Dynamic::UNIT,
+            [1] => decode_value(reader, inner)?,
+            [b] => return Err(rhai_err(format!("invalid option tag: {b}"))),
+        },
+        Ty::Vec(inner) => {
+            let len = reader.len()?;
+            (0..len)
+                .map(|_| decode_value(reader, inner))
+                .collect::<RhaiResult<Array>>()?
+                .into()
+        }
+    })
+}
+
+/// Decode Borsh `data` into a map following `layout`. Trailing bytes are
+/// ignored, so account padding does not need to be described.
+fn borsh_decode(layout: Array, data: Blob) -> RhaiResult<Map> {
+    let mut reader = Reader { data: &data };
+    let mut map = Map::new();
+    for (name, ty) in parse_layout(layout)? {
+        let value = decode_value(&mut reader, &ty).map_err(|e| rhai_err(format!("{name}: {e}")))?;
+        map.insert(name.into(), value);
+    }
+    Ok(map)
+}
+
+pub fn module() -> Module {
+    let mut module = Module::new();
+    FuncRegistration::new("pubkey").set_into_module(&mut module, pubkey);
+    FuncRegistration::new("pubkey").set_into_module(&mut module, pubkey_from_bytes);
+    FuncRegistration::new("pubkey_bytes").set_into_module(&mut module, pubkey_bytes);
+    FuncRegistration::new("is_pubkey").set_into_module(&mut module, is_pubkey);
+    FuncRegistration::new("is_on_curve").set_into_module(&mut module, is_on_curve);
+    FuncRegistration::new("find_program_address")
+        .set_into_module(&mut module, find_program_address);
+    FuncRegistration::new("associated_token_address")
+        .set_into_module(&mut module, associated_token_address);
+    FuncRegistration::new("associated_token_address")
+        .set_into_module(&mut module, associated_token_address_with_program);
+    FuncRegistration::new("lamports_to_sol").set_into_module(&mut module, lamports_to_sol);
+    FuncRegistration::new("sol_to_lamports").set_into_module(&mut module, sol_to_lamports);
+    FuncRegistration::new("to_ui_amount").set_into_module(&mut module, to_ui_amount);
+    FuncRegistration::new("to_token_amount").set_into_module(&mut module, to_token_amount);
+    FuncRegistration::new("verify").set_into_module(&mut module, verify);
+    FuncRegistration::new("borsh_encode").set_into_module(&mut module, borsh_encode);
+    FuncRegistration::new("borsh_decode").set_into_module(&mut module, borsh_decode);
+    module
+}
@@ -511,3 +511,208 @@ fn test_base58_decode_error() {
     let result = engine.eval_with_scope::<Dynamic>(&mut scope, r#"base58_decode("invalid!@#$")"#);
     assert!(result.is_err());
 }
+
+// ── AST cache ────────────────────────────────────────────────────
+
+#[test]
+fn test_ast_cache() {
+    let engine = setup_engine();
+    let mut cache = AstCache::new(2);
+
+    let mut scope = rhai::Scope::new();
+    scope.push("x", 2_i64);
+    let ast = cache.get_or_compile(&engine, "x * 21").unwrap();
+    let res = engine
+        .eval_ast_with_scope::<Dynamic>(&mut scope, ast)
+        .unwrap();
+    assert_eq!(res.as_int().unwrap(), 42);
+    cache.get_or_compile(&engine, "x * 21").unwrap();
+    assert_eq!(cache.len(), 1);
+
+    // parse errors are not cached
+    let err = cache.get_or_compile(&engine, "let x = {;").unwrap_err();
+    assert_eq!(ScriptError::from(err).error_type, "ParseError");
+    assert_eq!(cache.len(), 1);
+
+    // least recently used script is evicted
+    cache.get_or_compile(&engine, "1").unwrap();
+    cache.get_or_compile(&engine, "x * 21").unwrap();
+    cache.get_or_compile(&engine, "2").unwrap();
+    assert_eq!(cache.len(), 2);
+}
+
+// ── solana module ────────────────────────────────────────────────
+
+#[test]
+fn test_solana_pubkey() {
+    let mut scope = rhai::Scope::new();
+    let system = "11111111111111111111111111111111";
+    let res = eval(&format!(r#"solana::pubkey("{system}")"#), &mut scope);
+    assert_eq!(res.into_string().unwrap(), system);
+
+    let res = eval(
+        &format!(r#"solana::pubkey(solana::pubkey_bytes("{system}"))"#),
+        &mut scope,
+    );
+    assert_eq!(res.into_string().unwrap(), system);
+
+    let res = eval(r#"solana::is_pubkey("not a pubkey")"#, &mut scope);
+    assert!(!res.as_bool().unwrap());
+
+    let engine = setup_engine();
+    assert!(
+        engine
+            .eval_with_scope::<Dynamic>(&mut scope, r#"solana::pubkey("invalid")"#)
+            .is_err()
+    );
+}
+
+#[test]
+fn test_solana_find_program_address() {
+    let program_id = solana_pubkey::Pubkey::new_unique();
+    let owner = solana_pubkey::Pubkey::new_unique();
+    let (expected, bump) =
+        solana_pubkey::Pubkey::find_program_address(&[b"vault", owner.as_ref()], &program_id);
+
+    let mut scope = rhai::Scope::new();
+    let res = eval(
+        &format!(
+            r#"solana::find_program_address(["vault", solana::pubkey_bytes("{owner}")], "{program_id}")"#
+        ),
+        &mut scope,
+    );
+    let res = res.cast::<rhai::Array>();
+    assert_eq!(res[0].clone().into_string().unwrap(), expected.to_string());
+    assert_eq!(res[1].as_int().unwrap(), bump as i64);
+
+    let res = eval(&format!(r#"solana::is_on_curve("{expected}")"#), &mut scope);
+    assert!(!res.as_bool().unwrap());
+}
+
+#[test]
+fn test_solana_associated_token_address() {
+    let mut scope = rhai::Scope::new();
+    let wallet = solana_pubkey::Pubkey::new_unique();
+    let mint = solana_pubkey::Pubkey::new_unique();
+    let token_program =
+        solana_pubkey::Pubkey::from_str_const("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+    let (expected, _) = solana_pubkey::Pubkey::find_program_address(
+        &[wallet.as_ref(), token_program.as_ref(), mint.as_ref()],
+        &solana_pubkey::Pubkey::from_str_const("ATokenGPvbdGVxr1b2hRvp8K7LjPtzcv6FWZ6ow2c1cT"),
+    );
+    let res = eval(
+        &format!(r#"solana::associated_token_address("{wallet}", "{mint}", "{token_program}")"#),
+        &mut scope,
+    );
+    assert_eq!(res.into_string().unwrap(), expected.to_string());
+}
+
+#[test]
+fn test_solana_amounts() {
+    let mut scope = rhai::Scope::new();
+    scope.push_dynamic("lamports", value_to_dynamic(Value::U64(1_500_000_000)));
+    let res = eval("solana::lamports_to_sol(lamports)", &mut scope);
+    assert_eq!(res.as_decimal().unwrap().to_string(), "1.5");
+
+    let res = eval("solana::sol_to_lamports(0.25)", &mut scope);
+    assert_eq!(res.as_int().unwrap(), 250_000_000);
+
+    let res = eval(
+        r#"solana::to_token_amount(Decimal("12.345678"), 6)"#,
+        &mut scope,
+    );
+    assert_eq!(res.as_decimal().unwrap().to_string(), "12345678");
+
+    let res = eval("solana::to_ui_amount(u64_max, 0)", &mut {
+        let mut scope = rhai::Scope::new();
+        scope.push_dynamic("u64_max", value_to_dynamic(Value::U64(u64::MAX)));
+        scope
+    });
+    assert_eq!(res.as_decimal().unwrap().to_string(), u64::MAX.to_string());
+
+    let engine = setup_engine();
+    assert!(
+        engine
+            .eval_with_scope::<Dynamic>(&mut scope, "solana::to_token_amount(1.2345, 2)")
+            .is_err()
+    );
+}
+
+#[test]
+fn test_solana_verify() {
+    use solana_signer::Signer;
+
+    let keypair = flow_lib::solana::Keypair::new();
+    let signature = keypair.sign_message(b"hello");
+    let mut scope = rhai::Scope::new();
+    let res = eval(
+        &format!(
+            r#"solana::verify("{}", "hello", "{signature}")"#,
+            keypair.pubkey()
+        ),
+        &mut scope,
+    );
+    assert!(res.as_bool().unwrap());
+
+    let res = eval(
+        &format!(
+            r#"solana::verify("{}", "goodbye", "{signature}")"#,
+            keypair.pubkey()
+        ),
+        &mut scope,
+    );
+    assert!(!res.as_bool().unwrap());
+}
+
+#[test]
+fn test_solana_borsh() {
+    let mut scope = rhai::Scope::new();
+    let owner = solana_pubkey::Pubkey::new_unique();
+    let res = eval(
+        &format!(
+            r#"
+            let layout = [
+                ["amount", "u64"],
+                ["owner", "pubkey"],
+                #{{ name: "name", type: "string" }},
+                ["delegate", "option<pubkey>"],
+                ["values", "vec<u16>"],
+                ["active", "bool"],
+            ];
+            let data = solana::borsh_encode(layout, #{{
+                amount: 1000,
+                owner: "{owner}",
+                name: "vault",
+                values: [1, 2, 3],
+                active: true,
+            }});
+            [data, solana::borsh_decode(layout, data)]
+            "#
+        ),
+        &mut scope,
+    );
+    let [data, decoded] = <[Dynamic; 2]>::try_from(res.cast::<rhai::Array>()).unwrap();
+
+    let mut expected = 1000u64.to_le_bytes().to_vec();
+    expected.extend(owner.to_bytes());
+    expected.extend(5u32.to_le_bytes());
+    expected.extend(b"vault");
+    expected.push(0);
+    expected.extend(3u32.to_le_bytes());
+    expected.extend([1, 0, 2, 0, 3, 0]);
+    expected.push(1);
+    assert_eq!(data.cast::<rhai::Blob>(), expected);
+
+    let value = dynamic_to_value(decoded).unwrap();
+    assert_eq!(
+        value,
+        Value::Map(flow_lib::value::map! {
+            "amount" => Value::Decimal(1000.into()),
+            "owner" => owner.to_string(),
+            "name" => "vault",
+            "delegate" => Value::Null,
+            "values" => Value::Array(vec![Value::I64(1), Value::I64(2), Value::I64(3)]),
+            "active" => true,
+        })
+    );
+}
@@ -0,0 +1,10 @@
+alter table public.kvstore
+    add column if not exists version bigint not null default 1,
+    add column if not exists expires_at timestamp with time zone;
+
+create index if not exists kvstore_expires_at_idx
+    on public.kvstore (user_id, store_name, expires_at)
+    where expires_at is not null;
+
+comment on column public.kvstore.version is 'Incremented on every write, used for compare-and-swap.';
+comment on column public.kvstore.expires_at is 'Item is treated as deleted after this time.';
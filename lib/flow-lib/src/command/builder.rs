@@ -80,6 +80,9 @@ use serde::{Serialize, de::DeserializeOwned};
 use std::{future::Future, sync::LazyLock};
 use thiserror::Error as ThisError;
 
+pub mod fixture;
+pub mod lint;
+
 /// `fn build() -> BuildResult`.
 pub type BuildResult = FnNewResult;
 
@@ -245,6 +248,10 @@ impl CmdBuilder {
             fn read_capability(&self) -> ReadCapability {
                 self.read_capability
             }
+
+            fn check_inputs(&self, inputs: crate::ValueSet) -> Option<Result<(), value::Error>> {
+                Some(value::from_map::<T>(inputs).map(drop))
+            }
         }
 
         let mut cmd = Command {
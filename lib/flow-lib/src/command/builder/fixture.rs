@@ -0,0 +1,502 @@
+//! Test fixtures for node-definition files.
+//!
+//! A fixture is a JSONC file next to the node-definition, with `.test.jsonc`
+//! in place of the definition's extension (`extract.jsonc` is tested by
+//! `extract.test.jsonc`). It holds a list of cases:
+//!
+//! ```jsonc
+//! {
+//!   "cases": [
+//!     {
+//!       "name": "extract a field",
+//!       // plain or tagged JSON values
+//!       "inputs": { "json_input": { "a": 1, "b": 2 }, "field_path": "b" },
+//!       // expected values of these outputs, other outputs are not checked
+//!       "outputs": { "value": 2 },
+//!       // JSON-pointer assertions on the outputs
+//!       "assert": { "/trimmed_json/a": 1 }
+//!     },
+//!     {
+//!       "name": "missing input",
+//!       "inputs": {},
+//!       // the command must fail with an error containing this text
+//!       "error": "missing field"
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! A case can also set the node `config` (defaults to the `config` of the
+//! definition), the flow `environment`, and `rpc` responses keyed by
+//! JSON-RPC method, e.g. `"getBalance": { "context": { "slot": 1 }, "value": 5 }`.
+//! Other RPC methods fail, so cases never reach the network.
+//!
+//! Commands are looked up in the [`CommandFactory`], so the crates providing
+//! them must be linked:
+//!
+//! ```ignore
+//! use cmds_std as _;
+//!
+//! #[tokio::test]
+//! async fn node_fixtures() {
+//!     flow_lib::command::builder::fixture::assert_fixtures(concat!(
+//!         env!("CARGO_MANIFEST_DIR"),
+//!         "/node-definitions"
+//!     ))
+//!     .await;
+//! }
+//! ```
+
+use super::{CmdBuilder, CommandError};
+use crate::{
+    ValueSet,
+    command::{CommandFactory, CommandTrait, default_node_data, parse_value_tagged_or_json},
+    config::node::parse_jsonc_value,
+    context::CommandContext,
+    solana::{Pubkey, Signature},
+    utils::tower_client::unimplemented_svc,
+};
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::{Map, Value as JsonValue};
+use solana_commitment_config::CommitmentConfig;
+use solana_rpc_client::{
+    nonblocking::rpc_client::RpcClient,
+    rpc_client::RpcClientConfig,
+    rpc_sender::{RpcSender, RpcTransportStats},
+};
+use solana_rpc_client_api::{
+    client_error::Result as ClientResult,
+    request::{RpcError, RpcRequest},
+};
+use std::{
+    collections::HashMap,
+    fmt::Write as _,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use thiserror::Error as ThisError;
+use value::Value;
+
+/// Extension of fixture files, replacing `.jsonc` or `.json` of the definition.
+pub const FIXTURE_EXTENSION: &str = "test.jsonc";
+
+#[derive(ThisError, Debug)]
+pub enum FixtureError {
+    #[error("failed to read {}: {}", .0.display(), .1)]
+    Read(PathBuf, std::io::Error),
+    #[error("invalid node definition {}: {}", .0.display(), .1)]
+    Definition(PathBuf, serde_json::Error),
+    #[error("invalid fixture {}: {}", .0.display(), .1)]
+    Fixture(PathBuf, serde_json::Error),
+    #[error("no node definition for fixture {}", .0.display())]
+    NoDefinition(PathBuf),
+    #[error("command {0} is not registered, is its crate linked?")]
+    NotFound(String),
+    #[error("failed to build command {0}: {1}")]
+    Build(String, CommandError),
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Fixture {
+    pub cases: Vec<FixtureCase>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct FixtureCase {
+    pub name: String,
+    #[serde(default)]
+    pub inputs: Map<String, JsonValue>,
+    /// Node config, defaults to the `config` of the definition.
+    #[serde(default)]
+    pub config: Option<JsonValue>,
+    #[serde(default)]
+    pub environment: HashMap<String, String>,
+    /// Responses to JSON-RPC methods.
+    #[serde(default)]
+    pub rpc: HashMap<String, JsonValue>,
+    /// Expected values of outputs.
+    #[serde(default)]
+    pub outputs: Map<String, JsonValue>,
+    /// Expected values at JSON pointers into the outputs.
+    #[serde(default)]
+    pub assert: Map<String, JsonValue>,
+    /// The command must fail with an error containing this text.
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+/// Result of a fixture case, with a message for each failed check.
+#[derive(Debug, Clone)]
+pub struct CaseReport {
+    pub name: String,
+    pub failures: Vec<String>,
+}
+
+impl CaseReport {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FixtureReport {
+    pub definition: PathBuf,
+    pub node_id: String,
+    pub cases: Vec<CaseReport>,
+}
+
+impl FixtureReport {
+    pub fn passed(&self) -> bool {
+        self.cases.iter().all(CaseReport::passed)
+    }
+}
+
+impl std::fmt::Display for FixtureReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{} ({})", self.node_id, self.definition.display())?;
+        for case in &self.cases {
+            let status = if case.passed() { "ok" } else { "FAILED" };
+            writeln!(f, "  {} ... {}", case.name, status)?;
+            for failure in &case.failures {
+                for line in failure.lines() {
+                    writeln!(f, "      {line}")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Fixture file of a node-definition file.
+pub fn fixture_path(definition: &Path) -> PathBuf {
+    definition.with_extension(FIXTURE_EXTENSION)
+}
+
+fn is_fixture(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.ends_with(&format!(".{FIXTURE_EXTENSION}")))
+}
+
+/// Node-definition file of a fixture file.
+pub fn definition_path(fixture: &Path) -> Option<PathBuf> {
+    let name = fixture.file_name()?.to_str()?;
+    let stem = name.strip_suffix(&format!(".{FIXTURE_EXTENSION}"))?;
+    ["jsonc", "json"]
+        .into_iter()
+        .map(|ext| fixture.with_file_name(format!("{stem}.{ext}")))
+        .find(|path| path.is_file())
+}
+
+/// Node-definitions with a fixture in `path`, which can be a directory, a
+/// node-definition or a fixture file.
+pub fn find_definitions(path: &Path) -> Result<Vec<PathBuf>, FixtureError> {
+    if path.is_file() {
+        return if is_fixture(path) {
+            definition_path(path)
+                .map(|path| vec![path])
+                .ok_or_else(|| FixtureError::NoDefinition(path.to_owned()))
+        } else {
+            Ok(vec![path.to_owned()])
+        };
+    }
+
+    let mut result = Vec::new();
+    let mut dirs = vec![path.to_owned()];
+    while let Some(dir) = dirs.pop() {
+        let entries = std::fs::read_dir(&dir).map_err(|error| FixtureError::Read(dir, error))?;
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else if is_fixture(&path) {
+                result
+                    .push(definition_path(&path).ok_or_else(|| FixtureError::NoDefinition(path))?);
+            }
+        }
+    }
+    result.sort();
+    Ok(result)
+}
+
+/// Answers JSON-RPC requests from [`FixtureCase::rpc`].
+struct MockSender {
+    responses: HashMap<String, JsonValue>,
+}
+
+#[async_trait]
+impl RpcSender for MockSender {
+    async fn send(&self, request: RpcRequest, _: JsonValue) -> ClientResult<JsonValue> {
+        let method = request.to_string();
+        self.responses
+            .get(&method)
+            .cloned()
+            .ok_or_else(|| RpcError::ForUser(format!("no response for {method} in fixture")).into())
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        RpcTransportStats::default()
+    }
+
+    fn url(&self) -> String {
+        "fixture".to_owned()
+    }
+}
+
+/// [`CommandContext::test_context`] with the environment and RPC responses of
+/// a case.
+pub fn case_context(case: &FixtureCase) -> CommandContext {
+    let rpc = RpcClient::new_sender(
+        MockSender {
+            responses: case.rpc.clone(),
+        },
+        RpcClientConfig::with_commitment(CommitmentConfig::confirmed()),
+    );
+    let mut ctx = CommandContext::test_context_with(Arc::new(rpc), unimplemented_svc());
+    ctx.set_environment(case.environment.clone());
+    ctx
+}
+
+/// Plain JSON of a value, with public keys and signatures in base58 so they
+/// can be written as strings in fixtures.
+fn to_json(value: Value) -> JsonValue {
+    match value {
+        Value::B32(b) => Pubkey::new_from_array(b).to_string().into(),
+        Value::B64(b) => Signature::from(b).to_string().into(),
+        Value::Array(array) => array.into_iter().map(to_json).collect(),
+        Value::Map(map) => map
+            .into_iter()
+            .map(|(k, v)| (k, to_json(v)))
+            .collect::<Map<_, _>>()
+            .into(),
+        value => value.into(),
+    }
+}
+
+/// Expected values are compared in the same plain JSON form as outputs, so
+/// `1`, `{"U": "1"}` and `{"D": "1"}` are equal.
+fn expected_json(json: &JsonValue) -> JsonValue {
+    to_json(parse_value_tagged_or_json(json.clone()))
+}
+
+fn mismatch(what: &str, expected: &JsonValue, actual: Option<&JsonValue>) -> String {
+    let pretty = |json: &JsonValue| serde_json::to_string_pretty(json).unwrap_or_default();
+    let mut msg = format!("{}:\n  expected: {}", what, pretty(expected));
+    match actual {
+        Some(actual) => write!(msg, "\n  actual:   {}", pretty(actual)),
+        None => write!(msg, "\n  actual:   (missing)"),
+    }
+    .ok();
+    msg
+}
+
+/// Run a case against a command.
+pub async fn run_case(cmd: &dyn CommandTrait, case: &FixtureCase) -> CaseReport {
+    let inputs = case
+        .inputs
+        .iter()
+        .map(|(name, json)| (name.clone(), parse_value_tagged_or_json(json.clone())))
+        .collect::<ValueSet>();
+    let result = cmd.run(case_context(case), inputs).await;
+
+    let mut failures = Vec::new();
+    match (result, &case.error) {
+        (Err(error), Some(expected)) => {
+            let error = format!("{error:#}");
+            if !error.contains(expected.as_str()) {
+                failures.push(format!(
+                    "error does not contain {expected:?}:\n  actual: {error}"
+                ));
+            }
+        }
+        (Err(error), None) => failures.push(format!("command failed: {error:#}")),
+        (Ok(_), Some(expected)) => failures.push(format!("expected error containing {expected:?}")),
+        (Ok(outputs), None) => {
+            let outputs = to_json(Value::Map(outputs));
+            for (name, expected) in &case.outputs {
+                let expected = expected_json(expected);
+                let actual = outputs.get(name);
+                if actual != Some(&expected) {
+                    failures.push(mismatch(&format!("output {name:?}"), &expected, actual));
+                }
+            }
+            for (pointer, expected) in &case.assert {
+                let expected = expected_json(expected);
+                let actual = outputs.pointer(pointer);
+                if actual != Some(&expected) {
+                    failures.push(mismatch(pointer, &expected, actual));
+                }
+            }
+        }
+    }
+
+    CaseReport {
+        name: case.name.clone(),
+        failures,
+    }
+}
+
+/// Build the command of a node-definition and run the cases of its fixture.
+pub async fn test_definition(
+    factory: &CommandFactory,
+    definition: &Path,
+) -> Result<FixtureReport, FixtureError> {
+    let read = |path: &Path| {
+        std::fs::read_to_string(path).map_err(|error| FixtureError::Read(path.to_owned(), error))
+    };
+
+    let text = read(definition)?;
+    let def_error = |error| FixtureError::Definition(definition.to_owned(), error);
+    let builder = CmdBuilder::new(&text).map_err(def_error)?;
+    let default_config = parse_jsonc_value(&text)
+        .map_err(def_error)?
+        .get("config")
+        .cloned()
+        .unwrap_or_else(|| JsonValue::Object(<_>::default()));
+    let fixture_path = fixture_path(definition);
+    let fixture = parse_jsonc_value(&read(&fixture_path)?)
+        .and_then(serde_json::from_value::<Fixture>)
+        .map_err(|error| FixtureError::Fixture(fixture_path, error))?;
+
+    // node data as the definition would produce in a flow
+    let mut nd = default_node_data(
+        &*builder.build(|_, _: JsonValue| async { Ok::<_, CommandError>(JsonValue::Null) }),
+    );
+    let node_id = nd.node_id.clone();
+
+    let mut cases = Vec::with_capacity(fixture.cases.len());
+    for case in &fixture.cases {
+        nd.config = case
+            .config
+            .clone()
+            .unwrap_or_else(|| default_config.clone());
+        let cmd = factory
+            .init(&nd)
+            .await
+            .map_err(|error| FixtureError::Build(node_id.clone(), error))?
+            .ok_or_else(|| FixtureError::NotFound(node_id.clone()))?;
+        cases.push(run_case(&*cmd, case).await);
+    }
+
+    Ok(FixtureReport {
+        definition: definition.to_owned(),
+        node_id,
+        cases,
+    })
+}
+
+/// Run all fixtures in `path` and panic with the report if a case failed,
+/// for use in `#[tokio::test]`s.
+pub async fn assert_fixtures(path: impl AsRef<Path>) {
+    let factory = CommandFactory::collect();
+    let definitions = find_definitions(path.as_ref()).unwrap_or_else(|error| panic!("{error}"));
+    let mut failed = String::new();
+    for definition in definitions {
+        let report = test_definition(&factory, &definition)
+            .await
+            .unwrap_or_else(|error| panic!("{error}"));
+        if !report.passed() {
+            failed.push_str(&report.to_string());
+        }
+    }
+    assert!(failed.is_empty(), "node fixtures failed:\n{failed}");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::prelude::*;
+
+    const DEFINITION: &str = r#"{
+        "version": "0.1",
+        "name": "fixture_add",
+        "prefix": "test",
+        "type": "native",
+        "author_handle": "spo",
+        "ports": {
+            "inputs": [{ "name": "a" }, { "name": "b" }],
+            "outputs": [{ "name": "sum" }, { "name": "owner" }]
+        }
+    }"#;
+
+    #[derive(Deserialize)]
+    struct Input {
+        a: i64,
+        b: i64,
+    }
+
+    #[derive(Serialize)]
+    struct Output {
+        sum: i64,
+        #[serde(with = "value::pubkey")]
+        owner: Pubkey,
+    }
+
+    async fn run(_: CommandContext, input: Input) -> Result<Output, CommandError> {
+        Ok(Output {
+            sum: input
+                .a
+                .checked_add(input.b)
+                .ok_or_else(|| CommandError::msg("overflow"))?,
+            owner: Pubkey::default(),
+        })
+    }
+
+    fn case(json: JsonValue) -> FixtureCase {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn test_run_case() {
+        futures::executor::block_on(run_cases());
+    }
+
+    async fn run_cases() {
+        let cmd = CmdBuilder::new(DEFINITION).unwrap().build(run);
+
+        let passed = run_case(
+            &*cmd,
+            &case(serde_json::json!({
+                "name": "add",
+                "inputs": { "a": 1, "b": { "I": "-3" } },
+                "outputs": { "sum": { "D": "-2" } },
+                "assert": { "/owner": "11111111111111111111111111111111" },
+            })),
+        )
+        .await;
+        assert!(passed.passed(), "{:?}", passed.failures);
+
+        let failed = run_case(
+            &*cmd,
+            &case(serde_json::json!({
+                "name": "wrong",
+                "inputs": { "a": 1, "b": 1 },
+                "outputs": { "sum": 3, "missing": 1 },
+            })),
+        )
+        .await;
+        assert_eq!(failed.failures.len(), 2);
+
+        let error = run_case(
+            &*cmd,
+            &case(serde_json::json!({
+                "name": "overflow",
+                "inputs": { "a": i64::MAX, "b": 1 },
+                "error": "overflow",
+            })),
+        )
+        .await;
+        assert!(error.passed(), "{:?}", error.failures);
+    }
+
+    #[test]
+    fn test_paths() {
+        assert_eq!(
+            fixture_path(Path::new("defs/json/extract.jsonc")),
+            Path::new("defs/json/extract.test.jsonc")
+        );
+        assert!(is_fixture(Path::new("defs/extract.test.jsonc")));
+        assert!(!is_fixture(Path::new("defs/extract.jsonc")));
+    }
+}
@@ -0,0 +1,1455 @@
+//! Lint node-definition files.
+//!
+//! Checks that a definition follows `schema/node-v2.schema.json` and can be
+//! parsed by [`CmdBuilder`][super::CmdBuilder]:
+//! - required fields are present and there are no unknown fields,
+//! - `name`, `prefix` and `author_handle` are valid identifiers,
+//! - port names are unique, and port types are known [`ValueType`]s (unknown
+//!   types are accepted at runtime, so they are only warned about).
+//!
+//! Files in the legacy flat format (`data.node_id`, top-level `inputs` and
+//! `outputs`) are flagged, and [`migrate_definition`] converts them to V2.
+//!
+//! Native commands built with [`CmdBuilder`][super::CmdBuilder] are built
+//! from their registered [`CommandDescription`], and their `Input` is
+//! deserialized from placeholder values of the input ports, to find fields
+//! that have no port, ports that are not fields, ports whose type `Input`
+//! does not accept, and optional ports that `Input` requires. Outputs are
+//! not checked this way, and findings the placeholders can not confirm are
+//! only warnings.
+//!
+//! Commands can also register the schemas of their `Input` and `Output`
+//! structs, so that ports are compared with the fields the command actually
+//! reads and returns:
+//!
+//! ```ignore
+//! use flow_lib::command::prelude::*;
+//! use schemars::JsonSchema;
+//!
+//! #[derive(Deserialize, JsonSchema)]
+//! struct Input {
+//!     a: i64,
+//!     b: i64,
+//! }
+//!
+//! #[derive(Serialize, JsonSchema)]
+//! struct Output {
+//!     result: i64,
+//! }
+//!
+//! flow_lib::submit!(PortSchema::new::<Input, Output>("add"));
+//! ```
+//!
+//! Use [`assert_lint`] in a test to lint all definitions of a crate, or
+//! [`assert_ports`] to check a single definition against its structs.
+
+use crate::{
+    CommandType, ValueSet, ValueType,
+    command::{CommandDescription, CommandTrait, MatchName, command_name_candidates},
+    config::{
+        client::{InputPort, NodeData, OutputPort},
+        node::{Definition, parse_definition, parse_jsonc_value},
+    },
+};
+use futures::future::Either;
+use regex::Regex;
+use schemars::{JsonSchema, Schema, generate::SchemaSettings};
+use serde_json::{Map, Value as JsonValue, json};
+use solana_keypair::Keypair;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+    sync::LazyLock,
+};
+use thiserror::Error as ThisError;
+
+pub const SCHEMA_URL: &str = "https://schema.spaceoperator.com/node-v2.schema.json";
+
+const REQUIRED_FIELDS: &[&str] = &[
+    "version",
+    "name",
+    "prefix",
+    "type",
+    "author_handle",
+    "source_code",
+    "ports",
+    "config_schema",
+    "config",
+];
+
+const OPTIONAL_FIELDS: &[&str] = &[
+    "$schema",
+    "description",
+    "permissions",
+    "classification",
+    "external_version",
+    "internal",
+];
+
+const COMMAND_TYPES: &[&str] = &["native", "wasm", "deno", "bun", "python", "mock"];
+
+const INPUT_PORT_FIELDS: &[&str] = &["name", "type_bounds", "required", "passthrough", "tooltip"];
+
+const OUTPUT_PORT_FIELDS: &[&str] = &["name", "type", "optional", "tooltip"];
+
+static NAME_PATTERN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new("^[a-zA-Z][a-zA-Z0-9_-]*$").unwrap());
+
+static PREFIX_PATTERN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new("^[a-zA-Z][a-zA-Z0-9_]*$").unwrap());
+
+#[derive(ThisError, Debug)]
+pub enum LintError {
+    #[error("failed to read {}: {}", .0.display(), .1)]
+    Read(PathBuf, std::io::Error),
+    #[error("failed to write {}: {}", .0.display(), .1)]
+    Write(PathBuf, std::io::Error),
+}
+
+#[derive(ThisError, Debug, Clone, PartialEq, Eq)]
+pub enum MigrateError {
+    #[error("`data.instruction_info` has no V2 equivalent")]
+    InstructionInfo,
+    #[error("node ID `{0}` has no version, set `data.version`")]
+    MissingVersion(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    pub level: Level,
+    pub message: String,
+}
+
+impl Issue {
+    fn error(message: impl Into<String>) -> Self {
+        Self {
+            level: Level::Error,
+            message: message.into(),
+        }
+    }
+
+    fn warning(message: impl Into<String>) -> Self {
+        Self {
+            level: Level::Warning,
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for Issue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let level = match self.level {
+            Level::Warning => "warning",
+            Level::Error => "error",
+        };
+        write!(f, "{level}: {}", self.message)
+    }
+}
+
+/// Result of linting a node-definition file.
+#[derive(Debug, Clone)]
+pub struct LintReport {
+    pub definition: PathBuf,
+    pub issues: Vec<Issue>,
+    /// The file was rewritten in the V2 format.
+    pub migrated: bool,
+}
+
+impl LintReport {
+    pub fn passed(&self) -> bool {
+        self.count(Level::Error) == 0
+    }
+
+    pub fn count(&self, level: Level) -> usize {
+        self.issues
+            .iter()
+            .filter(|issue| issue.level == level)
+            .count()
+    }
+}
+
+impl std::fmt::Display for LintReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", self.definition.display())?;
+        if self.migrated {
+            writeln!(f, "  migrated to V2")?;
+        }
+        for issue in &self.issues {
+            writeln!(f, "  {issue}")?;
+        }
+        Ok(())
+    }
+}
+
+/// JSON schemas of the `Input` and `Output` structs of a command.
+///
+/// Register with [`inventory::submit`] to have the ports of the command's
+/// node-definition checked against them.
+pub struct PortSchema {
+    /// `name` of the node-definition.
+    pub name: &'static str,
+    pub inputs: fn() -> Schema,
+    pub outputs: fn() -> Schema,
+}
+
+impl PortSchema {
+    pub const fn new<I: JsonSchema, O: JsonSchema>(name: &'static str) -> Self {
+        Self {
+            name,
+            inputs: input_schema::<I>,
+            outputs: output_schema::<O>,
+        }
+    }
+}
+
+inventory::collect!(PortSchema);
+
+pub fn collect_port_schemas() -> BTreeMap<&'static str, &'static PortSchema> {
+    inventory::iter::<PortSchema>()
+        .map(|s| (s.name, s))
+        .collect()
+}
+
+fn input_schema<T: JsonSchema>() -> Schema {
+    SchemaSettings::default()
+        .for_deserialize()
+        .into_generator()
+        .into_root_schema_for::<T>()
+}
+
+fn output_schema<T: JsonSchema>() -> Schema {
+    SchemaSettings::default()
+        .for_serialize()
+        .into_generator()
+        .into_root_schema_for::<T>()
+}
+
+/// Whether `def` is in the legacy flat format.
+pub fn is_legacy(def: &JsonValue) -> bool {
+    def.get("ports").is_none() && def.pointer("/data/node_id").is_some()
+}
+
+/// Lint the text of a node-definition.
+pub fn lint_definition(text: &str) -> Vec<Issue> {
+    match parse_jsonc_value(text) {
+        Ok(def) => lint_value(&def),
+        Err(error) => vec![Issue::error(format!("invalid JSONC: {error}"))],
+    }
+}
+
+fn lint_value(def: &JsonValue) -> Vec<Issue> {
+    let Some(object) = def.as_object() else {
+        return vec![Issue::error("node definition is not an object")];
+    };
+
+    if is_legacy(def) {
+        let mut issues = vec![Issue::warning(
+            "legacy node definition format, run with --fix to migrate to V2",
+        )];
+        if let Err(error) = parse_definition(&def.to_string()) {
+            issues.push(Issue::error(format!("invalid node definition: {error}")));
+        }
+        return issues;
+    }
+
+    let mut issues = Vec::new();
+    for field in REQUIRED_FIELDS {
+        if !object.contains_key(*field) {
+            issues.push(Issue::error(format!("missing field `{field}`")));
+        }
+    }
+    for field in object.keys() {
+        if !REQUIRED_FIELDS.contains(&field.as_str()) && !OPTIONAL_FIELDS.contains(&field.as_str())
+        {
+            issues.push(Issue::error(format!("unknown field `{field}`")));
+        }
+    }
+    if !object.contains_key("$schema") {
+        issues.push(Issue::warning(format!(
+            "missing `$schema`, use {SCHEMA_URL}"
+        )));
+    }
+
+    for (field, pattern) in [
+        ("name", &*NAME_PATTERN),
+        ("author_handle", &*NAME_PATTERN),
+        ("prefix", &*PREFIX_PATTERN),
+    ] {
+        if let Some(value) = object.get(field)
+            && !value.as_str().is_some_and(|s| pattern.is_match(s))
+        {
+            issues.push(Issue::error(format!(
+                "`{field}` must match `{}`, found {value}",
+                pattern.as_str()
+            )));
+        }
+    }
+
+    if let Some(ty) = object.get("type")
+        && !ty.as_str().is_some_and(|ty| COMMAND_TYPES.contains(&ty))
+        && serde_json::from_value::<crate::CommandType>(ty.clone()).is_err()
+    {
+        issues.push(Issue::error(format!("unknown command type {ty}")));
+    }
+
+    for field in ["config_schema", "config"] {
+        if object.get(field).is_some_and(|value| !value.is_object()) {
+            issues.push(Issue::error(format!("`{field}` must be an object")));
+        }
+    }
+
+    if let Some(ports) = object.get("ports") {
+        lint_ports(ports, &mut issues);
+    }
+
+    if !issues.iter().any(|issue| issue.level == Level::Error)
+        && let Err(error) = parse_definition(&def.to_string())
+    {
+        issues.push(Issue::error(format!("invalid node definition: {error}")));
+    }
+
+    issues
+}
+
+fn lint_ports(ports: &JsonValue, issues: &mut Vec<Issue>) {
+    let Some(ports) = ports.as_object() else {
+        issues.push(Issue::error("`ports` must be an object"));
+        return;
+    };
+    for field in ports.keys() {
+        if field != "inputs" && field != "outputs" {
+            issues.push(Issue::error(format!("unknown field `ports.{field}`")));
+        }
+    }
+
+    for (kind, fields) in [
+        ("inputs", INPUT_PORT_FIELDS),
+        ("outputs", OUTPUT_PORT_FIELDS),
+    ] {
+        let Some(list) = ports.get(kind) else {
+            issues.push(Issue::error(format!("missing field `ports.{kind}`")));
+            continue;
+        };
+        let Some(list) = list.as_array() else {
+            issues.push(Issue::error(format!("`ports.{kind}` must be an array")));
+            continue;
+        };
+
+        let mut names = BTreeSet::new();
+        for (index, port) in list.iter().enumerate() {
+            let Some(port) = port.as_object() else {
+                issues.push(Issue::error(format!("{kind}[{index}] must be an object")));
+                continue;
+            };
+            let name = match port.get("name").and_then(JsonValue::as_str) {
+                Some("") | None => {
+                    issues.push(Issue::error(format!("{kind}[{index}] has no name")));
+                    continue;
+                }
+                Some(name) => name,
+            };
+            if !names.insert(name) {
+                issues.push(Issue::error(format!("duplicate {kind} port `{name}`")));
+            }
+            for field in port.keys() {
+                if !fields.contains(&field.as_str()) {
+                    issues.push(Issue::error(format!(
+                        "unknown field `{field}` in {kind} port `{name}`"
+                    )));
+                }
+            }
+
+            let types = match kind {
+                "inputs" => port
+                    .get("type_bounds")
+                    .and_then(JsonValue::as_array)
+                    .map(|types| types.iter().collect::<Vec<_>>())
+                    .unwrap_or_default(),
+                _ => match port.get("type") {
+                    Some(ty) => vec![ty],
+                    None => {
+                        issues.push(Issue::error(format!("output port `{name}` has no type")));
+                        Vec::new()
+                    }
+                },
+            };
+            for ty in types {
+                if !ty.is_string() {
+                    issues.push(Issue::error(format!(
+                        "type {ty} in {kind} port `{name}` must be a string"
+                    )));
+                } else if serde_json::from_value::<ValueType>(ty.clone())
+                    .is_ok_and(|ty| ty == ValueType::Other)
+                {
+                    issues.push(Issue::warning(format!(
+                        "unknown type {ty} in {kind} port `{name}`"
+                    )));
+                }
+            }
+        }
+    }
+}
+
+/// Compare the ports of `def` with the `Input` and `Output` structs of its
+/// command.
+pub fn check_ports(def: &Definition, schema: &PortSchema) -> Vec<Issue> {
+    let mut issues = Vec::new();
+
+    let inputs = (schema.inputs)();
+    if let Some(fields) = Fields::new(&inputs) {
+        for input in &def.inputs {
+            let Some(field) = fields.get(&input.name) else {
+                issues.push(Issue::warning(format!(
+                    "input `{}` is not a field of Input",
+                    input.name
+                )));
+                continue;
+            };
+            if field.required && !input.required {
+                issues.push(Issue::error(format!(
+                    "input `{}` is optional but required by Input",
+                    input.name
+                )));
+            } else if !field.required && input.required {
+                issues.push(Issue::warning(format!(
+                    "input `{}` is required but optional in Input",
+                    input.name
+                )));
+            }
+            if !input.type_bounds.is_empty()
+                && !input.type_bounds.iter().any(|ty| field.accepts(ty))
+            {
+                issues.push(Issue::error(format!(
+                    "input `{}` has type {} but Input expects {}",
+                    input.name,
+                    type_names(&input.type_bounds),
+                    field.describe(),
+                )));
+            }
+        }
+        for (name, field) in &fields.fields {
+            if def.inputs.iter().all(|input| input.name != *name) {
+                let message = format!("field `{name}` of Input has no input port");
+                issues.push(if field.required {
+                    Issue::error(message)
+                } else {
+                    Issue::warning(message)
+                });
+            }
+        }
+    }
+
+    let outputs = (schema.outputs)();
+    if let Some(fields) = Fields::new(&outputs) {
+        for output in &def.outputs {
+            let Some(field) = fields.get(&output.name) else {
+                let message = format!("output `{}` is not a field of Output", output.name);
+                issues.push(if output.optional {
+                    Issue::warning(message)
+                } else {
+                    Issue::error(message)
+                });
+                continue;
+            };
+            if !field.required && !output.optional {
+                issues.push(Issue::warning(format!(
+                    "output `{}` may be missing but is not optional",
+                    output.name
+                )));
+            }
+            if !field.accepts(&output.r#type) {
+                issues.push(Issue::error(format!(
+                    "output `{}` has type {} but Output returns {}",
+                    output.name,
+                    type_names(std::slice::from_ref(&output.r#type)),
+                    field.describe(),
+                )));
+            }
+        }
+        for name in fields.fields.keys() {
+            if def.outputs.iter().all(|output| output.name != *name) {
+                issues.push(Issue::warning(format!(
+                    "field `{name}` of Output has no output port"
+                )));
+            }
+        }
+    }
+
+    issues
+}
+
+/// Compare the input ports of `def` with the `Input` of `cmd`, see
+/// [`CommandTrait::check_inputs`].
+///
+/// `Input` is deserialized from a placeholder value of the type of every
+/// port. Then one port at a time is changed:
+/// - to a value of another type, to find the port whose type `Input` does
+///   not accept,
+/// - to a value of a wrong type, to find ports that are not fields of `Input`,
+/// - removed, to find optional ports required by `Input`.
+///
+/// Mismatches that involve several ports at once, or a field of a nested
+/// struct, are only reported as a warning.
+pub fn check_command(def: &Definition, cmd: &dyn CommandTrait) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    let mut inputs = def
+        .inputs
+        .iter()
+        .map(|input| (input.name.clone(), placeholder_of(&input.type_bounds)))
+        .collect::<ValueSet>();
+    let accepts = |inputs: &ValueSet| matches!(cmd.check_inputs(inputs.clone()), Some(Ok(())));
+    let error = match cmd.check_inputs(inputs.clone()) {
+        None => return issues,
+        Some(Ok(())) => None,
+        Some(Err(error)) => Some(error),
+    };
+
+    if let Some(error) = error {
+        // a field of a nested struct is still missing with the key added
+        if let Some(field) = missing_field(&error) {
+            let mut inputs = inputs.clone();
+            inputs.insert(field.to_owned(), value::Value::Null);
+            let nested = cmd.check_inputs(inputs).is_some_and(|result| {
+                result.is_err_and(|error| missing_field(&error) == Some(field))
+            });
+            if !nested {
+                issues.push(Issue::error(format!(
+                    "field `{field}` of Input has no input port"
+                )));
+            }
+            return issues;
+        }
+
+        let Some((input, ty)) = def.inputs.iter().find_map(|input| {
+            let ty = replacement_types(&input.type_bounds).find(|ty| {
+                let mut inputs = inputs.clone();
+                inputs.insert(input.name.clone(), placeholder(ty));
+                accepts(&inputs)
+            })?;
+            Some((input, ty))
+        }) else {
+            issues.push(Issue::warning(format!(
+                "Input does not deserialize from placeholders of the input ports: {error}"
+            )));
+            return issues;
+        };
+        // ports of any type, or with several types, accept the replacement
+        let listed = input.type_bounds.contains(&ty);
+        if !listed && input.type_bounds.iter().all(|ty| json_types(ty).is_some()) {
+            issues.push(Issue::error(format!(
+                "input `{}` has type {} but Input expects {}",
+                input.name,
+                type_names(&input.type_bounds),
+                type_names(std::slice::from_ref(&ty)),
+            )));
+            return issues;
+        }
+        inputs.insert(input.name.clone(), placeholder(&ty));
+    }
+
+    for input in &def.inputs {
+        let Some(probe) = wrong_type(&input.type_bounds) else {
+            continue;
+        };
+        let mut inputs = inputs.clone();
+        inputs.insert(input.name.clone(), probe);
+        if accepts(&inputs) {
+            issues.push(Issue::warning(format!(
+                "input `{}` is not a field of Input",
+                input.name
+            )));
+        }
+    }
+
+    for input in def.inputs.iter().filter(|input| !input.required) {
+        let mut inputs = inputs.clone();
+        inputs.shift_remove(&input.name);
+        if let Some(Err(error)) = cmd.check_inputs(inputs)
+            && missing_field(&error) == Some(input.name.as_str())
+        {
+            issues.push(Issue::error(format!(
+                "input `{}` is optional but required by Input",
+                input.name
+            )));
+        }
+    }
+    issues
+}
+
+/// Types with a placeholder, one per Rust type they usually deserialize into.
+const PLACEHOLDER_TYPES: &[ValueType] = &[
+    ValueType::Bool,
+    ValueType::U64,
+    ValueType::I64,
+    ValueType::U128,
+    ValueType::I128,
+    ValueType::F64,
+    ValueType::Decimal,
+    ValueType::Pubkey,
+    ValueType::Keypair,
+    ValueType::Signature,
+    ValueType::String,
+    ValueType::Bytes,
+    ValueType::Array,
+    ValueType::Map,
+];
+
+/// Types to try for a port whose placeholder `Input` rejects: its other
+/// types first, then every type.
+fn replacement_types(types: &[ValueType]) -> impl Iterator<Item = ValueType> + '_ {
+    types
+        .iter()
+        .skip(1)
+        .chain(PLACEHOLDER_TYPES)
+        .filter(|ty| json_types(ty).is_some())
+        .cloned()
+}
+
+fn placeholder_of(types: &[ValueType]) -> value::Value {
+    types.first().map_or(value::Value::Null, placeholder)
+}
+
+/// Value that deserializes into the usual Rust type of a port.
+fn placeholder(ty: &ValueType) -> value::Value {
+    use value::Value;
+    match ty {
+        ValueType::Bool => Value::Bool(false),
+        ValueType::U8 | ValueType::U16 | ValueType::U32 | ValueType::U64 => Value::U64(0),
+        ValueType::U128 => Value::U128(0),
+        ValueType::I8 | ValueType::I16 | ValueType::I32 | ValueType::I64 => Value::I64(0),
+        ValueType::I128 => Value::I128(0),
+        ValueType::F32 | ValueType::F64 => Value::F64(0.0),
+        ValueType::Decimal => Value::Decimal(value::Decimal::ZERO),
+        ValueType::Pubkey | ValueType::Address => Value::B32([1; 32]),
+        ValueType::Keypair => Value::B64(Keypair::new_from_array([1; 32]).to_bytes()),
+        ValueType::Signature => Value::B64([1; 64]),
+        ValueType::String => Value::String(String::new()),
+        ValueType::Bytes => Value::Bytes(Default::default()),
+        ValueType::Array => Value::Array(Vec::new()),
+        ValueType::Map => Value::Map(Default::default()),
+        ValueType::Json | ValueType::Free | ValueType::Other => Value::Null,
+    }
+}
+
+/// Value that no field of the port's types deserializes from, `None` for
+/// ports of any type.
+fn wrong_type(types: &[ValueType]) -> Option<value::Value> {
+    if types.is_empty() || types.iter().any(|ty| json_types(ty).is_none()) {
+        return None;
+    }
+    match (
+        types.contains(&ValueType::Bool),
+        types.contains(&ValueType::String),
+    ) {
+        (false, _) => Some(value::Value::Bool(true)),
+        (true, false) => Some(value::Value::String("lint".to_owned())),
+        (true, true) => None,
+    }
+}
+
+fn missing_field(error: &value::Error) -> Option<&str> {
+    match error {
+        value::Error::Custom(message) => message.strip_prefix("missing field `")?.strip_suffix('`'),
+        _ => None,
+    }
+}
+
+/// Build the native command registered for `def`, if there is one.
+fn build_command(def: &Definition) -> Option<Box<dyn CommandTrait>> {
+    if def.r#type != CommandType::Native {
+        return None;
+    }
+    let names = command_name_candidates(&def.data.node_id);
+    let description = inventory::iter::<CommandDescription>().find(|c| {
+        c.matcher.r#type == CommandType::Native
+            && matches!(&c.matcher.name, MatchName::Exact(name) if names.contains(name))
+    })?;
+    let Either::Left(fn_new) = description.fn_new else {
+        return None;
+    };
+    let data = NodeData {
+        r#type: CommandType::Native,
+        node_id: def.data.node_id.clone(),
+        outputs: def
+            .outputs
+            .iter()
+            .map(|output| OutputPort {
+                id: Default::default(),
+                name: output.name.clone(),
+                r#type: output.r#type.clone(),
+                optional: output.optional,
+                tooltip: None,
+            })
+            .collect(),
+        inputs: def
+            .inputs
+            .iter()
+            .map(|input| InputPort {
+                id: Default::default(),
+                name: input.name.clone(),
+                type_bounds: input.type_bounds.clone(),
+                required: input.required,
+                passthrough: input.passthrough,
+                tooltip: None,
+            })
+            .collect(),
+        config: JsonValue::Object(Map::new()),
+        wasm: None,
+        instruction_info: def.data.instruction_info.clone(),
+    };
+    fn_new(&data).ok()
+}
+
+fn type_names(types: &[ValueType]) -> String {
+    types
+        .iter()
+        .map(|ty| serde_json::to_value(ty).map_or_else(|_| "?".to_owned(), |ty| ty.to_string()))
+        .collect::<Vec<_>>()
+        .join(" | ")
+}
+
+/// Properties of an object schema, with coarse JSON types.
+struct Fields {
+    fields: BTreeMap<String, Field>,
+}
+
+struct Field {
+    /// `None` if any JSON value is accepted.
+    types: Option<BTreeSet<String>>,
+    required: bool,
+}
+
+impl Fields {
+    /// `None` if the schema does not describe an object with properties.
+    fn new(schema: &Schema) -> Option<Self> {
+        let root = schema.as_value();
+        let properties = root.get("properties")?.as_object()?;
+        let required: BTreeSet<&str> = root
+            .get("required")
+            .and_then(JsonValue::as_array)
+            .map(|required| required.iter().filter_map(JsonValue::as_str).collect())
+            .unwrap_or_default();
+        let fields = properties
+            .iter()
+            .map(|(name, schema)| {
+                let (types, nullable) = schema_types(schema, root);
+                let field = Field {
+                    types,
+                    required: required.contains(name.as_str()) && !nullable,
+                };
+                (name.clone(), field)
+            })
+            .collect();
+        Some(Self { fields })
+    }
+
+    fn get(&self, name: &str) -> Option<&Field> {
+        self.fields.get(name)
+    }
+}
+
+impl Field {
+    fn accepts(&self, ty: &ValueType) -> bool {
+        match (&self.types, json_types(ty)) {
+            (Some(types), Some(accepted)) => accepted.iter().any(|ty| types.contains(*ty)),
+            _ => true,
+        }
+    }
+
+    fn describe(&self) -> String {
+        self.types
+            .as_ref()
+            .map(|types| types.iter().cloned().collect::<Vec<_>>().join(" | "))
+            .unwrap_or_else(|| "any".to_owned())
+    }
+}
+
+/// JSON types a value type is (de)serialized from, `None` for any type.
+fn json_types(ty: &ValueType) -> Option<&'static [&'static str]> {
+    Some(match ty {
+        ValueType::Bool => &["boolean"],
+        ValueType::U8
+        | ValueType::U16
+        | ValueType::U32
+        | ValueType::U64
+        | ValueType::U128
+        | ValueType::I8
+        | ValueType::I16
+        | ValueType::I32
+        | ValueType::I64
+        | ValueType::I128 => &["integer"],
+        ValueType::F32 | ValueType::F64 => &["number", "integer"],
+        ValueType::Decimal => &["number", "integer", "string"],
+        ValueType::Pubkey
+        | ValueType::Keypair
+        | ValueType::Signature
+        | ValueType::Address
+        | ValueType::String => &["string"],
+        ValueType::Bytes => &["string", "array"],
+        ValueType::Array => &["array"],
+        ValueType::Map => &["object"],
+        ValueType::Json | ValueType::Free | ValueType::Other => return None,
+    })
+}
+
+/// JSON types of a schema, and whether it accepts `null`.
+fn schema_types(schema: &JsonValue, root: &JsonValue) -> (Option<BTreeSet<String>>, bool) {
+    if let Some(reference) = schema.get("$ref").and_then(JsonValue::as_str) {
+        let target = reference
+            .strip_prefix('#')
+            .and_then(|pointer| root.pointer(pointer));
+        return match target {
+            Some(target) if !is_value_schema(target) => schema_types(target, root),
+            _ => (None, false),
+        };
+    }
+
+    if let Some(ty) = schema.get("type") {
+        let mut types: BTreeSet<String> = match ty {
+            JsonValue::String(ty) => BTreeSet::from([ty.clone()]),
+            JsonValue::Array(types) => types
+                .iter()
+                .filter_map(JsonValue::as_str)
+                .map(str::to_owned)
+                .collect(),
+            _ => return (None, false),
+        };
+        let nullable = types.remove("null");
+        return ((!types.is_empty()).then_some(types), nullable);
+    }
+
+    for key in ["anyOf", "oneOf"] {
+        if let Some(variants) = schema.get(key).and_then(JsonValue::as_array) {
+            let mut types = Some(BTreeSet::new());
+            let mut nullable = false;
+            for variant in variants {
+                let (variant_types, variant_nullable) = schema_types(variant, root);
+                nullable |= variant_nullable;
+                if variant.get("type").and_then(JsonValue::as_str) == Some("null") {
+                    continue;
+                }
+                types = match (types, variant_types) {
+                    (Some(mut types), Some(variant_types)) => {
+                        types.extend(variant_types);
+                        Some(types)
+                    }
+                    _ => None,
+                };
+            }
+            return (types.filter(|types| !types.is_empty()), nullable);
+        }
+    }
+
+    (None, false)
+}
+
+fn is_value_schema(schema: &JsonValue) -> bool {
+    schema.get("$id").and_then(JsonValue::as_str) == Some(&*value::Value::schema_id())
+}
+
+/// Convert a legacy definition to V2, `None` if `def` is not legacy.
+///
+/// `prefix`, `name` and `author_handle` are taken from a scoped node ID
+/// (`@{author_handle}/{prefix}.{name}.{version}`). For plain node IDs, the
+/// version is `data.version`, and the prefix is the directory of `path`
+/// inside `node-definitions`, or the crate name without `cmds-`.
+///
+/// `config_schema` and `config` are kept, or taken from
+/// `targets_form.json_schema` and `targets_form.form_data`. The
+/// `defaultValue` of input ports is added to `config`.
+///
+/// Definitions with `data.instruction_info` are not migrated, V2 has no
+/// equivalent.
+pub fn migrate_definition(def: &JsonValue, path: &Path) -> Result<Option<JsonValue>, MigrateError> {
+    if !is_legacy(def) {
+        return Ok(None);
+    }
+    let data = &def["data"];
+    let Some(node_id) = data["node_id"].as_str() else {
+        return Ok(None);
+    };
+    if !data["instruction_info"].is_null() {
+        return Err(MigrateError::InstructionInfo);
+    }
+
+    let (author_handle, prefix, name, version) = match parse_scoped_id(node_id) {
+        Some(id) => id,
+        None => {
+            let version = data["version"]
+                .as_str()
+                .ok_or_else(|| MigrateError::MissingVersion(node_id.to_owned()))?;
+            (
+                "spo".to_owned(),
+                path_prefix(path),
+                node_id.to_owned(),
+                version.to_owned(),
+            )
+        }
+    };
+
+    let inputs = ports(def, "inputs", "targets", INPUT_PORT_FIELDS);
+    let outputs = ports(def, "outputs", "sources", OUTPUT_PORT_FIELDS);
+
+    let config_schema = def
+        .get("config_schema")
+        .or_else(|| targets_form(def, "json_schema"))
+        .filter(|schema| schema.is_object())
+        .cloned()
+        .unwrap_or_else(|| json!({}));
+
+    let mut config = def
+        .get("config")
+        .or_else(|| targets_form(def, "form_data"))
+        .and_then(JsonValue::as_object)
+        .into_iter()
+        .flatten()
+        .map(|(name, value)| (name.clone(), tagged(value)))
+        .collect::<Map<_, _>>();
+    for port in def
+        .get("inputs")
+        .or_else(|| def.get("targets"))
+        .and_then(JsonValue::as_array)
+        .into_iter()
+        .flatten()
+    {
+        if let Some(name) = port["name"].as_str()
+            && let Some(value) = port.get("defaultValue").filter(|value| !value.is_null())
+        {
+            config
+                .entry(name.to_owned())
+                .or_insert_with(|| tagged(value));
+        }
+    }
+
+    let source_code = source_code(path)
+        .or_else(|| {
+            data.pointer("/resources/source_code_url")
+                .and_then(JsonValue::as_str)
+                .map(str::to_owned)
+        })
+        .unwrap_or_default();
+
+    let mut result = Map::new();
+    result.insert("$schema".to_owned(), SCHEMA_URL.into());
+    result.insert("version".to_owned(), version.into());
+    result.insert("name".to_owned(), name.into());
+    result.insert("prefix".to_owned(), prefix.into());
+    if let Some(description) = data.get("description").filter(|d| d.is_string()) {
+        result.insert("description".to_owned(), description.clone());
+    }
+    result.insert("type".to_owned(), def["type"].clone());
+    result.insert("author_handle".to_owned(), author_handle.into());
+    result.insert("source_code".to_owned(), source_code.into());
+    result.insert(
+        "ports".to_owned(),
+        json!({ "inputs": inputs, "outputs": outputs }),
+    );
+    result.insert("config_schema".to_owned(), config_schema);
+    result.insert("config".to_owned(), config.into());
+    if let Some(permissions) = def.get("permissions") {
+        result.insert("permissions".to_owned(), permissions.clone());
+    }
+    Ok(Some(result.into()))
+}
+
+/// `targets_form.{key}`, either as a flat key (node-definition files) or
+/// nested (flow nodes).
+fn targets_form<'a>(def: &'a JsonValue, key: &str) -> Option<&'a JsonValue> {
+    def.get(format!("targets_form.{key}"))
+        .or_else(|| def.get("targets_form")?.get(key))
+}
+
+/// A `config` value in the tagged `Value` format, plain JSON is converted.
+fn tagged(value: &JsonValue) -> JsonValue {
+    serde_json::to_value(value::Value::from_tagged_or_json(value.clone()))
+        .expect("flow values are valid JSON")
+}
+
+fn parse_scoped_id(node_id: &str) -> Option<(String, String, String, String)> {
+    let (author, rest) = node_id.strip_prefix('@')?.split_once('/')?;
+    let (prefix, rest) = rest.split_once('.')?;
+    let (name, version) = rest.split_once('.')?;
+    Some((
+        author.to_owned(),
+        prefix.to_owned(),
+        name.to_owned(),
+        version.to_owned(),
+    ))
+}
+
+fn ports(def: &JsonValue, key: &str, alias: &str, fields: &[&str]) -> Vec<JsonValue> {
+    def.get(key)
+        .or_else(|| def.get(alias))
+        .and_then(JsonValue::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(JsonValue::as_object)
+        .map(|port| {
+            port.iter()
+                .filter(|(field, _)| fields.contains(&field.as_str()))
+                .map(|(field, value)| (field.clone(), value.clone()))
+                .collect::<Map<_, _>>()
+                .into()
+        })
+        .collect()
+}
+
+/// Directory containing `node-definitions`, and the path of `path` inside it.
+fn split_crate_path(path: &Path) -> Option<(&Path, PathBuf)> {
+    let dir = path.ancestors().find(|dir| {
+        dir.file_name()
+            .is_some_and(|name| name == "node-definitions")
+    })?;
+    Some((dir.parent()?, path.strip_prefix(dir).ok()?.to_owned()))
+}
+
+fn path_prefix(path: &Path) -> String {
+    let Some((crate_dir, relative)) = split_crate_path(path) else {
+        return "std".to_owned();
+    };
+    let mut components = relative.components();
+    components.next_back();
+    match components.next() {
+        Some(dir) => dir.as_os_str().to_string_lossy().into_owned(),
+        None => crate_dir
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .map(|name| {
+                name.strip_prefix("cmds-")
+                    .unwrap_or(&name)
+                    .replace('-', "_")
+            })
+            .unwrap_or_else(|| "std".to_owned()),
+    }
+}
+
+/// `crates/{crate}/src/{path}.rs` if the file exists.
+fn source_code(path: &Path) -> Option<String> {
+    let (crate_dir, relative) = split_crate_path(path)?;
+    let source = crate_dir.join("src").join(relative.with_extension("rs"));
+    if !source.is_file() {
+        return None;
+    }
+    let base = crate_dir.parent()?.parent()?;
+    let source = source.strip_prefix(base).ok()?;
+    Some(source.to_string_lossy().replace('\\', "/"))
+}
+
+/// Lint a node-definition file, migrating it to V2 if `fix` is set.
+///
+/// Migrated files are written back as plain JSON, comments are not kept.
+///
+/// Ports are checked against the [`PortSchema`] registered for the
+/// definition's `name`, or else against the registered native command.
+pub fn lint_file(
+    schemas: &BTreeMap<&'static str, &'static PortSchema>,
+    path: &Path,
+    fix: bool,
+) -> Result<LintReport, LintError> {
+    let text =
+        std::fs::read_to_string(path).map_err(|error| LintError::Read(path.to_owned(), error))?;
+    let mut report = LintReport {
+        definition: path.to_owned(),
+        issues: Vec::new(),
+        migrated: false,
+    };
+
+    let mut def = match parse_jsonc_value(&text) {
+        Ok(def) => def,
+        Err(error) => {
+            report
+                .issues
+                .push(Issue::error(format!("invalid JSONC: {error}")));
+            return Ok(report);
+        }
+    };
+
+    let mut migrate_error = None;
+    if fix {
+        match migrate_definition(&def, path) {
+            Ok(Some(migrated)) => {
+                let mut text = serde_json::to_string_pretty(&migrated).expect("serialize JSON");
+                text.push('\n');
+                std::fs::write(path, text)
+                    .map_err(|error| LintError::Write(path.to_owned(), error))?;
+                def = migrated;
+                report.migrated = true;
+            }
+            Ok(None) => {}
+            Err(error) => {
+                migrate_error = Some(Issue::error(format!("cannot migrate to V2: {error}")));
+            }
+        }
+    }
+
+    report.issues = lint_value(&def);
+    report.issues.extend(migrate_error);
+
+    if report.passed()
+        && let Ok(parsed) = parse_definition(&def.to_string())
+    {
+        if let Some(schema) = def
+            .get("name")
+            .and_then(JsonValue::as_str)
+            .and_then(|name| schemas.get(name))
+        {
+            report.issues.extend(check_ports(&parsed, schema));
+        } else if let Some(cmd) = build_command(&parsed) {
+            report.issues.extend(check_command(&parsed, &*cmd));
+        }
+    }
+
+    Ok(report)
+}
+
+/// Node-definition files in `path`, which can be a directory or a file.
+///
+/// Inside directories, only `.json` and `.jsonc` files in a
+/// `node-definitions` directory are included, and fixtures are skipped.
+pub fn find_definitions(path: &Path) -> Result<Vec<PathBuf>, LintError> {
+    if path.is_file() {
+        return Ok(vec![path.to_owned()]);
+    }
+
+    let mut result = Vec::new();
+    let mut dirs = vec![path.to_owned()];
+    while let Some(dir) = dirs.pop() {
+        let entries = std::fs::read_dir(&dir).map_err(|error| LintError::Read(dir, error))?;
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if path.is_dir() {
+                if !name.starts_with('.') && name != "target" && name != "node_modules" {
+                    dirs.push(path);
+                }
+            } else if (name.ends_with(".json") || name.ends_with(".jsonc"))
+                && !name.ends_with(&format!(".{}", super::fixture::FIXTURE_EXTENSION))
+                && split_crate_path(&path).is_some()
+            {
+                result.push(path);
+            }
+        }
+    }
+    result.sort();
+    Ok(result)
+}
+
+/// Lint all node-definitions in `path` and panic with the reports if there
+/// are errors, for use in tests.
+///
+/// Only definitions with a registered [`PortSchema`] have their outputs
+/// checked, and warnings do not fail.
+pub fn assert_lint(path: impl AsRef<Path>) {
+    let schemas = collect_port_schemas();
+    let definitions = find_definitions(path.as_ref()).unwrap_or_else(|error| panic!("{error}"));
+    let mut failed = String::new();
+    for definition in definitions {
+        let report =
+            lint_file(&schemas, &definition, false).unwrap_or_else(|error| panic!("{error}"));
+        if !report.passed() {
+            failed.push_str(&report.to_string());
+        }
+    }
+    assert!(failed.is_empty(), "node definitions have errors:\n{failed}");
+}
+
+/// Check a node-definition against the `Input` and `Output` structs of its
+/// command, and panic if there is any issue.
+pub fn assert_ports<I: JsonSchema, O: JsonSchema>(definition: &str) {
+    let def = parse_definition(definition).unwrap_or_else(|error| panic!("{error}"));
+    let issues = check_ports(&def, &PortSchema::new::<I, O>(""))
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+    assert!(
+        issues.is_empty(),
+        "ports do not match:\n{}",
+        issues.join("\n")
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::prelude::*;
+
+    const DEFINITION: &str = r#"{
+        "$schema": "https://schema.spaceoperator.com/node-v2.schema.json",
+        "version": "0.1",
+        "name": "lint_add",
+        "prefix": "test",
+        "type": "native",
+        "author_handle": "spo",
+        "source_code": "",
+        "ports": {
+            "inputs": [
+                { "name": "a", "type_bounds": ["i64"], "required": true },
+                { "name": "b", "type_bounds": ["i64"], "required": false }
+            ],
+            "outputs": [{ "name": "sum", "type": "i64" }]
+        },
+        "config_schema": {},
+        "config": {}
+    }"#;
+
+    #[derive(Deserialize, JsonSchema)]
+    #[allow(dead_code)]
+    struct Input {
+        a: i64,
+        #[serde(default)]
+        b: i64,
+    }
+
+    #[derive(Serialize, JsonSchema)]
+    #[allow(dead_code)]
+    struct Output {
+        sum: i64,
+    }
+
+    #[derive(Deserialize, JsonSchema)]
+    #[allow(dead_code)]
+    struct WrongInput {
+        a: String,
+        b: i64,
+        c: Value,
+    }
+
+    fn messages(issues: &[Issue]) -> Vec<String> {
+        issues.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn test_lint_valid() {
+        assert_eq!(lint_definition(DEFINITION), []);
+        assert_ports::<Input, Output>(DEFINITION);
+    }
+
+    #[test]
+    fn test_lint_errors() {
+        let issues = lint_definition(
+            r#"{
+                "version": "0.1",
+                "name": "1add",
+                "prefix": "test",
+                "type": "native",
+                "author_handle": "spo",
+                "ports": {
+                    "inputs": [{ "name": "a", "type_bounds": ["string[]"] }, { "name": "a" }],
+                    "outputs": [{ "name": "sum", "type": "i64", "default": 0 }]
+                },
+                "config_schema": {},
+                "config": {},
+                "extra": true
+            }"#,
+        );
+        assert_eq!(
+            messages(&issues),
+            [
+                "error: missing field `source_code`".to_owned(),
+                "error: unknown field `extra`".to_owned(),
+                format!("warning: missing `$schema`, use {SCHEMA_URL}"),
+                "error: `name` must match `^[a-zA-Z][a-zA-Z0-9_-]*$`, found \"1add\"".to_owned(),
+                "warning: unknown type \"string[]\" in inputs port `a`".to_owned(),
+                "error: duplicate inputs port `a`".to_owned(),
+                "error: unknown field `default` in outputs port `sum`".to_owned(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_ports() {
+        let def = parse_definition(DEFINITION).unwrap();
+        let issues = check_ports(&def, &PortSchema::new::<WrongInput, Output>("lint_add"));
+        assert_eq!(
+            messages(&issues),
+            [
+                "error: input `a` has type \"i64\" but Input expects string",
+                "error: input `b` is optional but required by Input",
+                "error: field `c` of Input has no input port",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_command() {
+        fn check<T: serde::de::DeserializeOwned + 'static>(definition: &str) -> Vec<String> {
+            let cmd = CmdBuilder::new(definition)
+                .unwrap()
+                .build(|_, _: T| async { Ok(Output { sum: 0 }) });
+            messages(&check_command(&parse_definition(definition).unwrap(), &*cmd))
+        }
+
+        #[derive(Deserialize)]
+        #[allow(dead_code)]
+        struct StringInput {
+            a: String,
+            #[serde(default)]
+            b: i64,
+        }
+
+        #[derive(Deserialize)]
+        #[allow(dead_code)]
+        struct RequiredInput {
+            a: i64,
+            b: i64,
+        }
+
+        assert_eq!(check::<Input>(DEFINITION), Vec::<String>::new());
+        assert_eq!(
+            check::<StringInput>(DEFINITION),
+            ["error: input `a` has type \"i64\" but Input expects \"string\""]
+        );
+        assert_eq!(
+            check::<RequiredInput>(DEFINITION),
+            ["error: input `b` is optional but required by Input"]
+        );
+        let extra = DEFINITION.replace(
+            r#"{ "name": "b","#,
+            r#"{ "name": "c", "type_bounds": ["string"], "required": false },
+                { "name": "b","#,
+        );
+        assert_eq!(
+            check::<Input>(&extra),
+            ["warning: input `c` is not a field of Input"]
+        );
+    }
+
+    #[test]
+    fn test_migrate() {
+        let legacy = serde_json::json!({
+            "type": "native",
+            "data": { "node_id": "json_extract", "version": "0.2", "description": "Extract a value" },
+            "inputs": [
+                { "name": "json_input", "type_bounds": ["json"], "required": true,
+                  "passthrough": false, "defaultValue": null },
+                { "name": "field_path", "type_bounds": ["string"], "required": true,
+                  "passthrough": false, "defaultValue": "a.b" }
+            ],
+            "outputs": [{ "name": "value", "type": "free", "optional": false }],
+            "targets_form.json_schema": { "type": "object" },
+            "targets_form.ui_schema": {},
+            "targets_form.form_data": { "json_input": { "a": 1 } },
+        });
+        assert!(is_legacy(&legacy));
+        assert_eq!(
+            messages(&lint_value(&legacy)),
+            ["warning: legacy node definition format, run with --fix to migrate to V2"]
+        );
+
+        let path = Path::new("crates/cmds-std/node-definitions/json/extract.json");
+        let migrated = migrate_definition(&legacy, path).unwrap().unwrap();
+        assert_eq!(migrated["name"], "json_extract");
+        assert_eq!(migrated["prefix"], "json");
+        assert_eq!(migrated["version"], "0.2");
+        assert_eq!(migrated["description"], "Extract a value");
+        assert_eq!(
+            migrated["ports"]["inputs"][0],
+            serde_json::json!({
+                "name": "json_input",
+                "type_bounds": ["json"],
+                "required": true,
+                "passthrough": false,
+            })
+        );
+        assert_eq!(
+            migrated["config_schema"],
+            serde_json::json!({ "type": "object" })
+        );
+        assert_eq!(
+            migrated["config"],
+            serde_json::json!({
+                "json_input": { "M": { "a": { "U": "1" } } },
+                "field_path": { "S": "a.b" },
+            })
+        );
+        assert_eq!(lint_value(&migrated), []);
+        assert!(migrate_definition(&migrated, path).unwrap().is_none());
+
+        let scoped = serde_json::json!({
+            "type": "native",
+            "data": { "node_id": "@spo/solana.transfer_sol.0.2" },
+            "sources": [],
+            "targets": [],
+        });
+        let migrated = migrate_definition(&scoped, Path::new("transfer_sol.json"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(migrated["author_handle"], "spo");
+        assert_eq!(migrated["prefix"], "solana");
+        assert_eq!(migrated["name"], "transfer_sol");
+        assert_eq!(migrated["version"], "0.2");
+    }
+
+    #[test]
+    fn test_migrate_refused() {
+        let unversioned = serde_json::json!({
+            "type": "native",
+            "data": { "node_id": "json_extract" },
+            "inputs": [],
+            "outputs": [],
+        });
+        assert_eq!(
+            migrate_definition(&unversioned, Path::new("extract.json")),
+            Err(MigrateError::MissingVersion("json_extract".to_owned()))
+        );
+
+        let instructions = serde_json::json!({
+            "type": "native",
+            "data": {
+                "node_id": "@spo/solana.transfer_sol.0.2",
+                "instruction_info": { "before": [], "signature": "signature", "after": [] },
+            },
+            "inputs": [],
+            "outputs": [],
+        });
+        assert_eq!(
+            migrate_definition(&instructions, Path::new("transfer_sol.json")),
+            Err(MigrateError::InstructionInfo)
+        );
+    }
+
+    #[test]
+    fn test_migrate_round_trip() {
+        let text = include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../../test_files/legacy_http_request.json"
+        ));
+        let legacy = parse_jsonc_value(text).unwrap();
+        let path = Path::new("crates/cmds-std/node-definitions/http_request.json");
+        let migrated = migrate_definition(&legacy, path).unwrap().unwrap();
+
+        assert_eq!(migrated["version"], "0.1");
+        assert_eq!(migrated["prefix"], "std");
+        assert_eq!(
+            migrated["config"],
+            serde_json::json!({ "method": { "S": "GET" } })
+        );
+        assert_eq!(
+            messages(&lint_value(&migrated)),
+            [
+                "warning: unknown type \"kv\" in inputs port `headers`",
+                "warning: unknown type \"kv\" in inputs port `form`",
+            ]
+        );
+
+        let before = parse_definition(text).unwrap();
+        let after = parse_definition(&migrated.to_string()).unwrap();
+        assert_eq!(after.data.node_id, "@spo/std.http_request.0.1");
+        assert_eq!(
+            serde_json::to_value(&after.inputs).unwrap(),
+            serde_json::to_value(&before.inputs).unwrap()
+        );
+        assert_eq!(
+            serde_json::to_value(&after.outputs).unwrap(),
+            serde_json::to_value(&before.outputs).unwrap()
+        );
+        assert_eq!(after.r#type, before.r#type);
+    }
+}
@@ -1,4 +1,6 @@
-use flow_lib::command::CommandError;
+//! Conditions used by `branch`, `filter` and other commands.
+
+use crate::command::CommandError;
 use num_bigint::BigInt;
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
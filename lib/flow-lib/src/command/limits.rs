@@ -0,0 +1,632 @@
+//! Resource limits for script nodes (Deno, Bun and Python).
+//!
+//! The deployment picks a [`LimitTier`] with `SCRIPT_LIMITS_TIER` (unlimited
+//! if unset), each limit can be overridden with `SCRIPT_LIMIT_<NAME>` (`0`
+//! removes it), and a node can lower them further with a `limits` object in
+//! its config.
+//!
+//! Memory and CPU are enforced by the kernel with `setrlimit`, see
+//! [`ScriptLimits::apply_rlimits`], and sampled from `/proc` as a backstop.
+//!
+//! Time spent waiting on the host, e.g. for a transaction to be executed or a
+//! wallet to sign, is tracked with [`HostCalls`] and does not count towards
+//! the wall clock.
+//!
+//! Violations are returned as [`LimitExceeded`] errors, which are reported
+//! with a distinct kind in [`NodeError`][crate::flow_run_events::NodeError].
+
+use crate::{context::CommandContext, utils::TowerClient};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::{
+    sync::{
+        Arc, Mutex, OnceLock,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+use tokio::time::{Instant, sleep_until};
+use tower::ServiceExt;
+use value::Value;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LimitTier {
+    Free,
+    Standard,
+    #[default]
+    Unlimited,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Limit {
+    Heap,
+    Memory,
+    Cpu,
+    WallClock,
+    Log,
+    OutputSize,
+}
+
+impl std::fmt::Display for Limit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Limit::Heap => "heap",
+            Limit::Memory => "memory",
+            Limit::Cpu => "cpu time",
+            Limit::WallClock => "wall clock",
+            Limit::Log => "log volume",
+            Limit::OutputSize => "output size",
+        })
+    }
+}
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[error("{limit} limit exceeded: {detail}")]
+pub struct LimitExceeded {
+    pub limit: Limit,
+    pub detail: String,
+}
+
+impl LimitExceeded {
+    pub fn new(limit: Limit, detail: impl Into<String>) -> Self {
+        Self {
+            limit,
+            detail: detail.into(),
+        }
+    }
+}
+
+/// `None` means unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScriptLimits {
+    /// JS heap size, passed to the runtime.
+    pub heap_mb: Option<u64>,
+    /// Resident memory of the script process.
+    pub memory_mb: Option<u64>,
+    /// CPU time used by a single run.
+    pub cpu_secs: Option<u64>,
+    /// Duration of a single run.
+    pub wall_clock_secs: Option<u64>,
+    /// Bytes written to stdout and stderr during a single run.
+    pub log_bytes: Option<u64>,
+    /// Size of the JSON-encoded output.
+    pub output_bytes: Option<u64>,
+}
+
+const MB: u64 = 1024 * 1024;
+
+fn min(a: Option<u64>, b: Option<u64>) -> Option<u64> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+impl ScriptLimits {
+    pub const fn tier(tier: LimitTier) -> Self {
+        match tier {
+            LimitTier::Free => Self {
+                heap_mb: Some(256),
+                memory_mb: Some(512),
+                cpu_secs: Some(30),
+                wall_clock_secs: Some(60),
+                log_bytes: Some(MB),
+                output_bytes: Some(MB),
+            },
+            LimitTier::Standard => Self {
+                heap_mb: Some(1024),
+                memory_mb: Some(2048),
+                cpu_secs: Some(120),
+                wall_clock_secs: Some(180),
+                log_bytes: Some(10 * MB),
+                output_bytes: Some(16 * MB),
+            },
+            LimitTier::Unlimited => Self {
+                heap_mb: None,
+                memory_mb: None,
+                cpu_secs: None,
+                wall_clock_secs: None,
+                log_bytes: None,
+                output_bytes: None,
+            },
+        }
+    }
+
+    /// Parse limits from environment variables.
+    pub fn from_env() -> Self {
+        let tier = std::env::var("SCRIPT_LIMITS_TIER")
+            .ok()
+            .and_then(|s| {
+                serde_json::from_value(JsonValue::String(s.trim().to_lowercase()))
+                    .inspect_err(|_| tracing::warn!("invalid SCRIPT_LIMITS_TIER: {}", s))
+                    .ok()
+            })
+            .unwrap_or_default();
+        let mut limits = Self::tier(tier);
+        for (name, field) in [
+            ("SCRIPT_LIMIT_HEAP_MB", &mut limits.heap_mb),
+            ("SCRIPT_LIMIT_MEMORY_MB", &mut limits.memory_mb),
+            ("SCRIPT_LIMIT_CPU_SECS", &mut limits.cpu_secs),
+            ("SCRIPT_LIMIT_WALL_CLOCK_SECS", &mut limits.wall_clock_secs),
+            ("SCRIPT_LIMIT_LOG_BYTES", &mut limits.log_bytes),
+            ("SCRIPT_LIMIT_OUTPUT_BYTES", &mut limits.output_bytes),
+        ] {
+            if let Some(value) = std::env::var(name)
+                .ok()
+                .and_then(|s| s.trim().parse::<u64>().ok())
+            {
+                *field = (value > 0).then_some(value);
+            }
+        }
+        limits
+    }
+
+    /// Limits of this deployment, read once from the environment.
+    pub fn deployment() -> &'static Self {
+        static LIMITS: OnceLock<ScriptLimits> = OnceLock::new();
+        LIMITS.get_or_init(Self::from_env)
+    }
+
+    /// Lower these limits with the `limits` object of a node config.
+    /// A node cannot raise a limit above the deployment's.
+    pub fn for_node(&self, config: &JsonValue) -> Self {
+        let node = match config.get("limits") {
+            Some(limits) => serde_json::from_value::<Self>(limits.clone())
+                .inspect_err(|error| tracing::warn!("invalid node limits: {}", error))
+                .unwrap_or_default(),
+            None => return *self,
+        };
+        Self {
+            heap_mb: min(self.heap_mb, node.heap_mb),
+            memory_mb: min(self.memory_mb, node.memory_mb),
+            cpu_secs: min(self.cpu_secs, node.cpu_secs),
+            wall_clock_secs: min(self.wall_clock_secs, node.wall_clock_secs),
+            log_bytes: min(self.log_bytes, node.log_bytes),
+            output_bytes: min(self.output_bytes, node.output_bytes),
+        }
+    }
+
+    pub fn wall_clock(&self) -> Option<Duration> {
+        self.wall_clock_secs.map(Duration::from_secs)
+    }
+
+    pub fn check_output(&self, output: &value::Map) -> Result<(), LimitExceeded> {
+        let Some(max) = self.output_bytes else {
+            return Ok(());
+        };
+        let size = serde_json::to_vec(&JsonValue::from(Value::Map(output.clone())))
+            .map(|bytes| bytes.len() as u64)
+            .unwrap_or(0);
+        if size > max {
+            return Err(LimitExceeded::new(
+                Limit::OutputSize,
+                format!("output is {size} bytes, max {max}"),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Enforce `memory_mb` and `cpu_secs` with `setrlimit` in the child,
+    /// before it executes the script runtime.
+    ///
+    /// Memory is capped with `RLIMIT_DATA`: V8 and JavaScriptCore reserve
+    /// gigabytes of address space up front, which would trip `RLIMIT_AS`
+    /// while only committed memory counts towards `RLIMIT_DATA`.
+    /// `RLIMIT_CPU` counts the whole life of the process, [`ScriptLimits::watch`]
+    /// moves it forward at the start of every run.
+    #[cfg(unix)]
+    pub fn apply_rlimits(&self, command: &mut tokio::process::Command) {
+        let memory = self.memory_mb.map(|mb| mb * MB);
+        let cpu_secs = self.cpu_secs;
+        if memory.is_none() && cpu_secs.is_none() {
+            return;
+        }
+        // SAFETY: getrlimit and setrlimit are async-signal-safe, nothing is allocated
+        unsafe {
+            command.pre_exec(move || {
+                if let Some(bytes) = memory {
+                    set_rlimit(libc::RLIMIT_DATA, bytes, true)?;
+                }
+                if let Some(secs) = cpu_secs {
+                    set_rlimit(libc::RLIMIT_CPU, secs, false)?;
+                }
+                Ok(())
+            });
+        }
+    }
+
+    /// The limit enforced by the kernel that ended a process, if any.
+    #[cfg(unix)]
+    pub fn exceeded_by_exit(&self, status: std::process::ExitStatus) -> Option<LimitExceeded> {
+        use std::os::unix::process::ExitStatusExt;
+        match (status.signal(), self.cpu_secs) {
+            (Some(libc::SIGXCPU), Some(max)) => Some(LimitExceeded::new(
+                Limit::Cpu,
+                format!("process used more than {max}s of CPU"),
+            )),
+            _ => None,
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub fn exceeded_by_exit(&self, _status: std::process::ExitStatus) -> Option<LimitExceeded> {
+        None
+    }
+
+    /// Resolve when the process or the run exceeds a limit.
+    ///
+    /// `log_bytes` counts bytes written by the process and is compared with
+    /// its value when the watch started. Time spent in `host_calls` is not
+    /// counted towards the wall clock. CPU and memory are sampled from
+    /// `/proc` and only checked on Linux, on top of the kernel limits set by
+    /// [`ScriptLimits::apply_rlimits`]. Never resolves if nothing is limited.
+    pub async fn watch(
+        &self,
+        pid: Option<u32>,
+        log_bytes: &AtomicU64,
+        host_calls: &HostCalls,
+    ) -> LimitExceeded {
+        const INTERVAL: Duration = Duration::from_millis(250);
+
+        let started = Instant::now();
+        let paused_start = host_calls.paused();
+        let deadline = |secs: u64| {
+            host_calls
+                .deadline(started + Duration::from_secs(secs))
+                .map(|deadline| deadline - paused_start)
+        };
+        let log_start = log_bytes.load(Ordering::Relaxed);
+        let cpu_start = pid.and_then(process_usage).map(|usage| usage.cpu);
+        #[cfg(target_os = "linux")]
+        if let (Some(pid), Some(start), Some(max)) = (pid, cpu_start, self.cpu_secs) {
+            // a second of slack, so that the poller reports the limit first
+            let until = start.as_secs() + max + 1;
+            if let Err(error) = set_process_cpu_rlimit(pid, until) {
+                tracing::warn!("could not set RLIMIT_CPU of {}: {}", pid, error);
+            }
+        }
+        loop {
+            let tick = Instant::now() + INTERVAL;
+            match self.wall_clock_secs.and_then(deadline) {
+                Some(until) if until <= tick => {
+                    sleep_until(until).await;
+                    // a host call could have started while sleeping
+                    if let Some(secs) = self.wall_clock_secs
+                        && deadline(secs).is_some_and(|until| until <= Instant::now())
+                    {
+                        return LimitExceeded::new(
+                            Limit::WallClock,
+                            format!("run took more than {secs}s"),
+                        );
+                    }
+                }
+                _ => sleep_until(tick).await,
+            }
+            if let Some(max) = self.log_bytes {
+                let written = log_bytes.load(Ordering::Relaxed).saturating_sub(log_start);
+                if written > max {
+                    return LimitExceeded::new(
+                        Limit::Log,
+                        format!("wrote {written} bytes of logs, max {max}"),
+                    );
+                }
+            }
+            if let Some(usage) = pid.and_then(process_usage) {
+                if let (Some(max), Some(start)) = (self.cpu_secs, cpu_start) {
+                    let used = usage.cpu.saturating_sub(start);
+                    if used > Duration::from_secs(max) {
+                        return LimitExceeded::new(
+                            Limit::Cpu,
+                            format!("used {:.1}s of CPU, max {max}s", used.as_secs_f64()),
+                        );
+                    }
+                }
+                if let Some(max) = self.memory_mb
+                    && usage.rss_bytes > max * MB
+                {
+                    return LimitExceeded::new(
+                        Limit::Memory,
+                        format!("resident memory {} MB, max {max} MB", usage.rss_bytes / MB),
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Time a run spent waiting on host calls.
+///
+/// Scripts call back into the host to execute transactions and request
+/// signatures, which can wait on a user's wallet for minutes. Use
+/// [`HostCalls::track`] on the context given to the script and pass the same
+/// value to [`ScriptLimits::watch`].
+#[derive(Debug, Clone, Default)]
+pub struct HostCalls(Arc<Mutex<HostCallsState>>);
+
+#[derive(Debug, Default)]
+struct HostCallsState {
+    in_flight: usize,
+    since: Option<Instant>,
+    paused: Duration,
+}
+
+/// Ends a host call when dropped.
+#[derive(Debug)]
+pub struct HostCallGuard(HostCalls);
+
+impl HostCalls {
+    /// Start a host call, the wall clock is paused until every started call
+    /// has ended.
+    pub fn start(&self) -> HostCallGuard {
+        let mut state = self.0.lock().unwrap();
+        if state.in_flight == 0 {
+            state.since = Some(Instant::now());
+        }
+        state.in_flight += 1;
+        HostCallGuard(self.clone())
+    }
+
+    /// Total time with at least one call in flight, up to now.
+    pub fn paused(&self) -> Duration {
+        let state = self.0.lock().unwrap();
+        state.paused + state.since.map_or(Duration::ZERO, |since| since.elapsed())
+    }
+
+    /// Move `deadline` by the time spent in host calls.
+    /// `None` while a call is in flight.
+    pub fn deadline(&self, deadline: Instant) -> Option<Instant> {
+        let state = self.0.lock().unwrap();
+        (state.in_flight == 0).then(|| deadline + state.paused)
+    }
+
+    /// Wrap a service, requests to it count as host calls.
+    pub fn wrap<T, U, E>(&self, service: TowerClient<T, U, E>) -> TowerClient<T, U, E>
+    where
+        T: Send + 'static,
+        U: Send + 'static,
+        E: Send + 'static,
+    {
+        let calls = self.clone();
+        TowerClient::new(tower::service_fn(move |request: T| {
+            let service = service.clone();
+            let guard = calls.start();
+            async move {
+                let result = service.oneshot(request).await;
+                drop(guard);
+                result
+            }
+        }))
+    }
+
+    /// Count calls to the execute and signer services of `ctx`.
+    pub fn track(&self, ctx: &mut CommandContext) {
+        let services = ctx.raw().services;
+        let execute = self.wrap(services.execute.clone());
+        let signer = self.wrap(services.signer.clone());
+        ctx.set_execute(execute);
+        ctx.set_signer(signer);
+    }
+}
+
+impl Drop for HostCallGuard {
+    fn drop(&mut self) {
+        let mut state = (self.0).0.lock().unwrap();
+        state.in_flight -= 1;
+        if state.in_flight == 0
+            && let Some(since) = state.since.take()
+        {
+            state.paused += since.elapsed();
+        }
+    }
+}
+
+/// Set the soft limit of `resource` to `value`, and the hard limit too if `hard`.
+/// Never raises the current hard limit. Runs between fork and exec.
+#[cfg(unix)]
+fn set_rlimit(resource: RlimitResource, value: u64, hard: bool) -> std::io::Result<()> {
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    // SAFETY: `limit` is a valid rlimit
+    if unsafe { libc::getrlimit(resource, &mut limit) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let value = (value as libc::rlim_t).min(limit.rlim_max);
+    limit.rlim_cur = value;
+    if hard {
+        limit.rlim_max = value;
+    }
+    // SAFETY: `limit` is a valid rlimit
+    if unsafe { libc::setrlimit(resource, &limit) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+type RlimitResource = libc::__rlimit_resource_t;
+#[cfg(all(unix, not(all(target_os = "linux", target_env = "gnu"))))]
+type RlimitResource = libc::c_int;
+
+/// Set the soft `RLIMIT_CPU` of a running process to `secs` of total CPU time.
+#[cfg(target_os = "linux")]
+fn set_process_cpu_rlimit(pid: u32, secs: u64) -> std::io::Result<()> {
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    // SAFETY: `limit` is a valid rlimit, a null new limit only reads it
+    if unsafe {
+        libc::prlimit(
+            pid as libc::pid_t,
+            libc::RLIMIT_CPU,
+            std::ptr::null(),
+            &mut limit,
+        )
+    } != 0
+    {
+        return Err(std::io::Error::last_os_error());
+    }
+    limit.rlim_cur = (secs as libc::rlim_t).min(limit.rlim_max);
+    // SAFETY: `limit` is a valid rlimit
+    if unsafe {
+        libc::prlimit(
+            pid as libc::pid_t,
+            libc::RLIMIT_CPU,
+            &limit,
+            std::ptr::null_mut(),
+        )
+    } != 0
+    {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProcessUsage {
+    pub cpu: Duration,
+    pub rss_bytes: u64,
+}
+
+/// CPU time and resident memory of a process, `None` if unavailable.
+#[cfg(target_os = "linux")]
+pub fn process_usage(pid: u32) -> Option<ProcessUsage> {
+    // USER_HZ, the unit of utime and stime, is 100 on every Linux architecture
+    const TICKS_PER_SEC: u64 = 100;
+
+    let stat = std::fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+    // comm may contain spaces, fields after it start with the state
+    let mut fields = stat.get(stat.rfind(')')? + 2..)?.split_whitespace();
+    let utime = fields.nth(11)?.parse::<u64>().ok()?;
+    let stime = fields.next()?.parse::<u64>().ok()?;
+
+    let status = std::fs::read_to_string(format!("/proc/{pid}/status")).ok()?;
+    let rss_kb = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))
+        .and_then(|rest| {
+            rest.trim()
+                .trim_end_matches("kB")
+                .trim()
+                .parse::<u64>()
+                .ok()
+        })?;
+
+    Some(ProcessUsage {
+        cpu: Duration::from_millis((utime + stime) * 1000 / TICKS_PER_SEC),
+        rss_bytes: rss_kb * 1024,
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn process_usage(_pid: u32) -> Option<ProcessUsage> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_for_node() {
+        let deployment = ScriptLimits::tier(LimitTier::Free);
+        let limits = deployment.for_node(&serde_json::json!({
+            "limits": { "cpu_secs": 5, "heap_mb": 4096 }
+        }));
+        assert_eq!(limits.cpu_secs, Some(5));
+        assert_eq!(limits.heap_mb, Some(256));
+        assert_eq!(limits.memory_mb, deployment.memory_mb);
+
+        let unlimited = ScriptLimits::tier(LimitTier::Unlimited);
+        let limits = unlimited.for_node(&serde_json::json!({ "limits": { "log_bytes": 10 } }));
+        assert_eq!(limits.log_bytes, Some(10));
+        assert_eq!(limits.cpu_secs, None);
+    }
+
+    #[test]
+    fn test_check_output() {
+        let limits = ScriptLimits {
+            output_bytes: Some(16),
+            ..<_>::default()
+        };
+        assert!(limits.check_output(&value::map! { "a" => 1 }).is_ok());
+        let error = limits
+            .check_output(&value::map! { "a" => "x".repeat(32) })
+            .unwrap_err();
+        assert_eq!(error.limit, Limit::OutputSize);
+    }
+
+    #[tokio::test]
+    async fn test_watch() {
+        let log_bytes = AtomicU64::new(100);
+        let limits = ScriptLimits {
+            log_bytes: Some(10),
+            wall_clock_secs: Some(5),
+            ..<_>::default()
+        };
+        let host_calls = HostCalls::default();
+        let watch = limits.watch(None, &log_bytes, &host_calls);
+        tokio::pin!(watch);
+        tokio::select! {
+            _ = &mut watch => panic!("limit exceeded too early"),
+            _ = tokio::time::sleep(Duration::from_millis(300)) => {}
+        }
+        log_bytes.fetch_add(11, Ordering::Relaxed);
+        assert_eq!(watch.await.limit, Limit::Log);
+    }
+
+    #[tokio::test]
+    async fn test_watch_host_calls() {
+        let log_bytes = AtomicU64::new(0);
+        let host_calls = HostCalls::default();
+        let limits = ScriptLimits {
+            wall_clock_secs: Some(1),
+            ..<_>::default()
+        };
+        let started = Instant::now();
+        let watch = limits.watch(None, &log_bytes, &host_calls);
+        tokio::pin!(watch);
+        let call = host_calls.start();
+        tokio::select! {
+            _ = &mut watch => panic!("time in host calls was counted"),
+            _ = tokio::time::sleep(Duration::from_millis(1500)) => {}
+        }
+        drop(call);
+        assert!(host_calls.paused() >= Duration::from_millis(1500));
+        assert_eq!(watch.await.limit, Limit::WallClock);
+        assert!(started.elapsed() >= Duration::from_millis(2500));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_apply_rlimits() {
+        let limits = ScriptLimits {
+            memory_mb: Some(64),
+            cpu_secs: Some(1),
+            ..<_>::default()
+        };
+        let mut command = tokio::process::Command::new("sh");
+        command.args(["-c", "ulimit -d; ulimit -t; while :; do :; done"]);
+        command.stdout(std::process::Stdio::piped());
+        limits.apply_rlimits(&mut command);
+        let output = command.output().await.unwrap();
+        // ulimit -d is in KiB
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "65536\n1\n");
+        assert_eq!(
+            limits.exceeded_by_exit(output.status).unwrap().limit,
+            Limit::Cpu
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_process_usage() {
+        let usage = process_usage(std::process::id()).unwrap();
+        assert!(usage.rss_bytes > 0);
+    }
+}
@@ -23,6 +23,8 @@ use uuid::Uuid;
 use value::Value;
 
 pub mod builder;
+pub mod condition;
+pub mod limits;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ReadCapability {
@@ -38,7 +40,7 @@ pub mod prelude {
         CmdOutputDescription as Output, FlowId, Name, ValueSet, ValueType,
         command::{
             CommandDescription, CommandError, CommandTrait, InstructionInfo, ReadCapability,
-            builder::{BuildResult, BuilderCache, BuilderError, CmdBuilder},
+            builder::{BuildResult, BuilderCache, BuilderError, CmdBuilder, lint::PortSchema},
         },
         config::{client::NodeData, node::Permissions},
         context::CommandContext,
@@ -78,7 +80,7 @@ pub fn parse_value_tagged_bool(json: &serde_json::Value) -> Option<bool> {
 }
 
 pub fn parse_value_tagged_or_json(json: serde_json::Value) -> Value {
-    parse_value_tagged(json.clone()).unwrap_or_else(|_| Value::from(json))
+    Value::from_tagged_or_json(json)
 }
 
 fn scoped_builtin_alias<'a>(id: &'a str) -> Option<Cow<'a, str>> {
@@ -175,6 +177,15 @@ pub trait CommandTrait: 'static {
     fn node_data(&self) -> NodeData {
         default_node_data(self)
     }
+
+    /// Deserialize `inputs` into the input type of the command without running
+    /// it, `None` if the command has no input type.
+    ///
+    /// Used by [`lint`][builder::lint] to compare node-definitions with the
+    /// command.
+    fn check_inputs(&self, _inputs: ValueSet) -> Option<Result<(), value::Error>> {
+        None
+    }
 }
 
 /// Specify how to convert inputs into passthrough outputs.
@@ -333,6 +333,7 @@ fn parse_command_type(s: &str) -> Option<CommandType> {
     match s {
         "deno" => Some(CommandType::Deno),
         "bun" => Some(CommandType::Bun),
+        "python" => Some(CommandType::Python),
         "WASM" => Some(CommandType::Wasm),
         "mock" => Some(CommandType::Mock),
         _ => None,
@@ -346,6 +347,8 @@ fn infer_command_type(node_type: &str, node_id: &str) -> CommandType {
             CommandType::Deno
         } else if name == "bun_script" || name.starts_with("bun_") {
             CommandType::Bun
+        } else if name == "python_script" || name.starts_with("python_") {
+            CommandType::Python
         } else {
             CommandType::Native
         }
@@ -355,13 +358,21 @@ fn infer_command_type(node_type: &str, node_id: &str) -> CommandType {
 impl NodeDataV2 {
     fn into_node_data(self, cmd_type: CommandType) -> NodeData {
         let config = JsonValue::Object(self.config.into_iter().collect::<JsonMap<_, _>>());
+        // module bytes are downloaded when the flow is loaded
+        let wasm = (cmd_type == CommandType::Wasm)
+            .then(|| config.get("supabase_id").and_then(JsonValue::as_i64))
+            .flatten()
+            .map(|supabase_id| WasmNode {
+                supabase_id,
+                bytes: None,
+            });
         NodeData {
             r#type: cmd_type,
             node_id: self.node_id,
             outputs: self.ports.outputs,
             inputs: self.ports.inputs,
             config,
-            wasm: None,
+            wasm,
             instruction_info: None,
         }
     }
@@ -657,4 +668,57 @@ mod tests {
         let parsed: Node = node.into();
         assert_eq!(parsed.data.r#type, CommandType::Bun);
     }
+
+    #[test]
+    fn node_v2_parses_python_nodes() {
+        let node = NodeV2 {
+            id: Uuid::nil(),
+            r#type: "python".to_owned(),
+            position: None,
+            width: None,
+            height: None,
+            data: NodeDataV2 {
+                node_id: "python_add".to_owned(),
+                version: None,
+                name: None,
+                ports: Ports::default(),
+                config: HashMap::new(),
+                style: None,
+            },
+        };
+        let parsed: Node = node.into();
+        assert_eq!(parsed.data.r#type, CommandType::Python);
+        assert_eq!(
+            infer_command_type("native", "python_script"),
+            CommandType::Python
+        );
+    }
+
+    #[test]
+    fn node_v2_wasm_node_references_module() {
+        let node = NodeV2 {
+            id: Uuid::nil(),
+            r#type: "WASM".to_owned(),
+            position: None,
+            width: None,
+            height: None,
+            data: NodeDataV2 {
+                node_id: "my_module".to_owned(),
+                version: None,
+                name: None,
+                ports: Ports::default(),
+                config: HashMap::from([("supabase_id".to_owned(), JsonValue::from(42))]),
+                style: None,
+            },
+        };
+        let parsed: Node = node.into();
+        assert_eq!(parsed.data.r#type, CommandType::Wasm);
+        assert_eq!(
+            parsed.data.wasm,
+            Some(WasmNode {
+                supabase_id: 42,
+                bytes: None
+            })
+        );
+    }
 }
@@ -106,6 +106,8 @@ pub enum CommandType {
     Deno,
     #[serde(rename = "bun")]
     Bun,
+    #[serde(rename = "python")]
+    Python,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -203,7 +205,32 @@ pub struct SolanaClientConfig {
     pub cluster: SolanaNet,
 }
 
+/// Key in a node's config to use a different RPC than the flow, e.g.
+/// `{ "solana_client": { "url": "http://127.0.0.1:8899" } }`.
+pub const NODE_SOLANA_CLIENT: &str = "solana_client";
+
+#[derive(Deserialize)]
+struct NodeSolanaClient {
+    url: String,
+    #[serde(default)]
+    cluster: Option<SolanaNet>,
+}
+
 impl SolanaClientConfig {
+    /// RPC override in a node's config, the cluster is guessed from the URL
+    /// if not set.
+    pub fn from_node_config(config: &JsonValue) -> Result<Option<Self>, serde_json::Error> {
+        let Some(value) = config.get(NODE_SOLANA_CLIENT).filter(|v| !v.is_null()) else {
+            return Ok(None);
+        };
+        let NodeSolanaClient { url, cluster } = serde_json::from_value(value.clone())?;
+        let cluster = match cluster {
+            Some(cluster) => cluster,
+            None => SolanaNet::from_url(&url).map_err(serde::de::Error::custom)?,
+        };
+        Ok(Some(Self { url, cluster }))
+    }
+
     pub fn build_client(&self, http: Option<reqwest::Client>) -> RpcClient {
         let url = if self.url.trim().is_empty() {
             self.cluster.url()
@@ -247,6 +274,13 @@ pub enum SolanaNet {
     Testnet,
     #[serde(rename = "mainnet-beta")]
     Mainnet,
+    /// Local test validator or surfnet, programs are expected to have their
+    /// mainnet addresses.
+    #[serde(rename = "localnet")]
+    Localnet,
+    /// Any other RPC, the URL must be set in [`SolanaClientConfig`].
+    #[serde(rename = "custom")]
+    Custom,
 }
 
 /// Unknown Sonana network.
@@ -262,6 +296,8 @@ impl FromStr for SolanaNet {
             "devnet" => Ok(Self::Devnet),
             "testnet" => Ok(Self::Testnet),
             "mainnet-beta" => Ok(Self::Mainnet),
+            "localnet" => Ok(Self::Localnet),
+            "custom" => Ok(Self::Custom),
             s => Err(UnknownNetwork(s.to_owned())),
         }
     }
@@ -291,6 +327,14 @@ impl SolanaNet {
                 });
                 URL.clone()
             }
+            // a custom cluster has no URL of its own
+            SolanaNet::Localnet | SolanaNet::Custom => {
+                static URL: LazyLock<String> = LazyLock::new(|| {
+                    std::env::var("SOLANA_LOCALNET_URL")
+                        .unwrap_or_else(|_| "http://127.0.0.1:8899".to_owned())
+                });
+                URL.clone()
+            }
         }
     }
 
@@ -299,9 +343,12 @@ impl SolanaNet {
             SolanaNet::Devnet => "devnet",
             SolanaNet::Testnet => "testnet",
             SolanaNet::Mainnet => "mainnet-beta",
+            SolanaNet::Localnet => "localnet",
+            SolanaNet::Custom => "custom",
         }
     }
 
+    /// Guess the cluster from an RPC URL, any other HTTP URL is [`SolanaNet::Custom`].
     pub fn from_url(url: &str) -> Result<Self, UnknownNetwork> {
         if url.contains("devnet") {
             Ok(SolanaNet::Devnet)
@@ -309,6 +356,13 @@ impl SolanaNet {
             Ok(SolanaNet::Testnet)
         } else if url.contains("mainnet") {
             Ok(SolanaNet::Mainnet)
+        } else if ["localhost", "127.0.0.1", "0.0.0.0", "[::1]"]
+            .iter()
+            .any(|host| url.contains(host))
+        {
+            Ok(SolanaNet::Localnet)
+        } else if url.starts_with("http://") || url.starts_with("https://") {
+            Ok(SolanaNet::Custom)
         } else {
             Err(UnknownNetwork(url.to_owned()))
         }
@@ -444,3 +498,47 @@ impl FlowConfig {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_solana_net_from_url() {
+        for (url, net) in [
+            ("https://api.devnet.solana.com", SolanaNet::Devnet),
+            (
+                "https://mainnet.helius-rpc.com/?api-key=x",
+                SolanaNet::Mainnet,
+            ),
+            ("http://127.0.0.1:8899", SolanaNet::Localnet),
+            ("http://localhost:8899", SolanaNet::Localnet),
+            ("https://rpc.example.com", SolanaNet::Custom),
+        ] {
+            assert_eq!(SolanaNet::from_url(url).unwrap(), net, "{url}");
+        }
+        assert!(SolanaNet::from_url("not a url").is_err());
+    }
+
+    #[test]
+    fn test_node_solana_client() {
+        let config = serde_json::json!({ "amount": 1 });
+        assert_eq!(SolanaClientConfig::from_node_config(&config).unwrap(), None);
+
+        let config = serde_json::json!({
+            "solana_client": { "url": "http://127.0.0.1:8899" },
+        });
+        let client = SolanaClientConfig::from_node_config(&config)
+            .unwrap()
+            .unwrap();
+        assert_eq!(client.cluster, SolanaNet::Localnet);
+
+        let config = serde_json::json!({
+            "solana_client": { "url": "https://rpc.example.com", "cluster": "mainnet-beta" },
+        });
+        let client = SolanaClientConfig::from_node_config(&config)
+            .unwrap()
+            .unwrap();
+        assert_eq!(client.cluster, SolanaNet::Mainnet);
+    }
+}
@@ -130,7 +130,7 @@ impl From<DefinitionV2> for Definition {
     }
 }
 
-fn parse_jsonc_value(def: &str) -> Result<JsonValue, serde_json::Error> {
+pub(crate) fn parse_jsonc_value(def: &str) -> Result<JsonValue, serde_json::Error> {
     jsonc_parser::parse_to_serde_value(def, &Default::default())
         .map_err(|error| {
             serde_json::Error::io(io::Error::new(
@@ -34,11 +34,24 @@ pub mod env {
     pub const COMPUTE_BUDGET: &str = "COMPUTE_BUDGET";
     pub const FALLBACK_COMPUTE_BUDGET: &str = "FALLBACK_COMPUTE_BUDGET";
     pub const PRIORITY_FEE: &str = "PRIORITY_FEE";
+    pub const PRIORITY_FEE_ESTIMATOR: &str = "PRIORITY_FEE_ESTIMATOR";
+    pub const PRIORITY_FEE_PERCENTILE: &str = "PRIORITY_FEE_PERCENTILE";
+    pub const MAX_PRIORITY_FEE: &str = "MAX_PRIORITY_FEE";
     pub const TX_COMMITMENT_LEVEL: &str = "TX_COMMITMENT_LEVEL";
     pub const WAIT_COMMITMENT_LEVEL: &str = "WAIT_COMMITMENT_LEVEL";
+    pub const DURABLE_NONCE: &str = "DURABLE_NONCE";
+    pub const REBROADCAST_INTERVAL: &str = "REBROADCAST_INTERVAL";
+    pub const MAX_REBUILDS: &str = "MAX_REBUILDS";
+    pub const JITO_BUNDLE: &str = "JITO_BUNDLE";
+    pub const JITO_BLOCK_ENGINE_URL: &str = "JITO_BLOCK_ENGINE_URL";
+    pub const JITO_TIP: &str = "JITO_TIP";
+    pub const JITO_FALLBACK: &str = "JITO_FALLBACK";
     pub const EXECUTE_ON: &str = "EXECUTE_ON";
     pub const DEVNET_LOOKUP_TABLE: &str = "DEVNET_LOOKUP_TABLE";
     pub const MAINNET_LOOKUP_TABLE: &str = "MAINNET_LOOKUP_TABLE";
+    pub const LOCALNET_LOOKUP_TABLE: &str = "LOCALNET_LOOKUP_TABLE";
+    pub const CUSTOM_LOOKUP_TABLE: &str = "CUSTOM_LOOKUP_TABLE";
+    pub const AUTO_LOOKUP_TABLE: &str = "AUTO_LOOKUP_TABLE";
 }
 
 pub mod api_input {
@@ -408,18 +421,36 @@ pub mod execute {
     }
 
     #[serde_as]
-    #[derive(Serialize, Clone, Copy)]
+    #[derive(Serialize, Clone, Copy, Default)]
     pub struct Response {
         #[serde_as(as = "Option<DisplayFromStr>")]
         pub signature: Option<Signature>,
+        /// How the transaction landed, if it was sent by this process.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub outcome: Option<TxOutcome>,
     }
 
+    impl Response {
+        pub fn signature(signature: Signature) -> Self {
+            Self {
+                signature: Some(signature),
+                outcome: None,
+            }
+        }
+    }
+
+    /// Encoded as the signature followed by the outcome.
+    ///
+    /// `outcome` was added later and must stay the last field: older decoders
+    /// read the signature and ignore the rest, and a missing outcome is decoded
+    /// as `None`.
     impl bincode::Encode for Response {
         fn encode<E: bincode::enc::Encoder>(
             &self,
             encoder: &mut E,
         ) -> Result<(), bincode::error::EncodeError> {
             self.signature.map(|s| *s.as_array()).encode(encoder)?;
+            self.outcome.encode(encoder)?;
             Ok(())
         }
     }
@@ -429,12 +460,61 @@ pub mod execute {
             decoder: &mut D,
         ) -> Result<Self, bincode::error::DecodeError> {
             let value = Option::<[u8; 64]>::decode(decoder)?;
+            let outcome = match Option::<TxOutcome>::decode(decoder) {
+                Ok(outcome) => outcome,
+                // sent by an older version
+                Err(bincode::error::DecodeError::UnexpectedEnd { .. }) => None,
+                Err(error) => return Err(error),
+            };
             Ok(Self {
                 signature: value.map(Signature::from),
+                outcome,
             })
         }
     }
 
+    /// Details of how a transaction was sent and confirmed.
+    #[derive(
+        Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, bincode::Encode, bincode::Decode,
+    )]
+    pub struct TxOutcome {
+        /// Number of times the transaction was sent, including rebroadcasts.
+        pub attempts: u32,
+        /// Number of times the transaction was rebuilt after expiring.
+        pub rebuilds: u32,
+        /// Slot the transaction landed in.
+        pub slot: u64,
+        /// Compute unit price, in micro-lamports.
+        pub priority_fee: Option<u64>,
+    }
+
+    /// Why a transaction was dropped.
+    #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum ExpiryReason {
+        BlockHeightExceeded {
+            last_valid_block_height: u64,
+            block_height: u64,
+        },
+        BlockhashNotFound,
+        NonceAdvanced,
+    }
+
+    impl std::fmt::Display for ExpiryReason {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self {
+                ExpiryReason::BlockHeightExceeded {
+                    last_valid_block_height,
+                    block_height,
+                } => write!(
+                    f,
+                    "block height {block_height} exceeded last valid block height {last_valid_block_height}"
+                ),
+                ExpiryReason::BlockhashNotFound => f.write_str("blockhash not found"),
+                ExpiryReason::NonceAdvanced => f.write_str("nonce account was advanced"),
+            }
+        }
+    }
+
     fn unwrap(s: &Option<String>) -> &str {
         s.as_ref().map(|v| v.as_str()).unwrap_or_default()
     }
@@ -454,6 +534,18 @@ pub mod execute {
         InsufficientSolanaBalance { needed: u64, balance: u64 },
         #[error("transaction simulation failed")]
         TxSimFailed,
+        #[error("transaction expired after {attempts} attempt(s): {reason}")]
+        TxExpired { reason: ExpiryReason, attempts: u32 },
+        #[error(
+            "transaction {signature} not confirmed after {attempts} attempt(s), it may still land"
+        )]
+        TxUnconfirmed {
+            #[serde_as(as = "DisplayFromStr")]
+            signature: Signature,
+            attempts: u32,
+        },
+        #[error("bundle did not land: {0}")]
+        BundleNotLanded(String),
         #[error("{}", crate::utils::verbose_solana_error(.error))]
         Solana {
             #[source]
@@ -596,6 +688,13 @@ impl CommandContext {
     pub fn test_context() -> Self {
         let config = ContextConfig::default();
         let solana_client = Arc::new(config.solana_client.build_client(None));
+        Self::test_context_with(solana_client, unimplemented_svc())
+    }
+
+    /// Same as [`CommandContext::test_context`], with a custom Solana client
+    /// and execute service, e.g. backed by a local SVM.
+    pub fn test_context_with(solana_client: Arc<SolanaClient>, execute: execute::Svc) -> Self {
+        let config = ContextConfig::default();
         let node_id = NodeId::nil();
         let times = 0;
         let (tx, _) = flow_run_events::channel();
@@ -617,7 +716,7 @@ impl CommandContext {
                     },
                 },
             },
-            execute: unimplemented_svc(),
+            execute,
             get_jwt: unimplemented_svc(),
             node_log: NodeLogSender::new(tx, node_id, times),
             flow: FlowServices {
@@ -665,6 +764,10 @@ impl CommandContext {
         &self.data.flow.environment
     }
 
+    pub fn set_environment(&mut self, environment: HashMap<String, String>) {
+        self.data.flow.environment = environment;
+    }
+
     pub fn is_read_only(&self) -> bool {
         self.data.flow.read_only
     }
@@ -673,6 +776,16 @@ impl CommandContext {
         self.data.flow.read_only = read_only;
     }
 
+    /// Replace the [`execute`] service, e.g. to wrap it.
+    pub fn set_execute(&mut self, execute: execute::Svc) {
+        self.execute = execute;
+    }
+
+    /// Replace the [`signer`] service, e.g. to wrap it.
+    pub fn set_signer(&mut self, signer: signer::Svc) {
+        self.flow.signer = signer;
+    }
+
     pub fn endpoints(&self) -> &Endpoints {
         &self.data.flow.set.endpoints
     }
@@ -849,6 +962,53 @@ mod tests {
 
         assert_eq!(request.kind, SignatureRequestKind::TransactionMessage);
     }
+
+    #[test]
+    fn execute_response_bincode() {
+        let response = super::execute::Response {
+            signature: Some(solana_signature::Signature::from([1; 64])),
+            outcome: Some(super::execute::TxOutcome {
+                attempts: 3,
+                rebuilds: 1,
+                slot: 100,
+                priority_fee: Some(1000),
+            }),
+        };
+        let bytes = bincode::encode_to_vec(response, bincode::config::standard()).unwrap();
+        let (decoded, _): (super::execute::Response, _) =
+            bincode::decode_from_slice(&bytes, bincode::config::standard()).unwrap();
+        assert_eq!(decoded.signature, response.signature);
+        assert_eq!(decoded.outcome, response.outcome);
+    }
+
+    #[test]
+    fn execute_response_bincode_compat() {
+        let signature = solana_signature::Signature::from([1; 64]);
+        let config = bincode::config::standard();
+
+        // older versions only encode the signature
+        let old = bincode::encode_to_vec(Some([1u8; 64]), config).unwrap();
+        let (decoded, _): (super::execute::Response, _) =
+            bincode::decode_from_slice(&old, config).unwrap();
+        assert_eq!(decoded.signature, Some(signature));
+        assert_eq!(decoded.outcome, None);
+
+        let new = bincode::encode_to_vec(
+            super::execute::Response {
+                signature: Some(signature),
+                outcome: Some(super::execute::TxOutcome {
+                    attempts: 1,
+                    rebuilds: 0,
+                    slot: 1,
+                    priority_fee: None,
+                }),
+            },
+            config,
+        )
+        .unwrap();
+        let (decoded, _): (Option<[u8; 64]>, _) = bincode::decode_from_slice(&new, config).unwrap();
+        assert_eq!(decoded, Some([1; 64]));
+    }
 }
 
 pub struct RawServices<'a> {
@@ -1,4 +1,8 @@
-use crate::{NodeId, context::signer::SignatureRequest};
+use crate::{
+    NodeId,
+    command::limits::{Limit, LimitExceeded},
+    context::{execute::TxOutcome, signer::SignatureRequest},
+};
 use bincode::{Decode, Encode};
 use chrono::{DateTime, Utc};
 use futures::channel::mpsc;
@@ -130,6 +134,24 @@ pub struct NodeError {
     pub node_id: NodeId,
     pub times: u32,
     pub error: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<NodeErrorKind>,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NodeErrorKind {
+    /// A script node exceeded one of its resource limits.
+    ResourceLimit { limit: Limit },
+}
+
+impl NodeErrorKind {
+    pub fn from_error(error: &anyhow::Error) -> Option<Self> {
+        error
+            .chain()
+            .find_map(|e| e.downcast_ref::<LimitExceeded>())
+            .map(|e| NodeErrorKind::ResourceLimit { limit: e.limit })
+    }
 }
 
 #[derive(actix::Message, Default, Clone, Debug, Serialize)]
@@ -192,6 +214,9 @@ pub struct NodeFinish {
     pub time: DateTime<Utc>,
     pub node_id: NodeId,
     pub times: u32,
+    /// How the transactions sent by this node landed.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub transactions: Vec<TxOutcome>,
 }
 
 pub fn channel() -> (EventSender, EventReceiver) {
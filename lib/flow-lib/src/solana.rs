@@ -334,6 +334,120 @@ impl<'de> Deserialize<'de> for InsertionBehavior {
     }
 }
 
+/// Where the transaction lifetime comes from, see [`ExecutionConfig::durable_nonce`].
+#[derive(Default, Debug, Clone, Copy, Eq, PartialEq)]
+pub enum DurableNonce {
+    /// Use a recent blockhash.
+    #[default]
+    No,
+    /// Use a pool of nonce accounts derived from the fee payer, created on demand.
+    Pool,
+    /// Use this nonce account, its authority must be one of the signers.
+    Account(Pubkey),
+}
+
+impl FromStr for DurableNonce {
+    type Err = <Pubkey as FromStr>::Err;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "no" | "" => DurableNonce::No,
+            "pool" => DurableNonce::Pool,
+            s => DurableNonce::Account(s.parse()?),
+        })
+    }
+}
+
+impl Display for DurableNonce {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DurableNonce::No => f.write_str("no"),
+            DurableNonce::Pool => f.write_str("pool"),
+            DurableNonce::Account(pubkey) => pubkey.fmt(f),
+        }
+    }
+}
+
+impl Serialize for DurableNonce {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.to_string().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for DurableNonce {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        use serde::de::Error;
+        <Cow<'de, str> as Deserialize>::deserialize(deserializer)?
+            .parse()
+            .map_err(D::Error::custom)
+    }
+}
+
+/// How the priority fee is estimated when `priority_fee` is `auto`.
+#[derive(Default, Debug, Clone, Copy, Eq, PartialEq)]
+pub enum FeeEstimator {
+    /// Helius if it is configured, RPC otherwise.
+    #[default]
+    Auto,
+    /// Helius `getPriorityFeeEstimate`.
+    Helius,
+    /// A percentile of `getRecentPrioritizationFees` for the writable accounts.
+    Rpc,
+    /// A fixed fee, in micro-lamports per compute unit.
+    Static(u64),
+}
+
+impl FromStr for FeeEstimator {
+    type Err = std::num::ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "auto" | "" => FeeEstimator::Auto,
+            "helius" => FeeEstimator::Helius,
+            "rpc" => FeeEstimator::Rpc,
+            s => FeeEstimator::Static(s.parse()?),
+        })
+    }
+}
+
+impl Display for FeeEstimator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FeeEstimator::Auto => f.write_str("auto"),
+            FeeEstimator::Helius => f.write_str("helius"),
+            FeeEstimator::Rpc => f.write_str("rpc"),
+            FeeEstimator::Static(fee) => fee.fmt(f),
+        }
+    }
+}
+
+impl Serialize for FeeEstimator {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.to_string().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for FeeEstimator {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        use serde::de::Error;
+        <Cow<'de, str> as Deserialize>::deserialize(deserializer)?
+            .parse()
+            .map_err(D::Error::custom)
+    }
+}
+
 const fn default_tx_level() -> CommitmentLevel {
     CommitmentLevel::Confirmed
 }
@@ -342,6 +456,10 @@ const fn default_wait_level() -> CommitmentLevel {
     CommitmentLevel::Confirmed
 }
 
+const fn default_true() -> bool {
+    true
+}
+
 #[serde_as]
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(untagged)]
@@ -370,6 +488,13 @@ pub struct ExecutionConfig {
 
     pub devnet_lookup_table: Option<Pubkey>,
     pub mainnet_lookup_table: Option<Pubkey>,
+    pub localnet_lookup_table: Option<Pubkey>,
+    pub custom_lookup_table: Option<Pubkey>,
+    /// Create and extend lookup tables of the fee payer when a transaction
+    /// is too large. Off by default, it costs the fee payer rent.
+    #[serde_as(as = "DisplayFromStr")]
+    #[serde(default)]
+    pub auto_lookup_table: bool,
 
     #[serde(default)]
     pub compute_budget: InsertionBehavior,
@@ -377,12 +502,50 @@ pub struct ExecutionConfig {
     pub fallback_compute_budget: Option<u64>,
     #[serde(default)]
     pub priority_fee: InsertionBehavior,
+    #[serde(default)]
+    pub priority_fee_estimator: FeeEstimator,
+    /// Percentile of recent fees used by [`FeeEstimator::Rpc`], defaults to 75.
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    pub priority_fee_percentile: Option<u8>,
+    /// Upper bound of estimated fees, in micro-lamports per compute unit.
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    pub max_priority_fee: Option<u64>,
 
     #[serde(default = "default_tx_level")]
     pub tx_commitment_level: CommitmentLevel,
     #[serde(default = "default_wait_level")]
     pub wait_commitment_level: CommitmentLevel,
 
+    /// Use a durable nonce instead of a recent blockhash, so that transactions
+    /// waiting on wallet signatures or API inputs do not expire.
+    #[serde(default)]
+    pub durable_nonce: DurableNonce,
+
+    /// Seconds between re-sending a transaction while waiting for confirmation,
+    /// `0` to send only once.
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    pub rebroadcast_interval: Option<u64>,
+    /// How many times an expired transaction is rebuilt with a fresh blockhash
+    /// and signed again.
+    #[serde_as(as = "DisplayFromStr")]
+    #[serde(default)]
+    pub max_rebuilds: u32,
+
+    /// Send the transactions of a flow run as one atomic Jito bundle.
+    #[serde_as(as = "DisplayFromStr")]
+    #[serde(default)]
+    pub jito_bundle: bool,
+    /// Defaults to the block engine of the network, Jito runs one on mainnet
+    /// and testnet.
+    pub jito_block_engine_url: Option<String>,
+    /// Tip in lamports, paid by the last transaction of the bundle.
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    pub jito_tip: Option<u64>,
+    /// Send the transactions one by one if the bundle does not land.
+    #[serde_as(as = "DisplayFromStr")]
+    #[serde(default = "default_true")]
+    pub jito_fallback: bool,
+
     #[serde(skip)]
     pub execute_on: ExecuteOn,
 }
@@ -416,6 +579,8 @@ impl ExecutionConfig {
             SolanaNet::Devnet => self.devnet_lookup_table,
             SolanaNet::Testnet => None,
             SolanaNet::Mainnet => self.mainnet_lookup_table,
+            SolanaNet::Localnet => self.localnet_lookup_table,
+            SolanaNet::Custom => self.custom_lookup_table,
         }
     }
 }
@@ -426,11 +591,24 @@ impl Default for ExecutionConfig {
             overwrite_feepayer: None,
             devnet_lookup_table: None,
             mainnet_lookup_table: None,
+            localnet_lookup_table: None,
+            custom_lookup_table: None,
+            auto_lookup_table: false,
             compute_budget: InsertionBehavior::default(),
             fallback_compute_budget: None,
             priority_fee: InsertionBehavior::default(),
+            priority_fee_estimator: FeeEstimator::default(),
+            priority_fee_percentile: None,
+            max_priority_fee: None,
             tx_commitment_level: default_tx_level(),
             wait_commitment_level: default_wait_level(),
+            durable_nonce: DurableNonce::default(),
+            rebroadcast_interval: None,
+            max_rebuilds: 0,
+            jito_bundle: false,
+            jito_block_engine_url: None,
+            jito_tip: None,
+            jito_fallback: true,
             execute_on: ExecuteOn::default(),
         }
     }
@@ -440,8 +618,10 @@ impl Default for ExecutionConfig {
 mod tests {
     use super::*;
     use crate::context::env::{
-        COMPUTE_BUDGET, FALLBACK_COMPUTE_BUDGET, OVERWRITE_FEEPAYER, PRIORITY_FEE,
-        TX_COMMITMENT_LEVEL, WAIT_COMMITMENT_LEVEL,
+        AUTO_LOOKUP_TABLE, COMPUTE_BUDGET, DURABLE_NONCE, FALLBACK_COMPUTE_BUDGET,
+        JITO_BLOCK_ENGINE_URL, JITO_BUNDLE, JITO_FALLBACK, JITO_TIP, MAX_PRIORITY_FEE,
+        MAX_REBUILDS, OVERWRITE_FEEPAYER, PRIORITY_FEE, PRIORITY_FEE_ESTIMATOR,
+        PRIORITY_FEE_PERCENTILE, REBROADCAST_INTERVAL, TX_COMMITMENT_LEVEL, WAIT_COMMITMENT_LEVEL,
     };
     use bincode::config::standard;
     use solana_program::pubkey;
@@ -512,6 +692,75 @@ mod tests {
                 ..<_>::default()
             },
         );
+        t(
+            [(REBROADCAST_INTERVAL, "3"), (MAX_REBUILDS, "2")],
+            ExecutionConfig {
+                rebroadcast_interval: Some(3),
+                max_rebuilds: 2,
+                ..<_>::default()
+            },
+        );
+        t(
+            [
+                (PRIORITY_FEE_ESTIMATOR, "rpc"),
+                (PRIORITY_FEE_PERCENTILE, "90"),
+                (MAX_PRIORITY_FEE, "50000"),
+            ],
+            ExecutionConfig {
+                priority_fee_estimator: FeeEstimator::Rpc,
+                priority_fee_percentile: Some(90),
+                max_priority_fee: Some(50000),
+                ..<_>::default()
+            },
+        );
+        t(
+            [(PRIORITY_FEE_ESTIMATOR, "2000")],
+            ExecutionConfig {
+                priority_fee_estimator: FeeEstimator::Static(2000),
+                ..<_>::default()
+            },
+        );
+        t(
+            [(AUTO_LOOKUP_TABLE, "true")],
+            ExecutionConfig {
+                auto_lookup_table: true,
+                ..<_>::default()
+            },
+        );
+        t(
+            [
+                (JITO_BUNDLE, "true"),
+                (JITO_BLOCK_ENGINE_URL, "http://127.0.0.1:8080"),
+                (JITO_TIP, "5000"),
+                (JITO_FALLBACK, "false"),
+            ],
+            ExecutionConfig {
+                jito_bundle: true,
+                jito_block_engine_url: Some("http://127.0.0.1:8080".to_owned()),
+                jito_tip: Some(5000),
+                jito_fallback: false,
+                ..<_>::default()
+            },
+        );
+        t(
+            [(DURABLE_NONCE, "pool")],
+            ExecutionConfig {
+                durable_nonce: DurableNonce::Pool,
+                ..<_>::default()
+            },
+        );
+        t(
+            [(
+                DURABLE_NONCE,
+                "HJbqSuV94woJfyxFNnJyfQdACvvJYaNWsW1x6wmJ8kiq",
+            )],
+            ExecutionConfig {
+                durable_nonce: DurableNonce::Account(pubkey!(
+                    "HJbqSuV94woJfyxFNnJyfQdACvvJYaNWsW1x6wmJ8kiq"
+                )),
+                ..<_>::default()
+            },
+        );
     }
 
     #[test]
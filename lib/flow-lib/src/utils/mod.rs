@@ -7,6 +7,7 @@ use solana_rpc_client_api::{
 use std::{future::Future, pin::Pin};
 
 pub mod extensions;
+pub mod net;
 pub mod tower_client;
 
 pub use extensions::Extensions;
@@ -0,0 +1,248 @@
+//! Guard against commands reaching private networks.
+//!
+//! Hosts supplied by users are only allowed if every address they resolve to
+//! is globally routable.
+//!
+//! Service endpoints (S3, Solana RPC, ...) can also be private when the
+//! deployment sets [`ALLOW_PRIVATE_ENDPOINTS_ENV`], e.g. a self-hosted server
+//! talking to a local validator.
+
+use reqwest::{Url, dns::Name as DomainName};
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::{Arc, LazyLock},
+};
+
+/// Process environment variable allowing user-supplied service endpoints to
+/// be private or loopback addresses, set to `1` or `true`.
+pub const ALLOW_PRIVATE_ENDPOINTS_ENV: &str = "ALLOW_PRIVATE_ENDPOINTS";
+
+/// DNS resolver for [`reqwest`] clients that rejects non-global addresses.
+///
+/// Checking the URL before sending a request is not enough, the name could
+/// resolve to a different address when the client connects.
+pub struct Resolver;
+
+pub fn is_global(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => Ipv4Ext::is_global(ip),
+        IpAddr::V6(ip) => Ipv6Ext::is_global(ip),
+    }
+}
+
+/// Resolve `host`, failing if any of its addresses is not global.
+pub async fn lookup_global(host: &str, port: u16) -> Result<Vec<SocketAddr>, anyhow::Error> {
+    tracing::debug!("resolving {}", host);
+    let addrs = tokio::net::lookup_host((host, port))
+        .await?
+        .collect::<Vec<_>>();
+    if let Some(addr) = addrs.iter().find(|addr| !is_global(&addr.ip())) {
+        return Err(anyhow::anyhow!("IP address not allowed: {}", addr.ip()));
+    }
+    if addrs.is_empty() {
+        return Err(anyhow::anyhow!("could not resolve {}", host));
+    }
+    Ok(addrs)
+}
+
+impl reqwest::dns::Resolve for Resolver {
+    fn resolve(&self, name: DomainName) -> reqwest::dns::Resolving {
+        let name = name.as_str().to_owned();
+        Box::pin(async move {
+            let addrs = lookup_global(&name, 0).await?;
+            let addrs: Box<dyn Iterator<Item = SocketAddr> + Send> = Box::new(addrs.into_iter());
+            Ok(addrs)
+        })
+    }
+}
+
+/// Check that `host` is a global IP address or a domain resolving only to
+/// global addresses.
+pub async fn check_host(host: &str) -> Result<(), anyhow::Error> {
+    match parse_ip(host) {
+        Some(ip) if is_global(&ip) => Ok(()),
+        Some(ip) => Err(anyhow::anyhow!("IP address not allowed: {}", ip)),
+        None => lookup_global(host, 0).await.map(|_| ()),
+    }
+}
+
+/// Parse the host of a URL as an IP address, IPv6 addresses are in brackets.
+fn parse_ip(host: &str) -> Option<IpAddr> {
+    host.strip_prefix('[')
+        .and_then(|host| host.strip_suffix(']'))
+        .unwrap_or(host)
+        .parse()
+        .ok()
+}
+
+pub async fn check_url(url: &Url) -> Result<(), anyhow::Error> {
+    match url.host_str() {
+        Some(host) => check_host(host).await,
+        None => Err(anyhow::anyhow!("URL has no host")),
+    }
+}
+
+/// Redirect policy for clients using [`Resolver`].
+///
+/// The resolver only sees domain names, redirects to IP addresses are
+/// checked here.
+pub fn redirect_policy() -> reqwest::redirect::Policy {
+    const MAX_REDIRECTS: usize = 10;
+
+    reqwest::redirect::Policy::custom(
+        |attempt| match attempt.url().host_str().and_then(parse_ip) {
+            Some(ip) if !is_global(&ip) => {
+                attempt.error(anyhow::anyhow!("IP address not allowed: {}", ip))
+            }
+            _ if attempt.previous().len() >= MAX_REDIRECTS => {
+                attempt.error(anyhow::anyhow!("too many redirects"))
+            }
+            _ => attempt.follow(),
+        },
+    )
+}
+
+/// Whether the deployment allows private service endpoints, see
+/// [`ALLOW_PRIVATE_ENDPOINTS_ENV`].
+pub fn private_endpoints_allowed() -> bool {
+    std::env::var(ALLOW_PRIVATE_ENDPOINTS_ENV).is_ok_and(|value| value == "1" || value == "true")
+}
+
+/// Check a user-supplied service endpoint, private addresses are only allowed
+/// if `allow_private` is set.
+pub async fn check_endpoint(url: &Url, allow_private: bool) -> Result<(), anyhow::Error> {
+    if allow_private {
+        return Ok(());
+    }
+    check_url(url).await
+}
+
+/// Client that only connects to global addresses, using [`Resolver`] and
+/// [`redirect_policy`].
+pub fn guarded_client() -> &'static reqwest::Client {
+    static HTTP: LazyLock<reqwest::Client> = LazyLock::new(|| {
+        reqwest::Client::builder()
+            .dns_resolver(Arc::new(Resolver))
+            .redirect(redirect_policy())
+            .build()
+            .expect("build HTTP client")
+    });
+    &HTTP
+}
+
+/// Client for user-supplied service endpoints, [`guarded_client`] unless
+/// `allow_private` is set.
+pub fn endpoint_client(allow_private: bool) -> &'static reqwest::Client {
+    static HTTP: LazyLock<reqwest::Client> = LazyLock::new(reqwest::Client::new);
+    if allow_private {
+        &HTTP
+    } else {
+        guarded_client()
+    }
+}
+
+// copied from nightly rust
+trait Ipv4Ext {
+    fn is_global(&self) -> bool;
+    fn is_shared(&self) -> bool;
+    fn is_benchmarking(&self) -> bool;
+    fn is_reserved(&self) -> bool;
+}
+
+impl Ipv4Ext for Ipv4Addr {
+    fn is_global(&self) -> bool {
+        !(self.octets()[0] == 0 // "This network"
+            || self.is_private()
+            || Ipv4Ext::is_shared(self)
+            || self.is_loopback()
+            || self.is_link_local()
+            // addresses reserved for future protocols (`192.0.0.0/24`)
+            ||(self.octets()[0] == 192 && self.octets()[1] == 0 && self.octets()[2] == 0)
+            || self.is_documentation()
+            || Ipv4Ext::is_benchmarking(self)
+            || Ipv4Ext::is_reserved(self)
+            || self.is_broadcast())
+    }
+
+    fn is_shared(&self) -> bool {
+        self.octets()[0] == 100 && (self.octets()[1] & 0b1100_0000 == 0b0100_0000)
+    }
+
+    fn is_benchmarking(&self) -> bool {
+        self.octets()[0] == 198 && (self.octets()[1] & 0xfe) == 18
+    }
+
+    fn is_reserved(&self) -> bool {
+        self.octets()[0] & 240 == 240 && !self.is_broadcast()
+    }
+}
+
+trait Ipv6Ext {
+    fn is_global(&self) -> bool;
+    fn is_documentation(&self) -> bool;
+    fn is_unique_local(&self) -> bool;
+    fn is_unicast_link_local(&self) -> bool;
+}
+
+impl Ipv6Ext for Ipv6Addr {
+    fn is_global(&self) -> bool {
+        !(self.is_unspecified()
+            || self.is_loopback()
+            // IPv4-mapped Address (`::ffff:0:0/96`)
+            || matches!(self.segments(), [0, 0, 0, 0, 0, 0xffff, _, _])
+            // IPv4-IPv6 Translat. (`64:ff9b:1::/48`)
+            || matches!(self.segments(), [0x64, 0xff9b, 1, _, _, _, _, _])
+            // Discard-Only Address Block (`100::/64`)
+            || matches!(self.segments(), [0x100, 0, 0, 0, _, _, _, _])
+            // IETF Protocol Assignments (`2001::/23`)
+            || (matches!(self.segments(), [0x2001, b, _, _, _, _, _, _] if b < 0x200)
+                && !(
+                    // Port Control Protocol Anycast (`2001:1::1`)
+                    u128::from_be_bytes(self.octets()) == 0x2001_0001_0000_0000_0000_0000_0000_0001
+                    // Traversal Using Relays around NAT Anycast (`2001:1::2`)
+                    || u128::from_be_bytes(self.octets()) == 0x2001_0001_0000_0000_0000_0000_0000_0002
+                    // AMT (`2001:3::/32`)
+                    || matches!(self.segments(), [0x2001, 3, _, _, _, _, _, _])
+                    // AS112-v6 (`2001:4:112::/48`)
+                    || matches!(self.segments(), [0x2001, 4, 0x112, _, _, _, _, _])
+                    // ORCHIDv2 (`2001:20::/28`)
+                    || matches!(self.segments(), [0x2001, b, _, _, _, _, _, _] if (0x20..=0x2F).contains(&b))
+                ))
+            || Ipv6Ext::is_documentation(self)
+            || Ipv6Ext::is_unique_local(self)
+            || Ipv6Ext::is_unicast_link_local(self))
+    }
+
+    fn is_documentation(&self) -> bool {
+        (self.segments()[0] == 0x2001) && (self.segments()[1] == 0xdb8)
+    }
+
+    fn is_unique_local(&self) -> bool {
+        (self.segments()[0] & 0xfe00) == 0xfc00
+    }
+
+    fn is_unicast_link_local(&self) -> bool {
+        (self.segments()[0] & 0xffc0) == 0xfe80
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_check_host() {
+        assert!(check_host("127.0.0.1").await.is_err());
+        assert!(check_host("[::1]").await.is_err());
+        assert!(check_host("10.1.2.3").await.is_err());
+        assert!(check_host("localhost").await.is_err());
+        assert!(check_host("1.1.1.1").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_check_endpoint() {
+        let url = Url::parse("http://127.0.0.1:8899").unwrap();
+        assert!(check_endpoint(&url, false).await.is_err());
+        assert!(check_endpoint(&url, true).await.is_ok());
+    }
+}
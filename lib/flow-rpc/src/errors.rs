@@ -1,4 +1,4 @@
-use flow_lib::context::execute;
+use flow_lib::{command::limits::LimitExceeded, context::execute};
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
 
@@ -10,6 +10,7 @@ pub enum TypedError {
     Unknown(#[serde_as(as = "flow_lib::errors::AsAnyhow")] anyhow::Error),
     Capnp(#[serde_as(as = "r#as::AsCapnp")] capnp::Error),
     Execute(execute::Error),
+    LimitExceeded(LimitExceeded),
 }
 
 impl TypedError {
@@ -18,6 +19,7 @@ impl TypedError {
             TypedError::Unknown(error) => error,
             TypedError::Capnp(error) => error.into(),
             TypedError::Execute(error) => error.into(),
+            TypedError::LimitExceeded(error) => error.into(),
         }
     }
 }
@@ -28,6 +30,10 @@ impl From<anyhow::Error> for TypedError {
             Ok(e) => return TypedError::Execute(e),
             Err(e) => e,
         };
+        let e = match e.downcast::<LimitExceeded>() {
+            Ok(e) => return TypedError::LimitExceeded(e),
+            Err(e) => e,
+        };
         let e = match e.downcast::<capnp::Error>() {
             Ok(e) => return TypedError::Capnp(e),
             Err(e) => e,
@@ -46,4 +52,23 @@ mod tests {
         let typed = TypedError::from(error);
         assert!(matches!(typed, TypedError::Execute(_)));
     }
+
+    #[test]
+    fn test_limit_exceeded_round_trip() {
+        use flow_lib::{command::limits::Limit, flow_run_events::NodeErrorKind};
+
+        let exceeded = LimitExceeded::new(Limit::WallClock, "run took longer than 1s");
+        let error = anyhow::Error::from(exceeded.clone()).context("run command");
+        let json = serde_json::to_string(&TypedError::from(error)).unwrap();
+        let error = serde_json::from_str::<TypedError>(&json)
+            .unwrap()
+            .to_anyhow();
+        assert_eq!(error.downcast_ref::<LimitExceeded>(), Some(&exceeded));
+        assert_eq!(
+            NodeErrorKind::from_error(&error),
+            Some(NodeErrorKind::ResourceLimit {
+                limit: Limit::WallClock
+            })
+        );
+    }
 }
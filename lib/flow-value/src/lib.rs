@@ -456,6 +456,31 @@ mod json {
         }
     }
 
+    impl Value {
+        /// Read JSON that can be tagged with value types, as in flow inputs.
+        ///
+        /// Objects that are valid tagged values are parsed as such, at any
+        /// depth. Everything else is converted like plain JSON.
+        pub fn from_tagged_or_json(json: serde_json::Value) -> Self {
+            match json {
+                serde_json::Value::Object(map) => serde_json::from_value(
+                    serde_json::Value::Object(map.clone()),
+                )
+                .unwrap_or_else(|_| {
+                    Value::Map(
+                        map.into_iter()
+                            .map(|(k, v)| (k, Value::from_tagged_or_json(v)))
+                            .collect(),
+                    )
+                }),
+                serde_json::Value::Array(vec) => {
+                    Value::Array(vec.into_iter().map(Value::from_tagged_or_json).collect())
+                }
+                json => Value::from(json),
+            }
+        }
+    }
+
     impl From<Value> for serde_json::Value {
         fn from(value: Value) -> Self {
             match value {
@@ -934,6 +959,39 @@ mod tests {
         )
     }
 
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_from_tagged_or_json() {
+        let json = serde_json::json!({
+            "a": [1, -1, 1.5, "x", true, null],
+            "b": { "S": "tagged" },
+            "c": { "U": "10" },
+        });
+        assert_eq!(
+            Value::from_tagged_or_json(json),
+            Value::Map(crate::map! {
+                "a" => Value::Array(vec![
+                    Value::U64(1),
+                    Value::I64(-1),
+                    Value::Decimal(dec!(1.5)),
+                    Value::String("x".to_owned()),
+                    Value::Bool(true),
+                    Value::Null,
+                ]),
+                "b" => "tagged",
+                "c" => 10u64,
+            })
+        );
+        assert_eq!(
+            Value::from_tagged_or_json(serde_json::json!({ "S": "hello" })),
+            Value::String("hello".to_owned())
+        );
+        assert_eq!(
+            Value::from_tagged_or_json(serde_json::json!({ "S": 1 })),
+            Value::Map(crate::map! { "S" => 1u64 })
+        );
+    }
+
     #[cfg(feature = "json")]
     #[test]
     fn test_number_into_json() {
@@ -1,11 +1,9 @@
 //! [serde_with](https://docs.rs/serde_with/latest/serde_with/) helpers.
 
-use serde::{
-    Deserialize, Serialize,
-    de::{self, MapAccess},
-};
+use serde::{Deserialize, Serialize, de};
 use serde_with::serde_conv;
 use std::{borrow::Cow, convert::Infallible};
+#[cfg(any(feature = "solana-pubkey", feature = "solana-signature"))]
 use std::{mem::MaybeUninit, ops::ControlFlow};
 
 pub use decimal::AsDecimal;
@@ -16,6 +14,7 @@ pub use pubkey::AsPubkey;
 #[cfg(feature = "solana-signature")]
 pub use signature::AsSignature;
 
+#[cfg(any(feature = "solana-pubkey", feature = "solana-signature"))]
 fn try_from_fn_erased<T: Copy, E>(
     buffer: &mut [MaybeUninit<T>],
     mut generator: impl FnMut(usize) -> Result<T, E>,
@@ -31,6 +30,7 @@ fn try_from_fn_erased<T: Copy, E>(
     ControlFlow::Continue(())
 }
 
+#[cfg(any(feature = "solana-pubkey", feature = "solana-signature"))]
 fn try_from_fn<const N: usize, T: Copy, E, F>(cb: F) -> Result<[T; N], E>
 where
     F: FnMut(usize) -> Result<T, E>,
@@ -182,7 +182,7 @@ pub(crate) mod pubkey {
 
         fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
         where
-            A: MapAccess<'de>,
+            A: de::MapAccess<'de>,
         {
             if self.map {
                 map.next_key::<Const<public_key>>()?;
@@ -17,3 +17,20 @@ pub struct RequestData {
     pub queries: Vec<String>,
     pub method: Method,
 }
+
+#[derive(Serialize, Deserialize)]
+pub struct KvRead {
+    pub store: String,
+    pub key: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct KvWrite {
+    pub store: String,
+    pub key: String,
+    /// MessagePack-encoded value.
+    pub value: Vec<u8>,
+}
+
+/// MessagePack-encoded value, `None` when the key does not exist.
+pub type KvResult = std::result::Result<Option<Vec<u8>>, String>;
@@ -1,10 +1,27 @@
 use crate::{
     Result,
-    common::{Method, RequestData},
+    common::{KvRead, KvResult, KvWrite, Method, RequestData},
 };
+use serde::de::DeserializeOwned;
 
 unsafe extern "C" {
     fn http_call_request(bytes: u32, bytes_len: u32) -> u64;
+    fn kv_read(bytes: u32, bytes_len: u32) -> u64;
+    fn kv_write(bytes: u32, bytes_len: u32) -> u64;
+}
+
+/// Read `[len, data]` written by the host at `offset`
+fn read_response<T: DeserializeOwned>(offset: u64) -> Result<T> {
+    if offset == 0 {
+        Err("Expected data, got null ptr")?
+    } else {
+        let slice = unsafe {
+            let len = *(offset as *const u32);
+            let data = (offset + 4) as *const u8;
+            std::slice::from_raw_parts(data, len as usize)
+        };
+        Ok(rmp_serde::from_slice(slice)?)
+    }
 }
 
 /// Calls http request, then returns body
@@ -23,16 +40,19 @@ pub fn call_request(
     };
     let bytes = rmp_serde::to_vec_named(&data)?;
     let offset = unsafe { http_call_request(bytes.as_ptr() as u32, bytes.len() as u32) };
+    read_response(offset)
+}
 
-    // Extract [len, data]
-    if offset == 0 {
-        Err("Expected data, got null ptr")?
-    } else {
-        let slice = unsafe {
-            let len = *(offset as *const u32);
-            let data = (offset + 4) as *const u8;
-            std::slice::from_raw_parts(data, len as usize)
-        };
-        Ok(rmp_serde::from_slice(slice)?)
-    }
+/// Reads an item from a KV store
+pub fn read_item(store: String, key: String) -> Result<Option<Vec<u8>>> {
+    let bytes = rmp_serde::to_vec_named(&KvRead { store, key })?;
+    let offset = unsafe { kv_read(bytes.as_ptr() as u32, bytes.len() as u32) };
+    Ok(read_response::<KvResult>(offset)??)
+}
+
+/// Writes an item to a KV store, then returns the old value
+pub fn write_item(store: String, key: String, value: Vec<u8>) -> Result<Option<Vec<u8>>> {
+    let bytes = rmp_serde::to_vec_named(&KvWrite { store, key, value })?;
+    let offset = unsafe { kv_write(bytes.as_ptr() as u32, bytes.len() as u32) };
+    Ok(read_response::<KvResult>(offset)??)
 }
@@ -0,0 +1,16 @@
+use crate::{Result, ffi};
+use serde::{Serialize, de::DeserializeOwned};
+
+/// Read `key` from a KV store of the flow owner, `None` if it does not exist.
+pub fn get<T: DeserializeOwned>(store: &str, key: &str) -> Result<Option<T>> {
+    ffi::read_item(store.to_owned(), key.to_owned())?
+        .map(|bytes| Ok(rmp_serde::from_slice(&bytes)?))
+        .transpose()
+}
+
+/// Write `value` to `key` in a KV store of the flow owner.
+pub fn set<T: Serialize>(store: &str, key: &str, value: &T) -> Result<()> {
+    let value = rmp_serde::to_vec_named(value)?;
+    ffi::write_item(store.to_owned(), key.to_owned(), value)?;
+    Ok(())
+}
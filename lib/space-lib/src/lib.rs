@@ -37,12 +37,24 @@
 //!     .call()?
 //!     .into_string()?;
 //! ```
+//!
+//! ## KV store
+//!
+//! ```rust,ignore
+//! use space_lib::kv;
+//!
+//! let count = kv::get::<u64>("counters", "runs")?.unwrap_or(0);
+//! kv::set("counters", "runs", &(count + 1))?;
+//! ```
+//!
+//! Environment variables of the flow are available with [`std::env::var`].
 
 // Modules
 pub mod common;
 mod error;
 mod ffi;
 mod http;
+pub mod kv;
 
 // Exports
 pub use error::{Error, Result};
@@ -0,0 +1,994 @@
+//! Typed client code to start deployments, generated from the `flow_input`
+//! and `flow_output` nodes of their flow.
+
+use crate::{
+    Error, fmt_code, read_file,
+    remote::{DeploymentId, DeploymentQuery, FlowId, load_client},
+    rust_type_serde_decor,
+    schema::ValueType,
+    value_type_to_rust_type, write_file,
+};
+use error_stack::{Report, ResultExt};
+use proc_macro2::{Ident, Span, TokenStream};
+use quote::quote;
+use serde_json::{Value as JsonValue, json};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Language {
+    Rust,
+    #[value(alias = "ts")]
+    Typescript,
+}
+
+/// Where to read the flow from.
+#[derive(Debug)]
+pub enum Source {
+    Deployment(DeploymentId),
+    Flow(FlowId),
+    File(PathBuf),
+}
+
+/// An input or output of a flow.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Port {
+    pub name: String,
+    pub r#type: ValueType,
+    /// Input has a default value
+    pub optional: bool,
+}
+
+/// Inputs and outputs of a flow.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct FlowInterface {
+    pub id: Option<FlowId>,
+    pub name: Option<String>,
+    pub inputs: Vec<Port>,
+    pub outputs: Vec<Port>,
+}
+
+/// Ports of a node, in flows saved by the editor (`data.ports.outputs`) or
+/// in deployments (`data.outputs`).
+fn node_ports<'a>(data: &'a JsonValue, kind: &str) -> &'a [JsonValue] {
+    data.get("ports")
+        .and_then(|ports| ports.get(kind))
+        .or_else(|| data.get(kind))
+        .and_then(JsonValue::as_array)
+        .map_or(&[][..], Vec::as_slice)
+}
+
+/// A string config value, plain or tagged.
+fn config_str<'a>(config: &'a JsonValue, key: &str) -> Option<&'a str> {
+    let value = config.get(key)?;
+    value.get("S").unwrap_or(value).as_str()
+}
+
+fn is_node(data: &JsonValue, name: &str) -> bool {
+    data["node_id"]
+        .as_str()
+        .and_then(|id| id.rsplit('/').next())
+        == Some(name)
+}
+
+fn port_type(port: &JsonValue) -> ValueType {
+    port["type"]
+        .as_str()
+        .or_else(|| port["type_bounds"][0].as_str())
+        .and_then(|ty| ty.parse().ok())
+        .unwrap_or(ValueType::Free)
+}
+
+fn port_name(port: Option<&JsonValue>) -> Option<&str> {
+    port.and_then(|port| port["name"].as_str())
+        .filter(|name| !name.is_empty())
+}
+
+/// Type of the output connected to an input port.
+fn source_type(
+    nodes: &[JsonValue],
+    edges: &[JsonValue],
+    target: &JsonValue,
+    port: &JsonValue,
+) -> Option<ValueType> {
+    let edge = edges
+        .iter()
+        .find(|edge| edge["target"] == target["id"] && edge["targetHandle"] == port["id"])?;
+    let handle = edge["sourceHandle"].as_str()?;
+    let (kind, handle) = match handle.strip_prefix("passthrough-") {
+        Some(handle) => ("inputs", handle),
+        None => ("outputs", handle),
+    };
+    let source = nodes.iter().find(|node| node["id"] == edge["source"])?;
+    let port = node_ports(&source["data"], kind)
+        .iter()
+        .find(|port| port["id"] == handle)?;
+    Some(port_type(port))
+}
+
+fn push_port(ports: &mut Vec<Port>, port: Port) {
+    if !ports.iter().any(|p| p.name == port.name) {
+        ports.push(port);
+    }
+}
+
+impl FlowInterface {
+    /// Read the `flow_input` and `flow_output` nodes of a flow exported with
+    /// `spo flow export`, or saved in a deployment.
+    pub fn from_flow(flow: &JsonValue) -> Self {
+        let flow = flow.get("flow").unwrap_or(flow);
+        let nodes = flow["nodes"].as_array().map_or(&[][..], Vec::as_slice);
+        let edges = flow["edges"].as_array().map_or(&[][..], Vec::as_slice);
+        let mut interface = Self {
+            id: flow["id"].as_str().and_then(|id| id.parse().ok()),
+            name: flow["name"].as_str().map(str::to_owned),
+            ..<_>::default()
+        };
+        for node in nodes {
+            let data = &node["data"];
+            let config = &data["config"];
+            if is_node(data, "flow_input") {
+                let port = node_ports(data, "outputs").first();
+                let Some(name) = port_name(port).or_else(|| config_str(config, "label")) else {
+                    continue;
+                };
+                let r#type = config_str(config, "type")
+                    .and_then(|ty| ty.parse().ok())
+                    .filter(|ty| *ty != ValueType::Free)
+                    .or_else(|| port.map(port_type))
+                    .unwrap_or(ValueType::Free);
+                let optional = ["value", "form_label"].into_iter().any(|key| {
+                    config
+                        .get(key)
+                        .is_some_and(|value| !value.is_null() && *value != json!({ "N": 0 }))
+                });
+                push_port(
+                    &mut interface.inputs,
+                    Port {
+                        name: name.to_owned(),
+                        r#type,
+                        optional,
+                    },
+                );
+            } else if is_node(data, "flow_output") {
+                let port = node_ports(data, "inputs").first();
+                let Some(name) = port_name(port)
+                    .or_else(|| config_str(config, "label"))
+                    .or_else(|| port_name(node_ports(data, "outputs").first()))
+                else {
+                    continue;
+                };
+                let r#type = port
+                    .and_then(|port| source_type(nodes, edges, node, port))
+                    .unwrap_or(ValueType::Free);
+                push_port(
+                    &mut interface.outputs,
+                    Port {
+                        name: name.to_owned(),
+                        r#type,
+                        optional: false,
+                    },
+                );
+            }
+        }
+        interface
+    }
+
+    fn title(&self) -> String {
+        match (&self.name, &self.id) {
+            (Some(name), _) => format!("flow {name:?}"),
+            (None, Some(id)) => format!("flow {id}"),
+            (None, None) => "flow".to_owned(),
+        }
+    }
+
+    fn has_type(&self, ty: ValueType) -> bool {
+        self.inputs
+            .iter()
+            .chain(&self.outputs)
+            .any(|port| port.r#type == ty)
+    }
+}
+
+fn snake_case(name: &str) -> String {
+    let mut s = String::new();
+    let mut prev_lower = false;
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            if c.is_ascii_uppercase() && prev_lower {
+                s.push('_');
+            }
+            prev_lower = c.is_ascii_lowercase() || c.is_ascii_digit();
+            s.push(c.to_ascii_lowercase());
+        } else if !s.is_empty() && !s.ends_with('_') {
+            s.push('_');
+            prev_lower = false;
+        }
+    }
+    let s = s.trim_end_matches('_');
+    match s {
+        "" => "value".to_owned(),
+        "self" | "super" | "crate" => format!("{s}_"),
+        s if s.starts_with(|c: char| c.is_ascii_digit()) => format!("_{s}"),
+        s => s.to_owned(),
+    }
+}
+
+fn field_ident(name: &str) -> Ident {
+    let name = snake_case(name);
+    syn::parse_str::<Ident>(&name).unwrap_or_else(|_| Ident::new_raw(&name, Span::call_site()))
+}
+
+fn rust_field(port: &Port, input: bool) -> TokenStream {
+    let name = &port.name;
+    let ident = field_ident(name);
+    let rename = (ident.to_string().trim_start_matches("r#") != name.as_str())
+        .then(|| quote! { #[serde(rename = #name)] });
+    let optional = input && port.optional;
+    let skip = optional.then(|| quote! { #[serde(skip_serializing_if = "Option::is_none")] });
+    let (ty, decor) = match port.r#type {
+        ValueType::Keypair => (
+            quote! { Keypair },
+            if optional {
+                quote! { #[serde_as(as = "Option<AsKeypair>")] }
+            } else {
+                quote! { #[serde_as(as = "AsKeypair")] }
+            },
+        ),
+        ref ty => (
+            value_type_to_rust_type(ty.clone()),
+            rust_type_serde_decor(&[<&str>::from(ty).to_owned()], optional, &JsonValue::Null),
+        ),
+    };
+    let ty = if optional {
+        quote! { Option<#ty> }
+    } else {
+        ty
+    };
+    quote! {
+        #rename
+        #decor
+        #skip
+        pub #ident: #ty
+    }
+}
+
+fn rust_query(query: &DeploymentQuery) -> TokenStream {
+    match query {
+        DeploymentQuery::Id(id) => {
+            let id = id.to_string();
+            quote! { &[("id", #id)] }
+        }
+        DeploymentQuery::FlowTag(flow, tag) => {
+            let flow = flow.to_string();
+            quote! { &[("flow", #flow), ("tag", #tag)] }
+        }
+    }
+}
+
+/// Rust module with `Input` and `Output` structs, and functions to start the
+/// deployment and follow its runs.
+pub fn rust_client(interface: &FlowInterface, query: &DeploymentQuery) -> String {
+    let doc = format!(
+        " Client for the deployment of {}, generated by `spo generate client`.",
+        interface.title()
+    );
+    let mut imports = Vec::new();
+    let mut with = Vec::new();
+    for (ty, import, with_type) in [
+        (
+            ValueType::Decimal,
+            quote! { flow_value::Decimal },
+            Some(quote! { AsDecimal }),
+        ),
+        (
+            ValueType::Pubkey,
+            quote! { solana_pubkey::Pubkey },
+            Some(quote! { AsPubkey }),
+        ),
+        (
+            ValueType::Signature,
+            quote! { solana_signature::Signature },
+            Some(quote! { AsSignature }),
+        ),
+        (
+            ValueType::Keypair,
+            quote! { solana_keypair::Keypair },
+            Some(quote! { AsKeypair }),
+        ),
+        (ValueType::Bytes, quote! { bytes::Bytes }, None),
+        (ValueType::Map, quote! { flow_value::Map as ValueSet }, None),
+        (
+            ValueType::Json,
+            quote! { serde_json::Value as JsonValue },
+            None,
+        ),
+    ] {
+        if interface.has_type(ty) {
+            imports.push(quote! { use #import; });
+            with.extend(with_type);
+        }
+    }
+    let with = (!with.is_empty()).then(|| quote! { use flow_value::with::{#(#with),*}; });
+    let query = rust_query(query);
+    let inputs = interface.inputs.iter().map(|port| rust_field(port, true));
+    let outputs = interface.outputs.iter().map(|port| rust_field(port, false));
+
+    let code = quote! {
+        #![doc = #doc]
+        //!
+        //! Dependencies: `flow-value`, `futures`, `reqwest` with `json`, `serde`,
+        //! `serde_json`, `serde_with`, `thiserror`, `tokio-tungstenite`, `url`, and
+        //! the Solana crates of the types used by the flow.
+
+        use flow_value::Value;
+        use futures::{SinkExt, Stream, StreamExt};
+        use serde::{Deserialize, Deserializer, Serialize};
+        use serde_json::json;
+        use serde_with::serde_as;
+        use tokio_tungstenite::tungstenite::Message;
+        #(#imports)*
+        #with
+
+        /// Query of `POST /deployment/start` selecting the deployment.
+        pub const DEPLOYMENT: &[(&str, &str)] = #query;
+
+        #[derive(Debug, thiserror::Error)]
+        pub enum Error {
+            #[error(transparent)]
+            Http(#[from] reqwest::Error),
+            #[error(transparent)]
+            WebSocket(#[from] tokio_tungstenite::tungstenite::Error),
+            #[error(transparent)]
+            Json(#[from] serde_json::Error),
+            #[error(transparent)]
+            Value(#[from] flow_value::Error),
+            #[error(transparent)]
+            Url(#[from] url::ParseError),
+            #[error("unsupported URL scheme: {0}")]
+            Scheme(String),
+            #[error("response from server: {0}")]
+            Server(String),
+            #[error("flow failed: {0}")]
+            FlowFailed(String),
+            #[error("connection closed before the flow finished")]
+            Closed,
+        }
+
+        /// Credentials to start the deployment with.
+        #[derive(Debug, Clone)]
+        pub enum Auth {
+            /// API key
+            ApiKey(String),
+            /// JWT, or base58 public key if the deployment allows it
+            Bearer(String),
+        }
+
+        impl Auth {
+            fn apply(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+                match self {
+                    Auth::ApiKey(key) => request.header("x-api-key", key),
+                    Auth::Bearer(token) => request.bearer_auth(token),
+                }
+            }
+        }
+
+        /// Inputs of the flow.
+        #[serde_as]
+        #[derive(Deserialize, Serialize, Debug)]
+        pub struct Input {
+            #(#inputs),*
+        }
+
+        /// Outputs of the flow.
+        #[serde_as]
+        #[derive(Deserialize, Serialize, Debug)]
+        pub struct Output {
+            #(#outputs),*
+        }
+
+        /// A started flow run.
+        #[derive(Deserialize, Debug, Clone)]
+        pub struct FlowRun {
+            pub flow_run_id: String,
+            /// Token to subscribe to events of the run
+            pub token: String,
+        }
+
+        /// Events of a flow run.
+        #[derive(Deserialize, Debug)]
+        #[serde(tag = "event", content = "data")]
+        pub enum Event {
+            FlowStart {},
+            FlowError {
+                error: String,
+            },
+            FlowLog {
+                level: String,
+                content: String,
+            },
+            FlowFinish {
+                not_run: Vec<String>,
+                #[serde(deserialize_with = "deserialize_output")]
+                output: Output,
+            },
+            NodeStart {
+                node_id: String,
+                times: u32,
+            },
+            NodeOutput {
+                node_id: String,
+                times: u32,
+                output: Value,
+            },
+            NodeError {
+                node_id: String,
+                times: u32,
+                error: String,
+            },
+            NodeLog {
+                node_id: String,
+                times: u32,
+                level: String,
+                content: String,
+            },
+            NodeFinish {
+                node_id: String,
+                times: u32,
+            },
+            SignatureRequest {
+                id: i64,
+                pubkey: String,
+                /// Base64-encoded message to sign
+                message: String,
+            },
+            ApiInput {
+                url: String,
+            },
+        }
+
+        fn deserialize_output<'de, D: Deserializer<'de>>(d: D) -> Result<Output, D::Error> {
+            let value = Value::deserialize(d)?;
+            flow_value::from_value(value).map_err(serde::de::Error::custom)
+        }
+
+        /// Start the deployment.
+        pub async fn start(
+            client: &reqwest::Client,
+            base_url: &str,
+            auth: &Auth,
+            input: &Input,
+        ) -> Result<FlowRun, Error> {
+            let inputs = flow_value::to_map(input)?;
+            let url = format!("{}/deployment/start", base_url.trim_end_matches('/'));
+            let resp = auth
+                .apply(client.post(url))
+                .query(DEPLOYMENT)
+                .json(&json!({ "inputs": inputs }))
+                .send()
+                .await?;
+            if !resp.status().is_success() {
+                return Err(Error::Server(resp.text().await?));
+            }
+            Ok(resp.json().await?)
+        }
+
+        /// Subscribe to events of a flow run.
+        pub async fn subscribe(
+            base_url: &str,
+            run: &FlowRun,
+        ) -> Result<impl Stream<Item = Result<Event, Error>>, Error> {
+            let mut url = url::Url::parse(base_url)?;
+            let scheme = match url.scheme() {
+                "http" => "ws",
+                "https" => "wss",
+                scheme => return Err(Error::Scheme(scheme.to_owned())),
+            };
+            url.set_scheme(scheme)
+                .map_err(|()| Error::Scheme(scheme.to_owned()))?;
+            url.set_path(&format!("{}/ws", url.path().trim_end_matches('/')));
+            let (mut ws, _) = tokio_tungstenite::connect_async(url.as_str()).await?;
+            let request = json!({
+                "id": 0,
+                "method": "SubscribeFlowRunEvents",
+                "params": { "flow_run_id": run.flow_run_id, "token": run.token },
+            });
+            ws.send(Message::text(request.to_string())).await?;
+            Ok(ws.filter_map(|msg| async move {
+                let text = match msg {
+                    Ok(Message::Text(text)) => text,
+                    Ok(_) => return None,
+                    Err(error) => return Some(Err(error.into())),
+                };
+                let msg = match serde_json::from_str::<serde_json::Value>(&text) {
+                    Ok(msg) => msg,
+                    Err(error) => return Some(Err(error.into())),
+                };
+                if let Some(error) = msg.get("Err") {
+                    return Some(Err(Error::Server(error.to_string())));
+                }
+                msg.get("event")?;
+                Some(serde_json::from_value(msg).map_err(Error::from))
+            }))
+        }
+
+        /// Wait for a flow run to finish and return its outputs.
+        pub async fn output(base_url: &str, run: &FlowRun) -> Result<Output, Error> {
+            let mut events = std::pin::pin!(subscribe(base_url, run).await?);
+            while let Some(event) = events.next().await {
+                match event? {
+                    Event::FlowError { error } => return Err(Error::FlowFailed(error)),
+                    Event::FlowFinish { output, .. } => return Ok(output),
+                    _ => {}
+                }
+            }
+            Err(Error::Closed)
+        }
+
+        /// Start the deployment and wait for its outputs.
+        pub async fn run(
+            client: &reqwest::Client,
+            base_url: &str,
+            auth: &Auth,
+            input: &Input,
+        ) -> Result<Output, Error> {
+            let run = start(client, base_url, auth, input).await?;
+            output(base_url, &run).await
+        }
+    };
+    fmt_code(code)
+}
+
+/// TypeScript input and output types, and expressions to encode `{}` to and
+/// decode `v` from a `Value`.
+fn ts_type(ty: &ValueType) -> (&'static str, &'static str, &'static str, &'static str) {
+    match ty {
+        ValueType::Bool => ("boolean", "boolean", "Value.Boolean({})", "v.asBool()"),
+        ValueType::U8 | ValueType::U16 | ValueType::U32 => {
+            ("number", "number", "Value.U64({})", "v.asNumber()")
+        }
+        ValueType::I8 | ValueType::I16 | ValueType::I32 => {
+            ("number", "number", "Value.I64({})", "v.asNumber()")
+        }
+        ValueType::U64 => ("bigint | number", "bigint", "Value.U64({})", "v.asBigInt()"),
+        ValueType::I64 => ("bigint | number", "bigint", "Value.I64({})", "v.asBigInt()"),
+        ValueType::U128 => (
+            "bigint | number",
+            "bigint",
+            "Value.U128({})",
+            "v.asBigInt()",
+        ),
+        ValueType::I128 => (
+            "bigint | number",
+            "bigint",
+            "Value.I128({})",
+            "v.asBigInt()",
+        ),
+        ValueType::F32 | ValueType::F64 => ("number", "number", "Value.Float({})", "v.asNumber()"),
+        ValueType::Decimal => (
+            "number | string",
+            "string",
+            "Value.Decimal({})",
+            "v.D ?? v.asNumber()?.toString()",
+        ),
+        ValueType::Pubkey => (
+            "web3.PublicKeyInitData",
+            "web3.PublicKey",
+            "Value.PublicKey({})",
+            "v.asPubkey()",
+        ),
+        ValueType::Keypair => (
+            "web3.Keypair | Uint8Array | string",
+            "web3.Keypair",
+            "Value.Keypair({})",
+            "v.asKeypair()",
+        ),
+        ValueType::Signature => (
+            "Uint8Array | string",
+            "Uint8Array",
+            "Value.Signature({})",
+            "v.asBytes()",
+        ),
+        ValueType::Address | ValueType::String => {
+            ("string", "string", "Value.String({})", "v.asString()")
+        }
+        ValueType::Bytes => ("Uint8Array", "Uint8Array", "Value.Bytes({})", "v.asBytes()"),
+        ValueType::Array => (
+            "unknown[]",
+            "unknown[]",
+            "new Value({})",
+            "v.asArray()?.map((x) => x.toJSObject())",
+        ),
+        ValueType::Map => (
+            "Record<string, unknown>",
+            "Record<string, unknown>",
+            "new Value({})",
+            "v.asMap() && v.toJSObject()",
+        ),
+        ValueType::Json => ("JsonValue", "JsonValue", "new Value({})", "v.toJSObject()"),
+        ValueType::Free => ("FlowValueInput", "Value", "{}", "v"),
+    }
+}
+
+fn is_ts_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+}
+
+fn ts_string(s: &str) -> String {
+    JsonValue::from(s).to_string()
+}
+
+fn ts_property(name: &str) -> String {
+    if is_ts_identifier(name) {
+        name.to_owned()
+    } else {
+        ts_string(name)
+    }
+}
+
+/// TypeScript module using `@space-operator/client-next`, with `Input` and
+/// `Output` types, and functions to start the deployment and follow its runs.
+pub fn ts_client(interface: &FlowInterface, query: &DeploymentQuery) -> String {
+    let mut types = vec!["FlowFinish", "FlowInputs", "FlowRunEvent", "FlowRunHandle"];
+    if interface.has_type(ValueType::Free) {
+        types.push("FlowValueInput");
+    }
+    if interface.has_type(ValueType::Json) {
+        types.push("JsonValue");
+    }
+    types.extend([
+        "PublicKeyProvider",
+        "RequestOptions",
+        "SpaceOperatorClient",
+        "SubscribeFlowRunOptions",
+    ]);
+    let mut imports = types
+        .into_iter()
+        .map(|ty| format!("  type {ty},\n"))
+        .collect::<String>();
+    imports.push_str("  Value,\n");
+    if interface.has_type(ValueType::Pubkey) || interface.has_type(ValueType::Keypair) {
+        imports.push_str("  web3,\n");
+    }
+
+    let deployment = match query {
+        DeploymentQuery::Id(id) => format!("{{ id: {} }}", ts_string(&id.to_string())),
+        DeploymentQuery::FlowTag(flow, tag) => format!(
+            "{{ flow: {}, tag: {} }}",
+            ts_string(&flow.to_string()),
+            ts_string(tag)
+        ),
+    };
+
+    let mut input_fields = String::new();
+    let mut encode = String::new();
+    for port in &interface.inputs {
+        let (ty, _, encoder, _) = ts_type(&port.r#type);
+        let property = ts_property(&port.name);
+        let key = ts_string(&port.name);
+        let optional = if port.optional { "?" } else { "" };
+        input_fields.push_str(&format!("  {property}{optional}: {ty};\n"));
+        let access = if is_ts_identifier(&port.name) {
+            format!("input.{}", port.name)
+        } else {
+            format!("input[{key}]")
+        };
+        let value = encoder.replace("{}", &access);
+        if port.optional {
+            encode.push_str(&format!(
+                "  if ({access} !== undefined) inputs[{key}] = {value};\n"
+            ));
+        } else {
+            encode.push_str(&format!("  inputs[{key}] = {value};\n"));
+        }
+    }
+
+    let mut output_fields = String::new();
+    let mut decode = String::new();
+    for port in &interface.outputs {
+        let (_, ty, _, decoder) = ts_type(&port.r#type);
+        let property = ts_property(&port.name);
+        let key = ts_string(&port.name);
+        output_fields.push_str(&format!("  {property}: {ty};\n"));
+        decode.push_str(&format!(
+            "    {property}: field(output, {key}, (v) => {decoder}),\n"
+        ));
+    }
+
+    format!(
+        r#"// Client for the deployment of {title}, generated by `spo generate client`.
+import {{
+{imports}}} from "@space-operator/client-next";
+
+/** Deployment started by {{@link start}}. */
+export const DEPLOYMENT = {deployment} as const;
+
+/** Inputs of the flow. */
+export interface Input {{
+{input_fields}}}
+
+/** Outputs of the flow. */
+export interface Output {{
+{output_fields}}}
+
+/** Encode inputs as flow values. */
+export function encodeInput(input: Input): FlowInputs {{
+  const inputs: FlowInputs = {{}};
+{encode}  return inputs;
+}}
+
+function field<T>(
+  output: Record<string, Value>,
+  name: string,
+  decode: (v: Value) => T | undefined,
+): T {{
+  const value = output[name];
+  const decoded = value === undefined ? undefined : decode(value);
+  if (decoded === undefined) {{
+    throw new TypeError(`missing or invalid flow output "${{name}}"`);
+  }}
+  return decoded;
+}}
+
+/** Decode outputs of a flow run. */
+export function decodeOutput(value: Value): Output {{
+  const output = value.asMap() ?? {{}};
+  return {{
+{decode}  }};
+}}
+
+/** Start the deployment. */
+export async function start(
+  client: SpaceOperatorClient,
+  input: Input,
+  options: RequestOptions & {{ publicKey?: PublicKeyProvider }} = {{}},
+): Promise<FlowRunHandle> {{
+  return await client.deployments.start(
+    DEPLOYMENT,
+    {{ inputs: encodeInput(input) }},
+    options,
+  );
+}}
+
+/** Events of a flow run, with decoded outputs in `FlowFinish`. */
+export type Event =
+  | Exclude<FlowRunEvent, {{ event: "FlowFinish" }}>
+  | {{
+    stream_id: number;
+    event: "FlowFinish";
+    data: Omit<FlowFinish, "output"> & {{ output: Output }};
+  }};
+
+/** Subscribe to events of a flow run. */
+export async function* events(
+  run: FlowRunHandle,
+  options: SubscribeFlowRunOptions = {{}},
+): AsyncGenerator<Event> {{
+  const subscription = await run.events(options);
+  try {{
+    for await (const event of subscription) {{
+      if (event.event === "FlowFinish") {{
+        const output = decodeOutput(event.data.output);
+        yield {{ ...event, data: {{ ...event.data, output }} }};
+      }} else {{
+        yield event;
+      }}
+    }}
+  }} finally {{
+    await subscription.close();
+  }}
+}}
+
+/** Wait for a flow run to finish and return its outputs. */
+export async function output(
+  run: FlowRunHandle,
+  options: SubscribeFlowRunOptions = {{}},
+): Promise<Output> {{
+  const finish = await run.waitForFinish(options);
+  return decodeOutput(finish.output);
+}}
+"#,
+        title = interface.title(),
+    )
+}
+
+pub async fn generate_client(
+    source: Source,
+    tag: &str,
+    lang: Language,
+    output: Option<&Path>,
+) -> Result<(), Report<Error>> {
+    let (flow, query) = match source {
+        Source::Deployment(id) => {
+            let flow = load_client().await?.deployment_flow(id).await?;
+            (flow, DeploymentQuery::Id(id))
+        }
+        Source::Flow(id) => {
+            let flow = load_client().await?.export_flow(id).await?;
+            (flow, DeploymentQuery::FlowTag(id, tag.to_owned()))
+        }
+        Source::File(path) => {
+            let text = read_file(&path).await?;
+            let flow = serde_json::from_str::<JsonValue>(&text).change_context(Error::Json)?;
+            let id = FlowInterface::from_flow(&flow)
+                .id
+                .ok_or(Error::InvalidValue)
+                .attach_printable("exported flow has no UUID `id`")?;
+            (flow, DeploymentQuery::FlowTag(id, tag.to_owned()))
+        }
+    };
+    let interface = FlowInterface::from_flow(&flow);
+    if interface.inputs.is_empty() && interface.outputs.is_empty() {
+        eprintln!("flow has no flow_input or flow_output nodes");
+    }
+    let code = match lang {
+        Language::Rust => rust_client(&interface, &query),
+        Language::Typescript => ts_client(&interface, &query),
+    };
+    let path = output.unwrap_or(Path::new("-"));
+    if write_file(path, code).await? {
+        eprintln!("wrote client to {}", path.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flow() -> JsonValue {
+        json!({
+            "flow": {
+                "id": "8e4c9b5e-37f4-4f5a-9a43-7d8c2d1f0b11",
+                "name": "Transfer",
+                "nodes": [
+                    {
+                        "id": "6f7c5a0e-0b1d-4e3a-8f0e-3a1f9c2b4d01",
+                        "data": {
+                            "node_id": "@spo/flow_input",
+                            "ports": { "outputs": [{ "id": "00000000-0000-0000-0000-000000000001", "name": "amount", "type": "free" }] },
+                            "config": { "label": { "S": "amount" }, "type": { "S": "u64" }, "value": { "N": 0 } }
+                        }
+                    },
+                    {
+                        "id": "6f7c5a0e-0b1d-4e3a-8f0e-3a1f9c2b4d02",
+                        "data": {
+                            "node_id": "flow_input",
+                            "outputs": [{ "id": "00000000-0000-0000-0000-000000000002", "name": "", "type": "pubkey" }],
+                            "config": { "label": "Fee Payer", "value": { "B3": "11111111111111111111111111111111" } }
+                        }
+                    },
+                    {
+                        "id": "6f7c5a0e-0b1d-4e3a-8f0e-3a1f9c2b4d03",
+                        "data": {
+                            "node_id": "@spo/transfer_sol",
+                            "ports": { "outputs": [{ "id": "00000000-0000-0000-0000-000000000003", "name": "signature", "type": "signature" }] }
+                        }
+                    },
+                    {
+                        "id": "6f7c5a0e-0b1d-4e3a-8f0e-3a1f9c2b4d04",
+                        "data": {
+                            "node_id": "@spo/flow_output",
+                            "ports": { "inputs": [{ "id": "00000000-0000-0000-0000-000000000004", "name": "signature", "type_bounds": ["free"] }] },
+                            "config": { "label": { "S": "signature" } }
+                        }
+                    }
+                ],
+                "edges": [
+                    {
+                        "source": "6f7c5a0e-0b1d-4e3a-8f0e-3a1f9c2b4d03",
+                        "sourceHandle": "00000000-0000-0000-0000-000000000003",
+                        "target": "6f7c5a0e-0b1d-4e3a-8f0e-3a1f9c2b4d04",
+                        "targetHandle": "00000000-0000-0000-0000-000000000004"
+                    }
+                ]
+            }
+        })
+    }
+
+    fn query() -> DeploymentQuery {
+        DeploymentQuery::FlowTag(
+            "8e4c9b5e-37f4-4f5a-9a43-7d8c2d1f0b11".parse().unwrap(),
+            "latest".to_owned(),
+        )
+    }
+
+    #[test]
+    fn test_from_flow() {
+        let interface = FlowInterface::from_flow(&flow());
+        assert_eq!(interface.name.as_deref(), Some("Transfer"));
+        assert_eq!(
+            interface.inputs,
+            [
+                Port {
+                    name: "amount".to_owned(),
+                    r#type: ValueType::U64,
+                    optional: false,
+                },
+                Port {
+                    name: "Fee Payer".to_owned(),
+                    r#type: ValueType::Pubkey,
+                    optional: true,
+                },
+            ]
+        );
+        assert_eq!(
+            interface.outputs,
+            [Port {
+                name: "signature".to_owned(),
+                r#type: ValueType::Signature,
+                optional: false,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_rust_client() {
+        let code = rust_client(&FlowInterface::from_flow(&flow()), &query());
+        let file = syn::parse_file(&code).unwrap();
+        assert!(!file.items.is_empty());
+        assert!(code.contains("pub amount: u64"));
+        assert!(code.contains("#[serde(rename = \"Fee Payer\")]"));
+        assert!(code.contains("pub fee_payer: Option<Pubkey>"));
+        assert!(code.contains("#[serde_as(as = \"AsSignature\")]"));
+        assert!(code.contains("url.set_scheme(scheme)"));
+    }
+
+    /// Type-check a generated TypeScript client against the local
+    /// `@space-operator/client-next`, skipped when Deno is not installed.
+    fn deno_check(code: &str) {
+        if std::process::Command::new("deno")
+            .arg("--version")
+            .output()
+            .is_err()
+        {
+            eprintln!("deno not found, skipping type check");
+            return;
+        }
+        let package = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../../@space-operator/client-next")
+            .canonicalize()
+            .unwrap();
+        let file = package.join(format!("tests/.generated_client_{}.ts", std::process::id()));
+        let code = code.replace("\"@space-operator/client-next\"", "\"../src/mod.ts\"");
+        std::fs::write(&file, code).unwrap();
+        let output = std::process::Command::new("deno")
+            .arg("check")
+            .arg(&file)
+            .current_dir(&package)
+            .output();
+        std::fs::remove_file(&file).unwrap();
+        let output = output.unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    #[test]
+    fn test_ts_client() {
+        let code = ts_client(&FlowInterface::from_flow(&flow()), &query());
+        assert!(code.contains("  amount: bigint | number;\n"));
+        assert!(code.contains("  \"Fee Payer\"?: web3.PublicKeyInitData;\n"));
+        assert!(code.contains("  inputs[\"amount\"] = Value.U64(input.amount);\n"));
+        assert!(code.contains(
+            "  if (input[\"Fee Payer\"] !== undefined) inputs[\"Fee Payer\"] = Value.PublicKey(input[\"Fee Payer\"]);\n"
+        ));
+        assert!(
+            code.contains("    signature: field(output, \"signature\", (v) => v.asBytes()),\n")
+        );
+        deno_check(&code);
+    }
+
+    #[test]
+    fn test_field_ident() {
+        assert_eq!(field_ident("Fee Payer").to_string(), "fee_payer");
+        assert_eq!(field_ident("inputAmount").to_string(), "input_amount");
+        assert_eq!(field_ident("type").to_string(), "r#type");
+        assert_eq!(field_ident("1st").to_string(), "_1st");
+    }
+}
@@ -24,9 +24,11 @@ use serde_json::json;
 use std::{
     borrow::{Borrow, Cow},
     cmp::Ordering,
+    ffi::OsString,
     fmt::Display,
     io::{BufReader, Stdin, Write},
     path::{Path, PathBuf},
+    process::ExitStatus,
     sync::LazyLock,
 };
 use strum::IntoEnumIterator;
@@ -38,6 +40,8 @@ use xshell::{Shell, cmd};
 
 static CLIENT: LazyLock<reqwest::Client> = LazyLock::new(Default::default);
 
+pub mod bindings;
+pub mod remote;
 pub mod schema;
 
 pub mod claim_token {
@@ -187,6 +191,18 @@ enum Commands {
         #[command(subcommand)]
         command: GenerateCommands,
     },
+    /// Manage and run flows
+    #[command(visible_alias = "f")]
+    Flow {
+        #[command(subcommand)]
+        command: FlowCommands,
+    },
+    /// Manage and start deployments
+    #[command(visible_alias = "d")]
+    Deployment {
+        #[command(subcommand)]
+        command: DeploymentCommands,
+    },
     /// Run various binaries
     Run {
         /// Specify binary to run
@@ -203,6 +219,151 @@ enum Binaries {
     DenoCmdsServer,
 }
 
+#[derive(Subcommand, Debug)]
+enum FlowCommands {
+    /// Run an exported flow with the commands of this repository, without
+    /// flow-server. Exits with 1 if a node failed.
+    #[command(visible_alias = "r")]
+    Run {
+        /// Path to exported flow JSON
+        path: PathBuf,
+        /// Flow input, value is JSON or a string
+        #[arg(long = "input", short, value_name = "NAME=VALUE")]
+        inputs: Vec<String>,
+        /// JSON file with flow inputs, `--input` takes precedence
+        #[arg(long, value_name = "PATH")]
+        inputs_file: Option<PathBuf>,
+        /// Keypair file to sign with, can be repeated
+        #[arg(long = "keypair", short, value_name = "PATH")]
+        keypairs: Vec<PathBuf>,
+        /// Flow called by interflow nodes, can be repeated
+        #[arg(long = "with-flow", value_name = "PATH")]
+        flows: Vec<PathBuf>,
+        /// Write outputs to a file instead of stdout
+        #[arg(long, short, value_name = "PATH")]
+        output: Option<PathBuf>,
+        /// Print events as JSON lines
+        #[arg(long)]
+        json_events: bool,
+        /// Stop the flow after this many seconds
+        #[arg(long, value_name = "SECS")]
+        timeout: Option<u64>,
+        /// Use `--release` build
+        #[arg(long)]
+        release: bool,
+    },
+    /// Export a flow to JSON
+    #[command(visible_alias = "e")]
+    Export {
+        /// Flow to export
+        flow_id: Uuid,
+        /// Write to a file instead of stdout
+        #[arg(long, short, value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+    /// Import exported flow JSON as a new flow
+    #[command(visible_alias = "i")]
+    Import {
+        /// Path to exported flow JSON, or `-` for stdin
+        path: PathBuf,
+        /// Replace an existing flow instead
+        #[arg(long, value_name = "FLOW_ID")]
+        into: Option<Uuid>,
+    },
+    /// Start a flow on flow-server and print the run ID
+    Start {
+        /// Flow to start
+        flow_id: Uuid,
+        #[command(flatten)]
+        inputs: remote::InputArgs,
+        #[command(flatten)]
+        follow: remote::FollowArgs,
+    },
+    /// Deploy a flow and print the deployment ID
+    Deploy {
+        /// Flow to deploy
+        flow_id: Uuid,
+        /// Also point this tag to the deployment
+        #[arg(long)]
+        tag: Option<String>,
+    },
+    /// Print events of a flow run until it finishes, then print its outputs.
+    /// Exits with 1 if a node failed.
+    #[command(visible_alias = "t")]
+    Tail {
+        /// Flow run to follow
+        run_id: Uuid,
+        /// Run token returned when starting a deployment
+        #[arg(long)]
+        token: Option<String>,
+        /// Flow of the run, to print node names
+        #[arg(long, value_name = "FLOW_ID")]
+        flow: Option<Uuid>,
+        /// Keypair file to answer signature requests with, can be repeated
+        #[arg(long = "keypair", short, value_name = "PATH")]
+        keypairs: Vec<PathBuf>,
+    },
+    /// Stop a flow run
+    Stop {
+        /// Flow run to stop
+        run_id: Uuid,
+        /// Reason sent to the flow
+        #[arg(long)]
+        reason: Option<String>,
+    },
+    /// Wait for the next signature request of a flow run and sign it
+    Sign {
+        /// Flow run waiting for a signature
+        run_id: Uuid,
+        /// Keypair file to sign with, can be repeated
+        #[arg(long = "keypair", short, value_name = "PATH", required = true)]
+        keypairs: Vec<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum DeploymentCommands {
+    /// Start a deployment and print the run ID
+    #[command(visible_alias = "s")]
+    Start {
+        /// Deployment to start
+        #[arg(long, required_unless_present = "flow", conflicts_with_all = ["flow", "tag"])]
+        id: Option<Uuid>,
+        /// Start the deployment of this flow with `--tag`
+        #[arg(long, value_name = "FLOW_ID")]
+        flow: Option<Uuid>,
+        /// Deployment tag to start with `--flow`
+        #[arg(long, default_value = "latest", requires = "flow")]
+        tag: String,
+        #[command(flatten)]
+        inputs: remote::InputArgs,
+        #[command(flatten)]
+        follow: remote::FollowArgs,
+    },
+    /// List deployment tags of a flow
+    Tags {
+        /// Flow of the deployments
+        flow_id: Uuid,
+    },
+    /// Point a tag of the deployment's flow to the deployment
+    Tag {
+        /// Deployment to tag
+        deployment_id: Uuid,
+        /// Tag name
+        tag: String,
+        /// Description of the tag
+        #[arg(long)]
+        description: Option<String>,
+    },
+    /// Delete a deployment tag
+    Untag {
+        /// Flow of the deployment
+        flow_id: Uuid,
+        /// Tag name
+        tag: String,
+    },
+}
+
 #[derive(Subcommand, Debug)]
 enum GenerateCommands {
     /// Generate input struct
@@ -217,6 +378,30 @@ enum GenerateCommands {
         /// Path to node definition file
         path: PathBuf,
     },
+    /// Generate typed client code to start a deployment, from the
+    /// `flow_input` and `flow_output` nodes of its flow
+    #[command(visible_alias = "c")]
+    Client {
+        /// Deployment to start
+        #[arg(long, required_unless_present_any = ["flow", "path"], conflicts_with_all = ["flow", "path", "tag"])]
+        id: Option<Uuid>,
+        /// Start the deployment of this flow with `--tag`
+        #[arg(long, value_name = "FLOW_ID", conflicts_with = "path")]
+        flow: Option<Uuid>,
+        /// Read the flow from exported flow JSON instead, and start its
+        /// deployment with `--tag`
+        #[arg(long)]
+        path: Option<PathBuf>,
+        /// Deployment tag to start with `--flow` or `--path`
+        #[arg(long, default_value = "latest")]
+        tag: String,
+        /// Language of the generated code
+        #[arg(long, short, value_enum)]
+        lang: bindings::Language,
+        /// Write to a file instead of stdout
+        #[arg(long, short, value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
 }
 
 #[derive(Subcommand, Debug)]
@@ -243,6 +428,32 @@ enum NodeCommands {
         #[arg(long)]
         no_confirm: bool,
     },
+    /// Run test fixtures (`*.test.jsonc`) of node definitions with the commands
+    /// of this repository. Exits with 1 if a case failed.
+    #[command(visible_alias = "t")]
+    Test {
+        /// Directory, node definition or fixture file
+        #[arg(default_value = ".")]
+        paths: Vec<PathBuf>,
+        /// Use `--release` build
+        #[arg(long)]
+        release: bool,
+    },
+    /// Check node definitions against the V2 schema and the inputs of their
+    /// native commands. Exits with 1 if there are errors.
+    #[command(visible_alias = "l")]
+    Lint {
+        /// Directory or node definition file
+        #[arg(default_value = ".")]
+        paths: Vec<PathBuf>,
+        /// Migrate legacy definitions to V2. Migrated files are rewritten as
+        /// plain JSON, without their comments
+        #[arg(long)]
+        fix: bool,
+        /// Use `--release` build
+        #[arg(long)]
+        release: bool,
+    },
 }
 
 #[derive(Serialize, Deserialize)]
@@ -321,6 +532,10 @@ pub enum Error {
     NotLib(String),
     #[error("invalid value")]
     InvalidValue,
+    #[error("invalid keypair file {}", .0.display())]
+    Keypair(PathBuf),
+    #[error("WebSocket error")]
+    WebSocket,
 }
 
 #[derive(Deserialize, ThisError, Debug)]
@@ -1678,6 +1893,46 @@ async fn run_cmds_server(release: bool, bin: &str) -> Result<(), Report<Error>>
     Ok(())
 }
 
+/// Build and run a binary of `flow-runner` package, exiting with its exit code
+/// if it fails.
+async fn run_flow_runner_bin(
+    bin: &'static str,
+    release: bool,
+    args: Vec<OsString>,
+) -> Result<(), Report<Error>> {
+    let meta =
+        cargo_metadata().attach_printable("make sure you are inside flow-backend repository")?;
+    find_binary_by_name(&meta, bin)
+        .attach_printable("make sure you are inside flow-backend repository")?;
+
+    let handler = spawn_blocking(move || -> Result<ExitStatus, Report<Error>> {
+        let sh = Shell::new().change_context(Error::Shell)?;
+        let build_dir = release.then_some("release/").unwrap_or("debug/");
+        let release = release.then_some("--release");
+        cmd!(sh, "cargo build --bin {bin} {release...}")
+            .run()
+            .change_context(Error::Subprocess)?;
+        let binary = meta.target_directory.join(build_dir).join(bin);
+        std::process::Command::new(binary)
+            .args(args)
+            .status()
+            .change_context(Error::Subprocess)
+    });
+
+    // flow-runner stops the flow on Ctrl-C, wait for it to write outputs
+    let result = match future::select(std::pin::pin!(ctrl_c()), handler).await {
+        future::Either::Left((_, handler)) => handler.await,
+        future::Either::Right((result, _)) => result,
+    };
+
+    let status = result.change_context(Error::Thread)??;
+    if !status.success() {
+        std::process::exit(status.code().unwrap_or(1));
+    }
+
+    Ok(())
+}
+
 async fn start_flow_server(
     config: &Option<PathBuf>,
     mut docker: bool,
@@ -1898,10 +2153,127 @@ async fn run() -> Result<(), Report<Error>> {
                 check_latest_version().await?;
                 upload_node(path, *dry_run, *no_confirm).await?;
             }
+            NodeCommands::Test { paths, release } => {
+                let args = paths.iter().map(OsString::from).collect();
+                run_flow_runner_bin("node-test", *release, args).await?;
+            }
+            NodeCommands::Lint {
+                paths,
+                fix,
+                release,
+            } => {
+                let mut args: Vec<OsString> = paths.iter().map(OsString::from).collect();
+                if *fix {
+                    args.push("--fix".into());
+                }
+                run_flow_runner_bin("node-lint", *release, args).await?;
+            }
         },
         Some(Commands::Generate { command }) => match command {
             GenerateCommands::Input { path } => generate_input_struct(path).await?,
             GenerateCommands::Output { path } => generate_output_struct(path).await?,
+            GenerateCommands::Client {
+                id,
+                flow,
+                path,
+                tag,
+                lang,
+                output,
+            } => {
+                let source = match (id, flow, path) {
+                    (Some(id), _, _) => bindings::Source::Deployment(*id),
+                    (None, Some(flow), _) => bindings::Source::Flow(*flow),
+                    (None, None, Some(path)) => bindings::Source::File(path.clone()),
+                    (None, None, None) => return Err(Error::InvalidValue.into()),
+                };
+                bindings::generate_client(source, tag, *lang, output.as_deref()).await?
+            }
+        },
+        Some(Commands::Flow { command }) => match command {
+            FlowCommands::Run {
+                path,
+                inputs,
+                inputs_file,
+                keypairs,
+                flows,
+                output,
+                json_events,
+                timeout,
+                release,
+            } => {
+                let mut args = vec![OsString::from(path)];
+                for input in inputs {
+                    args.extend(["--input".into(), input.into()]);
+                }
+                if let Some(path) = inputs_file {
+                    args.extend(["--inputs-file".into(), path.into()]);
+                }
+                for path in keypairs {
+                    args.extend(["--keypair".into(), path.into()]);
+                }
+                for path in flows {
+                    args.extend(["--with-flow".into(), path.into()]);
+                }
+                if let Some(path) = output {
+                    args.extend(["--output".into(), path.into()]);
+                }
+                if *json_events {
+                    args.push("--json-events".into());
+                }
+                if let Some(timeout) = timeout {
+                    args.extend(["--timeout".into(), timeout.to_string().into()]);
+                }
+                run_flow_runner_bin("flow-runner", *release, args).await?;
+            }
+            FlowCommands::Export { flow_id, output } => {
+                remote::export_flow(*flow_id, output.as_deref()).await?
+            }
+            FlowCommands::Import { path, into } => remote::import_flow(path, *into).await?,
+            FlowCommands::Start {
+                flow_id,
+                inputs,
+                follow,
+            } => remote::start_flow(*flow_id, inputs, follow).await?,
+            FlowCommands::Deploy { flow_id, tag } => {
+                remote::deploy_flow(*flow_id, tag.as_deref()).await?
+            }
+            FlowCommands::Tail {
+                run_id,
+                token,
+                flow,
+                keypairs,
+            } => remote::tail_flow_run(*run_id, token.clone(), *flow, keypairs).await?,
+            FlowCommands::Stop { run_id, reason } => {
+                remote::stop_flow_run(*run_id, reason.clone()).await?
+            }
+            FlowCommands::Sign { run_id, keypairs } => {
+                remote::sign_flow_run(*run_id, keypairs).await?
+            }
+        },
+        Some(Commands::Deployment { command }) => match command {
+            DeploymentCommands::Start {
+                id,
+                flow,
+                tag,
+                inputs,
+                follow,
+            } => {
+                let query = match (id, flow) {
+                    (Some(id), _) => remote::DeploymentQuery::Id(*id),
+                    (None, Some(flow)) => remote::DeploymentQuery::FlowTag(*flow, tag.clone()),
+                    (None, None) => return Err(Error::InvalidValue.into()),
+                };
+                remote::start_deployment(query, inputs, follow).await?
+            }
+            DeploymentCommands::Tags { flow_id } => remote::list_deployment_tags(*flow_id).await?,
+            DeploymentCommands::Tag {
+                deployment_id,
+                tag,
+                description,
+            } => remote::tag_deployment(*deployment_id, tag, description.as_deref()).await?,
+            DeploymentCommands::Untag { flow_id, tag } => {
+                remote::untag_deployment(*flow_id, tag).await?
+            }
         },
         Some(Commands::Run { bin, release }) => match bin {
             Binaries::AllCmdsServer => run_cmds_server(*release, "all-cmds-server").await?,
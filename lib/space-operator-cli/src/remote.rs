@@ -0,0 +1,871 @@
+//! Manage flows, deployments and flow runs on flow-server.
+
+use crate::{
+    ApiClient, CLIENT, Error, FlowServerErrorBody, PostgrestErrorBody, read_file,
+    read_json_response, write_file,
+};
+use console::{StyledObject, style};
+use ed25519_dalek::{Signer, SigningKey};
+use error_stack::{Report, ResultExt};
+use futures::{SinkExt, StreamExt};
+use reqwest::{Method, RequestBuilder, header::AUTHORIZATION};
+use serde::Deserialize;
+use serde_json::{Map, Value as JsonValue, json};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+use tokio_tungstenite::tungstenite::Message;
+use uuid::Uuid;
+
+pub type FlowId = Uuid;
+pub type FlowRunId = Uuid;
+pub type DeploymentId = Uuid;
+
+/// Columns of `flows_v2` included in an exported flow.
+const EXPORT_COLUMNS: &str = "id:uuid,user_id,name,description,nodes,edges,viewport,environment,sol_network:current_network,instructions_bundling";
+
+/// Convert JSON to a tagged flow value, with the same rules as flow-runner.
+/// Values that are already tagged are kept as is.
+pub fn to_flow_value(json: JsonValue) -> JsonValue {
+    serde_json::to_value(flow_value::Value::from_tagged_or_json(json))
+        .expect("flow values are valid JSON")
+}
+
+/// Convert a tagged flow value to plain JSON, for printing. Untagged JSON is
+/// read with the same rules as [`to_flow_value`].
+pub fn from_flow_value(json: &JsonValue) -> JsonValue {
+    plain_json(flow_value::Value::from_tagged_or_json(json.clone()))
+}
+
+/// Like the `From` conversion of `flow-value`, but public keys and
+/// signatures are printed in base58 instead of as byte arrays.
+fn plain_json(value: flow_value::Value) -> JsonValue {
+    use flow_value::Value;
+    match value {
+        Value::B32(bytes) => bs58::encode(bytes).into_string().into(),
+        Value::B64(bytes) => bs58::encode(bytes).into_string().into(),
+        Value::Array(array) => array.into_iter().map(plain_json).collect(),
+        Value::Map(map) => map
+            .into_iter()
+            .map(|(k, v)| (k, plain_json(v)))
+            .collect::<Map<_, _>>()
+            .into(),
+        value => value.into(),
+    }
+}
+
+/// Parse a `NAME=VALUE` flow input. The value is JSON, or a string if it is
+/// not valid JSON.
+pub fn parse_input(s: &str) -> Result<(String, JsonValue), Report<Error>> {
+    let (name, value) = s
+        .split_once('=')
+        .filter(|(name, _)| !name.is_empty())
+        .ok_or(Error::InvalidValue)
+        .attach_printable_lazy(|| format!("expected NAME=VALUE, got {s:?}"))?;
+    let value = serde_json::from_str::<JsonValue>(value)
+        .unwrap_or_else(|_| JsonValue::String(value.to_owned()));
+    Ok((name.to_owned(), to_flow_value(value)))
+}
+
+/// Read flow inputs from `--inputs-file` and `--input` flags.
+pub async fn read_inputs(
+    inputs: &[String],
+    inputs_file: Option<&Path>,
+) -> Result<Map<String, JsonValue>, Report<Error>> {
+    let mut result = Map::new();
+    if let Some(path) = inputs_file {
+        let text = read_file(path).await?;
+        let map = serde_json::from_str::<Map<String, JsonValue>>(&text)
+            .change_context(Error::Json)
+            .attach_printable("inputs file must be a JSON object")?;
+        result.extend(map.into_iter().map(|(k, v)| (k, to_flow_value(v))));
+    }
+    for input in inputs {
+        let (name, value) = parse_input(input)?;
+        result.insert(name, value);
+    }
+    Ok(result)
+}
+
+/// A keypair file, in the JSON format of `solana-keygen` or as a base58 string.
+pub struct Keypair(SigningKey);
+
+impl Keypair {
+    pub async fn read(path: &Path) -> Result<Self, Report<Error>> {
+        let text = read_file(path).await?;
+        let text = text.trim();
+        let bytes = if text.starts_with('[') {
+            serde_json::from_str::<Vec<u8>>(text).ok()
+        } else {
+            bs58::decode(text).into_vec().ok()
+        };
+        let key = bytes
+            .and_then(|bytes| <[u8; 64]>::try_from(bytes).ok())
+            .and_then(|bytes| SigningKey::from_keypair_bytes(&bytes).ok())
+            .ok_or_else(|| Error::Keypair(path.to_owned()))?;
+        Ok(Self(key))
+    }
+
+    pub async fn read_all(paths: &[PathBuf]) -> Result<Vec<Self>, Report<Error>> {
+        let mut keypairs = Vec::with_capacity(paths.len());
+        for path in paths {
+            keypairs.push(Self::read(path).await?);
+        }
+        Ok(keypairs)
+    }
+
+    pub fn pubkey(&self) -> String {
+        bs58::encode(self.0.verifying_key().as_bytes()).into_string()
+    }
+
+    pub fn sign(&self, message: &[u8]) -> String {
+        bs58::encode(self.0.sign(message).to_bytes()).into_string()
+    }
+}
+
+#[derive(Debug)]
+pub enum DeploymentQuery {
+    Id(DeploymentId),
+    FlowTag(FlowId, String),
+}
+
+#[derive(Deserialize, Debug)]
+pub struct DeploymentTag {
+    pub tag: String,
+    pub deployment_id: DeploymentId,
+    pub description: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SignatureRequest {
+    pub id: Option<i64>,
+    pub pubkey: String,
+    /// Base64-encoded message
+    pub message: String,
+}
+
+impl ApiClient {
+    async fn flow_server_request(
+        &mut self,
+        method: Method,
+        path: &str,
+    ) -> Result<RequestBuilder, Report<Error>> {
+        let url = self
+            .config
+            .flow_server
+            .join(path)
+            .change_context(Error::Url)?;
+        let token = self.get_access_token().await?;
+        Ok(CLIENT
+            .request(method, url)
+            .header(AUTHORIZATION, format!("Bearer {token}")))
+    }
+
+    async fn send_flow_server<T: serde::de::DeserializeOwned>(
+        request: RequestBuilder,
+    ) -> Result<T, Report<Error>> {
+        let resp = request.send().await.change_context(Error::Http)?;
+        read_json_response::<_, FlowServerErrorBody>(resp).await
+    }
+
+    pub fn user_id(&self) -> Uuid {
+        self.config.jwt.user_id
+    }
+
+    /// Export a flow as `{"flow": {...}}`, the format read by `spo flow import`
+    /// and `spo flow run`.
+    pub async fn export_flow(&mut self, id: FlowId) -> Result<JsonValue, Report<Error>> {
+        let resp = self
+            .pg
+            .from("flows_v2")
+            .auth(self.get_access_token().await?)
+            .eq("uuid", id.to_string())
+            .select(EXPORT_COLUMNS)
+            .single()
+            .execute()
+            .await
+            .change_context(Error::Postgrest)?;
+        let flow = read_json_response::<JsonValue, PostgrestErrorBody>(resp).await?;
+        Ok(json!({ "flow": flow }))
+    }
+
+    /// Import an exported flow as a new flow, or into an existing flow.
+    pub async fn import_flow(
+        &mut self,
+        export: JsonValue,
+        into: Option<FlowId>,
+    ) -> Result<FlowId, Report<Error>> {
+        let mut flow = match export {
+            JsonValue::Object(mut map) if map.contains_key("flow") => map.remove("flow").unwrap(),
+            json => json,
+        };
+        let flow = flow
+            .as_object_mut()
+            .ok_or(Error::Json)
+            .attach_printable("flow must be a JSON object")?;
+        let mut row = Map::new();
+        for (key, column) in [
+            ("name", "name"),
+            ("description", "description"),
+            ("nodes", "nodes"),
+            ("edges", "edges"),
+            ("viewport", "viewport"),
+            ("environment", "environment"),
+            ("sol_network", "current_network"),
+            ("instructions_bundling", "instructions_bundling"),
+        ] {
+            if let Some(value) = flow.remove(key).filter(|value| !value.is_null()) {
+                row.insert(column.to_owned(), value);
+            }
+        }
+        let token = self.get_access_token().await?;
+        let query = self.pg.from("flows_v2").auth(token);
+        let query = match into {
+            Some(id) => query
+                .eq("uuid", id.to_string())
+                .update(JsonValue::Object(row).to_string()),
+            None => {
+                row.insert("user_id".to_owned(), self.user_id().to_string().into());
+                query.insert(JsonValue::Object(row).to_string())
+            }
+        };
+        let resp = query
+            .select("uuid")
+            .single()
+            .execute()
+            .await
+            .change_context(Error::Postgrest)?;
+
+        #[derive(Deserialize)]
+        struct Resp {
+            uuid: FlowId,
+        }
+
+        Ok(read_json_response::<Resp, PostgrestErrorBody>(resp)
+            .await?
+            .uuid)
+    }
+
+    pub async fn start_flow(
+        &mut self,
+        id: FlowId,
+        inputs: Map<String, JsonValue>,
+    ) -> Result<FlowRunId, Report<Error>> {
+        #[derive(Deserialize)]
+        struct Output {
+            flow_run_id: FlowRunId,
+        }
+
+        let request = self
+            .flow_server_request(Method::POST, &format!("/flow/start/{id}"))
+            .await?
+            .json(&json!({ "inputs": inputs }));
+        Ok(Self::send_flow_server::<Output>(request).await?.flow_run_id)
+    }
+
+    pub async fn stop_flow(
+        &mut self,
+        run_id: FlowRunId,
+        reason: Option<String>,
+    ) -> Result<(), Report<Error>> {
+        let request = self
+            .flow_server_request(Method::POST, &format!("/flow/stop/{run_id}"))
+            .await?
+            .json(&json!({ "reason": reason }));
+        Self::send_flow_server::<JsonValue>(request).await?;
+        Ok(())
+    }
+
+    pub async fn deploy_flow(&mut self, id: FlowId) -> Result<DeploymentId, Report<Error>> {
+        #[derive(Deserialize)]
+        struct Output {
+            deployment_id: DeploymentId,
+        }
+
+        let request = self
+            .flow_server_request(Method::POST, &format!("/flow/deploy/{id}"))
+            .await?;
+        Ok(Self::send_flow_server::<Output>(request)
+            .await?
+            .deployment_id)
+    }
+
+    /// Start a deployment, returning the run ID and a token to subscribe to
+    /// the run.
+    pub async fn start_deployment(
+        &mut self,
+        query: &DeploymentQuery,
+        inputs: Map<String, JsonValue>,
+    ) -> Result<(FlowRunId, String), Report<Error>> {
+        #[derive(Deserialize)]
+        struct Output {
+            flow_run_id: FlowRunId,
+            token: String,
+        }
+
+        let query = match query {
+            DeploymentQuery::Id(id) => vec![("id", id.to_string())],
+            DeploymentQuery::FlowTag(flow, tag) => {
+                vec![("flow", flow.to_string()), ("tag", tag.clone())]
+            }
+        };
+        let request = self
+            .flow_server_request(Method::POST, "/deployment/start")
+            .await?
+            .query(&query)
+            .json(&json!({ "inputs": inputs }));
+        let output = Self::send_flow_server::<Output>(request).await?;
+        Ok((output.flow_run_id, output.token))
+    }
+
+    pub async fn list_deployment_tags(
+        &mut self,
+        flow: FlowId,
+    ) -> Result<Vec<DeploymentTag>, Report<Error>> {
+        let resp = self
+            .pg
+            .from("flow_deployments_tags")
+            .auth(self.get_access_token().await?)
+            .eq("entrypoint", flow.to_string())
+            .select("tag,deployment_id,description")
+            .order("tag")
+            .execute()
+            .await
+            .change_context(Error::Postgrest)?;
+        read_json_response::<_, PostgrestErrorBody>(resp).await
+    }
+
+    /// Flow started by a deployment.
+    pub async fn deployment_entrypoint(
+        &mut self,
+        id: DeploymentId,
+    ) -> Result<FlowId, Report<Error>> {
+        #[derive(Deserialize)]
+        struct Deployment {
+            entrypoint: FlowId,
+        }
+
+        let resp = self
+            .pg
+            .from("flow_deployments")
+            .auth(self.get_access_token().await?)
+            .eq("id", id.to_string())
+            .select("entrypoint")
+            .single()
+            .execute()
+            .await
+            .change_context(Error::Postgrest)?;
+        Ok(read_json_response::<Deployment, PostgrestErrorBody>(resp)
+            .await?
+            .entrypoint)
+    }
+
+    /// Entrypoint flow of a deployment, as it was when deployed.
+    pub async fn deployment_flow(&mut self, id: DeploymentId) -> Result<JsonValue, Report<Error>> {
+        #[derive(Deserialize)]
+        struct Row {
+            data: JsonValue,
+        }
+
+        let flow = self.deployment_entrypoint(id).await?;
+        let resp = self
+            .pg
+            .from("flow_deployments_flows")
+            .auth(self.get_access_token().await?)
+            .eq("deployment_id", id.to_string())
+            .eq("flow_id", flow.to_string())
+            .select("data")
+            .single()
+            .execute()
+            .await
+            .change_context(Error::Postgrest)?;
+        Ok(read_json_response::<Row, PostgrestErrorBody>(resp)
+            .await?
+            .data)
+    }
+
+    /// Point `tag` of the deployment's flow to the deployment.
+    pub async fn set_deployment_tag(
+        &mut self,
+        id: DeploymentId,
+        tag: &str,
+        description: Option<&str>,
+    ) -> Result<FlowId, Report<Error>> {
+        let flow = self.deployment_entrypoint(id).await?;
+
+        // tags cannot be updated, only deleted and inserted
+        self.delete_deployment_tag(flow, tag).await?;
+        let row = json!({
+            "user_id": self.user_id(),
+            "entrypoint": flow,
+            "tag": tag,
+            "deployment_id": id,
+            "description": description,
+        });
+        let resp = self
+            .pg
+            .from("flow_deployments_tags")
+            .auth(self.get_access_token().await?)
+            .insert(row.to_string())
+            .execute()
+            .await
+            .change_context(Error::Postgrest)?;
+        if !resp.status().is_success() {
+            read_json_response::<JsonValue, PostgrestErrorBody>(resp).await?;
+        }
+        Ok(flow)
+    }
+
+    pub async fn delete_deployment_tag(
+        &mut self,
+        flow: FlowId,
+        tag: &str,
+    ) -> Result<(), Report<Error>> {
+        let resp = self
+            .pg
+            .from("flow_deployments_tags")
+            .auth(self.get_access_token().await?)
+            .eq("entrypoint", flow.to_string())
+            .eq("tag", tag)
+            .delete()
+            .execute()
+            .await
+            .change_context(Error::Postgrest)?;
+        if !resp.status().is_success() {
+            read_json_response::<JsonValue, PostgrestErrorBody>(resp).await?;
+        }
+        Ok(())
+    }
+
+    /// Wait for the next signature request of a run.
+    pub async fn get_signature_request(
+        &mut self,
+        run_id: FlowRunId,
+    ) -> Result<SignatureRequest, Report<Error>> {
+        let request = self
+            .flow_server_request(Method::GET, &format!("/flow/signature_request/{run_id}"))
+            .await?;
+        Self::send_flow_server(request).await
+    }
+
+    /// Sign a signature request with the matching keypair and submit it.
+    pub async fn answer_signature_request(
+        &self,
+        req: &SignatureRequest,
+        keypairs: &[Keypair],
+    ) -> Result<bool, Report<Error>> {
+        let Some(keypair) = keypairs.iter().find(|k| k.pubkey() == req.pubkey) else {
+            return Ok(false);
+        };
+        let id = req
+            .id
+            .ok_or(Error::InvalidValue)
+            .attach_printable("signature request has no ID")?;
+        let message = data_encoding::BASE64
+            .decode(req.message.as_bytes())
+            .change_context(Error::InvalidValue)
+            .attach_printable("invalid signature request message")?;
+        let url = self
+            .config
+            .flow_server
+            .join("/signature/submit")
+            .change_context(Error::Url)?;
+        let request = CLIENT.post(url).json(&json!({
+            "id": id,
+            "signature": keypair.sign(&message),
+        }));
+        Self::send_flow_server::<JsonValue>(request).await?;
+        Ok(true)
+    }
+
+    /// Print the events of a flow run until it finishes, answering signature
+    /// requests of `keypairs`. Returns the flow outputs, and whether a node or
+    /// the flow failed.
+    pub async fn tail_flow_run(
+        &mut self,
+        run_id: FlowRunId,
+        run_token: Option<String>,
+        node_names: &HashMap<Uuid, String>,
+        keypairs: &[Keypair],
+    ) -> Result<(JsonValue, bool), Report<Error>> {
+        let mut url = self
+            .config
+            .flow_server
+            .join("/ws")
+            .change_context(Error::Url)?;
+        let scheme = if url.scheme() == "https" { "wss" } else { "ws" };
+        url.set_scheme(scheme)
+            .map_err(|_| Error::Url)
+            .attach_printable("invalid flow-server URL")?;
+        let (mut ws, _) = tokio_tungstenite::connect_async(url.as_str())
+            .await
+            .change_context(Error::WebSocket)?;
+
+        let token = self.get_access_token().await?;
+        let requests = [
+            json!({ "id": 0, "method": "Authenticate", "params": { "token": token } }),
+            json!({
+                "id": 1,
+                "method": "SubscribeFlowRunEvents",
+                "params": { "flow_run_id": run_id, "token": run_token },
+            }),
+        ];
+        for request in requests {
+            ws.send(Message::text(request.to_string()))
+                .await
+                .change_context(Error::WebSocket)?;
+        }
+
+        let mut failed = false;
+        while let Some(msg) = ws.next().await {
+            let text = match msg.change_context(Error::WebSocket)? {
+                Message::Text(text) => text,
+                Message::Close(_) => break,
+                _ => continue,
+            };
+            let msg = serde_json::from_str::<JsonValue>(&text).change_context(Error::Json)?;
+            if let Some(error) = msg.get("Err") {
+                let error = error.as_str().unwrap_or_default().to_owned();
+                return Err(Error::ErrorResponse(error).into());
+            }
+            let (Some(event), Some(data)) = (msg["event"].as_str(), msg.get("data")) else {
+                continue;
+            };
+            let node = || {
+                let id = data["node_id"].as_str().unwrap_or_default();
+                let name = id
+                    .parse::<Uuid>()
+                    .ok()
+                    .and_then(|id| node_names.get(&id))
+                    .map_or("node", String::as_str);
+                format!(
+                    "{name}[{}]#{}",
+                    id.get(..8).unwrap_or(id),
+                    data["times"].as_u64().unwrap_or_default()
+                )
+            };
+            match event {
+                "FlowStart" => eprintln!("{}", style("flow started").bold()),
+                "FlowError" => {
+                    failed = true;
+                    eprintln!("{} {}", style("flow error:").red().bold(), data["error"]);
+                }
+                "FlowLog" => eprintln!(
+                    "{} {}",
+                    level(&data["level"]),
+                    data["content"].as_str().unwrap_or_default()
+                ),
+                "NodeStart" => eprintln!("{} {}", style("▶").dim(), node()),
+                "NodeLog" => eprintln!(
+                    "{} {} {}",
+                    style(node()).dim(),
+                    level(&data["level"]),
+                    data["content"].as_str().unwrap_or_default()
+                ),
+                "NodeError" => {
+                    failed = true;
+                    eprintln!(
+                        "{} {} {}",
+                        style("✗").red(),
+                        node(),
+                        data["error"].as_str().unwrap_or_default()
+                    );
+                }
+                "NodeFinish" => eprintln!("{} {}", style("✓").green(), node()),
+                "ApiInput" => eprintln!(
+                    "waiting for API input at {}",
+                    data["url"].as_str().unwrap_or_default()
+                ),
+                "SignatureRequest" => {
+                    let req = serde_json::from_value::<SignatureRequest>(data.clone())
+                        .change_context(Error::Json)?;
+                    if self.answer_signature_request(&req, keypairs).await? {
+                        eprintln!("signed request for {}", req.pubkey);
+                    } else {
+                        eprintln!(
+                            "{} signature requested for {}, pass its keypair with --keypair",
+                            style("!").yellow(),
+                            req.pubkey
+                        );
+                    }
+                }
+                "FlowFinish" => {
+                    let not_run = data["not_run"].as_array().map_or(0, Vec::len);
+                    eprintln!(
+                        "{}",
+                        style(format!("flow finished, {not_run} nodes not run")).bold()
+                    );
+                    return Ok((from_flow_value(&data["output"]), failed));
+                }
+                _ => {}
+            }
+        }
+
+        Err(Error::WebSocket).attach_printable("connection closed before the flow finished")
+    }
+
+    /// Names of the nodes of a flow, to print events.
+    pub async fn node_names(&mut self, flow: FlowId) -> HashMap<Uuid, String> {
+        let Ok(export) = self.export_flow(flow).await else {
+            return HashMap::new();
+        };
+        export["flow"]["nodes"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|node| {
+                let id = node["id"].as_str()?.parse().ok()?;
+                let data = &node["data"];
+                let name = data["name"].as_str().or_else(|| data["node_id"].as_str())?;
+                Some((id, name.to_owned()))
+            })
+            .collect()
+    }
+}
+
+#[derive(clap::Args, Debug)]
+pub struct InputArgs {
+    /// Flow input, value is JSON or a string
+    #[arg(long = "input", short, value_name = "NAME=VALUE")]
+    pub inputs: Vec<String>,
+    /// JSON file with flow inputs, `--input` takes precedence
+    #[arg(long, value_name = "PATH")]
+    pub inputs_file: Option<PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct FollowArgs {
+    /// Print events until the flow finishes, then print its outputs. Exits
+    /// with 1 if a node failed.
+    #[arg(long, short)]
+    pub follow: bool,
+    /// Keypair file to answer signature requests with, can be repeated
+    #[arg(long = "keypair", short, value_name = "PATH")]
+    pub keypairs: Vec<PathBuf>,
+}
+
+pub async fn load_client() -> Result<ApiClient, Report<Error>> {
+    ApiClient::load().await.change_context(Error::NotLogin)
+}
+
+async fn follow_run(
+    client: &mut ApiClient,
+    run_id: FlowRunId,
+    run_token: Option<String>,
+    flow: Option<FlowId>,
+    keypairs: &[PathBuf],
+) -> Result<(), Report<Error>> {
+    let keypairs = Keypair::read_all(keypairs).await?;
+    let names = match flow {
+        Some(flow) => client.node_names(flow).await,
+        None => HashMap::new(),
+    };
+    let (output, failed) = client
+        .tail_flow_run(run_id, run_token, &names, &keypairs)
+        .await?;
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&output).change_context(Error::Json)?
+    );
+    if failed {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+pub async fn export_flow(id: FlowId, output: Option<&Path>) -> Result<(), Report<Error>> {
+    let mut client = load_client().await?;
+    let export = client.export_flow(id).await?;
+    let text = serde_json::to_string_pretty(&export).change_context(Error::Json)? + "\n";
+    let path = output.unwrap_or(Path::new("-"));
+    if write_file(path, text).await? {
+        eprintln!("exported flow to {}", path.display());
+    }
+    Ok(())
+}
+
+pub async fn import_flow(path: &Path, into: Option<FlowId>) -> Result<(), Report<Error>> {
+    let mut client = load_client().await?;
+    let text = read_file(path).await?;
+    let export = serde_json::from_str::<JsonValue>(&text).change_context(Error::Json)?;
+    let id = client.import_flow(export, into).await?;
+    eprintln!("{} flow {}", style("imported").green(), id);
+    println!("{id}");
+    Ok(())
+}
+
+pub async fn start_flow(
+    id: FlowId,
+    inputs: &InputArgs,
+    follow: &FollowArgs,
+) -> Result<(), Report<Error>> {
+    let mut client = load_client().await?;
+    let inputs = read_inputs(&inputs.inputs, inputs.inputs_file.as_deref()).await?;
+    let run_id = client.start_flow(id, inputs).await?;
+    if follow.follow {
+        eprintln!("started flow run {run_id}");
+        follow_run(&mut client, run_id, None, Some(id), &follow.keypairs).await
+    } else {
+        println!("{run_id}");
+        Ok(())
+    }
+}
+
+pub async fn deploy_flow(id: FlowId, tag: Option<&str>) -> Result<(), Report<Error>> {
+    let mut client = load_client().await?;
+    let deployment_id = client.deploy_flow(id).await?;
+    eprintln!("{} deployment {}", style("created").green(), deployment_id);
+    if let Some(tag) = tag {
+        client.set_deployment_tag(deployment_id, tag, None).await?;
+        eprintln!("tagged as {tag:?}");
+    }
+    println!("{deployment_id}");
+    Ok(())
+}
+
+pub async fn start_deployment(
+    query: DeploymentQuery,
+    inputs: &InputArgs,
+    follow: &FollowArgs,
+) -> Result<(), Report<Error>> {
+    let mut client = load_client().await?;
+    let inputs = read_inputs(&inputs.inputs, inputs.inputs_file.as_deref()).await?;
+    let (run_id, token) = client.start_deployment(&query, inputs).await?;
+    if follow.follow {
+        eprintln!("started flow run {run_id}");
+        let flow = match query {
+            DeploymentQuery::FlowTag(flow, _) => Some(flow),
+            DeploymentQuery::Id(_) => None,
+        };
+        follow_run(&mut client, run_id, Some(token), flow, &follow.keypairs).await
+    } else {
+        println!("{run_id}");
+        eprintln!("run token: {token}");
+        Ok(())
+    }
+}
+
+pub async fn tail_flow_run(
+    run_id: FlowRunId,
+    token: Option<String>,
+    flow: Option<FlowId>,
+    keypairs: &[PathBuf],
+) -> Result<(), Report<Error>> {
+    let mut client = load_client().await?;
+    follow_run(&mut client, run_id, token, flow, keypairs).await
+}
+
+pub async fn stop_flow_run(run_id: FlowRunId, reason: Option<String>) -> Result<(), Report<Error>> {
+    let mut client = load_client().await?;
+    client.stop_flow(run_id, reason).await?;
+    eprintln!("{} flow run {}", style("stopped").green(), run_id);
+    Ok(())
+}
+
+pub async fn sign_flow_run(run_id: FlowRunId, keypairs: &[PathBuf]) -> Result<(), Report<Error>> {
+    let mut client = load_client().await?;
+    let keypairs = Keypair::read_all(keypairs).await?;
+    let req = client.get_signature_request(run_id).await?;
+    error_stack::ensure!(
+        client.answer_signature_request(&req, &keypairs).await?,
+        Error::ErrorResponse(format!("no keypair for {}", req.pubkey))
+    );
+    eprintln!("{} request for {}", style("signed").green(), req.pubkey);
+    Ok(())
+}
+
+pub async fn list_deployment_tags(flow: FlowId) -> Result<(), Report<Error>> {
+    let mut client = load_client().await?;
+    for tag in client.list_deployment_tags(flow).await? {
+        println!(
+            "{}\t{}\t{}",
+            tag.tag,
+            tag.deployment_id,
+            tag.description.unwrap_or_default()
+        );
+    }
+    Ok(())
+}
+
+pub async fn tag_deployment(
+    id: DeploymentId,
+    tag: &str,
+    description: Option<&str>,
+) -> Result<(), Report<Error>> {
+    let mut client = load_client().await?;
+    let flow = client.set_deployment_tag(id, tag, description).await?;
+    eprintln!(
+        "{} {:?} of flow {} to {}",
+        style("set").green(),
+        tag,
+        flow,
+        id
+    );
+    Ok(())
+}
+
+pub async fn untag_deployment(flow: FlowId, tag: &str) -> Result<(), Report<Error>> {
+    let mut client = load_client().await?;
+    client.delete_deployment_tag(flow, tag).await?;
+    eprintln!("{} {:?} of flow {}", style("deleted").green(), tag, flow);
+    Ok(())
+}
+
+fn level(level: &JsonValue) -> StyledObject<&str> {
+    let level = level.as_str().unwrap_or_default();
+    match level {
+        "Error" => style(level).red(),
+        "Warn" => style(level).yellow(),
+        "Info" => style(level).green(),
+        _ => style(level).dim(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flow_value() {
+        let json = json!({ "a": [1, -1, 1.5, "x", true, null], "b": { "S": "tagged" } });
+        let value = to_flow_value(json.clone());
+        assert_eq!(
+            value,
+            json!({ "M": {
+                "a": { "A": [
+                    { "U": "1" }, { "I": "-1" }, { "D": "1.5" },
+                    { "S": "x" }, { "B": true }, { "N": 0 },
+                ] },
+                "b": { "S": "tagged" },
+            } })
+        );
+        assert_eq!(
+            from_flow_value(&value),
+            json!({ "a": [1, -1, 1.5, "x", true, null], "b": "tagged" })
+        );
+
+        let pubkey = "96gYZGLnJYVFmbjzopPSU6QiEV5fGqZNyN9nmNhvrZU5";
+        assert_eq!(
+            from_flow_value(&json!({ "M": {
+                "key": { "B3": pubkey },
+                "amount": { "U1": "5" },
+            } })),
+            json!({ "key": pubkey, "amount": 5 })
+        );
+    }
+
+    #[test]
+    fn test_parse_input() {
+        assert_eq!(
+            parse_input("n=10").unwrap(),
+            ("n".to_owned(), json!({ "U": "10" }))
+        );
+        assert_eq!(
+            parse_input("to=a=b").unwrap(),
+            ("to".to_owned(), json!({ "S": "a=b" }))
+        );
+        assert!(parse_input("=1").is_err());
+        assert!(parse_input("n").is_err());
+    }
+}